@@ -0,0 +1,54 @@
+//! Find the batch size where parallel hashing starts to beat serial hashing
+//!
+//! Run with `cargo run --release --example hash_parallel_bench [file_size_bytes]`.
+//! The defaults in `movs::hash::ParallelThreshold` are based on its output.
+
+use movs::hash::{hash_files_parallel_with_threshold, ParallelThreshold};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 50;
+
+fn time(paths: &[&Path], threshold: ParallelThreshold) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        hash_files_parallel_with_threshold(paths.iter().copied(), threshold);
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() -> std::io::Result<()> {
+    let file_size: usize = std::env::args()
+        .nth(1)
+        .and_then(|a| a.parse().ok())
+        .unwrap_or(4 * 1024);
+
+    let dir = tempfile::TempDir::new()?;
+    let files: Vec<_> = (0..256)
+        .map(|i| {
+            let path = dir.path().join(format!("clip_{:03}.wav", i));
+            fs::write(&path, vec![i as u8; file_size])?;
+            Ok(path)
+        })
+        .collect::<std::io::Result<_>>()?;
+
+    println!("file size {} bytes, {} threads", file_size, available_threads());
+    println!("{:>6} {:>12} {:>12}", "files", "serial", "parallel");
+
+    for count in [1, 2, 4, 8, 16, 32, 64, 128, 256] {
+        let paths: Vec<&Path> = files[..count].iter().map(|p| p.as_path()).collect();
+        // Warm the page cache so both modes read from memory
+        time(&paths, ParallelThreshold::never());
+
+        let serial = time(&paths, ParallelThreshold::never());
+        let parallel = time(&paths, ParallelThreshold::always());
+        println!("{:>6} {:>12.2?} {:>12.2?}", count, serial, parallel);
+    }
+
+    Ok(())
+}
+
+fn available_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
@@ -0,0 +1,50 @@
+//! Compare restore throughput with and without content verification
+//!
+//! Run with `cargo run --release --example restore_bench [file_count] [file_size_kb]`.
+
+use movs::metadata::init_repository;
+use movs::restore::{restore_snapshot, RestoreOptions};
+use movs::snapshot::create_snapshot;
+use std::fs;
+use std::time::Instant;
+
+fn main() -> movs::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let file_count: usize = args.next().and_then(|a| a.parse().ok()).unwrap_or(64);
+    let file_size_kb: usize = args.next().and_then(|a| a.parse().ok()).unwrap_or(4096);
+
+    let project = tempfile::TempDir::new()?;
+    init_repository(project.path())?;
+
+    for i in 0..file_count {
+        let content: Vec<u8> = (0..file_size_kb * 1024)
+            .map(|n| (n.wrapping_mul(31) ^ i) as u8)
+            .collect();
+        fs::write(project.path().join(format!("take_{:04}.wav", i)), content)?;
+    }
+
+    let id = create_snapshot(project.path(), "Benchmark", None, &Default::default())?;
+    let total_mb = (file_count * file_size_kb) as f64 / 1024.0;
+    println!("{} files, {:.0} MB", file_count, total_mb);
+
+    for verify in [true, false] {
+        let target = tempfile::TempDir::new()?;
+        let options = RestoreOptions {
+            verify,
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        restore_snapshot(project.path(), &id, target.path(), &options)?;
+        let elapsed = start.elapsed();
+
+        println!(
+            "verify={:<5} {:>8.2?}  {:>8.1} MB/s",
+            verify,
+            elapsed,
+            total_mb / elapsed.as_secs_f64()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,424 @@
+use crate::error::{MovsError, Result};
+use crate::log::{self as oplog, Operation};
+use crate::metadata::config::load_config;
+use crate::metadata::lock::lock_for_write;
+use crate::metadata::persistence::save_snapshot;
+use crate::metadata::refs::write_head;
+use crate::metadata::snapshot_exists;
+use crate::observer::NullObserver;
+use crate::types::{FileEntry, SnapshotBuilder, SnapshotId, SnapshotMetadata};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Annotation key under which a merged snapshot records the snapshot
+/// merged from
+pub const MERGED_FROM_KEY: &str = "merged_from";
+
+/// How a path changed on each side of a three-way diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStatus {
+    /// Both sides still match the base
+    Unchanged,
+
+    /// Only side `a` changed, added or removed the file
+    ChangedInA,
+
+    /// Only side `b` changed, added or removed the file
+    ChangedInB,
+
+    /// Both sides made the same change, e.g. added the same sample
+    ChangedInBoth,
+
+    /// The sides changed the file differently; needs a manual choice
+    Conflict,
+}
+
+/// One path of a three-way diff, with its entry on each side
+///
+/// An entry is `None` where the path does not exist.
+#[derive(Debug, Clone)]
+pub struct MergeEntry {
+    /// Path relative to the project root
+    pub path: PathBuf,
+
+    /// How the path changed
+    pub status: MergeStatus,
+
+    /// The file in the common ancestor
+    pub base: Option<FileEntry>,
+
+    /// The file on side `a`
+    pub a: Option<FileEntry>,
+
+    /// The file on side `b`
+    pub b: Option<FileEntry>,
+}
+
+impl MergeEntry {
+    /// The file the merge keeps for this path, `None` if it is deleted
+    ///
+    /// Conflicts have no merged file and yield `None` as well.
+    pub fn merged(&self) -> Option<&FileEntry> {
+        match self.status {
+            MergeStatus::Unchanged | MergeStatus::ChangedInA | MergeStatus::ChangedInBoth => {
+                self.a.as_ref()
+            }
+            MergeStatus::ChangedInB => self.b.as_ref(),
+            MergeStatus::Conflict => None,
+        }
+    }
+}
+
+/// Result of comparing two snapshots against their common ancestor
+#[derive(Debug, Clone)]
+pub struct MergeReport {
+    /// The common ancestor
+    pub base: SnapshotId,
+
+    /// The snapshot merged into; the merged snapshot's parent
+    pub a: SnapshotId,
+
+    /// The snapshot merged from
+    pub b: SnapshotId,
+
+    /// Every path present in any of the three snapshots, sorted
+    pub entries: Vec<MergeEntry>,
+}
+
+impl MergeReport {
+    /// Paths changed differently on both sides
+    pub fn conflicts(&self) -> impl Iterator<Item = &MergeEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == MergeStatus::Conflict)
+    }
+
+    /// Check if any path conflicts
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts().next().is_some()
+    }
+}
+
+/// Classify every path by how `a` and `b` changed it relative to `base`
+///
+/// Files are compared by content hash. A file deleted on one side and
+/// modified on the other is a conflict, as is a file added on both sides
+/// with different content. Audio is binary, so conflicts are only
+/// reported; nothing is merged within a file.
+///
+/// The caller picks `base`. When merging from the same branch again, use
+/// the snapshot the previous merge recorded with `merged_from`, so changes
+/// already merged are not reported a second time.
+///
+/// # Arguments
+///
+/// * `base` - Common ancestor of `a` and `b`
+/// * `a` - One side, typically the current branch
+/// * `b` - The other side, typically the branch being merged in
+pub fn three_way_diff(
+    base: &SnapshotMetadata,
+    a: &SnapshotMetadata,
+    b: &SnapshotMetadata,
+) -> MergeReport {
+    let base_by_path = by_path(base);
+    let a_by_path = by_path(a);
+    let b_by_path = by_path(b);
+
+    let paths: BTreeSet<&Path> = base_by_path
+        .keys()
+        .chain(a_by_path.keys())
+        .chain(b_by_path.keys())
+        .copied()
+        .collect();
+
+    let entries = paths
+        .into_iter()
+        .map(|path| {
+            let base_entry = base_by_path.get(path).copied();
+            let a_entry = a_by_path.get(path).copied();
+            let b_entry = b_by_path.get(path).copied();
+
+            let a_changed = !same_content(base_entry, a_entry);
+            let b_changed = !same_content(base_entry, b_entry);
+            let status = match (a_changed, b_changed) {
+                (false, false) => MergeStatus::Unchanged,
+                (true, false) => MergeStatus::ChangedInA,
+                (false, true) => MergeStatus::ChangedInB,
+                (true, true) if same_content(a_entry, b_entry) => MergeStatus::ChangedInBoth,
+                (true, true) => MergeStatus::Conflict,
+            };
+
+            MergeEntry {
+                path: path.to_path_buf(),
+                status,
+                base: base_entry.cloned(),
+                a: a_entry.cloned(),
+                b: b_entry.cloned(),
+            }
+        })
+        .collect();
+
+    MergeReport {
+        base: base.id.clone(),
+        a: a.id.clone(),
+        b: b.id.clone(),
+        entries,
+    }
+}
+
+/// Create a snapshot combining both sides of a conflict-free merge
+///
+/// The merged snapshot is parented to `a` and records `b` under the
+/// `MERGED_FROM_KEY` annotation. HEAD moves to it, advancing the current
+/// branch if there is one. Every file comes from an existing snapshot, so
+/// no objects are stored.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `report` - Result of `three_way_diff`
+/// * `message` - Message for the merged snapshot
+///
+/// # Errors
+///
+/// Returns `MovsError::MergeConflict` listing the conflicting paths if the
+/// report has any; resolve them by choosing a side and snapshotting instead.
+pub fn apply_merge(project_root: &Path, report: &MergeReport, message: &str) -> Result<SnapshotId> {
+    if report.has_conflicts() {
+        return Err(MovsError::MergeConflict {
+            paths: report.conflicts().map(|entry| entry.path.clone()).collect(),
+        });
+    }
+    let _lock = lock_for_write(project_root)?;
+    if !snapshot_exists(project_root, &report.a) {
+        return Err(MovsError::SnapshotNotFound(report.a.to_string()));
+    }
+
+    let config = load_config(project_root)?;
+    let mut builder = SnapshotBuilder::new()
+        .message(message)
+        .parent(report.a.clone())
+        .id_scheme(config.id_scheme);
+    builder.extend(
+        report
+            .entries
+            .iter()
+            .filter_map(MergeEntry::merged)
+            .cloned(),
+    );
+    let mut metadata = builder.build();
+    metadata
+        .metadata
+        .insert(MERGED_FROM_KEY.to_string(), report.b.to_string());
+
+    save_snapshot(project_root, &metadata)?;
+    write_head(project_root, &metadata.id)?;
+    oplog::record(
+        project_root,
+        Operation::Merge,
+        Some(&metadata.id),
+        None,
+        &NullObserver,
+    );
+
+    Ok(metadata.id)
+}
+
+/// The snapshot a merged snapshot was merged from, if it is a merge
+///
+/// This is the natural `base` for the next merge from the same branch.
+pub fn merged_from(metadata: &SnapshotMetadata) -> Option<SnapshotId> {
+    metadata
+        .metadata
+        .get(MERGED_FROM_KEY)
+        .map(|id| SnapshotId::new(id.clone()))
+}
+
+fn by_path(metadata: &SnapshotMetadata) -> HashMap<&Path, &FileEntry> {
+    metadata
+        .files
+        .iter()
+        .map(|entry| (entry.path.as_path(), entry))
+        .collect()
+}
+
+fn same_content(x: Option<&FileEntry>, y: Option<&FileEntry>) -> bool {
+    match (x, y) {
+        (Some(x), Some(y)) => x.same_content(y),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::load_snapshot;
+    use crate::metadata::refs::read_head;
+    use crate::snapshot::create_snapshot;
+    use crate::types::FileHash;
+    use chrono::Utc;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn snapshot(files: &[(&str, u8)]) -> SnapshotMetadata {
+        let mut builder = SnapshotBuilder::new();
+        builder.extend(files.iter().map(|(path, hash)| {
+            FileEntry::new(
+                PathBuf::from(path),
+                FileHash::new(vec![*hash]),
+                1,
+                Utc::now(),
+            )
+        }));
+        builder.build()
+    }
+
+    fn status_of(report: &MergeReport, path: &str) -> MergeStatus {
+        report
+            .entries
+            .iter()
+            .find(|entry| entry.path == Path::new(path))
+            .unwrap()
+            .status
+    }
+
+    #[test]
+    fn test_three_way_diff_classifies_paths() {
+        let base = snapshot(&[
+            ("same.wav", 1),
+            ("mix.als", 2),
+            ("vox.wav", 3),
+            ("drums.wav", 4),
+            ("bass.wav", 5),
+        ]);
+        let a = snapshot(&[
+            ("same.wav", 1),
+            ("mix.als", 20),
+            ("vox.wav", 3),
+            ("drums.wav", 40),
+            ("bass.wav", 5),
+            ("pad.wav", 6),
+        ]);
+        let b = snapshot(&[
+            ("same.wav", 1),
+            ("mix.als", 2),
+            ("drums.wav", 41),
+            ("pad.wav", 6),
+        ]);
+
+        let report = three_way_diff(&base, &a, &b);
+        assert_eq!(status_of(&report, "same.wav"), MergeStatus::Unchanged);
+        assert_eq!(status_of(&report, "mix.als"), MergeStatus::ChangedInA);
+        assert_eq!(status_of(&report, "vox.wav"), MergeStatus::ChangedInB);
+        assert_eq!(status_of(&report, "drums.wav"), MergeStatus::Conflict);
+        assert_eq!(status_of(&report, "bass.wav"), MergeStatus::ChangedInB);
+        assert_eq!(status_of(&report, "pad.wav"), MergeStatus::ChangedInBoth);
+
+        let conflicts: Vec<_> = report.conflicts().map(|entry| &entry.path).collect();
+        assert_eq!(conflicts, vec![Path::new("drums.wav")]);
+
+        // Deleted on one side, modified on the other
+        let b = snapshot(&[("same.wav", 1), ("vox.wav", 30)]);
+        let report = three_way_diff(&base, &a, &b);
+        assert_eq!(status_of(&report, "mix.als"), MergeStatus::Conflict);
+    }
+
+    #[test]
+    fn test_apply_merge() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("mix.als"), b"mix v1").unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox v1").unwrap();
+        let base_id = create_snapshot(project_root, "base", None, &Default::default()).unwrap();
+
+        fs::write(project_root.join("vox.wav"), b"vox v2").unwrap();
+        let b_id = create_snapshot(project_root, "new vox", None, &Default::default()).unwrap();
+
+        fs::write(project_root.join("mix.als"), b"mix v2").unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox v1").unwrap();
+        let a_id = create_snapshot(project_root, "new mix", None, &Default::default()).unwrap();
+
+        let base = load_snapshot(project_root, &base_id).unwrap();
+        let a = load_snapshot(project_root, &a_id).unwrap();
+        let b = load_snapshot(project_root, &b_id).unwrap();
+
+        let report = three_way_diff(&base, &a, &b);
+        assert!(!report.has_conflicts());
+        let merged_id = apply_merge(project_root, &report, "Merge vox").unwrap();
+
+        let merged = load_snapshot(project_root, &merged_id).unwrap();
+        assert_eq!(merged.parent, Some(a_id));
+        assert_eq!(merged_from(&merged), Some(b_id.clone()));
+        assert_eq!(read_head(project_root).unwrap(), Some(merged_id));
+        let contents: Vec<_> = merged
+            .files
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.hash.clone()))
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                (PathBuf::from("mix.als"), crate::hash::hash_bytes(b"mix v2")),
+                (PathBuf::from("vox.wav"), crate::hash::hash_bytes(b"vox v2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeated_merge_from_recorded_base() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("mix.als"), b"mix v1").unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox v1").unwrap();
+        let base_id = create_snapshot(project_root, "base", None, &Default::default()).unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox v2").unwrap();
+        let b_id = create_snapshot(project_root, "new vox", None, &Default::default()).unwrap();
+        fs::write(project_root.join("mix.als"), b"mix v2").unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox v1").unwrap();
+        let a_id = create_snapshot(project_root, "new mix", None, &Default::default()).unwrap();
+
+        let load = |id| load_snapshot(project_root, id).unwrap();
+        let report = three_way_diff(&load(&base_id), &load(&a_id), &load(&b_id));
+        let merged_id = apply_merge(project_root, &report, "Merge vox").unwrap();
+
+        // The other branch moves on after the merge
+        fs::write(project_root.join("mix.als"), b"mix v1").unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox v2").unwrap();
+        fs::write(project_root.join("fx.wav"), b"fx").unwrap();
+        let b2_id = create_snapshot(project_root, "add fx", None, &Default::default()).unwrap();
+
+        let merged = load(&merged_id);
+        let base_id = merged_from(&merged).unwrap();
+        assert_eq!(base_id, b_id);
+        let base = load(&base_id);
+        let report = three_way_diff(&base, &merged, &load(&b2_id));
+        assert_eq!(status_of(&report, "vox.wav"), MergeStatus::Unchanged);
+        assert_eq!(status_of(&report, "fx.wav"), MergeStatus::ChangedInB);
+        assert_eq!(status_of(&report, "mix.als"), MergeStatus::ChangedInA);
+    }
+
+    #[test]
+    fn test_apply_merge_refuses_conflicts() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let base = snapshot(&[("mix.als", 1)]);
+        let a = snapshot(&[("mix.als", 2)]);
+        let b = snapshot(&[("mix.als", 3)]);
+
+        let report = three_way_diff(&base, &a, &b);
+        match apply_merge(project_root, &report, "Merge") {
+            Err(MovsError::MergeConflict { paths }) => {
+                assert_eq!(paths, vec![PathBuf::from("mix.als")])
+            }
+            other => panic!("expected a merge conflict, got {:?}", other),
+        }
+    }
+}
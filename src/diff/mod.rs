@@ -0,0 +1,244 @@
+use crate::error::Result;
+use crate::tree::WorkingTree;
+use crate::types::{FileEntry, FileHash, SnapshotDiff, SnapshotMetadata};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub mod merge;
+
+pub use merge::{
+    apply_merge, merged_from, three_way_diff, MergeEntry, MergeReport, MergeStatus, MERGED_FROM_KEY,
+};
+
+/// Options controlling what counts as a modification
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Only content changes count as modifications (default true)
+    ///
+    /// A file whose hash is unchanged is never reported, however its
+    /// modification time moved. Turn this off to also report files that
+    /// were only touched, e.g. to audit batch tools rewriting timestamps.
+    pub compare_content_only: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            compare_content_only: true,
+        }
+    }
+}
+
+/// Compute the changes between two file lists
+///
+/// A file is "modified" when it exists in both lists with a different hash.
+/// Paths in each category are sorted.
+pub fn diff_entries(old: &[FileEntry], new: &[FileEntry]) -> SnapshotDiff {
+    diff_entries_with_options(old, new, &DiffOptions::default())
+}
+
+/// Compute the changes between two file lists with explicit options
+///
+/// Like `diff_entries`, but with `compare_content_only` unset a file whose
+/// hash matches and whose modification time differs is also "modified".
+pub fn diff_entries_with_options(
+    old: &[FileEntry],
+    new: &[FileEntry],
+    options: &DiffOptions,
+) -> SnapshotDiff {
+    let old_by_path: HashMap<&Path, &FileEntry> =
+        old.iter().map(|f| (f.path.as_path(), f)).collect();
+    let new_by_path: HashMap<&Path, &FileEntry> =
+        new.iter().map(|f| (f.path.as_path(), f)).collect();
+
+    let mut diff = SnapshotDiff::new();
+
+    for (path, new_entry) in &new_by_path {
+        match old_by_path.get(path) {
+            None => diff.added.push(path.to_path_buf()),
+            Some(old_entry)
+                if !old_entry.same_content(new_entry)
+                    || (!options.compare_content_only
+                        && old_entry.modified != new_entry.modified) =>
+            {
+                diff.modified.push(path.to_path_buf())
+            }
+            Some(_) => {}
+        }
+    }
+
+    for path in old_by_path.keys() {
+        if !new_by_path.contains_key(path) {
+            diff.removed.push(path.to_path_buf());
+        }
+    }
+
+    diff.added.sort();
+    diff.modified.sort();
+    diff.removed.sort();
+    diff
+}
+
+/// Compute the changes from an older snapshot to a newer one
+pub fn diff_snapshots(old: &SnapshotMetadata, new: &SnapshotMetadata) -> SnapshotDiff {
+    diff_entries(&old.files, &new.files)
+}
+
+/// Compute the changes from an older snapshot to a newer one with explicit options
+pub fn diff_snapshots_with_options(
+    old: &SnapshotMetadata,
+    new: &SnapshotMetadata,
+    options: &DiffOptions,
+) -> SnapshotDiff {
+    diff_entries_with_options(&old.files, &new.files, options)
+}
+
+/// Compute the changes between two directories without creating snapshots
+///
+/// Both trees are scanned and hashed in parallel, then compared exactly like
+/// two snapshots. Each tree is filtered by its own `.movsignore`: rules in
+/// `a` apply only to `a`, rules in `b` only to `b`. A `.movs` directory in
+/// either root is always skipped. Neither directory needs to be a repository.
+///
+/// # Arguments
+///
+/// * `a` - The older tree, e.g. a backup
+/// * `b` - The newer tree, e.g. the current project
+pub fn diff_trees(a: &Path, b: &Path) -> Result<SnapshotDiff> {
+    let old = WorkingTree::open(a)?.hash_all()?;
+    let new = WorkingTree::open(b)?.hash_all()?;
+
+    Ok(diff_entries(&old, &new))
+}
+
+/// Check if two snapshots contain exactly the same files with the same content
+///
+/// Only the set of `(path, hash)` pairs is compared; IDs, timestamps,
+/// messages and file modification times are ignored.
+pub fn snapshots_identical(a: &SnapshotMetadata, b: &SnapshotMetadata) -> bool {
+    if a.files.len() != b.files.len() {
+        return false;
+    }
+
+    content_set(a) == content_set(b)
+}
+
+fn content_set(metadata: &SnapshotMetadata) -> Vec<(&PathBuf, &FileHash)> {
+    let mut set: Vec<_> = metadata.files.iter().map(|f| (&f.path, &f.hash)).collect();
+    set.sort_by(|a, b| {
+        a.0.cmp(b.0)
+            .then_with(|| a.1.as_bytes().cmp(b.1.as_bytes()))
+    });
+    set
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FileEntry, SnapshotId};
+    use chrono::Utc;
+
+    fn snapshot(files: &[(&str, u8)]) -> SnapshotMetadata {
+        let entries = files
+            .iter()
+            .map(|(path, hash)| {
+                FileEntry::new(
+                    PathBuf::from(path),
+                    FileHash::new(vec![*hash]),
+                    1,
+                    Utc::now(),
+                )
+            })
+            .collect();
+
+        SnapshotMetadata::new(
+            SnapshotId::generate(),
+            "msg".to_string(),
+            None,
+            None,
+            entries,
+        )
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let old = snapshot(&[("kept.wav", 1), ("changed.als", 2), ("gone.mid", 3)]);
+        let new = snapshot(&[("kept.wav", 1), ("changed.als", 4), ("new.wav", 5)]);
+
+        let diff = diff_snapshots(&old, &new);
+        assert_eq!(diff.added, vec![PathBuf::from("new.wav")]);
+        assert_eq!(diff.modified, vec![PathBuf::from("changed.als")]);
+        assert_eq!(diff.removed, vec![PathBuf::from("gone.mid")]);
+
+        assert!(!diff_snapshots(&old, &old).has_changes());
+    }
+
+    #[test]
+    fn test_diff_options_mtime_only_changes() {
+        let old = snapshot(&[("touched.wav", 1), ("changed.als", 2)]);
+        let mut new = snapshot(&[("touched.wav", 1), ("changed.als", 3)]);
+        new.files[0].modified = old.files[0].modified + chrono::Duration::seconds(60);
+
+        let diff = diff_snapshots_with_options(&old, &new, &DiffOptions::default());
+        assert_eq!(diff.modified, vec![PathBuf::from("changed.als")]);
+
+        let options = DiffOptions {
+            compare_content_only: false,
+        };
+        let diff = diff_snapshots_with_options(&old, &new, &options);
+        assert_eq!(
+            diff.modified,
+            vec![PathBuf::from("changed.als"), PathBuf::from("touched.wav")]
+        );
+    }
+
+    #[test]
+    fn test_snapshots_identical_ignores_order_and_metadata() {
+        let a = snapshot(&[("a.wav", 1), ("b.wav", 2)]);
+        let mut b = snapshot(&[("b.wav", 2), ("a.wav", 1)]);
+        b.message = "Different message".to_string();
+
+        assert!(snapshots_identical(&a, &b));
+    }
+
+    #[test]
+    fn test_snapshots_differ() {
+        let base = snapshot(&[("a.wav", 1), ("b.wav", 2)]);
+
+        assert!(!snapshots_identical(
+            &base,
+            &snapshot(&[("a.wav", 1), ("b.wav", 3)])
+        ));
+        assert!(!snapshots_identical(&base, &snapshot(&[("a.wav", 1)])));
+        assert!(!snapshots_identical(
+            &base,
+            &snapshot(&[("a.wav", 1), ("c.wav", 2)])
+        ));
+    }
+
+    #[test]
+    fn test_diff_trees() {
+        use std::fs;
+
+        let backup = tempfile::TempDir::new().unwrap();
+        let current = tempfile::TempDir::new().unwrap();
+
+        fs::write(backup.path().join("song.als"), b"v1").unwrap();
+        fs::write(backup.path().join("old.wav"), b"old").unwrap();
+        fs::write(backup.path().join("same.wav"), b"same").unwrap();
+
+        fs::write(current.path().join("song.als"), b"v2").unwrap();
+        fs::write(current.path().join("same.wav"), b"same").unwrap();
+        fs::write(current.path().join("new.wav"), b"new").unwrap();
+        fs::write(current.path().join("cache.tmp"), b"ignored").unwrap();
+        fs::write(current.path().join(".movsignore"), "*.tmp\n").unwrap();
+
+        let diff = diff_trees(backup.path(), current.path()).unwrap();
+        assert_eq!(
+            diff.added,
+            vec![PathBuf::from(".movsignore"), PathBuf::from("new.wav")]
+        );
+        assert_eq!(diff.modified, vec![PathBuf::from("song.als")]);
+        assert_eq!(diff.removed, vec![PathBuf::from("old.wav")]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::types::SnapshotId;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -16,6 +17,15 @@ pub enum MovsError {
     #[error("Failed to serialize metadata: {0}")]
     SerializationError(#[from] serde_json::Error),
 
+    #[error("Failed to parse snapshot '{id}': {source}")]
+    SnapshotParseError {
+        id: SnapshotId,
+        source: serde_json::Error,
+    },
+
+    #[error("Invalid hash: {0}")]
+    InvalidHash(String),
+
     #[error("Snapshot not found: {0}")]
     SnapshotNotFound(String),
 
@@ -37,18 +47,65 @@ pub enum MovsError {
     #[error("Restore error: {0}")]
     RestoreError(String),
 
+    #[error("Invalid search pattern: {0}")]
+    InvalidPattern(String),
+
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
     #[error("File not found in snapshot: {0}")]
     FileNotFoundInSnapshot(PathBuf),
 
+    #[error("Paths differ only by case and would collide on case-insensitive filesystems: '{first}' and '{second}'")]
+    PathCollision {
+        first: PathBuf,
+        second: PathBuf,
+    },
+
+    #[error(
+        "No files to snapshot in '{}'{}",
+        root.display(),
+        if *all_ignored { " (every file is excluded by .movsignore)" } else { "" }
+    )]
+    EmptySnapshot { root: PathBuf, all_ignored: bool },
+
+    #[error("File changed while it was being read: '{0}'")]
+    FileChangedDuringRead(PathBuf),
+
     #[error("Checksum mismatch for file '{path}': expected {expected}, got {actual}")]
     ChecksumMismatch {
         path: PathBuf,
         expected: String,
         actual: String,
     },
+
+    #[error(
+        "Repository is locked{}",
+        holder_pid.map(|pid| format!(" by process {}", pid)).unwrap_or_default()
+    )]
+    Locked { holder_pid: Option<u32> },
+
+    #[error("Branch not found: {0}")]
+    BranchNotFound(String),
+
+    #[error("Branch already exists: {0}")]
+    BranchAlreadyExists(String),
+
+    #[error("Invalid branch name: {0}")]
+    InvalidBranchName(String),
+
+    #[error("Merge has {} conflicting path(s)", paths.len())]
+    MergeConflict { paths: Vec<PathBuf> },
+
+    #[error("Snapshot would store {new_bytes} new bytes, over the budget of {budget}")]
+    BudgetExceeded { new_bytes: u64, budget: u64 },
+
+    #[error("Snapshot '{id}' does not match its digest: expected {expected}, got {actual}")]
+    SnapshotDigestMismatch {
+        id: SnapshotId,
+        expected: String,
+        actual: String,
+    },
 }
 
 /// Convenience Result type for MOVS operations
@@ -67,6 +124,19 @@ mod tests {
         assert!(err.to_string().contains("/test/path"));
     }
 
+    #[test]
+    fn test_snapshot_parse_error_names_snapshot() {
+        use std::error::Error as _;
+
+        let source = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let err = MovsError::SnapshotParseError {
+            id: SnapshotId::new("snapshot_a".to_string()),
+            source,
+        };
+        assert!(err.to_string().contains("snapshot_a"));
+        assert!(err.source().is_some());
+    }
+
     #[test]
     fn test_io_error_conversion() {
         let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "file not found");
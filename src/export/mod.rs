@@ -0,0 +1,222 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::persistence::load_snapshot;
+use crate::storage::open_verified_object_reader;
+use crate::types::{EntryKind, FileHash, SnapshotId};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tar::{Builder, EntryType, Header};
+
+/// Export a snapshot as a reproducible tar archive
+///
+/// Entries are written in sorted path order with each entry's mtime taken
+/// from the snapshot, fixed permissions, and uid/gid and owner names zeroed,
+/// so exporting the same snapshot twice yields byte-identical archives.
+/// File content is streamed from the object store and checked against
+/// each entry's size and hash on the way, so a corrupt object fails the
+/// export instead of producing a malformed archive.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `snapshot_id` - ID of the snapshot to export
+/// * `writer` - Destination for the archive bytes
+///
+/// # Returns
+///
+/// The writer, after the archive has been finished
+pub fn export_snapshot_tar<W: Write>(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    writer: W,
+) -> Result<W> {
+    let metadata = load_snapshot(project_root, snapshot_id)?;
+
+    let mut entries: Vec<_> = metadata.files.iter().collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut builder = Builder::new(writer);
+
+    for entry in entries {
+        let mut header = Header::new_gnu();
+        header.set_mtime(entry.modified.timestamp().max(0) as u64);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_username("")?;
+        header.set_groupname("")?;
+
+        match &entry.kind {
+            EntryKind::File => {
+                header.set_entry_type(EntryType::Regular);
+                header.set_mode(0o644);
+                header.set_size(entry.size);
+                let reader = open_verified_object_reader(project_root, &entry.hash, entry.size)?;
+                builder.append_data(&mut header, &entry.path, reader)?;
+            }
+            EntryKind::Symlink { target } => {
+                header.set_entry_type(EntryType::Symlink);
+                header.set_mode(0o777);
+                header.set_size(0);
+                builder.append_link(&mut header, &entry.path, target)?;
+            }
+            EntryKind::Directory => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                builder.append_data(&mut header, &entry.path, io::empty())?;
+            }
+        }
+    }
+
+    builder.into_inner().map_err(MovsError::Io)
+}
+
+/// List every regular file in a snapshot with its hash and size
+///
+/// Paths are relative to the project root and sorted, so they can be
+/// written one per line and fed to e.g. `rsync --files-from`. Symlinks
+/// and directories are left out.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `snapshot_id` - ID of the snapshot to list
+pub fn export_manifest(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+) -> Result<Vec<(PathBuf, FileHash, u64)>> {
+    let metadata = load_snapshot(project_root, snapshot_id)?;
+
+    let mut manifest: Vec<_> = metadata
+        .files
+        .into_iter()
+        .filter(|entry| entry.is_file())
+        .map(|entry| (entry.path, entry.hash, entry.size))
+        .collect();
+    manifest.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(manifest)
+}
+
+/// Like `export_manifest`, but with absolute paths into the working tree
+///
+/// The paths are where each file lives once the snapshot is restored; a
+/// relative `project_root` is resolved against the current directory.
+pub fn export_manifest_absolute(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+) -> Result<Vec<(PathBuf, FileHash, u64)>> {
+    let root = std::path::absolute(project_root)?;
+
+    Ok(export_manifest(project_root, snapshot_id)?
+        .into_iter()
+        .map(|(path, hash, size)| (root.join(path), hash, size))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_bytes;
+    use crate::metadata::init_repository;
+    use crate::snapshot::create_snapshot;
+    use crate::storage::get_object_path;
+    use std::fs;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_export_is_reproducible() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::create_dir(project_root.join("Samples")).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::write(project_root.join("Samples/kick.wav"), b"kick").unwrap();
+        let id = create_snapshot(project_root, "First", None, &Default::default()).unwrap();
+
+        let first = export_snapshot_tar(project_root, &id, Vec::new()).unwrap();
+        let second = export_snapshot_tar(project_root, &id, Vec::new()).unwrap();
+        assert_eq!(hash_bytes(&first), hash_bytes(&second));
+
+        let mut archive = tar::Archive::new(first.as_slice());
+        let mut seen = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            assert_eq!(entry.header().uid().unwrap(), 0);
+            assert_eq!(entry.header().gid().unwrap(), 0);
+
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+            seen.push((entry.path().unwrap().into_owned(), content));
+        }
+
+        assert_eq!(
+            seen,
+            vec![
+                ("Samples/kick.wav".into(), b"kick".to_vec()),
+                ("song.als".into(), b"project".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_uses_snapshot_mtime() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        let id = create_snapshot(project_root, "First", None, &Default::default()).unwrap();
+        let modified = load_snapshot(project_root, &id).unwrap().files[0].modified;
+
+        let archive = export_snapshot_tar(project_root, &id, Vec::new()).unwrap();
+        let mut archive = tar::Archive::new(archive.as_slice());
+        let entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.header().mtime().unwrap(), modified.timestamp() as u64);
+    }
+
+    #[test]
+    fn test_export_rejects_corrupt_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        let id = create_snapshot(project_root, "First", None, &Default::default()).unwrap();
+        let object = get_object_path(project_root, &hash_bytes(b"project")).unwrap();
+
+        for corrupt in [&b"projekt"[..], b"proj", b"project data"] {
+            fs::remove_file(&object).unwrap();
+            fs::write(&object, corrupt).unwrap();
+            assert!(export_snapshot_tar(project_root, &id, Vec::new()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_export_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::create_dir(project_root.join("Samples")).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::write(project_root.join("Samples/kick.wav"), b"kick").unwrap();
+        let id = create_snapshot(project_root, "First", None, &Default::default()).unwrap();
+
+        let manifest = export_manifest(project_root, &id).unwrap();
+        assert_eq!(
+            manifest,
+            vec![
+                (PathBuf::from("Samples/kick.wav"), hash_bytes(b"kick"), 4),
+                (PathBuf::from("song.als"), hash_bytes(b"project"), 7),
+            ]
+        );
+
+        let absolute = export_manifest_absolute(project_root, &id).unwrap();
+        assert_eq!(absolute.len(), 2);
+        assert!(absolute[0].0.is_absolute());
+        assert_eq!(absolute[1].0, project_root.join("song.als"));
+        assert_eq!(absolute[1].1, hash_bytes(b"project"));
+    }
+}
@@ -0,0 +1,538 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::config::load_config;
+use crate::metadata::get_movs_dir;
+use crate::metadata::lock::lock_for_write;
+use crate::metadata::persistence::iter_snapshots;
+use crate::metadata::store::MetadataStore;
+use crate::storage::delta::{delta_base, list_delta_files};
+use crate::storage::list_loose_objects;
+use crate::storage::pack::{remove_packed, PackIndex};
+use crate::types::FileHash;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+pub mod refcounts;
+
+pub use refcounts::{load_refcounts, rebuild_refcounts, RefCounts};
+
+/// Summary of a garbage collection run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// Number of unreferenced objects removed
+    pub objects_removed: usize,
+
+    /// Bytes freed on disk
+    pub bytes_freed: u64,
+}
+
+/// Collect every object hash needed to read the repository's snapshots
+///
+/// This is every hash recorded in a snapshot plus, transitively, the base
+/// objects that reachable delta objects are encoded against.
+pub fn reachable_objects(project_root: &Path) -> Result<HashSet<FileHash>> {
+    let mut referenced = Vec::new();
+    for metadata in iter_snapshots(project_root)? {
+        referenced.extend(metadata?.files.into_iter().map(|entry| entry.hash));
+    }
+
+    with_delta_bases(project_root, referenced)
+}
+
+/// Collect every object hash recorded in a metadata store's snapshots
+pub(crate) fn referenced_objects(store: &dyn MetadataStore) -> Result<HashSet<FileHash>> {
+    let mut referenced = HashSet::new();
+    for snapshot_id in store.list_snapshots()? {
+        let metadata = store.load_snapshot(&snapshot_id)?;
+        referenced.extend(metadata.files.into_iter().map(|entry| entry.hash));
+    }
+    Ok(referenced)
+}
+
+/// Extend a set of referenced objects with the delta bases they depend on
+fn with_delta_bases(
+    project_root: &Path,
+    referenced: impl IntoIterator<Item = FileHash>,
+) -> Result<HashSet<FileHash>> {
+    let mut reachable = HashSet::new();
+    let mut pending = Vec::new();
+
+    for hash in referenced {
+        if reachable.insert(hash.clone()) {
+            pending.push(hash);
+        }
+    }
+
+    // Keep delta bases alive for as long as a reachable delta needs them
+    while let Some(hash) = pending.pop() {
+        if let Some(base) = delta_base(project_root, &hash)? {
+            if reachable.insert(base.clone()) {
+                pending.push(base);
+            }
+        }
+    }
+
+    Ok(reachable)
+}
+
+/// Objects a garbage collection would delete, computed without deleting
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcPlan {
+    /// Each unreferenced object with its size on disk, in bytes
+    pub objects: Vec<(FileHash, u64)>,
+
+    /// Sum of all object sizes: the space `gc` would reclaim
+    pub total_bytes: u64,
+
+    /// Where each object in `objects` lives, in the same order; `None`
+    /// for a packed object
+    paths: Vec<Option<PathBuf>>,
+}
+
+/// List the objects that no snapshot references
+///
+/// This is the dry run of `gc`: nothing is deleted. Liveness is recounted
+/// from every snapshot rather than trusted to the persisted reference
+/// counts, which may lag behind an interrupted or concurrent write; the
+/// fresh counts are saved as a side effect. Packed objects are listed with
+/// their length within the pack, which is what rewriting the pack frees.
+///
+/// Fails with `MovsError::ConfigError` when the repository uses an
+/// external `objects_dir`: other working trees may share that store, and
+/// their snapshots are invisible here.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn gc_plan(project_root: &Path) -> Result<GcPlan> {
+    check_local_store(project_root)?;
+    plan_unreachable(project_root, &live_objects(project_root)?)
+}
+
+/// Fail unless the repository's objects live in its own `.movs/objects`
+fn check_local_store(project_root: &Path) -> Result<()> {
+    if uses_external_store(project_root) {
+        return Err(MovsError::ConfigError(
+            "Garbage collection is not supported with an external objects_dir, \
+             which other working trees may share"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// List the loose, delta and packed objects not in `reachable`
+fn plan_unreachable(project_root: &Path, reachable: &HashSet<FileHash>) -> Result<GcPlan> {
+    let mut plan = GcPlan::default();
+
+    let loose = list_loose_objects(project_root)?;
+    let deltas = list_delta_files(project_root)?;
+
+    for (hash, path) in loose.into_iter().chain(deltas) {
+        if reachable.contains(&hash) {
+            continue;
+        }
+
+        let len = fs::metadata(&path)?.len();
+        plan.objects.push((hash, len));
+        plan.paths.push(Some(path));
+        plan.total_bytes += len;
+    }
+
+    for (hash, location) in PackIndex::load(project_root)?.iter() {
+        if !reachable.contains(&hash) {
+            plan.objects.push((hash, location.len));
+            plan.paths.push(None);
+            plan.total_bytes += location.len;
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Delete the objects listed in a plan from `gc_plan`
+///
+/// Objects that became referenced since the plan was made, e.g. by a new
+/// snapshot, and objects already gone are skipped.
+pub fn apply_gc_plan(project_root: &Path, plan: &GcPlan) -> Result<GcReport> {
+    let _lock = lock_for_write(project_root)?;
+    remove_planned(project_root, plan, &live_objects(project_root)?)
+}
+
+/// Delete objects outside `referenced` and their delta bases
+///
+/// Liveness is up to the caller, e.g. `Repository::gc` collecting it from
+/// its metadata store, which must hold the repository lock while both run.
+pub(crate) fn remove_unreachable(
+    project_root: &Path,
+    referenced: impl IntoIterator<Item = FileHash>,
+) -> Result<GcReport> {
+    let _lock = lock_for_write(project_root)?;
+    check_local_store(project_root)?;
+    let reachable = with_delta_bases(project_root, referenced)?;
+    let plan = plan_unreachable(project_root, &reachable)?;
+    remove_planned(project_root, &plan, &reachable)
+}
+
+/// Delete the planned objects that are still not in `reachable`
+///
+/// Packed objects are dropped by rewriting the packs that hold them.
+fn remove_planned(
+    project_root: &Path,
+    plan: &GcPlan,
+    reachable: &HashSet<FileHash>,
+) -> Result<GcReport> {
+    let mut report = GcReport::default();
+    let mut packed = HashSet::new();
+
+    for ((hash, len), path) in plan.objects.iter().zip(&plan.paths) {
+        if reachable.contains(hash) {
+            continue;
+        }
+        let Some(path) = path else {
+            packed.insert(hash.clone());
+            continue;
+        };
+
+        match fs::remove_file(path) {
+            Ok(()) => {
+                report.objects_removed += 1;
+                report.bytes_freed += len;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let repacked = remove_packed(project_root, &packed)?;
+    report.objects_removed += repacked.objects_removed;
+    report.bytes_freed += repacked.bytes_freed;
+
+    Ok(report)
+}
+
+/// Delete objects that no snapshot references
+///
+/// Equivalent to applying a fresh `gc_plan`. Packs holding unreferenced
+/// objects are rewritten without them. Run this after deleting snapshots
+/// to reclaim their space.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn gc(project_root: &Path) -> Result<GcReport> {
+    apply_gc_plan(project_root, &gc_plan(project_root)?)
+}
+
+/// Objects referenced by a snapshot, plus their delta bases
+///
+/// The count file is trusted when it loads: writers count new references
+/// before a snapshot lands and release old ones after, so it can only
+/// keep too many objects alive. It is rebuilt from the snapshots when it
+/// is missing or does not parse.
+fn live_objects(project_root: &Path) -> Result<HashSet<FileHash>> {
+    let counts = match load_refcounts(project_root) {
+        Ok(Some(counts)) => counts,
+        Ok(None) | Err(_) => rebuild_refcounts(project_root)?,
+    };
+    with_delta_bases(project_root, counts.into_keys())
+}
+
+/// Check if the repository's objects live outside `.movs/objects`
+fn uses_external_store(project_root: &Path) -> bool {
+    load_config(project_root).is_ok_and(|config| config.objects_dir.is_some())
+}
+
+/// File name prefix of the temporary files used for atomic writes
+const TEMP_PREFIX: &str = ".tmp";
+
+/// Remove leftovers of snapshot creation that was interrupted by a crash
+///
+/// Snapshot creation stores objects first, then writes the snapshot
+/// metadata to a temporary file and renames it into place, and moves HEAD
+/// last. A crash therefore never leaves a partially written snapshot, only
+/// objects no snapshot references and temporary files. This removes both:
+/// unreferenced objects via `gc`, and every `.tmp*` file under `.movs`.
+/// The repository lock is held throughout, so snapshots being created
+/// through this library are never mistaken for leftovers; do not run it
+/// while other tools write to `.movs` directly. With an
+/// external `objects_dir`, where `gc` cannot run, only temporary files are
+/// removed.
+///
+/// # Returns
+///
+/// The number of files removed
+pub fn cleanup_incomplete(project_root: &Path) -> Result<usize> {
+    let _lock = lock_for_write(project_root)?;
+    let mut removed = if uses_external_store(project_root) {
+        0
+    } else {
+        gc(project_root)?.objects_removed
+    };
+
+    for entry in WalkDir::new(get_movs_dir(project_root)).min_depth(1) {
+        let entry = entry.map_err(|e| MovsError::Io(e.into()))?;
+        let is_temp = entry.file_name().to_string_lossy().starts_with(TEMP_PREFIX);
+        if entry.file_type().is_file() && is_temp {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_bytes;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::delete_snapshot;
+    use crate::snapshot::create_snapshot;
+    use crate::storage::delta::store_delta_object;
+    use crate::storage::{load_object, object_exists, store_object_bytes};
+    use crate::types::{FileEntry, SnapshotId, SnapshotMetadata};
+    use chrono::Utc;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_gc_removes_unreferenced_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let first = create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        create_snapshot(project_root, "v2", None, &Default::default()).unwrap();
+
+        // Nothing is garbage while both snapshots exist
+        assert_eq!(gc(project_root).unwrap(), GcReport::default());
+
+        delete_snapshot(project_root, &first).unwrap();
+        let report = gc(project_root).unwrap();
+        assert_eq!(report.objects_removed, 1);
+        assert_eq!(report.bytes_freed, 2);
+
+        assert!(!object_exists(project_root, &hash_bytes(b"v1")).unwrap());
+        assert!(object_exists(project_root, &hash_bytes(b"v2")).unwrap());
+    }
+
+    #[test]
+    fn test_gc_rewrites_packs() {
+        use crate::storage::pack::{get_pack_dir, pack_objects};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        let orphan = store_object_bytes(project_root, b"orphan").unwrap();
+        let old_pack = pack_objects(project_root).unwrap().pack_file.unwrap();
+
+        let plan = gc_plan(project_root).unwrap();
+        assert_eq!(plan.objects, vec![(orphan.clone(), 6)]);
+
+        let report = gc(project_root).unwrap();
+        assert_eq!(report.objects_removed, 1);
+        assert_eq!(report.bytes_freed, 6);
+
+        assert!(!object_exists(project_root, &orphan).unwrap());
+        assert_eq!(
+            load_object(project_root, &hash_bytes(b"v1")).unwrap(),
+            b"v1"
+        );
+        assert_eq!(PackIndex::load(project_root).unwrap().len(), 1);
+        assert!(!old_pack.exists());
+        assert_eq!(
+            fs::read_dir(get_pack_dir(project_root).unwrap())
+                .unwrap()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_gc_plan_is_a_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        let orphan = store_object_bytes(project_root, b"orphan").unwrap();
+
+        let plan = gc_plan(project_root).unwrap();
+        assert_eq!(plan.objects, vec![(orphan.clone(), 6)]);
+        assert_eq!(plan.total_bytes, 6);
+        assert!(object_exists(project_root, &orphan).unwrap());
+
+        let report = apply_gc_plan(project_root, &plan).unwrap();
+        assert_eq!(report.objects_removed, 1);
+        assert_eq!(report.bytes_freed, plan.total_bytes);
+        assert!(!object_exists(project_root, &orphan).unwrap());
+    }
+
+    #[test]
+    fn test_apply_gc_plan_skips_newly_referenced() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        store_object_bytes(project_root, b"v1").unwrap();
+        let plan = gc_plan(project_root).unwrap();
+        assert_eq!(plan.objects.len(), 1);
+
+        // A snapshot adopts the object after the plan was made
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+
+        assert_eq!(
+            apply_gc_plan(project_root, &plan).unwrap().objects_removed,
+            0
+        );
+        assert!(object_exists(project_root, &hash_bytes(b"v1")).unwrap());
+    }
+
+    #[test]
+    fn test_gc_keeps_delta_bases() {
+        use crate::storage::delta::get_delta_path;
+        use crate::storage::naming::encode_object_name;
+        use crate::types::ObjectEncoding;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let base_content = vec![7u8; 4096];
+        let mut new_content = base_content.clone();
+        new_content[100] = 8;
+
+        let base = store_object_bytes(project_root, &base_content).unwrap();
+        let hash = hash_bytes(&new_content);
+        assert!(store_delta_object(project_root, &hash, &new_content, &base).unwrap());
+
+        // Only the delta is referenced by a snapshot
+        let metadata = SnapshotMetadata::new(
+            SnapshotId::new("snapshot_a".to_string()),
+            "Delta only".to_string(),
+            None,
+            None,
+            vec![FileEntry::new(
+                PathBuf::from("song.als"),
+                hash.clone(),
+                4096,
+                Utc::now(),
+            )],
+        );
+        crate::metadata::persistence::save_snapshot(project_root, &metadata).unwrap();
+
+        assert_eq!(gc(project_root).unwrap().objects_removed, 0);
+        assert_eq!(load_object(project_root, &hash).unwrap(), new_content);
+
+        // A migration to base32 interrupted after renaming only the delta
+        let delta_path = get_delta_path(project_root, &hash).unwrap();
+        let renamed = delta_path.with_file_name(encode_object_name(&hash, ObjectEncoding::Base32));
+        fs::rename(&delta_path, &renamed).unwrap();
+
+        assert_eq!(gc(project_root).unwrap().objects_removed, 0);
+        assert!(object_exists(project_root, &base).unwrap());
+        assert_eq!(load_object(project_root, &hash).unwrap(), new_content);
+    }
+
+    #[test]
+    fn test_gc_rebuilds_missing_refcounts() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        store_object_bytes(project_root, b"orphan").unwrap();
+        fs::remove_file(refcounts::get_refcounts_path(project_root)).unwrap();
+
+        assert_eq!(gc(project_root).unwrap().objects_removed, 1);
+        assert!(object_exists(project_root, &hash_bytes(b"v1")).unwrap());
+        assert!(load_refcounts(project_root).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_gc_trusts_refcounts() {
+        use crate::metadata::get_snapshot_path;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let id = create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        store_object_bytes(project_root, b"orphan").unwrap();
+
+        // An unreadable snapshot would fail gc if it were loaded
+        fs::write(get_snapshot_path(project_root, &id), "garbage").unwrap();
+
+        assert_eq!(gc(project_root).unwrap().objects_removed, 1);
+        assert!(object_exists(project_root, &hash_bytes(b"v1")).unwrap());
+    }
+
+    #[test]
+    fn test_gc_rebuilds_unparseable_refcounts() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        fs::write(refcounts::get_refcounts_path(project_root), "{ torn").unwrap();
+
+        assert_eq!(gc(project_root).unwrap().objects_removed, 0);
+        assert!(object_exists(project_root, &hash_bytes(b"v1")).unwrap());
+        assert_eq!(
+            load_refcounts(project_root).unwrap().unwrap()[&hash_bytes(b"v1")],
+            1
+        );
+    }
+
+    #[test]
+    fn test_gc_refuses_external_store() {
+        use crate::metadata::config::save_config;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path().join("mix");
+        let shared = temp_dir.path().join("shared");
+        fs::create_dir_all(&project_root).unwrap();
+        fs::create_dir(&shared).unwrap();
+        init_repository(&project_root).unwrap();
+
+        let mut config = load_config(&project_root).unwrap();
+        config.objects_dir = Some(shared);
+        save_config(&project_root, &config).unwrap();
+
+        assert!(matches!(gc(&project_root), Err(MovsError::ConfigError(_))));
+        assert_eq!(cleanup_incomplete(&project_root).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_cleanup_incomplete() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+
+        // A crash after staging an object and starting the metadata write
+        let orphan = store_object_bytes(project_root, b"staged").unwrap();
+        let snapshots_dir = crate::metadata::get_snapshots_dir(project_root);
+        fs::write(snapshots_dir.join(".tmpAbC123"), b"{\"id\":").unwrap();
+
+        assert_eq!(cleanup_incomplete(project_root).unwrap(), 2);
+        assert!(!object_exists(project_root, &orphan).unwrap());
+        assert!(object_exists(project_root, &hash_bytes(b"v1")).unwrap());
+        assert!(!snapshots_dir.join(".tmpAbC123").exists());
+        assert_eq!(cleanup_incomplete(project_root).unwrap(), 0);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::error::Result;
+use crate::metadata::get_movs_dir;
+use crate::metadata::lock::lock_for_write;
+use crate::metadata::persistence::iter_snapshots;
+use crate::types::{FileHash, HashAlgorithm, SnapshotMetadata};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// Reference count file name inside `.movs`
+pub const REFCOUNTS_FILE: &str = "refcounts.json";
+
+/// Number of snapshots referencing each object
+///
+/// Objects with no references are absent rather than stored with a zero.
+pub type RefCounts = HashMap<FileHash, u64>;
+
+/// Get the path to the reference count file
+pub fn get_refcounts_path(project_root: &Path) -> PathBuf {
+    get_movs_dir(project_root).join(REFCOUNTS_FILE)
+}
+
+/// Load the persisted reference counts
+///
+/// Returns `None` when the file does not exist, which is the case for
+/// repositories created before reference counting and after a failed
+/// update. Call `rebuild_refcounts` to recreate it. Counts can be too high
+/// after an interrupted write, never too low.
+pub fn load_refcounts(project_root: &Path) -> Result<Option<RefCounts>> {
+    let content = match fs::read_to_string(get_refcounts_path(project_root)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let raw: BTreeMap<String, u64> = serde_json::from_str(&content)?;
+    let mut counts = RefCounts::with_capacity(raw.len());
+    for (hex, count) in raw {
+        counts.insert(
+            FileHash::from_hex_checked(&hex, HashAlgorithm::Sha256)?,
+            count,
+        );
+    }
+
+    Ok(Some(counts))
+}
+
+/// Recount references from every snapshot and persist the result
+///
+/// This is the repair path for when the persisted counts drifted from the
+/// snapshots on disk, for example after a crash between writing a snapshot
+/// and updating its counts.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn rebuild_refcounts(project_root: &Path) -> Result<RefCounts> {
+    let _lock = lock_for_write(project_root)?;
+    let mut counts = RefCounts::new();
+
+    for metadata in iter_snapshots(project_root)? {
+        for hash in distinct_hashes(&metadata?) {
+            *counts.entry(hash.clone()).or_insert(0) += 1;
+        }
+    }
+
+    save_refcounts(project_root, &counts)?;
+    Ok(counts)
+}
+
+/// Write an empty reference count file for a new repository
+pub(crate) fn init_refcounts(project_root: &Path) -> Result<()> {
+    save_refcounts(project_root, &RefCounts::new())
+}
+
+/// Move the counts from one version of a snapshot to another
+///
+/// Pass `removed` when a snapshot is deleted or overwritten, and `added`
+/// when one is written. Does nothing for repositories without a count file.
+/// If the update fails the file is removed, so that `gc` rebuilds it
+/// instead of trusting stale counts. Callers hold the repository lock.
+pub(crate) fn update_refcounts(
+    project_root: &Path,
+    removed: Option<&SnapshotMetadata>,
+    added: Option<&SnapshotMetadata>,
+) {
+    let result = (|| -> Result<()> {
+        let Some(mut counts) = load_refcounts(project_root)? else {
+            return Ok(());
+        };
+
+        if let Some(metadata) = removed {
+            for hash in distinct_hashes(metadata) {
+                if let Some(count) = counts.get_mut(hash) {
+                    *count = count.saturating_sub(1);
+                    if *count == 0 {
+                        counts.remove(hash);
+                    }
+                }
+            }
+        }
+        if let Some(metadata) = added {
+            for hash in distinct_hashes(metadata) {
+                *counts.entry(hash.clone()).or_insert(0) += 1;
+            }
+        }
+
+        save_refcounts(project_root, &counts)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(get_refcounts_path(project_root));
+    }
+}
+
+/// Check if two versions of a snapshot reference the same objects
+pub(crate) fn same_references(a: &SnapshotMetadata, b: &SnapshotMetadata) -> bool {
+    distinct_hashes(a) == distinct_hashes(b)
+}
+
+/// Each object a snapshot references, counted once
+fn distinct_hashes(metadata: &SnapshotMetadata) -> HashSet<&FileHash> {
+    metadata.files.iter().map(|entry| &entry.hash).collect()
+}
+
+fn save_refcounts(project_root: &Path, counts: &RefCounts) -> Result<()> {
+    // Sorted hex keys keep the file stable and diffable
+    let raw: BTreeMap<String, u64> = counts
+        .iter()
+        .map(|(hash, count)| (hash.to_hex(), *count))
+        .collect();
+    let json = serde_json::to_string_pretty(&raw)?;
+
+    let mut temp = NamedTempFile::new_in(get_movs_dir(project_root))?;
+    temp.write_all(json.as_bytes())?;
+    temp.persist(get_refcounts_path(project_root))
+        .map_err(|e| e.error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_bytes;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::delete_snapshot;
+    use crate::snapshot::create_snapshot;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_counts_follow_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        assert_eq!(
+            load_refcounts(project_root).unwrap(),
+            Some(RefCounts::new())
+        );
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        fs::write(project_root.join("kick.wav"), b"kick").unwrap();
+        let first = create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        create_snapshot(project_root, "v2", None, &Default::default()).unwrap();
+
+        let counts = load_refcounts(project_root).unwrap().unwrap();
+        assert_eq!(counts[&hash_bytes(b"kick")], 2);
+        assert_eq!(counts[&hash_bytes(b"v1")], 1);
+
+        delete_snapshot(project_root, &first).unwrap();
+        let counts = load_refcounts(project_root).unwrap().unwrap();
+        assert_eq!(counts[&hash_bytes(b"kick")], 1);
+        assert!(!counts.contains_key(&hash_bytes(b"v1")));
+        assert_eq!(counts, rebuild_refcounts(project_root).unwrap());
+    }
+
+    #[test]
+    fn test_rebuild_repairs_drift() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        save_refcounts(project_root, &RefCounts::new()).unwrap();
+
+        let counts = rebuild_refcounts(project_root).unwrap();
+        assert_eq!(counts[&hash_bytes(b"v1")], 1);
+        assert_eq!(load_refcounts(project_root).unwrap(), Some(counts));
+    }
+
+    #[test]
+    fn test_annotation_edits_keep_counts() {
+        use crate::metadata::persistence::{load_snapshot, save_snapshot};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let id = create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        let before = fs::metadata(get_refcounts_path(project_root)).unwrap();
+
+        let mut metadata = load_snapshot(project_root, &id).unwrap();
+        metadata.message = "renamed".to_string();
+        save_snapshot(project_root, &metadata).unwrap();
+
+        let after = fs::metadata(get_refcounts_path(project_root)).unwrap();
+        assert_eq!(before.modified().unwrap(), after.modified().unwrap());
+        assert_eq!(
+            load_refcounts(project_root).unwrap().unwrap()[&hash_bytes(b"v1")],
+            1
+        );
+    }
+
+    #[test]
+    fn test_missing_file_is_not_created_by_updates() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::remove_file(get_refcounts_path(project_root)).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+
+        assert_eq!(load_refcounts(project_root).unwrap(), None);
+    }
+}
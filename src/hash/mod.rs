@@ -1,17 +1,21 @@
 use crate::error::{MovsError, Result};
+use crate::tree::path::fs_path;
 use crate::types::FileHash;
 use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::path::Path;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
 
 const BUFFER_SIZE: usize = 1024 * 1024; // 1 MB 
 
 /// Calculate SHA-256 hash of a file
 /// 
 /// This function streams the file content to avoid loading large files into memory.
+/// Paths beyond Windows' `MAX_PATH` are opened through `fs_path`.
 pub fn hash_file(path: &Path) -> Result<FileHash> {
-    let file = File::open(path).map_err(|e| MovsError::HashError {
+    let file = File::open(fs_path(path)?).map_err(|e| MovsError::HashError {
         path: path.to_path_buf(),
         source: e,
     })?;
@@ -37,25 +41,250 @@ pub fn hash_file(path: &Path) -> Result<FileHash> {
     Ok(FileHash::new(hash_bytes))
 }
 
+/// Calculate SHA-256 hash of a file, failing if it changed while being read
+///
+/// The file's size and modification time are recorded before hashing and
+/// compared again afterwards. If either differs (e.g. a DAW is still
+/// rendering into it), `MovsError::FileChangedDuringRead` is returned so the
+/// caller can retry instead of recording a torn state.
+pub fn hash_file_checked(path: &Path) -> Result<FileHash> {
+    hash_file_checked_with(path, || {})
+}
+
+fn hash_file_checked_with(path: &Path, after_hash: impl FnOnce()) -> Result<FileHash> {
+    let target = fs_path(path)?;
+    let stat = || {
+        std::fs::metadata(&target)
+            .and_then(|m| Ok((m.len(), m.modified()?)))
+            .map_err(|e| MovsError::HashError {
+                path: path.to_path_buf(),
+                source: e,
+            })
+    };
+
+    let before = stat()?;
+    let hash = hash_file(path)?;
+    after_hash();
+
+    if stat()? != before {
+        return Err(MovsError::FileChangedDuringRead(path.to_path_buf()));
+    }
+
+    Ok(hash)
+}
+
+/// Calculate SHA-256 hash of everything a reader yields
+///
+/// The input is streamed in fixed-size chunks, so pipes and sockets of any
+/// length can be hashed. There is no path to attach to a read failure, so
+/// errors are returned as plain `MovsError::Io`.
+pub fn hash_reader(mut reader: impl Read) -> Result<FileHash> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    loop {
+        let bytes_read = match reader.read(&mut buffer) {
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(MovsError::Io(e)),
+        };
+
+        if bytes_read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(FileHash::new(hasher.finalize().to_vec()))
+}
+
+/// Calculate SHA-256 hash of standard input until end of file
+///
+/// For piped content, e.g. `render | movs hash-object`. Like `hash_reader`,
+/// errors carry no path context.
+pub fn hash_stdin() -> Result<FileHash> {
+    hash_reader(std::io::stdin().lock())
+}
+
+/// Calculate SHA-256 hash of an in-memory byte slice
+pub fn hash_bytes(data: &[u8]) -> FileHash {
+    FileHash::new(Sha256::digest(data).to_vec())
+}
+
+/// Calculate a fast, non-cryptographic 64-bit hash of a file (xxHash64)
+///
+/// Only meant for "did this file change?" checks, where it is several times
+/// cheaper than SHA-256. Quick hashes are never used as object names; every
+/// object is still addressed by its SHA-256 hash.
+pub fn quick_hash(path: &Path) -> Result<u64> {
+    let hash_error = |e| MovsError::HashError {
+        path: path.to_path_buf(),
+        source: e,
+    };
+
+    let mut reader =
+        BufReader::with_capacity(BUFFER_SIZE, File::open(fs_path(path)?).map_err(hash_error)?);
+    let mut hasher = twox_hash::XxHash64::with_seed(0);
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    loop {
+        let bytes_read = reader.read(&mut buffer).map_err(hash_error)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.write(&buffer[..bytes_read]);
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Batches with fewer files than this are hashed serially by default
+pub const PARALLEL_MIN_FILES: usize = 8;
+
+/// Batches with less total content than this are hashed serially by default
+pub const PARALLEL_MIN_BYTES: u64 = 256 * 1024; // 256 KB
+
+/// Size below which a batch of files is hashed on the calling thread
+///
+/// For a handful of small files the cost of spreading work over the rayon
+/// pool outweighs the gain; `examples/hash_parallel_bench.rs` measures the
+/// crossover that the defaults are based on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParallelThreshold {
+    /// Minimum number of files for parallel hashing
+    pub min_files: usize,
+
+    /// Minimum total size in bytes for parallel hashing
+    pub min_bytes: u64,
+}
+
+impl ParallelThreshold {
+    /// Always hash in parallel, regardless of batch size
+    pub fn always() -> Self {
+        Self {
+            min_files: 0,
+            min_bytes: 0,
+        }
+    }
+
+    /// Never hash in parallel
+    pub fn never() -> Self {
+        Self {
+            min_files: usize::MAX,
+            min_bytes: u64::MAX,
+        }
+    }
+}
+
+impl Default for ParallelThreshold {
+    fn default() -> Self {
+        Self {
+            min_files: PARALLEL_MIN_FILES,
+            min_bytes: PARALLEL_MIN_BYTES,
+        }
+    }
+}
+
 /// Calculate hashes for multiple files in parallel
 /// 
 /// Uses rayon for parallel processing to speed up hashing of multiple files.
+/// Small batches (see `ParallelThreshold::default`) are hashed serially, as
+/// is everything when the `parallel` feature is disabled.
 pub fn hash_files_parallel<'a, I>(paths: I) -> Vec<(std::path::PathBuf, Result<FileHash>)>
 where
     I: IntoIterator<Item = &'a Path>,
     I::IntoIter: Send,
 {
-    use rayon::prelude::*;
-
-    paths
-        .into_iter()
-        .collect::<Vec<_>>()
-        .par_iter()
-        .map(|&path| {
-            let hash_result = hash_file(path);
-            (path.to_path_buf(), hash_result)
-        })
-        .collect()
+    hash_files_parallel_with_threshold(paths, ParallelThreshold::default())
+}
+
+/// Calculate hashes for multiple files, in parallel only above a threshold
+///
+/// The batch is hashed serially if it has fewer than `threshold.min_files`
+/// files or less than `threshold.min_bytes` of content in total. Results
+/// are in input order either way.
+pub fn hash_files_parallel_with_threshold<'a, I>(
+    paths: I,
+    threshold: ParallelThreshold,
+) -> Vec<(std::path::PathBuf, Result<FileHash>)>
+where
+    I: IntoIterator<Item = &'a Path>,
+    I::IntoIter: Send,
+{
+    let paths: Vec<&Path> = paths.into_iter().collect();
+    let hash_one = |path: &&Path| (path.to_path_buf(), hash_file(path));
+
+    if paths.len() < threshold.min_files || total_size_below(&paths, threshold.min_bytes) {
+        return paths.iter().map(hash_one).collect();
+    }
+
+    crate::parallel::par_map(&paths, hash_one)
+}
+
+/// Check if the files add up to less than `limit` bytes, stopping early
+///
+/// Files that can't be stat'ed count as empty; hashing reports their error.
+fn total_size_below(paths: &[&Path], limit: u64) -> bool {
+    let mut total = 0u64;
+    for path in paths {
+        if total >= limit {
+            return false;
+        }
+        total += std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    }
+    total < limit
+}
+
+/// Calculate hashes for multiple files in parallel, split by outcome
+///
+/// Successful hashes are returned in a map keyed by path for lookup; files
+/// that could not be hashed are returned separately with their error.
+pub fn hash_files_parallel_map<'a, I>(
+    paths: I,
+) -> (HashMap<PathBuf, FileHash>, Vec<(PathBuf, MovsError)>)
+where
+    I: IntoIterator<Item = &'a Path>,
+    I::IntoIter: Send,
+{
+    let mut hashes = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (path, result) in hash_files_parallel(paths) {
+        match result {
+            Ok(hash) => {
+                hashes.insert(path, hash);
+            }
+            Err(e) => errors.push((path, e)),
+        }
+    }
+
+    (hashes, errors)
+}
+
+/// Collect the output of `hash_files_parallel`, failing on the first error
+///
+/// The fail-fast counterpart to `hash_files_parallel_map`: the first
+/// failure in input order is returned, and I/O errors are wrapped in
+/// `MovsError::HashError` so they name the file. Otherwise every hash is
+/// returned keyed by path.
+pub fn collect_hash_results<I>(results: I) -> Result<HashMap<PathBuf, FileHash>>
+where
+    I: IntoIterator<Item = (PathBuf, Result<FileHash>)>,
+{
+    let mut hashes = HashMap::new();
+
+    for (path, result) in results {
+        match result {
+            Ok(hash) => {
+                hashes.insert(path, hash);
+            }
+            Err(MovsError::Io(source)) => return Err(MovsError::HashError { path, source }),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(hashes)
 }
 
 /// Check if two files have the same content by comparing their hashes
@@ -65,6 +294,71 @@ pub fn files_identical(path1: &Path, path2: &Path) -> Result<bool> {
     Ok(hash1 == hash2)
 }
 
+/// Find the byte offset at which two files first differ
+///
+/// Both files are streamed side by side in fixed-size chunks, so this works
+/// on arbitrarily large files. Returns `None` if the files are identical.
+/// If one file is a prefix of the other, the offset is the shorter file's
+/// length.
+pub fn first_difference(path1: &Path, path2: &Path) -> Result<Option<u64>> {
+    let open = |path: &Path| {
+        File::open(fs_path(path)?).map_err(|e| MovsError::HashError {
+            path: path.to_path_buf(),
+            source: e,
+        })
+    };
+    let mut reader1 = open(path1)?;
+    let mut reader2 = open(path2)?;
+
+    let mut buffer1 = vec![0u8; BUFFER_SIZE];
+    let mut buffer2 = vec![0u8; BUFFER_SIZE];
+    let mut offset = 0u64;
+
+    loop {
+        let len1 = read_full(&mut reader1, &mut buffer1, path1)?;
+        let len2 = read_full(&mut reader2, &mut buffer2, path2)?;
+
+        let common = len1.min(len2);
+        if let Some(i) = buffer1[..common]
+            .iter()
+            .zip(&buffer2[..common])
+            .position(|(a, b)| a != b)
+        {
+            return Ok(Some(offset + i as u64));
+        }
+
+        if len1 != len2 {
+            return Ok(Some(offset + common as u64));
+        }
+        if len1 == 0 {
+            return Ok(None);
+        }
+
+        offset += common as u64;
+    }
+}
+
+/// Fill `buffer` as far as possible, stopping early only at end of file
+fn read_full(reader: &mut impl Read, buffer: &mut [u8], path: &Path) -> Result<usize> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                return Err(MovsError::HashError {
+                    path: path.to_path_buf(),
+                    source: e,
+                })
+            }
+        }
+    }
+
+    Ok(filled)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -93,6 +387,48 @@ mod tests {
         assert_eq!(hash, hash2);
     }
 
+    #[test]
+    fn test_hash_reader_matches_hash_bytes() {
+        assert_eq!(hash_reader(&b"Hello, MOVS!"[..]).unwrap(), hash_bytes(b"Hello, MOVS!"));
+        assert_eq!(hash_reader(std::io::empty()).unwrap(), hash_bytes(b""));
+
+        // Spans several buffers
+        let data = vec![7u8; BUFFER_SIZE * 2 + 3];
+        let streamed = hash_reader(std::io::repeat(7).take(data.len() as u64)).unwrap();
+        assert_eq!(streamed, hash_bytes(&data));
+    }
+
+    #[test]
+    fn test_collect_hash_results() {
+        let temp_dir = TempDir::new().unwrap();
+        let kick = temp_dir.path().join("kick.wav");
+        let snare = temp_dir.path().join("snare.wav");
+        let missing = temp_dir.path().join("missing.wav");
+        fs::write(&kick, b"kick").unwrap();
+        fs::write(&snare, b"snare").unwrap();
+
+        let hashes =
+            collect_hash_results(hash_files_parallel([kick.as_path(), snare.as_path()])).unwrap();
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[&kick], hash_bytes(b"kick"));
+
+        let results = hash_files_parallel([kick.as_path(), missing.as_path()]);
+        match collect_hash_results(results) {
+            Err(MovsError::HashError { path, .. }) => assert_eq!(path, missing),
+            other => panic!("expected a hash error, got {:?}", other),
+        }
+
+        // Bare I/O errors gain the path
+        let results = vec![(
+            snare.clone(),
+            Err(MovsError::Io(std::io::ErrorKind::Other.into())),
+        )];
+        match collect_hash_results(results) {
+            Err(MovsError::HashError { path, .. }) => assert_eq!(path, snare),
+            other => panic!("expected a hash error, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_hash_empty_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -110,6 +446,29 @@ mod tests {
         assert_eq!(hash.to_hex(), expected);
     }
 
+    #[test]
+    fn test_hash_bytes_matches_hash_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, b"Hello, MOVS!").unwrap();
+
+        assert_eq!(hash_bytes(b"Hello, MOVS!"), hash_file(&file_path).unwrap());
+    }
+
+    #[test]
+    fn test_quick_hash_detects_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("kick.wav");
+
+        fs::write(&file_path, b"kick v1").unwrap();
+        let first = quick_hash(&file_path).unwrap();
+        assert_eq!(quick_hash(&file_path).unwrap(), first);
+        assert_eq!(first, twox_hash::XxHash64::oneshot(0, b"kick v1"));
+
+        fs::write(&file_path, b"kick v2").unwrap();
+        assert_ne!(quick_hash(&file_path).unwrap(), first);
+    }
+
     #[test]
     fn test_hash_large_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -197,4 +556,105 @@ mod tests {
         assert_eq!(hash1, hash2);
         assert_eq!(hash2, hash3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_first_difference() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a.wav");
+        let b = temp_dir.path().join("b.wav");
+
+        let mut content = vec![0u8; BUFFER_SIZE + 100];
+        fs::write(&a, &content).unwrap();
+        fs::write(&b, &content).unwrap();
+        assert_eq!(first_difference(&a, &b).unwrap(), None);
+
+        // Difference in the second chunk
+        content[BUFFER_SIZE + 10] = 1;
+        fs::write(&b, &content).unwrap();
+        assert_eq!(
+            first_difference(&a, &b).unwrap(),
+            Some(BUFFER_SIZE as u64 + 10)
+        );
+
+        // One file is a prefix of the other
+        fs::write(&a, b"kick").unwrap();
+        fs::write(&b, b"kick drum").unwrap();
+        assert_eq!(first_difference(&a, &b).unwrap(), Some(4));
+        assert_eq!(first_difference(&b, &a).unwrap(), Some(4));
+    }
+
+    #[test]
+    fn test_hash_file_checked() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("render.wav");
+        fs::write(&file_path, b"rendered audio").unwrap();
+
+        assert_eq!(
+            hash_file_checked(&file_path).unwrap(),
+            hash_file(&file_path).unwrap()
+        );
+
+        // Simulate the DAW appending while we hash
+        let result = hash_file_checked_with(&file_path, || {
+            fs::OpenOptions::new()
+                .append(true)
+                .open(&file_path)
+                .unwrap()
+                .write_all(b" more")
+                .unwrap();
+        });
+        assert!(matches!(result, Err(MovsError::FileChangedDuringRead(_))));
+    }
+
+    #[test]
+    fn test_hash_files_parallel_map() {
+        let temp_dir = TempDir::new().unwrap();
+        let present = temp_dir.path().join("kick.wav");
+        let missing = temp_dir.path().join("missing.wav");
+        fs::write(&present, b"kick").unwrap();
+
+        let (hashes, errors) = hash_files_parallel_map([present.as_path(), missing.as_path()]);
+
+        assert_eq!(hashes.len(), 1);
+        assert_eq!(hashes[&present], hash_bytes(b"kick"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, missing);
+    }
+
+    #[test]
+    fn test_hash_files_threshold_modes_agree() {
+        let temp_dir = TempDir::new().unwrap();
+        let paths: Vec<_> = (0..20)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("take{}.wav", i));
+                fs::write(&path, format!("take {}", i)).unwrap();
+                path
+            })
+            .collect();
+
+        let hashes = |threshold| -> Vec<(PathBuf, FileHash)> {
+            hash_files_parallel_with_threshold(paths.iter().map(|p| p.as_path()), threshold)
+                .into_iter()
+                .map(|(path, result)| (path, result.unwrap()))
+                .collect()
+        };
+
+        let serial = hashes(ParallelThreshold::never());
+        assert_eq!(serial, hashes(ParallelThreshold::always()));
+        assert_eq!(serial, hashes(ParallelThreshold::default()));
+        assert_eq!(serial[3], (paths[3].clone(), hash_bytes(b"take 3")));
+    }
+
+    #[test]
+    fn test_total_size_below() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a");
+        let b = temp_dir.path().join("b");
+        fs::write(&a, [0u8; 10]).unwrap();
+        fs::write(&b, [0u8; 10]).unwrap();
+
+        assert!(total_size_below(&[&a, &b], 21));
+        assert!(!total_size_below(&[&a, &b], 20));
+        assert!(total_size_below(&[&a, &temp_dir.path().join("missing")], 11));
+    }
+}
@@ -7,11 +7,37 @@ pub mod error;
 pub mod types;
 pub mod hash;
 pub mod metadata;
+pub mod observer;
+pub mod clock;
+pub mod storage;
+pub mod diff;
+pub mod tree;
+pub mod snapshot;
+pub mod restore;
+pub mod repository;
+mod retry;
+mod parallel;
+pub mod stats;
+pub mod verify;
+pub mod export;
+pub mod gc;
+pub mod preview;
+pub mod log;
+pub mod recover;
+#[cfg(feature = "watch")]
+pub mod watch;
 
 // Public exports
 pub use error::{MovsError, Result};
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use observer::{NullObserver, Observer};
+pub use repository::{MemoryRepository, Repository};
+#[cfg(feature = "watch")]
+pub use watch::Watcher;
 pub use types::{
-    FileEntry, FileHash, SnapshotDiff, SnapshotId, SnapshotMetadata,
+    DiffPercentages, EntryKind, FileEntry, FileHash, FileType, HashAlgorithm, IdScheme,
+    ObjectEncoding, SnapshotBuilder, SnapshotDiff, SnapshotId, SnapshotMetadata, StorageMode,
+    SHORT_HASH_LEN, SNAPSHOT_SCHEMA_VERSION,
 };
 
 /// Library version constant
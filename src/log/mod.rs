@@ -0,0 +1,228 @@
+use crate::error::Result;
+use crate::metadata::get_movs_dir;
+use crate::observer::Observer;
+use crate::types::SnapshotId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Operations log file name inside `.movs`
+pub const OPLOG_FILE: &str = "oplog.jsonl";
+
+/// Kind of repository mutation recorded in the operations log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// A snapshot was created
+    Snapshot,
+
+    /// A snapshot was restored into a directory
+    Restore,
+
+    /// A snapshot was deleted
+    Delete,
+
+    /// A range of snapshots was squashed into one
+    Squash,
+
+    /// Two snapshots were merged into a new one
+    Merge,
+}
+
+/// One line of the operations log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpLogEntry {
+    /// When the operation completed
+    pub timestamp: DateTime<Utc>,
+
+    /// What was done
+    pub operation: Operation,
+
+    /// Snapshot the operation applied to
+    pub snapshot_id: Option<SnapshotId>,
+
+    /// Who did it: the snapshot author if given, otherwise the OS user
+    pub author: Option<String>,
+}
+
+/// Get the path to the operations log
+pub fn get_oplog_path(project_root: &Path) -> PathBuf {
+    get_movs_dir(project_root).join(OPLOG_FILE)
+}
+
+/// Append an entry to the operations log
+///
+/// Each entry is written as a single line with one `write` call, so
+/// concurrent appends do not interleave within a line. If a crash left the
+/// last line unterminated, the entry starts on a fresh line rather than
+/// being glued onto the torn one.
+pub fn append_oplog(project_root: &Path, entry: &OpLogEntry) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(get_oplog_path(project_root))?;
+
+    let mut line = String::new();
+    if file.metadata()?.len() > 0 {
+        let mut last = [0u8];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            line.push('\n');
+        }
+    }
+    line.push_str(&serde_json::to_string(entry)?);
+    line.push('\n');
+
+    file.write_all(line.as_bytes())?;
+
+    Ok(())
+}
+
+/// Read every entry of the operations log, oldest first
+///
+/// A missing log yields no entries. Malformed lines, such as one truncated
+/// by a crash mid-append, are skipped so one torn write cannot make the
+/// rest of the log unreadable.
+pub fn read_oplog(project_root: &Path) -> Result<Vec<OpLogEntry>> {
+    let path = get_oplog_path(project_root);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Record an operation without ever failing the caller
+///
+/// Auditing must not block the operation itself, so a failed append is
+/// reported through `Observer::on_warning` and otherwise ignored.
+pub(crate) fn record(
+    project_root: &Path,
+    operation: Operation,
+    snapshot_id: Option<&SnapshotId>,
+    author: Option<&str>,
+    observer: &dyn Observer,
+) {
+    let entry = OpLogEntry {
+        timestamp: Utc::now(),
+        operation,
+        snapshot_id: snapshot_id.cloned(),
+        author: author.map(str::to_string).or_else(current_user),
+    };
+
+    if let Err(e) = append_oplog(project_root, &entry) {
+        observer.on_warning(&format!("Failed to write operations log: {}", e));
+    }
+}
+
+/// Name of the logged-in OS user, if known
+fn current_user() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        .filter(|user| !user.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::delete_snapshot;
+    use crate::restore::{restore_snapshot, RestoreOptions};
+    use crate::snapshot::create_snapshot;
+    use std::cell::RefCell;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_mutating_operations_are_logged() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        assert!(read_oplog(project_root).unwrap().is_empty());
+
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        let id = create_snapshot(project_root, "First", Some("ana"), &Default::default()).unwrap();
+        restore_snapshot(
+            project_root,
+            &id,
+            &temp_dir.path().join("out"),
+            &RestoreOptions::default(),
+        )
+        .unwrap();
+        delete_snapshot(project_root, &id).unwrap();
+
+        let log = read_oplog(project_root).unwrap();
+        let ops: Vec<_> = log.iter().map(|entry| entry.operation).collect();
+        assert_eq!(
+            ops,
+            vec![Operation::Snapshot, Operation::Restore, Operation::Delete]
+        );
+        assert!(log
+            .iter()
+            .all(|entry| entry.snapshot_id.as_ref() == Some(&id)));
+        assert_eq!(log[0].author.as_deref(), Some("ana"));
+    }
+
+    #[test]
+    fn test_oplog_recovers_from_torn_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        record(
+            project_root,
+            Operation::Delete,
+            None,
+            Some("ana"),
+            &crate::NullObserver,
+        );
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(get_oplog_path(project_root))
+            .unwrap();
+        file.write_all(b"{\"timestamp\":").unwrap();
+
+        assert_eq!(read_oplog(project_root).unwrap().len(), 1);
+
+        // The next entry starts on its own line, after the torn one
+        record(
+            project_root,
+            Operation::Snapshot,
+            None,
+            Some("ana"),
+            &crate::NullObserver,
+        );
+        let ops: Vec<_> = read_oplog(project_root)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.operation)
+            .collect();
+        assert_eq!(ops, vec![Operation::Delete, Operation::Snapshot]);
+    }
+
+    #[test]
+    fn test_record_failure_does_not_propagate() {
+        struct Warnings(RefCell<Vec<String>>);
+        impl Observer for Warnings {
+            fn on_warning(&self, message: &str) {
+                self.0.borrow_mut().push(message.to_string());
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let warnings = Warnings(RefCell::new(Vec::new()));
+
+        // No repository, so the log cannot be opened
+        record(temp_dir.path(), Operation::Snapshot, None, None, &warnings);
+        assert_eq!(warnings.0.borrow().len(), 1);
+    }
+}
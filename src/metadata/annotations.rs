@@ -0,0 +1,145 @@
+use crate::error::Result;
+use crate::metadata::persistence::{load_snapshot, save_snapshot};
+use crate::types::SnapshotId;
+use std::path::Path;
+
+/// Set a key/value annotation on an existing snapshot
+///
+/// The snapshot file is rewritten atomically. Passing `None` removes the key.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `snapshot_id` - Snapshot to annotate
+/// * `key` - Annotation key
+/// * `value` - New value, or `None` to remove the annotation
+pub fn set_annotation(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    key: &str,
+    value: Option<&str>,
+) -> Result<()> {
+    let mut metadata = load_snapshot(project_root, snapshot_id)?;
+
+    match value {
+        Some(value) => {
+            metadata.metadata.insert(key.to_string(), value.to_string());
+        }
+        None => {
+            metadata.metadata.remove(key);
+        }
+    }
+
+    save_snapshot(project_root, &metadata)
+}
+
+/// Get a key/value annotation from a snapshot
+pub fn get_annotation(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    key: &str,
+) -> Result<Option<String>> {
+    Ok(load_snapshot(project_root, snapshot_id)?
+        .metadata
+        .remove(key))
+}
+
+/// Set or clear the long-form description of an existing snapshot
+///
+/// The snapshot file is rewritten atomically.
+pub fn set_description(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    description: Option<&str>,
+) -> Result<()> {
+    let mut metadata = load_snapshot(project_root, snapshot_id)?;
+    metadata.description = description.map(str::to_string);
+    save_snapshot(project_root, &metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::{get_snapshot_path, get_snapshots_dir};
+    use crate::types::SnapshotMetadata;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn save_empty_snapshot(project_root: &Path) -> SnapshotId {
+        let metadata = SnapshotMetadata::new(
+            SnapshotId::new("snapshot_1".to_string()),
+            "Rough mix".to_string(),
+            None,
+            None,
+            Vec::new(),
+        );
+        save_snapshot(project_root, &metadata).unwrap();
+        metadata.id
+    }
+
+    #[test]
+    fn test_annotations_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        let id = save_empty_snapshot(project_root);
+
+        assert_eq!(get_annotation(project_root, &id, "bpm").unwrap(), None);
+
+        set_annotation(project_root, &id, "bpm", Some("128")).unwrap();
+        set_annotation(project_root, &id, "key", Some("F minor")).unwrap();
+        assert_eq!(
+            get_annotation(project_root, &id, "bpm").unwrap().as_deref(),
+            Some("128")
+        );
+
+        set_annotation(project_root, &id, "bpm", None).unwrap();
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        assert_eq!(metadata.metadata.len(), 1);
+        assert_eq!(metadata.message, "Rough mix");
+
+        // Only the snapshot itself is left, no temporary files
+        assert_eq!(
+            fs::read_dir(get_snapshots_dir(project_root))
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_set_description() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        let id = save_empty_snapshot(project_root);
+
+        set_description(project_root, &id, Some("Vocal chain: 1176 -> LA-2A")).unwrap();
+        assert_eq!(
+            load_snapshot(project_root, &id)
+                .unwrap()
+                .description
+                .as_deref(),
+            Some("Vocal chain: 1176 -> LA-2A")
+        );
+    }
+
+    #[test]
+    fn test_old_snapshots_load_without_annotations() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = SnapshotId::new("snapshot_old".to_string());
+        fs::write(
+            get_snapshot_path(project_root, &id),
+            r#"{"id":"snapshot_old","timestamp":"2024-01-01T00:00:00Z","message":"Old","author":null,"parent":null,"files":[]}"#,
+        )
+        .unwrap();
+
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        assert_eq!(metadata.description, None);
+        assert!(metadata.metadata.is_empty());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::error::{MovsError, Result};
+use bincode::config::{Configuration, Fixint, LittleEndian};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The one bincode configuration used for everything MOVS persists
+///
+/// Pinned so repositories stay readable when synced between machines.
+/// Integers, including length prefixes, are fixed-width little-endian, so
+/// a `FileHash` is an 8-byte length followed by its digest. Changing this
+/// is a repository format change. Types using `skip_serializing_if` or
+/// `flatten` cannot be stored this way, since bincode is not
+/// self-describing.
+pub const BINCODE_CONFIG: Configuration<LittleEndian, Fixint> = bincode::config::standard()
+    .with_little_endian()
+    .with_fixed_int_encoding();
+
+/// Encode a value in the repository's binary wire format
+pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    bincode::serde::encode_to_vec(value, BINCODE_CONFIG)
+        .map_err(|e| MovsError::StorageError(format!("Binary encoding failed: {}", e)))
+}
+
+/// Decode a value from the repository's binary wire format
+///
+/// Trailing bytes after the value are rejected, so a truncated or
+/// mis-framed file fails instead of decoding to something plausible.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let (value, read) = bincode::serde::decode_from_slice(bytes, BINCODE_CONFIG)
+        .map_err(|e| MovsError::StorageError(format!("Binary decoding failed: {}", e)))?;
+
+    if read != bytes.len() {
+        return Err(MovsError::StorageError(format!(
+            "Binary decoding failed: {} trailing bytes",
+            bytes.len() - read
+        )));
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_bytes;
+    use crate::types::FileHash;
+
+    #[test]
+    fn test_file_hash_wire_format() {
+        let hash = hash_bytes(b"kick");
+        let bytes = encode(&hash).unwrap();
+
+        // Fixed 8-byte little-endian length prefix, then the digest
+        assert_eq!(&bytes[..8], &32u64.to_le_bytes());
+        assert_eq!(&bytes[8..], hash.as_bytes());
+        assert_eq!(decode::<FileHash>(&bytes).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_mismatched_config_is_detected() {
+        let hashes = vec![hash_bytes(b"kick"), hash_bytes(b"snare")];
+
+        // Written by a machine using a different configuration
+        let big_endian = bincode::config::standard()
+            .with_big_endian()
+            .with_fixed_int_encoding();
+        let foreign = bincode::serde::encode_to_vec(&hashes, big_endian).unwrap();
+        assert!(decode::<Vec<FileHash>>(&foreign).is_err());
+
+        let varint = bincode::config::standard();
+        let foreign = bincode::serde::encode_to_vec(&hashes, varint).unwrap();
+        assert!(decode::<Vec<FileHash>>(&foreign).is_err());
+
+        // A separately built identical configuration reads it back
+        let same = bincode::config::standard()
+            .with_little_endian()
+            .with_fixed_int_encoding();
+        let native = bincode::serde::encode_to_vec(&hashes, same).unwrap();
+        assert_eq!(decode::<Vec<FileHash>>(&native).unwrap(), hashes);
+    }
+}
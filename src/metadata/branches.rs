@@ -0,0 +1,325 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::refs::write_head;
+use crate::metadata::{get_movs_dir, repository_exists, snapshot_exists};
+use crate::types::SnapshotId;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Subdirectory of `.movs` holding named refs
+pub const REFS_DIR: &str = "refs";
+
+/// Subdirectory of the refs directory holding one file per branch
+pub const BRANCHES_DIR: &str = "branches";
+
+/// File recording the name of the branch HEAD tracks, if any
+pub const BRANCH_FILE: &str = "BRANCH";
+
+/// Get the path to the branches directory
+pub fn get_branches_dir(project_root: &Path) -> PathBuf {
+    get_movs_dir(project_root).join(REFS_DIR).join(BRANCHES_DIR)
+}
+
+/// Get the path to a branch's ref file
+pub fn get_branch_path(project_root: &Path, name: &str) -> PathBuf {
+    get_branches_dir(project_root).join(name)
+}
+
+/// Get the path to the file naming the current branch
+pub fn get_branch_file(project_root: &Path) -> PathBuf {
+    get_movs_dir(project_root).join(BRANCH_FILE)
+}
+
+/// Check that a branch name can be stored as a single ref file
+///
+/// Names must be non-empty, must not start with `.`, and must not contain
+/// path separators, `:`, whitespace or control characters.
+pub fn validate_branch_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name
+            .chars()
+            .any(|c| matches!(c, '/' | '\\' | ':') || c.is_whitespace() || c.is_control());
+
+    if !valid {
+        return Err(MovsError::InvalidBranchName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Create a branch pointing at an existing snapshot
+///
+/// The new branch is not switched to.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `name` - Name of the new branch
+/// * `from` - Snapshot the branch starts at
+pub fn create_branch(project_root: &Path, name: &str, from: &SnapshotId) -> Result<()> {
+    check_repository(project_root)?;
+    validate_branch_name(name)?;
+
+    if !snapshot_exists(project_root, from) {
+        return Err(MovsError::SnapshotNotFound(from.to_string()));
+    }
+    if get_branch_path(project_root, name).exists() {
+        return Err(MovsError::BranchAlreadyExists(name.to_string()));
+    }
+
+    fs::create_dir_all(get_branches_dir(project_root))?;
+    write_branch(project_root, name, from)
+}
+
+/// Read the snapshot a branch points at
+pub fn read_branch(project_root: &Path, name: &str) -> Result<SnapshotId> {
+    check_repository(project_root)?;
+    validate_branch_name(name)?;
+
+    let content = match fs::read_to_string(get_branch_path(project_root, name)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(MovsError::BranchNotFound(name.to_string()))
+        }
+        Err(e) => return Err(MovsError::Io(e)),
+    };
+
+    let id = content.trim();
+    if id.is_empty() {
+        return Err(MovsError::InvalidSnapshotId(format!(
+            "Branch '{}' has an empty ref",
+            name
+        )));
+    }
+    Ok(SnapshotId::new(id.to_string()))
+}
+
+/// List every branch with the snapshot it points at, sorted by name
+pub fn list_branches(project_root: &Path) -> Result<Vec<(String, SnapshotId)>> {
+    check_repository(project_root)?;
+
+    let branches_dir = get_branches_dir(project_root);
+    if !branches_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut branches = Vec::new();
+    for entry in fs::read_dir(&branches_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        // Stray files that could not have been created as branches are skipped
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if validate_branch_name(&name).is_ok() {
+            let id = read_branch(project_root, &name)?;
+            branches.push((name, id));
+        }
+    }
+
+    branches.sort();
+    Ok(branches)
+}
+
+/// Delete a branch without touching any snapshot
+///
+/// Deleting the current branch detaches HEAD, which keeps pointing at the
+/// same snapshot.
+pub fn delete_branch(project_root: &Path, name: &str) -> Result<()> {
+    read_branch(project_root, name)?;
+
+    if current_branch(project_root)?.as_deref() == Some(name) {
+        detach_head(project_root)?;
+    }
+    fs::remove_file(get_branch_path(project_root, name))?;
+    Ok(())
+}
+
+/// Make HEAD track a branch and point it at the branch's snapshot
+///
+/// Snapshots committed afterwards advance the branch. The working tree is
+/// left as it is; restore the returned snapshot to check it out.
+pub fn switch_branch(project_root: &Path, name: &str) -> Result<SnapshotId> {
+    let id = read_branch(project_root, name)?;
+
+    fs::write(get_branch_file(project_root), format!("{}\n", name))?;
+    write_head(project_root, &id)?;
+    Ok(id)
+}
+
+/// Get the name of the branch HEAD tracks
+///
+/// Returns `None` if HEAD is detached, which is the case until a branch is
+/// switched to.
+pub fn current_branch(project_root: &Path) -> Result<Option<String>> {
+    check_repository(project_root)?;
+
+    let content = match fs::read_to_string(get_branch_file(project_root)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(MovsError::Io(e)),
+    };
+
+    let name = content.trim();
+    Ok((!name.is_empty()).then(|| name.to_string()))
+}
+
+/// Stop HEAD tracking a branch
+///
+/// HEAD keeps pointing at the same snapshot and later snapshots no longer
+/// move any branch.
+pub fn detach_head(project_root: &Path) -> Result<()> {
+    check_repository(project_root)?;
+
+    match fs::remove_file(get_branch_file(project_root)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(MovsError::Io(e)),
+        _ => Ok(()),
+    }
+}
+
+/// Point a branch at a snapshot, creating its ref if needed
+pub(crate) fn write_branch(project_root: &Path, name: &str, id: &SnapshotId) -> Result<()> {
+    fs::write(get_branch_path(project_root, name), format!("{}\n", id))?;
+    Ok(())
+}
+
+/// Move the current branch, if any, along with HEAD
+pub(crate) fn advance_current_branch(project_root: &Path, id: &SnapshotId) -> Result<()> {
+    match current_branch(project_root)? {
+        Some(name) => write_branch(project_root, &name, id),
+        None => Ok(()),
+    }
+}
+
+fn check_repository(project_root: &Path) -> Result<()> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::refs::read_head;
+    use crate::snapshot::{commit, create_snapshot};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_branch_names() {
+        for name in ["main", "risky-mix", "v2_master", "mix.alt"] {
+            assert!(validate_branch_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", ".hidden", "a/b", "a\\b", "a:b", "two words", "tab\t"] {
+            assert!(
+                matches!(
+                    validate_branch_name(name),
+                    Err(MovsError::InvalidBranchName(_))
+                ),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_and_list_branches() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        assert!(list_branches(project_root).unwrap().is_empty());
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let first = create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+
+        create_branch(project_root, "risky-mix", &first).unwrap();
+        create_branch(project_root, "main", &first).unwrap();
+        assert!(get_branch_path(project_root, "main").exists());
+        assert_eq!(
+            list_branches(project_root).unwrap(),
+            vec![
+                ("main".to_string(), first.clone()),
+                ("risky-mix".to_string(), first.clone()),
+            ]
+        );
+
+        assert!(matches!(
+            create_branch(project_root, "main", &first),
+            Err(MovsError::BranchAlreadyExists(_))
+        ));
+        assert!(matches!(
+            create_branch(project_root, "ghost", &SnapshotId::new("nope".to_string())),
+            Err(MovsError::SnapshotNotFound(_))
+        ));
+        assert!(matches!(
+            read_branch(project_root, "ghost"),
+            Err(MovsError::BranchNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_commit_advances_current_branch() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let base = create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        create_branch(project_root, "main", &base).unwrap();
+        create_branch(project_root, "risky-mix", &base).unwrap();
+        assert_eq!(current_branch(project_root).unwrap(), None);
+
+        assert_eq!(switch_branch(project_root, "risky-mix").unwrap(), base);
+        assert_eq!(
+            current_branch(project_root).unwrap().as_deref(),
+            Some("risky-mix")
+        );
+
+        fs::write(project_root.join("song.als"), b"distorted").unwrap();
+        let risky = commit(project_root, "distortion", None).unwrap().unwrap();
+        assert_eq!(read_branch(project_root, "risky-mix").unwrap(), risky);
+        assert_eq!(read_branch(project_root, "main").unwrap(), base);
+
+        // Switching back moves HEAD, and the next commit builds on main
+        switch_branch(project_root, "main").unwrap();
+        assert_eq!(read_head(project_root).unwrap(), Some(base.clone()));
+        fs::write(project_root.join("song.als"), b"clean").unwrap();
+        let clean = commit(project_root, "clean mix", None).unwrap().unwrap();
+        assert_eq!(read_branch(project_root, "main").unwrap(), clean);
+        assert_eq!(read_branch(project_root, "risky-mix").unwrap(), risky);
+        assert_eq!(
+            crate::metadata::persistence::load_snapshot(project_root, &clean)
+                .unwrap()
+                .parent,
+            Some(base)
+        );
+
+        // A detached HEAD leaves every branch alone
+        detach_head(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"detached").unwrap();
+        commit(project_root, "detached", None).unwrap();
+        assert_eq!(read_branch(project_root, "main").unwrap(), clean);
+    }
+
+    #[test]
+    fn test_delete_current_branch_detaches() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let base = create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        create_branch(project_root, "main", &base).unwrap();
+        switch_branch(project_root, "main").unwrap();
+
+        delete_branch(project_root, "main").unwrap();
+        assert_eq!(current_branch(project_root).unwrap(), None);
+        assert_eq!(read_head(project_root).unwrap(), Some(base));
+        assert!(matches!(
+            delete_branch(project_root, "main"),
+            Err(MovsError::BranchNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::error::Result;
+use crate::metadata::persistence::load_snapshot;
+use crate::metadata::{get_compressed_snapshot_path, get_snapshot_path, list_snapshots};
+use crate::types::{SnapshotId, SnapshotMetadata};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Identifies one version of a snapshot file on disk
+///
+/// Snapshots are saved by renaming a temporary file into place, so every
+/// save produces a new inode. Including it catches rewrites that keep the
+/// same size within the filesystem's mtime resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    path: PathBuf,
+    modified: SystemTime,
+    len: u64,
+    inode: Option<u64>,
+}
+
+/// Cache of parsed snapshot metadata for repeated history refreshes
+///
+/// Each snapshot is keyed by the path, modification time, size and (on
+/// unix) inode of its file. `load_all` only re-parses files whose key changed since the last
+/// call, and drops snapshots whose files were deleted. Hold one cache for
+/// the lifetime of a view that polls the repository.
+#[derive(Debug, Clone)]
+pub struct SnapshotCache {
+    project_root: PathBuf,
+    entries: HashMap<SnapshotId, (FileStamp, Arc<SnapshotMetadata>)>,
+}
+
+impl SnapshotCache {
+    /// Create an empty cache for a repository
+    pub fn new(project_root: &Path) -> Self {
+        Self {
+            project_root: project_root.to_path_buf(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Load every snapshot, re-parsing only files that changed
+    ///
+    /// Returns snapshots in the same order as `list_snapshots`.
+    pub fn load_all(&mut self) -> Result<Vec<Arc<SnapshotMetadata>>> {
+        let ids = list_snapshots(&self.project_root)?;
+        let mut entries = HashMap::with_capacity(ids.len());
+        let mut snapshots = Vec::with_capacity(ids.len());
+
+        for id in ids {
+            let stamp = self.stamp(&id)?;
+
+            let metadata = match self.entries.remove(&id) {
+                Some((cached, metadata)) if cached == stamp => metadata,
+                _ => Arc::new(load_snapshot(&self.project_root, &id)?),
+            };
+
+            snapshots.push(Arc::clone(&metadata));
+            entries.insert(id, (stamp, metadata));
+        }
+
+        // Anything left over belongs to deleted snapshots
+        self.entries = entries;
+        Ok(snapshots)
+    }
+
+    /// Number of cached snapshots
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop every cached snapshot, forcing a full reload next time
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Stamp the file `load_snapshot` would read for this ID
+    fn stamp(&self, id: &SnapshotId) -> Result<FileStamp> {
+        let compressed = get_compressed_snapshot_path(&self.project_root, id);
+        let path = if compressed.is_file() {
+            compressed
+        } else {
+            get_snapshot_path(&self.project_root, id)
+        };
+
+        let metadata = fs::metadata(&path)?;
+        Ok(FileStamp {
+            path,
+            modified: metadata.modified()?,
+            len: metadata.len(),
+            inode: inode(&metadata),
+        })
+    }
+}
+
+/// Inode number of a file, where the platform reports it
+fn inode(metadata: &fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.ino())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::{delete_snapshot, save_snapshot};
+    use tempfile::TempDir;
+
+    fn metadata(id: &str, message: &str) -> SnapshotMetadata {
+        SnapshotMetadata::new(
+            SnapshotId::new(id.to_string()),
+            message.to_string(),
+            None,
+            None,
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn test_cache_reuses_unchanged_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        save_snapshot(project_root, &metadata("snapshot_a", "First")).unwrap();
+        save_snapshot(project_root, &metadata("snapshot_b", "Second")).unwrap();
+
+        let mut cache = SnapshotCache::new(project_root);
+        let first = cache.load_all().unwrap();
+        assert_eq!(first.len(), 2);
+
+        let second = cache.load_all().unwrap();
+        assert!(Arc::ptr_eq(&first[0], &second[0]));
+        assert!(Arc::ptr_eq(&first[1], &second[1]));
+    }
+
+    #[test]
+    fn test_cache_reloads_changed_and_drops_deleted() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        save_snapshot(project_root, &metadata("snapshot_a", "First")).unwrap();
+        save_snapshot(project_root, &metadata("snapshot_b", "Second")).unwrap();
+
+        let mut cache = SnapshotCache::new(project_root);
+        let before = cache.load_all().unwrap();
+
+        save_snapshot(project_root, &metadata("snapshot_a", "First, edited")).unwrap();
+        delete_snapshot(project_root, &SnapshotId::new("snapshot_b".to_string())).unwrap();
+
+        let after = cache.load_all().unwrap();
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].message, "First, edited");
+        assert!(!Arc::ptr_eq(&before[0], &after[0]));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cache_reloads_same_size_rewrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        save_snapshot(project_root, &metadata("snapshot_a", "First")).unwrap();
+        let id = SnapshotId::new("snapshot_a".to_string());
+        let path = get_snapshot_path(project_root, &id);
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+        let mut cache = SnapshotCache::new(project_root);
+        cache.load_all().unwrap();
+
+        // Same length and mtime, only the inode differs
+        save_snapshot(project_root, &metadata("snapshot_a", "Frist")).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let after = cache.load_all().unwrap();
+        assert_eq!(after[0].message, "Frist");
+    }
+}
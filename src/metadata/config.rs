@@ -0,0 +1,386 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::{
+    get_config_file, get_movs_dir, repository_exists, OBJECTS_DIR, REPO_FORMAT_VERSION,
+};
+use crate::types::{IdScheme, ObjectEncoding, StorageMode};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Retry behaviour for transient I/O errors (e.g. on network drives)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub attempts: u32,
+
+    /// Delay before the first retry in milliseconds, doubled for each further retry
+    pub backoff_ms: u64,
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn none() -> Self {
+        Self {
+            attempts: 1,
+            backoff_ms: 0,
+        }
+    }
+
+    /// Delay before the first retry
+    pub fn backoff(&self) -> Duration {
+        Duration::from_millis(self.backoff_ms)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff_ms: 50,
+        }
+    }
+}
+
+/// Repository configuration stored in `.movs/config.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepositoryConfig {
+    /// Version of MOVS that last wrote this config
+    pub version: String,
+
+    /// On-disk repository format version
+    #[serde(default = "default_format_version")]
+    pub format_version: u32,
+
+    /// When the repository was created
+    pub created_at: DateTime<Utc>,
+
+    /// Scheme used to generate new snapshot IDs
+    #[serde(default)]
+    pub id_scheme: IdScheme,
+
+    /// Retry behaviour for transient I/O errors
+    #[serde(default)]
+    pub retry: RetryPolicy,
+
+    /// Store changed files as binary deltas against their parent version
+    #[serde(default)]
+    pub delta_objects: bool,
+
+    /// Write snapshot metadata as gzip-compressed `.json.gz` files
+    #[serde(default)]
+    pub compress_metadata: bool,
+
+    /// Gzip-compress stored objects, except already compressed audio
+    ///
+    /// Each object records whether it is compressed, so objects written
+    /// before or after changing this setting all stay readable.
+    #[serde(default)]
+    pub compress_objects: bool,
+
+    /// Number of two-character prefix directories loose objects are fanned out into (1 or 2)
+    #[serde(default = "default_fanout_depth")]
+    pub fanout_depth: u8,
+
+    /// Apply built-in ignore patterns for DAWs detected in the project root
+    ///
+    /// On for new repositories. Configs written before this option existed
+    /// leave it off, so upgrading does not silently drop files from their
+    /// next snapshot.
+    #[serde(default)]
+    pub daw_default_ignores: bool,
+
+    /// Store waveform previews for WAV files in snapshot metadata
+    #[serde(default)]
+    pub compute_previews: bool,
+
+    /// Whether objects are copied or hardlinked from the working tree
+    #[serde(default)]
+    pub storage_mode: StorageMode,
+
+    /// Let `status` skip SHA-256 for files whose xxHash64 is unchanged
+    #[serde(default)]
+    pub quick_hash_status: bool,
+
+    /// How object file names are derived from hashes
+    ///
+    /// Change it on a repository with objects via `storage::migrate_object_encoding`.
+    #[serde(default)]
+    pub object_encoding: ObjectEncoding,
+
+    /// Object store outside `.movs/objects`, e.g. shared with another working tree
+    ///
+    /// Relative paths are resolved against the project root. When several
+    /// working trees share one store, each only knows its own snapshots, so
+    /// garbage collection refuses to run: it would delete objects the other
+    /// trees still reference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub objects_dir: Option<PathBuf>,
+
+    /// Largest file size in bytes that scans will hash, if limited
+    ///
+    /// Larger files fail the scan with `MovsError::StorageError` instead of
+    /// exhausting memory. Unlimited on 64-bit targets; elsewhere the
+    /// default is the addressable size.
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: Option<u64>,
+
+    /// Keys written by other MOVS versions, preserved on save
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+fn default_format_version() -> u32 {
+    1
+}
+
+fn default_fanout_depth() -> u8 {
+    1
+}
+
+pub(crate) fn default_max_file_size() -> Option<u64> {
+    (usize::BITS < 64).then_some(usize::MAX as u64)
+}
+
+/// The supported values of `fanout_depth`
+pub(crate) const FANOUT_DEPTHS: std::ops::RangeInclusive<u8> = 1..=2;
+
+/// Check that a fan-out depth is supported
+pub fn validate_fanout_depth(depth: u8) -> Result<()> {
+    if !FANOUT_DEPTHS.contains(&depth) {
+        return Err(MovsError::ConfigError(format!(
+            "Invalid fanout_depth {}: must be 1 or 2",
+            depth
+        )));
+    }
+    Ok(())
+}
+
+impl RepositoryConfig {
+    /// Create the configuration for a freshly initialized repository
+    pub fn new() -> Self {
+        Self {
+            version: crate::VERSION.to_string(),
+            format_version: REPO_FORMAT_VERSION,
+            created_at: Utc::now(),
+            id_scheme: IdScheme::default(),
+            retry: RetryPolicy::default(),
+            delta_objects: false,
+            compress_metadata: false,
+            compress_objects: false,
+            fanout_depth: default_fanout_depth(),
+            daw_default_ignores: true,
+            compute_previews: false,
+            storage_mode: StorageMode::default(),
+            quick_hash_status: false,
+            object_encoding: ObjectEncoding::default(),
+            objects_dir: None,
+            max_file_size: default_max_file_size(),
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    /// Resolve the object store directory this config points to
+    ///
+    /// Unlike `get_objects_dir`, this does not read the config again, so
+    /// code that already holds a config resolves paths without any I/O.
+    pub fn objects_dir_in(&self, project_root: &Path) -> PathBuf {
+        match &self.objects_dir {
+            Some(dir) => project_root.join(dir),
+            None => get_movs_dir(project_root).join(OBJECTS_DIR),
+        }
+    }
+}
+
+impl Default for RepositoryConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Load the repository configuration
+pub fn load_config(project_root: &Path) -> Result<RepositoryConfig> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    let json = fs::read_to_string(get_config_file(project_root))?;
+    let config: RepositoryConfig = serde_json::from_str(&json)
+        .map_err(|e| MovsError::ConfigError(format!("Invalid config.json: {}", e)))?;
+
+    validate_fanout_depth(config.fanout_depth)?;
+    Ok(config)
+}
+
+/// Save the repository configuration
+///
+/// Changing `fanout_depth` is rejected while the store holds loose objects,
+/// since they would no longer be found at their expected paths; use
+/// `storage::migrate_fanout_depth` instead. Likewise `object_encoding`
+/// needs `storage::migrate_object_encoding` once the store holds objects.
+pub fn save_config(project_root: &Path, config: &RepositoryConfig) -> Result<()> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    validate_fanout_depth(config.fanout_depth)?;
+    let current = load_config(project_root)?;
+    if current.fanout_depth != config.fanout_depth
+        && !crate::storage::list_loose_objects(project_root)?.is_empty()
+    {
+        return Err(MovsError::ConfigError(format!(
+            "Cannot change fanout_depth from {} to {} on a repository with objects; \
+             run migrate_fanout_depth instead",
+            current.fanout_depth, config.fanout_depth
+        )));
+    }
+
+    if current.object_encoding != config.object_encoding && !object_store_is_empty(project_root)? {
+        return Err(MovsError::ConfigError(format!(
+            "Cannot change object_encoding from {:?} to {:?} on a repository with objects; \
+             run migrate_object_encoding instead",
+            current.object_encoding, config.object_encoding
+        )));
+    }
+
+    if current.objects_dir != config.objects_dir {
+        if let Some(dir) = &config.objects_dir {
+            check_objects_dir(&project_root.join(dir))?;
+        }
+        if !object_store_is_empty(project_root)? {
+            return Err(MovsError::ConfigError(
+                "Cannot change objects_dir on a repository with objects".to_string(),
+            ));
+        }
+    }
+
+    write_config_unchecked(project_root, config)
+}
+
+/// Check that a configured external object store can be used
+///
+/// Returns `MovsError::ConfigError` if `objects_dir` is set but the
+/// directory does not exist or is not writable. Nothing is checked for
+/// the default `.movs/objects`.
+pub fn validate_objects_dir(project_root: &Path) -> Result<()> {
+    match load_config(project_root)?.objects_dir {
+        Some(dir) => check_objects_dir(&project_root.join(dir)),
+        None => Ok(()),
+    }
+}
+
+fn check_objects_dir(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(MovsError::ConfigError(format!(
+            "objects_dir '{}' does not exist",
+            dir.display()
+        )));
+    }
+
+    tempfile::tempfile_in(dir).map_err(|e| {
+        MovsError::ConfigError(format!(
+            "objects_dir '{}' is not writable: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    Ok(())
+}
+
+/// Check if the current object store holds no loose, delta or packed objects
+fn object_store_is_empty(project_root: &Path) -> Result<bool> {
+    Ok(crate::storage::list_loose_objects(project_root)?.is_empty()
+        && crate::storage::delta::list_delta_objects(project_root)?.is_empty()
+        && crate::storage::pack::PackIndex::load(project_root)?.is_empty())
+}
+
+/// Write the configuration without checking for unsupported changes
+pub(crate) fn write_config_unchecked(project_root: &Path, config: &RepositoryConfig) -> Result<()> {
+    fs::write(
+        get_config_file(project_root),
+        serde_json::to_string_pretty(config)?,
+    )?;
+    Ok(())
+}
+
+/// Get the retry policy for a repository, falling back to the default
+///
+/// A missing or unreadable config never prevents an operation from running.
+pub fn retry_policy(project_root: &Path) -> RetryPolicy {
+    load_config(project_root)
+        .map(|config| config.retry)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_config_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        assert_eq!(config.format_version, REPO_FORMAT_VERSION);
+        assert_eq!(config.retry, RetryPolicy::default());
+
+        config.retry = RetryPolicy {
+            attempts: 5,
+            backoff_ms: 200,
+        };
+        save_config(project_root, &config).unwrap();
+
+        assert_eq!(load_config(project_root).unwrap(), config);
+        assert_eq!(retry_policy(project_root).attempts, 5);
+    }
+
+    #[test]
+    fn test_config_preserves_unknown_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let mut raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(get_config_file(project_root)).unwrap())
+                .unwrap();
+        raw["future_option"] = serde_json::json!(true);
+        fs::write(get_config_file(project_root), raw.to_string()).unwrap();
+
+        let config = load_config(project_root).unwrap();
+        save_config(project_root, &config).unwrap();
+
+        let saved = fs::read_to_string(get_config_file(project_root)).unwrap();
+        assert!(saved.contains("future_option"));
+    }
+
+    #[test]
+    fn test_daw_ignores_off_for_older_configs() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        assert!(load_config(project_root).unwrap().daw_default_ignores);
+
+        let mut raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(get_config_file(project_root)).unwrap())
+                .unwrap();
+        raw.as_object_mut().unwrap().remove("daw_default_ignores");
+        fs::write(get_config_file(project_root), raw.to_string()).unwrap();
+
+        assert!(!load_config(project_root).unwrap().daw_default_ignores);
+    }
+
+    #[test]
+    fn test_retry_policy_defaults_without_repository() {
+        let temp_dir = TempDir::new().unwrap();
+
+        assert_eq!(retry_policy(temp_dir.path()), RetryPolicy::default());
+    }
+}
@@ -0,0 +1,551 @@
+use crate::error::{MovsError, Result};
+use crate::gc::{gc, GcReport};
+use crate::log::{self as oplog, Operation};
+use crate::metadata::branches::{list_branches, write_branch};
+use crate::metadata::config::load_config;
+use crate::metadata::lock::lock_for_write;
+use crate::metadata::persistence::{
+    delete_snapshot, load_all_snapshots, load_snapshot, save_snapshot,
+};
+use crate::metadata::refs::{clear_head, read_head, write_head};
+use crate::observer::NullObserver;
+use crate::types::{FileHash, SnapshotId, SnapshotMetadata};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Load a snapshot and all of its ancestors by following parent links
+///
+/// Snapshots are returned newest first, starting with `start`.
+pub fn ancestors(project_root: &Path, start: &SnapshotId) -> Result<Vec<SnapshotMetadata>> {
+    let mut history = Vec::new();
+    let mut visited = HashSet::new();
+    let mut current = Some(start.clone());
+
+    while let Some(id) = current {
+        if !visited.insert(id.clone()) {
+            return Err(MovsError::StorageError(format!(
+                "Cycle detected in snapshot history at '{}'",
+                id
+            )));
+        }
+
+        let metadata = load_snapshot(project_root, &id)?;
+        current = metadata.parent.clone();
+        history.push(metadata);
+    }
+
+    Ok(history)
+}
+
+/// List the distinct versions a file had across history
+///
+/// Walks from HEAD to the first snapshot via parent links and records the
+/// snapshot in which each version of the file first appeared, newest first.
+/// Consecutive snapshots with the same hash are collapsed into one record.
+/// Snapshots where the file is absent are skipped; if it reappears later
+/// with the same content it is reported again, since it was re-added.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `path` - Path relative to the project root
+pub fn file_history(project_root: &Path, path: &Path) -> Result<Vec<(SnapshotId, FileHash)>> {
+    let Some(head) = read_head(project_root)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut history: Vec<(SnapshotId, FileHash)> = Vec::new();
+    let mut previous: Option<FileHash> = None;
+
+    for metadata in ancestors(project_root, &head)? {
+        let Some(entry) = metadata.find_file(path) else {
+            previous = None;
+            continue;
+        };
+
+        match (&previous, history.last_mut()) {
+            // Same content as the newer snapshot: the change happened earlier
+            (Some(hash), Some(last)) if hash == &entry.hash => last.0 = metadata.id.clone(),
+            _ => history.push((metadata.id.clone(), entry.hash.clone())),
+        }
+
+        previous = Some(entry.hash.clone());
+    }
+
+    Ok(history)
+}
+
+/// Collapse the range of snapshots from `from` to `to` into a single snapshot
+///
+/// `from` must be an ancestor of (or equal to) `to`. The new snapshot has
+/// `to`'s tree and is parented to `from`'s parent. Every snapshot in the
+/// range is deleted afterwards; snapshots that descended from the range
+/// are re-parented onto the new snapshot, and HEAD and any branches that
+/// pointed into the range are moved to it. Objects are never deleted
+/// here, so everything the new snapshot references stays in the store.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `from` - Oldest snapshot of the range
+/// * `to` - Newest snapshot of the range, whose tree is kept
+/// * `message` - Message for the squashed snapshot
+pub fn squash(
+    project_root: &Path,
+    from: &SnapshotId,
+    to: &SnapshotId,
+    message: &str,
+) -> Result<SnapshotId> {
+    let _lock = lock_for_write(project_root)?;
+    let mut range = Vec::new();
+    for metadata in ancestors(project_root, to)? {
+        let reached = &metadata.id == from;
+        range.push(metadata);
+        if reached {
+            break;
+        }
+    }
+
+    let oldest = range
+        .last()
+        .filter(|metadata| &metadata.id == from)
+        .ok_or_else(|| {
+            MovsError::StorageError(format!("'{}' is not an ancestor of '{}'", from, to))
+        })?;
+
+    let config = load_config(project_root)?;
+    let newest = &range[0];
+    let squashed = SnapshotMetadata::new(
+        SnapshotId::generate_with(config.id_scheme),
+        message.to_string(),
+        newest.author.clone(),
+        oldest.parent.clone(),
+        newest.files.clone(),
+    );
+    save_snapshot(project_root, &squashed)?;
+
+    let removed: HashSet<&SnapshotId> = range.iter().map(|metadata| &metadata.id).collect();
+
+    // Re-point everything that referenced the range before deleting it
+    for mut metadata in load_all_snapshots(project_root)? {
+        if metadata
+            .parent
+            .as_ref()
+            .is_some_and(|parent| removed.contains(parent))
+            && !removed.contains(&metadata.id)
+        {
+            metadata.parent = Some(squashed.id.clone());
+            save_snapshot(project_root, &metadata)?;
+        }
+    }
+
+    if read_head(project_root)?.is_some_and(|head| removed.contains(&head)) {
+        write_head(project_root, &squashed.id)?;
+    }
+    for (name, tip) in list_branches(project_root)? {
+        if removed.contains(&tip) {
+            write_branch(project_root, &name, &squashed.id)?;
+        }
+    }
+
+    for id in removed {
+        delete_snapshot(project_root, id)?;
+    }
+
+    oplog::record(
+        project_root,
+        Operation::Squash,
+        Some(&squashed.id),
+        squashed.author.as_deref(),
+        &NullObserver,
+    );
+    Ok(squashed.id)
+}
+
+/// Outcome of a batch snapshot deletion
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeleteReport {
+    /// Snapshots that were deleted
+    pub deleted: Vec<SnapshotId>,
+
+    /// Requested snapshots that did not exist
+    pub missing: Vec<SnapshotId>,
+
+    /// Result of the garbage collection run, if one was requested
+    pub gc: Option<GcReport>,
+}
+
+/// Delete several snapshots at once, optionally collecting their objects
+///
+/// Unknown IDs are reported in `DeleteReport::missing` instead of aborting
+/// the batch. Snapshots whose parent is deleted are re-parented onto their
+/// nearest surviving ancestor, and HEAD is moved the same way (or removed
+/// if no ancestor survives). Branches are moved the same way too, but since
+/// they keep their snapshots alive the batch fails, with nothing deleted,
+/// if it would leave a branch with no surviving snapshot. With `run_gc`,
+/// `gc` runs once after all metadata is gone.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `ids` - Snapshots to delete
+/// * `run_gc` - Remove objects no longer referenced afterwards
+pub fn delete_snapshots(
+    project_root: &Path,
+    ids: &[SnapshotId],
+    run_gc: bool,
+) -> Result<DeleteReport> {
+    let _lock = lock_for_write(project_root)?;
+    let all = load_all_snapshots(project_root)?;
+    let parents: HashMap<&SnapshotId, Option<&SnapshotId>> = all
+        .iter()
+        .map(|metadata| (&metadata.id, metadata.parent.as_ref()))
+        .collect();
+
+    let mut report = DeleteReport::default();
+    let mut removed = HashSet::new();
+    for id in ids {
+        if !parents.contains_key(id) {
+            report.missing.push(id.clone());
+        } else if removed.insert(id) {
+            report.deleted.push(id.clone());
+        }
+    }
+
+    // Walk up past deleted snapshots to the first one that survives
+    let surviving = |start: &SnapshotId| -> Option<SnapshotId> {
+        let mut current = Some(start);
+        let mut steps = 0;
+        while let Some(id) = current.filter(|id| removed.contains(id)) {
+            steps += 1;
+            if steps > parents.len() {
+                return None;
+            }
+            current = parents.get(id).copied().flatten();
+        }
+        current.cloned()
+    };
+
+    let mut moved_branches = Vec::new();
+    for (name, tip) in list_branches(project_root)? {
+        if removed.contains(&tip) {
+            let new_tip = surviving(&tip).ok_or_else(|| {
+                MovsError::StorageError(format!(
+                    "Deleting these snapshots would leave branch '{}' empty; delete the branch first",
+                    name
+                ))
+            })?;
+            moved_branches.push((name, new_tip));
+        }
+    }
+
+    for metadata in &all {
+        if removed.contains(&metadata.id) {
+            continue;
+        }
+        if let Some(parent) = metadata.parent.as_ref().filter(|p| removed.contains(p)) {
+            let mut updated = metadata.clone();
+            updated.parent = surviving(parent);
+            save_snapshot(project_root, &updated)?;
+        }
+    }
+
+    if let Some(head) = read_head(project_root)?.filter(|head| removed.contains(head)) {
+        match surviving(&head) {
+            Some(new_head) => write_head(project_root, &new_head)?,
+            None => clear_head(project_root)?,
+        }
+    }
+    for (name, new_tip) in &moved_branches {
+        write_branch(project_root, name, new_tip)?;
+    }
+
+    for id in &report.deleted {
+        delete_snapshot(project_root, id)?;
+    }
+
+    if run_gc {
+        report.gc = Some(gc(project_root)?);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::snapshot_exists;
+    use crate::types::FileEntry;
+    use chrono::Utc;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    /// Save a linear chain of snapshots, each with the given `(path, hash)` files
+    fn save_chain(project_root: &Path, snapshots: &[&[(&str, u8)]]) -> Vec<SnapshotId> {
+        let mut ids = Vec::new();
+        let mut parent = None;
+
+        for (i, files) in snapshots.iter().enumerate() {
+            let entries = files
+                .iter()
+                .map(|(path, hash)| {
+                    FileEntry::new(
+                        PathBuf::from(path),
+                        FileHash::new(vec![*hash]),
+                        1,
+                        Utc::now(),
+                    )
+                })
+                .collect();
+
+            let metadata = SnapshotMetadata::new(
+                SnapshotId::new(format!("snapshot_{}", i)),
+                format!("Snapshot {}", i),
+                None,
+                parent.clone(),
+                entries,
+            );
+            save_snapshot(project_root, &metadata).unwrap();
+            parent = Some(metadata.id.clone());
+            ids.push(metadata.id);
+        }
+
+        write_head(project_root, ids.last().unwrap()).unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let ids = save_chain(project_root, &[&[], &[], &[]]);
+
+        let history = ancestors(project_root, &ids[2]).unwrap();
+        let walked: Vec<_> = history.iter().map(|m| m.id.clone()).collect();
+        assert_eq!(walked, vec![ids[2].clone(), ids[1].clone(), ids[0].clone()]);
+    }
+
+    #[test]
+    fn test_file_history_collapses_unchanged() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let ids = save_chain(
+            project_root,
+            &[
+                &[("drums.wav", 1)],
+                &[("drums.wav", 1), ("bass.wav", 9)],
+                &[("drums.wav", 2)],
+                &[("drums.wav", 2)],
+            ],
+        );
+
+        let history = file_history(project_root, Path::new("drums.wav")).unwrap();
+        assert_eq!(
+            history,
+            vec![
+                (ids[2].clone(), FileHash::new(vec![2])),
+                (ids[0].clone(), FileHash::new(vec![1])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_history_with_absent_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let ids = save_chain(
+            project_root,
+            &[
+                &[("vox.wav", 1)],
+                &[],
+                &[("vox.wav", 1)],
+                &[("other.wav", 3)],
+            ],
+        );
+
+        let history = file_history(project_root, Path::new("vox.wav")).unwrap();
+        assert_eq!(
+            history,
+            vec![
+                (ids[2].clone(), FileHash::new(vec![1])),
+                (ids[0].clone(), FileHash::new(vec![1])),
+            ]
+        );
+
+        assert!(file_history(project_root, Path::new("missing.wav"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_file_history_empty_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        assert!(file_history(project_root, Path::new("a.wav"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_squash_middle_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let ids = save_chain(
+            project_root,
+            &[&[("a", 1)], &[("a", 2)], &[("a", 3)], &[("a", 4)]],
+        );
+
+        let squashed = squash(project_root, &ids[1], &ids[2], "Squashed").unwrap();
+
+        assert!(!snapshot_exists(project_root, &ids[1]));
+        assert!(!snapshot_exists(project_root, &ids[2]));
+
+        let metadata = load_snapshot(project_root, &squashed).unwrap();
+        assert_eq!(metadata.message, "Squashed");
+        assert_eq!(metadata.parent, Some(ids[0].clone()));
+        assert_eq!(metadata.files[0].hash, FileHash::new(vec![3]));
+
+        // The descendant now hangs off the squashed snapshot
+        let walked: Vec<_> = ancestors(project_root, &ids[3])
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(walked, vec![ids[3].clone(), squashed, ids[0].clone()]);
+        assert_eq!(read_head(project_root).unwrap(), Some(ids[3].clone()));
+    }
+
+    #[test]
+    fn test_squash_moves_head() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let ids = save_chain(project_root, &[&[("a", 1)], &[("a", 2)], &[("a", 3)]]);
+
+        let squashed = squash(project_root, &ids[0], &ids[2], "All").unwrap();
+
+        assert_eq!(read_head(project_root).unwrap(), Some(squashed.clone()));
+        assert_eq!(load_snapshot(project_root, &squashed).unwrap().parent, None);
+        assert_eq!(
+            crate::metadata::list_snapshots(project_root).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_squash_rejects_non_ancestor() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let ids = save_chain(project_root, &[&[], &[]]);
+
+        assert!(squash(project_root, &ids[1], &ids[0], "Backwards").is_err());
+        assert!(snapshot_exists(project_root, &ids[0]));
+        assert!(snapshot_exists(project_root, &ids[1]));
+    }
+
+    #[test]
+    fn test_delete_snapshots_batch() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let ids = save_chain(project_root, &[&[], &[], &[], &[]]);
+        let unknown = SnapshotId::new("snapshot_missing".to_string());
+
+        let report = delete_snapshots(
+            project_root,
+            &[
+                ids[1].clone(),
+                unknown.clone(),
+                ids[2].clone(),
+                ids[3].clone(),
+            ],
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.deleted,
+            vec![ids[1].clone(), ids[2].clone(), ids[3].clone()]
+        );
+        assert_eq!(report.missing, vec![unknown]);
+        assert!(report.gc.is_none());
+        assert!(!snapshot_exists(project_root, &ids[2]));
+
+        // HEAD falls back to the nearest surviving ancestor
+        assert_eq!(read_head(project_root).unwrap(), Some(ids[0].clone()));
+    }
+
+    #[test]
+    fn test_delete_snapshots_reparents_survivors() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let ids = save_chain(project_root, &[&[], &[], &[], &[]]);
+        delete_snapshots(project_root, &[ids[1].clone(), ids[2].clone()], false).unwrap();
+
+        let survivor = load_snapshot(project_root, &ids[3]).unwrap();
+        assert_eq!(survivor.parent, Some(ids[0].clone()));
+        assert_eq!(read_head(project_root).unwrap(), Some(ids[3].clone()));
+
+        // Deleting everything clears HEAD
+        delete_snapshots(project_root, &[ids[0].clone(), ids[3].clone()], false).unwrap();
+        assert_eq!(read_head(project_root).unwrap(), None);
+    }
+
+    #[test]
+    fn test_branches_follow_squash_and_delete() {
+        use crate::metadata::branches::{create_branch, read_branch};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let ids = save_chain(project_root, &[&[], &[], &[], &[]]);
+        create_branch(project_root, "risky-mix", &ids[2]).unwrap();
+        create_branch(project_root, "first", &ids[0]).unwrap();
+
+        let squashed = squash(project_root, &ids[1], &ids[2], "Squashed").unwrap();
+        assert_eq!(read_branch(project_root, "risky-mix").unwrap(), squashed);
+
+        delete_snapshots(project_root, &[squashed], false).unwrap();
+        assert_eq!(read_branch(project_root, "risky-mix").unwrap(), ids[0]);
+
+        // A branch keeps its last snapshot alive
+        let result = delete_snapshots(project_root, &[ids[0].clone()], false);
+        assert!(matches!(result, Err(MovsError::StorageError(_))));
+        assert!(snapshot_exists(project_root, &ids[0]));
+        assert_eq!(read_branch(project_root, "first").unwrap(), ids[0]);
+    }
+
+    #[test]
+    fn test_delete_snapshots_with_gc() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        std::fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let first = crate::snapshot::create_snapshot(project_root, "v1", None, &Default::default())
+            .unwrap();
+        std::fs::write(project_root.join("song.als"), b"v2").unwrap();
+        crate::snapshot::create_snapshot(project_root, "v2", None, &Default::default()).unwrap();
+
+        let report = delete_snapshots(project_root, &[first], true).unwrap();
+        assert_eq!(report.gc.unwrap().objects_removed, 1);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::{get_movs_dir, repository_exists};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+/// Lock file inside `.movs`, holding the ID of the process that owns it
+pub const LOCK_FILE: &str = "lock";
+
+/// Delay between attempts in `acquire_lock_timeout`
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long MOVS's own writers wait for another process to finish
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Exclusive lock on a repository, released when dropped
+///
+/// Processes that mutate the same repository, such as a watcher daemon and
+/// an interactive CLI, take this lock around each operation; snapshot
+/// creation and deletion, garbage collection and the reference counts take
+/// it internally. It is an OS file lock on `.movs/lock`, so the OS releases
+/// it when its holder exits or crashes. Within a thread the lock is
+/// reentrant: taking it again while held yields another handle, and it is
+/// released once the last handle is dropped.
+#[derive(Debug, Clone)]
+pub struct RepositoryLock {
+    _held: Arc<HeldLock>,
+}
+
+#[derive(Debug)]
+struct HeldLock {
+    file: File,
+    key: LockKey,
+}
+
+impl Drop for HeldLock {
+    fn drop(&mut self) {
+        held_locks().retain(|(key, _)| *key != self.key);
+        let _ = self.file.unlock();
+    }
+}
+
+/// Canonical `.movs` directory and the thread holding its lock
+type LockKey = (PathBuf, ThreadId);
+
+/// Locks held by this process, so a thread can take its own lock again
+static HELD_LOCKS: Mutex<Vec<(LockKey, Weak<HeldLock>)>> = Mutex::new(Vec::new());
+
+fn held_locks() -> MutexGuard<'static, Vec<(LockKey, Weak<HeldLock>)>> {
+    HELD_LOCKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Get the path to the repository lock file
+pub fn get_lock_path(project_root: &Path) -> PathBuf {
+    get_movs_dir(project_root).join(LOCK_FILE)
+}
+
+/// Take the repository lock, failing at once if it is held
+///
+/// Another thread of the same process holding the lock counts as held.
+/// A lock file left behind by a process that died is not held and is
+/// simply taken over; there is no stale lock to clean up.
+///
+/// # Errors
+///
+/// Returns `MovsError::Locked` with the holder's process ID, if it can be
+/// read; platforms with mandatory locking, like Windows, hide it.
+pub fn acquire_lock(project_root: &Path) -> Result<RepositoryLock> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    let key = (
+        get_movs_dir(project_root).canonicalize()?,
+        thread::current().id(),
+    );
+    let mut held = held_locks();
+    if let Some(lock) = held
+        .iter()
+        .find(|(held_key, _)| *held_key == key)
+        .and_then(|(_, lock)| lock.upgrade())
+    {
+        return Ok(RepositoryLock { _held: lock });
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(get_lock_path(project_root))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            return Err(MovsError::Locked {
+                holder_pid: read_holder(&mut file),
+            })
+        }
+        Err(TryLockError::Error(e)) => return Err(e.into()),
+    }
+
+    // The process ID is only informational; the OS lock is what excludes
+    file.set_len(0)?;
+    file.write_all(std::process::id().to_string().as_bytes())?;
+
+    let lock = Arc::new(HeldLock {
+        file,
+        key: key.clone(),
+    });
+    held.push((key, Arc::downgrade(&lock)));
+    Ok(RepositoryLock { _held: lock })
+}
+
+/// Take the repository lock, waiting up to `timeout` for it to be released
+///
+/// Polls `acquire_lock` until it succeeds or the timeout elapses. A zero
+/// timeout tries exactly once.
+///
+/// # Errors
+///
+/// Returns `MovsError::Locked` if the lock is still held at the deadline.
+pub fn acquire_lock_timeout(project_root: &Path, timeout: Duration) -> Result<RepositoryLock> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        match acquire_lock(project_root) {
+            Err(MovsError::Locked { holder_pid }) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(MovsError::Locked { holder_pid });
+                }
+                thread::sleep(POLL_INTERVAL.min(deadline - now));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Take the lock around one of MOVS's own mutating operations
+pub(crate) fn lock_for_write(project_root: &Path) -> Result<RepositoryLock> {
+    acquire_lock_timeout(project_root, WRITE_TIMEOUT)
+}
+
+/// Read the process ID recorded in a lock file
+fn read_holder(file: &mut File) -> Option<u32> {
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Try to take the lock from a thread that does not hold it
+    fn acquire_elsewhere(project_root: &Path) -> Result<RepositoryLock> {
+        let project_root = project_root.to_path_buf();
+        thread::spawn(move || acquire_lock(&project_root))
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let lock = acquire_lock(project_root).unwrap();
+        let result = acquire_elsewhere(project_root);
+        #[cfg(unix)]
+        assert!(matches!(
+            result,
+            Err(MovsError::Locked { holder_pid: Some(p) }) if p == std::process::id()
+        ));
+        assert!(matches!(result, Err(MovsError::Locked { .. })));
+
+        drop(lock);
+        acquire_elsewhere(project_root).unwrap();
+    }
+
+    #[test]
+    fn test_lock_is_reentrant_within_a_thread() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let outer = acquire_lock(project_root).unwrap();
+        let inner = acquire_lock(project_root).unwrap();
+        drop(outer);
+        assert!(matches!(
+            acquire_elsewhere(project_root),
+            Err(MovsError::Locked { .. })
+        ));
+
+        drop(inner);
+        acquire_elsewhere(project_root).unwrap();
+    }
+
+    #[test]
+    fn test_lock_timeout() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let lock = acquire_lock(project_root).unwrap();
+        let start = Instant::now();
+        let result = {
+            let project_root = project_root.to_path_buf();
+            thread::spawn(move || acquire_lock_timeout(&project_root, Duration::from_millis(120)))
+                .join()
+                .unwrap()
+        };
+        assert!(matches!(result, Err(MovsError::Locked { .. })));
+        assert!(start.elapsed() >= Duration::from_millis(120));
+
+        // Released by another thread while waiting
+        let waiter = {
+            let project_root = project_root.to_path_buf();
+            thread::spawn(move || acquire_lock_timeout(&project_root, Duration::from_secs(10)))
+        };
+        thread::sleep(Duration::from_millis(100));
+        drop(lock);
+        assert!(waiter.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_leftover_lock_file_is_not_held() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        // Left behind by a process that crashed while holding the lock
+        fs::write(get_lock_path(project_root), b"4194305").unwrap();
+
+        let _lock = acquire_lock(project_root).unwrap();
+        let mut file = File::open(get_lock_path(project_root)).unwrap();
+        assert_eq!(read_holder(&mut file), Some(std::process::id()));
+    }
+
+    /// Hold the lock on another thread for a moment, returning when it was
+    /// released
+    fn hold_lock_briefly(project_root: &Path) -> thread::JoinHandle<Instant> {
+        let project_root = project_root.to_path_buf();
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let handle = thread::spawn(move || {
+            let lock = acquire_lock(&project_root).unwrap();
+            locked_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(150));
+            let released = Instant::now();
+            drop(lock);
+            released
+        });
+        locked_rx.recv().unwrap();
+        handle
+    }
+
+    #[test]
+    fn test_writers_wait_for_the_lock() {
+        use crate::snapshot::create_snapshot;
+        use crate::storage::migrate_fanout_depth;
+        use crate::storage::pack::pack_objects;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+
+        let holder = hold_lock_briefly(project_root);
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        assert!(Instant::now() >= holder.join().unwrap());
+
+        let holder = hold_lock_briefly(project_root);
+        pack_objects(project_root).unwrap();
+        assert!(Instant::now() >= holder.join().unwrap());
+
+        let holder = hold_lock_briefly(project_root);
+        migrate_fanout_depth(project_root, 1).unwrap();
+        assert!(Instant::now() >= holder.join().unwrap());
+    }
+}
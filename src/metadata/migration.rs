@@ -0,0 +1,285 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::{
+    get_config_file, get_movs_dir, get_snapshots_dir, repository_exists, REPO_FORMAT_VERSION,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the config backup written before a migration rewrites it
+pub const CONFIG_BACKUP_FILE: &str = "config.json.bak";
+
+/// Summary of what a repository migration did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Format version the repository was at before migrating
+    pub from_version: u32,
+
+    /// Format version the repository is at after migrating
+    pub to_version: u32,
+
+    /// Number of snapshot metadata files that were rewritten
+    pub snapshots_rewritten: usize,
+}
+
+impl MigrationReport {
+    /// Check if the migration had nothing to do
+    pub fn is_noop(&self) -> bool {
+        self.from_version == self.to_version
+    }
+}
+
+/// Read the repository format version from `config.json`
+///
+/// Configs written before format versioning are treated as version 1.
+pub fn read_format_version(project_root: &Path) -> Result<u32> {
+    let config = read_config(project_root)?;
+    Ok(format_version_of(&config))
+}
+
+/// Check that this version of the library can safely use a repository
+///
+/// Repositories at an older format version are accepted; they stay
+/// readable and `migrate_repository` upgrades them. A repository whose
+/// config is not written yet counts as current.
+///
+/// # Errors
+///
+/// Returns `MovsError::ConfigError` if the repository was created by a
+/// newer version with a format this library does not understand.
+pub fn check_format_version(project_root: &Path) -> Result<()> {
+    if !get_config_file(project_root).exists() {
+        return Ok(());
+    }
+
+    let version = read_format_version(project_root)?;
+    if version > REPO_FORMAT_VERSION {
+        return Err(MovsError::ConfigError(format!(
+            "Repository format version {} is newer than the supported version {}; \
+             upgrade MOVS to open this repository",
+            version, REPO_FORMAT_VERSION
+        )));
+    }
+    Ok(())
+}
+
+/// Upgrade a repository to the current format version
+///
+/// Applies each migration step in order, starting from the version recorded
+/// in `config.json`. The original config is backed up to `config.json.bak`
+/// before it is rewritten. Running this on an up-to-date repository does
+/// nothing.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn migrate_repository(project_root: &Path) -> Result<MigrationReport> {
+    check_format_version(project_root)?;
+    let mut config = read_config(project_root)?;
+    let from_version = format_version_of(&config);
+
+    let mut report = MigrationReport {
+        from_version,
+        to_version: from_version,
+        snapshots_rewritten: 0,
+    };
+
+    if from_version == REPO_FORMAT_VERSION {
+        return Ok(report);
+    }
+
+    // Back up the original config before touching anything
+    let config_path = get_config_file(project_root);
+    fs::copy(
+        &config_path,
+        get_movs_dir(project_root).join(CONFIG_BACKUP_FILE),
+    )?;
+
+    let mut version = from_version;
+    while version < REPO_FORMAT_VERSION {
+        match version {
+            1 => report.snapshots_rewritten += migrate_v1_to_v2(project_root)?,
+            _ => {
+                return Err(MovsError::ConfigError(format!(
+                    "No migration available from repository format version {}",
+                    version
+                )))
+            }
+        }
+        version += 1;
+    }
+
+    config["format_version"] = serde_json::json!(version);
+    config["version"] = serde_json::json!(crate::VERSION);
+    fs::write(&config_path, serde_json::to_string_pretty(&config)?)?;
+
+    report.to_version = version;
+    Ok(report)
+}
+
+/// Version 1 -> 2: add an explicit `schema_version` to every snapshot file
+fn migrate_v1_to_v2(project_root: &Path) -> Result<usize> {
+    let mut rewritten = 0;
+
+    for path in snapshot_files(project_root)? {
+        let json = fs::read_to_string(&path)?;
+        let mut value: serde_json::Value = serde_json::from_str(&json)?;
+
+        let Some(object) = value.as_object_mut() else {
+            return Err(MovsError::StorageError(format!(
+                "Snapshot file '{}' is not a JSON object",
+                path.display()
+            )));
+        };
+
+        if object.contains_key("schema_version") {
+            continue;
+        }
+
+        object.insert("schema_version".to_string(), serde_json::json!(1));
+        fs::write(&path, serde_json::to_string_pretty(&value)?)?;
+        rewritten += 1;
+    }
+
+    Ok(rewritten)
+}
+
+fn read_config(project_root: &Path) -> Result<serde_json::Value> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    let json = fs::read_to_string(get_config_file(project_root))?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn format_version_of(config: &serde_json::Value) -> u32 {
+    config
+        .get("format_version")
+        .and_then(|v| v.as_u64())
+        .map_or(1, |v| v as u32)
+}
+
+fn snapshot_files(project_root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(get_snapshots_dir(project_root))? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::persistence::load_snapshot;
+    use crate::metadata::{get_snapshot_path, init_repository};
+    use crate::types::SnapshotId;
+    use tempfile::TempDir;
+
+    /// Rewrite a fresh repository so it looks like one created before versioning
+    fn make_v1_repository(project_root: &Path) -> SnapshotId {
+        init_repository(project_root).unwrap();
+
+        let config = serde_json::json!({
+            "version": "0.1.0",
+            "created_at": "2024-01-01T00:00:00+00:00",
+        });
+        fs::write(get_config_file(project_root), config.to_string()).unwrap();
+
+        let snapshot_id = SnapshotId::new("snapshot_old".to_string());
+        let snapshot = serde_json::json!({
+            "id": "snapshot_old",
+            "timestamp": "2024-01-01T00:00:00Z",
+            "message": "Old snapshot",
+            "author": null,
+            "parent": null,
+            "files": [],
+        });
+        fs::write(
+            get_snapshot_path(project_root, &snapshot_id),
+            snapshot.to_string(),
+        )
+        .unwrap();
+
+        snapshot_id
+    }
+
+    #[test]
+    fn test_new_repository_is_current() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        assert_eq!(
+            read_format_version(project_root).unwrap(),
+            REPO_FORMAT_VERSION
+        );
+
+        let report = migrate_repository(project_root).unwrap();
+        assert!(report.is_noop());
+        assert!(!get_movs_dir(project_root).join(CONFIG_BACKUP_FILE).exists());
+    }
+
+    #[test]
+    fn test_migrate_v1_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        let snapshot_id = make_v1_repository(project_root);
+        assert_eq!(read_format_version(project_root).unwrap(), 1);
+
+        let report = migrate_repository(project_root).unwrap();
+        assert_eq!(report.from_version, 1);
+        assert_eq!(report.to_version, REPO_FORMAT_VERSION);
+        assert_eq!(report.snapshots_rewritten, 1);
+
+        // Config is updated and the original is backed up
+        assert_eq!(
+            read_format_version(project_root).unwrap(),
+            REPO_FORMAT_VERSION
+        );
+        let backup =
+            fs::read_to_string(get_movs_dir(project_root).join(CONFIG_BACKUP_FILE)).unwrap();
+        assert!(!backup.contains("format_version"));
+
+        // Snapshot now carries an explicit schema version and still loads
+        let json = fs::read_to_string(get_snapshot_path(project_root, &snapshot_id)).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["schema_version"], 1);
+        assert_eq!(
+            load_snapshot(project_root, &snapshot_id).unwrap().message,
+            "Old snapshot"
+        );
+    }
+
+    #[test]
+    fn test_migration_is_idempotent() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        make_v1_repository(project_root);
+
+        migrate_repository(project_root).unwrap();
+        let config_after_first = fs::read_to_string(get_config_file(project_root)).unwrap();
+
+        let report = migrate_repository(project_root).unwrap();
+        assert!(report.is_noop());
+        assert_eq!(report.snapshots_rewritten, 0);
+        assert_eq!(
+            fs::read_to_string(get_config_file(project_root)).unwrap(),
+            config_after_first
+        );
+    }
+
+    #[test]
+    fn test_migrate_missing_repository() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let result = migrate_repository(temp_dir.path());
+        assert!(matches!(result, Err(MovsError::RepositoryNotFound(_))));
+    }
+}
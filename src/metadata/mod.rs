@@ -1,9 +1,21 @@
 use crate::error::{MovsError, Result};
-use crate::types::{SnapshotId};
+use crate::types::SnapshotId;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+pub mod annotations;
+pub mod binary;
+pub mod branches;
+pub mod cache;
+pub mod config;
+pub mod history;
+pub mod lock;
+pub mod migration;
 pub mod persistence;
+pub mod refs;
+pub mod relocate;
+pub mod search;
+pub mod store;
 
 /// The name of the MOVS repository directory
 pub const MOVS_DIR: &str = ".movs";
@@ -17,6 +29,15 @@ pub const OBJECTS_DIR: &str = "objects";
 /// Configuration file name
 pub const CONFIG_FILE: &str = "config.json";
 
+/// File recording the ID of the current snapshot
+pub const HEAD_FILE: &str = "HEAD";
+
+/// On-disk repository format version written by this library
+///
+/// Repositories created before format versioning have no `format_version`
+/// in their config and are treated as version 1.
+pub const REPO_FORMAT_VERSION: u32 = 2;
+
 /// Get the path to the .movs directory for a given project root
 pub fn get_movs_dir(project_root: &Path) -> PathBuf {
     project_root.join(MOVS_DIR)
@@ -28,8 +49,14 @@ pub fn get_snapshots_dir(project_root: &Path) -> PathBuf {
 }
 
 /// Get the path to the objects directory
-pub fn get_objects_dir(project_root: &Path) -> PathBuf {
-    get_movs_dir(project_root).join(OBJECTS_DIR)
+///
+/// This is `.movs/objects` unless the config sets an external
+/// `objects_dir`. Reads the config on every call; code that already holds
+/// a config uses `RepositoryConfig::objects_dir_in` instead. A config that
+/// exists but cannot be read is an error rather than a silent fallback to
+/// `.movs/objects`.
+pub fn get_objects_dir(project_root: &Path) -> Result<PathBuf> {
+    Ok(crate::storage::load_settings(project_root)?.objects_dir_in(project_root))
 }
 
 /// Get the path to the config file
@@ -37,6 +64,11 @@ pub fn get_config_file(project_root: &Path) -> PathBuf {
     get_movs_dir(project_root).join(CONFIG_FILE)
 }
 
+/// Get the path to the HEAD file
+pub fn get_head_file(project_root: &Path) -> PathBuf {
+    get_movs_dir(project_root).join(HEAD_FILE)
+}
+
 /// Check if a MOVS repository exists at the given path
 pub fn repository_exists(project_root: &Path) -> bool {
     get_movs_dir(project_root).exists()
@@ -55,26 +87,26 @@ pub fn init_repository(project_root: &Path) -> Result<()> {
         return Err(MovsError::RepositoryAlreadyExists(movs_dir));
     }
 
-    // Create directory structure
-    fs::create_dir(&movs_dir)?;
+    // Create directory structure; creating `.movs` is the exclusive step
+    // that decides which of two racing initializations wins
+    fs::create_dir(&movs_dir).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => MovsError::RepositoryAlreadyExists(movs_dir.clone()),
+        _ => MovsError::Io(e),
+    })?;
     fs::create_dir(get_snapshots_dir(project_root))?;
-    fs::create_dir(get_objects_dir(project_root))?;
+    fs::create_dir(get_objects_dir(project_root)?)?;
 
     // Create default config
-    let default_config = serde_json::json!({
-        "version": crate::VERSION,
-        "created_at": chrono::Utc::now().to_rfc3339(),
-    });
-
-    fs::write(
-        get_config_file(project_root),
-        serde_json::to_string_pretty(&default_config)?,
-    )?;
+    config::write_config_unchecked(project_root, &config::RepositoryConfig::new())?;
+    crate::gc::refcounts::init_refcounts(project_root)?;
 
     Ok(())
 }
 
 /// List all snapshot IDs in the repository
+///
+/// Files in the snapshots directory that are not snapshot metadata,
+/// including any whose names are not valid UTF-8, are ignored.
 pub fn list_snapshots(project_root: &Path) -> Result<Vec<SnapshotId>> {
     let snapshots_dir = get_snapshots_dir(project_root);
 
@@ -90,29 +122,110 @@ pub fn list_snapshots(project_root: &Path) -> Result<Vec<SnapshotId>> {
         let entry = entry?;
         let path = entry.path();
 
-        if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
-            if let Some(file_stem) = path.file_stem() {
-                if let Some(name) = file_stem.to_str() {
-                    snapshot_ids.push(SnapshotId::new(name.to_string()));
-                }
-            }
+        if !path.is_file() {
+            continue;
+        }
+
+        // Snapshot IDs are always UTF-8, so other names are never snapshots
+        if let Some(id) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(snapshot_file_stem)
+        {
+            snapshot_ids.push(SnapshotId::new(id.to_string()));
         }
     }
 
     // Sort by name (which includes timestamp)
-    snapshot_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    snapshot_ids.sort();
+    snapshot_ids.dedup();
 
     Ok(snapshot_ids)
 }
 
+/// Count the snapshots in the repository without building their IDs
+///
+/// Cheaper than `list_snapshots(..)?.len()` for callers that poll often.
+pub fn snapshot_count(project_root: &Path) -> Result<usize> {
+    let snapshots_dir = get_snapshots_dir(project_root);
+
+    if !snapshots_dir.exists() {
+        return Err(MovsError::RepositoryNotFound(
+            get_movs_dir(project_root),
+        ));
+    }
+
+    let mut count = 0;
+
+    for entry in fs::read_dir(&snapshots_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+
+        // A snapshot briefly has both variants while its format is switched;
+        // count the compressed file only when no plain one exists
+        if name.ends_with(".json") {
+            count += 1;
+        } else if let Some(stem) = name.strip_suffix(".gz") {
+            if snapshot_file_stem(name).is_some() && !snapshots_dir.join(stem).exists() {
+                count += 1;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// Check if the repository has no snapshots
+///
+/// Stops at the first snapshot file found.
+pub fn is_empty(project_root: &Path) -> Result<bool> {
+    let snapshots_dir = get_snapshots_dir(project_root);
+
+    if !snapshots_dir.exists() {
+        return Err(MovsError::RepositoryNotFound(
+            get_movs_dir(project_root),
+        ));
+    }
+
+    for entry in fs::read_dir(&snapshots_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file()
+            && entry.file_name().to_str().and_then(snapshot_file_stem).is_some()
+        {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Snapshots are stored as either `<id>.json` or `<id>.json.gz`
+fn snapshot_file_stem(name: &str) -> Option<&str> {
+    name.strip_suffix(".json")
+        .or_else(|| name.strip_suffix(".json.gz"))
+}
+
 /// Get the file path for a snapshot's metadata
 pub fn get_snapshot_path(project_root: &Path, snapshot_id: &SnapshotId) -> PathBuf {
     get_snapshots_dir(project_root).join(format!("{}.json", snapshot_id.as_str()))
 }
 
+/// Get the file path for a snapshot's gzip-compressed metadata
+pub fn get_compressed_snapshot_path(project_root: &Path, snapshot_id: &SnapshotId) -> PathBuf {
+    get_snapshots_dir(project_root).join(format!("{}.json.gz", snapshot_id.as_str()))
+}
+
 /// Check if a snapshot exists
 pub fn snapshot_exists(project_root: &Path, snapshot_id: &SnapshotId) -> bool {
     get_snapshot_path(project_root, snapshot_id).exists()
+        || get_compressed_snapshot_path(project_root, snapshot_id).exists()
 }
 
 #[cfg(test)]
@@ -135,7 +248,7 @@ mod tests {
         );
 
         assert_eq!(
-            get_objects_dir(project_root),
+            get_objects_dir(project_root).unwrap(),
             PathBuf::from("/test/project/.movs/objects")
         );
     }
@@ -157,7 +270,7 @@ mod tests {
         // Directories should be created
         assert!(get_movs_dir(project_root).exists());
         assert!(get_snapshots_dir(project_root).exists());
-        assert!(get_objects_dir(project_root).exists());
+        assert!(get_objects_dir(project_root).unwrap().exists());
         assert!(get_config_file(project_root).exists());
 
         // Can't initialize twice
@@ -165,6 +278,18 @@ mod tests {
         assert!(matches!(result, Err(MovsError::RepositoryAlreadyExists(_))));
     }
 
+    #[test]
+    fn test_objects_dir_with_unreadable_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        fs::write(get_config_file(project_root), "not json").unwrap();
+
+        // Falling back to `.movs/objects` would hide an external store
+        assert!(get_objects_dir(project_root).is_err());
+    }
+
     #[test]
     fn test_list_snapshots_empty() {
         let temp_dir = TempDir::new().unwrap();
@@ -195,4 +320,38 @@ mod tests {
         // Should now exist
         assert!(snapshot_exists(project_root, &snapshot_id));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_snapshot_count_and_is_empty() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        assert!(matches!(
+            snapshot_count(project_root),
+            Err(MovsError::RepositoryNotFound(_))
+        ));
+        assert!(matches!(
+            is_empty(project_root),
+            Err(MovsError::RepositoryNotFound(_))
+        ));
+
+        init_repository(project_root).unwrap();
+        assert_eq!(snapshot_count(project_root).unwrap(), 0);
+        assert!(is_empty(project_root).unwrap());
+
+        let first = SnapshotId::new("first".to_string());
+        let second = SnapshotId::new("second".to_string());
+        fs::write(get_snapshot_path(project_root, &first), "{}").unwrap();
+        fs::write(get_compressed_snapshot_path(project_root, &second), "").unwrap();
+        // Stray files and a duplicate compressed variant are not counted twice
+        fs::write(get_snapshots_dir(project_root).join("notes.txt"), "").unwrap();
+        fs::write(get_compressed_snapshot_path(project_root, &first), "").unwrap();
+
+        assert_eq!(snapshot_count(project_root).unwrap(), 2);
+        assert_eq!(
+            snapshot_count(project_root).unwrap(),
+            list_snapshots(project_root).unwrap().len()
+        );
+        assert!(!is_empty(project_root).unwrap());
+    }
+}
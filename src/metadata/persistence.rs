@@ -1,26 +1,94 @@
 use crate::error::{MovsError, Result};
-use crate::metadata::{get_snapshot_path, snapshot_exists};
-use crate::types::{SnapshotId, SnapshotMetadata};
+use crate::gc::refcounts::{same_references, update_refcounts};
+use crate::log::{self as oplog, Operation};
+use crate::metadata::config::{load_config, retry_policy};
+use crate::metadata::lock::lock_for_write;
+use crate::observer::NullObserver;
+use crate::metadata::{
+    get_compressed_snapshot_path, get_snapshot_path, get_snapshots_dir, list_snapshots,
+    snapshot_exists,
+};
+use crate::retry::with_retry;
+use crate::types::{SnapshotId, SnapshotMetadata, SNAPSHOT_SCHEMA_VERSION};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use std::fs;
+use std::io::{Read, Write};
 use std::path::Path;
+use tempfile::NamedTempFile;
 
 /// Save snapshot metadata to disk
 /// 
 /// Serializes the metadata to JSON and writes it to the snapshots directory.
+/// Transient I/O errors are retried according to the repository's retry policy.
+/// When `compress_metadata` is enabled in the config, the JSON is written
+/// gzip-compressed to `<id>.json.gz` instead, and any stale variant in the
+/// other format is removed. Object reference counts are moved from any
+/// previous version of the snapshot to the new one: the new references are
+/// counted before the file is written and the old ones released after, so
+/// an interruption leaves the counts too high rather than too low. A
+/// rewrite that references the same objects leaves the counts alone. The
+/// written file carries a freshly computed `content_digest`, replacing any
+/// in `metadata`.
 /// 
 /// # Arguments
 /// 
 /// * `project_root` - Root directory of the project
 /// * `metadata` - Snapshot metadata to save
 pub fn save_snapshot(project_root: &Path, metadata: &SnapshotMetadata) -> Result<()> {
-    let snapshot_path = get_snapshot_path(project_root, &metadata.id);
+    let _lock = lock_for_write(project_root)?;
+    let config = load_config(project_root).unwrap_or_default();
+    let plain_path = get_snapshot_path(project_root, &metadata.id);
+    let compressed_path = get_compressed_snapshot_path(project_root, &metadata.id);
+    let previous = if snapshot_exists(project_root, &metadata.id) {
+        load_snapshot(project_root, &metadata.id).ok()
+    } else {
+        None
+    };
+
+    // Editing annotations keeps the references, so the counts stay as they are
+    let moved = previous
+        .as_ref()
+        .is_none_or(|previous| !same_references(previous, metadata));
+    if moved {
+        update_refcounts(project_root, None, Some(metadata));
+    }
 
-    // Serialize to pretty JSON for human readability
-    let json = serde_json::to_string_pretty(metadata)?;
+    let mut digested = metadata.clone();
+    // Whatever version it was loaded as, the file now carries a digest
+    digested.schema_version = digested.schema_version.max(SNAPSHOT_SCHEMA_VERSION);
+    digested.content_digest = None;
+    digested.content_digest = Some(digested.compute_content_digest()?);
 
-    // Write to file
-    fs::write(&snapshot_path, json)?;
+    // Serialize to pretty JSON for human readability
+    let json = serde_json::to_string_pretty(&digested)?;
+
+    let (snapshot_path, stale_path, content) = if config.compress_metadata {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(json.as_bytes())?;
+        (compressed_path, plain_path, encoder.finish()?)
+    } else {
+        (plain_path, compressed_path, json.into_bytes())
+    };
+
+    // Write to a temporary file and rename it into place, so readers never
+    // observe a half-written snapshot when an existing one is edited
+    let snapshots_dir = get_snapshots_dir(project_root);
+    with_retry(&config.retry, || {
+        let mut temp = NamedTempFile::new_in(&snapshots_dir)?;
+        temp.write_all(&content)?;
+        temp.persist(&snapshot_path).map_err(|e| e.error)?;
+        Ok(())
+    })?;
+
+    if stale_path.exists() {
+        fs::remove_file(stale_path)?;
+    }
 
+    if moved {
+        update_refcounts(project_root, previous.as_ref(), None);
+    }
     Ok(())
 }
 
@@ -34,22 +102,105 @@ pub fn save_snapshot(project_root: &Path, metadata: &SnapshotMetadata) -> Result
 /// # Returns
 /// 
 /// The deserialized snapshot metadata
+///
+/// Returns `MovsError::ConfigError` if the snapshot was written with a newer
+/// schema version than this library understands.
 pub fn load_snapshot(project_root: &Path, snapshot_id: &SnapshotId) -> Result<SnapshotMetadata> {
     if !snapshot_exists(project_root, snapshot_id) {
         return Err(MovsError::SnapshotNotFound(snapshot_id.to_string()));
     }
 
-    let snapshot_path = get_snapshot_path(project_root, snapshot_id);
-
-    // Read file content
-    let json = fs::read_to_string(&snapshot_path)?;
+    // Read file content, choosing the format by extension
+    let compressed_path = get_compressed_snapshot_path(project_root, snapshot_id);
+    let json = if compressed_path.is_file() {
+        let content = with_retry(&retry_policy(project_root), || fs::read(&compressed_path))?;
+        let mut json = String::new();
+        GzDecoder::new(content.as_slice()).read_to_string(&mut json)?;
+        json
+    } else {
+        let snapshot_path = get_snapshot_path(project_root, snapshot_id);
+        with_retry(&retry_policy(project_root), || {
+            fs::read_to_string(&snapshot_path)
+        })?
+    };
+
+    // Check the schema version before deserializing so that newer formats
+    // produce a clear error instead of an obscure parse failure
+    let parse_error = |source| MovsError::SnapshotParseError {
+        id: snapshot_id.clone(),
+        source,
+    };
+    let value: serde_json::Value = serde_json::from_str(&json).map_err(parse_error)?;
+    let schema_version = value
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(1);
+
+    if schema_version > u64::from(SNAPSHOT_SCHEMA_VERSION) {
+        return Err(MovsError::ConfigError(format!(
+            "Snapshot '{}' uses schema version {}, but this version of MOVS only supports up to {}",
+            snapshot_id, schema_version, SNAPSHOT_SCHEMA_VERSION
+        )));
+    }
 
     // Deserialize
-    let metadata: SnapshotMetadata = serde_json::from_str(&json)?;
+    let metadata: SnapshotMetadata = serde_json::from_value(value).map_err(parse_error)?;
 
     Ok(metadata)
 }
 
+/// Load a snapshot and check it for internal consistency
+///
+/// Like `load_snapshot`, but additionally runs `SnapshotMetadata::validate`,
+/// checks that the stored ID matches the file it was loaded from, and
+/// recomputes the snapshot's `content_digest` to detect edits made outside
+/// MOVS. Snapshots saved before digests existed are not digest-checked,
+/// but a current snapshot whose digest was removed is rejected.
+pub fn load_snapshot_validated(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+) -> Result<SnapshotMetadata> {
+    let metadata = load_snapshot(project_root, snapshot_id)?;
+
+    if &metadata.id != snapshot_id {
+        return Err(MovsError::StorageError(format!(
+            "Snapshot file '{}' contains snapshot '{}'",
+            snapshot_id, metadata.id
+        )));
+    }
+    metadata.validate()?;
+    metadata.verify_content_digest()?;
+
+    Ok(metadata)
+}
+
+/// Load metadata for every snapshot in the repository
+///
+/// Snapshots are parsed in parallel and returned in the same order as
+/// `list_snapshots`.
+pub fn load_all_snapshots(project_root: &Path) -> Result<Vec<SnapshotMetadata>> {
+    crate::parallel::par_map(&list_snapshots(project_root)?, |id| {
+        load_snapshot(project_root, id)
+    })
+    .into_iter()
+    .collect()
+}
+
+/// Lazily load snapshots one at a time, in the same order as `list_snapshots`
+///
+/// Only the snapshot IDs are read up front; each snapshot file is parsed
+/// when the iterator reaches it, so aggregates over long histories run in
+/// constant memory.
+pub fn iter_snapshots(
+    project_root: &Path,
+) -> Result<impl Iterator<Item = Result<SnapshotMetadata>>> {
+    let project_root = project_root.to_path_buf();
+
+    Ok(list_snapshots(&project_root)?
+        .into_iter()
+        .map(move |id| load_snapshot(&project_root, &id)))
+}
+
 /// Delete a snapshot from disk
 /// 
 /// # Arguments
@@ -57,13 +208,29 @@ pub fn load_snapshot(project_root: &Path, snapshot_id: &SnapshotId) -> Result<Sn
 /// * `project_root` - Root directory of the project
 /// * `snapshot_id` - ID of the snapshot to delete
 pub fn delete_snapshot(project_root: &Path, snapshot_id: &SnapshotId) -> Result<()> {
+    let _lock = lock_for_write(project_root)?;
     if !snapshot_exists(project_root, snapshot_id) {
         return Err(MovsError::SnapshotNotFound(snapshot_id.to_string()));
     }
+    let previous = load_snapshot(project_root, snapshot_id).ok();
+
+    for snapshot_path in [
+        get_snapshot_path(project_root, snapshot_id),
+        get_compressed_snapshot_path(project_root, snapshot_id),
+    ] {
+        if snapshot_path.exists() {
+            fs::remove_file(snapshot_path)?;
+        }
+    }
 
-    let snapshot_path = get_snapshot_path(project_root, snapshot_id);
-    fs::remove_file(snapshot_path)?;
-
+    update_refcounts(project_root, previous.as_ref(), None);
+    oplog::record(
+        project_root,
+        Operation::Delete,
+        Some(snapshot_id),
+        None,
+        &NullObserver,
+    );
     Ok(())
 }
 
@@ -123,6 +290,24 @@ mod tests {
         assert_eq!(loaded_metadata.file_count(), original_metadata.file_count());
     }
 
+    #[test]
+    fn test_load_corrupted_snapshot_names_it() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let snapshot_id = SnapshotId::new("snapshot_broken".to_string());
+        fs::write(get_snapshot_path(project_root, &snapshot_id), "{\"id\": ").unwrap();
+
+        let err = load_snapshot(project_root, &snapshot_id).unwrap_err();
+        assert!(matches!(
+            &err,
+            MovsError::SnapshotParseError { id, .. } if id == &snapshot_id
+        ));
+        assert!(err.to_string().contains("snapshot_broken"));
+    }
+
     #[test]
     fn test_load_nonexistent_snapshot() {
         let temp_dir = TempDir::new().unwrap();
@@ -136,6 +321,53 @@ mod tests {
         assert!(matches!(result, Err(MovsError::SnapshotNotFound(_))));
     }
 
+    #[test]
+    fn test_load_all_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let mut first = create_test_metadata();
+        first.id = SnapshotId::new("snapshot_a".to_string());
+        let mut second = create_test_metadata();
+        second.id = SnapshotId::new("snapshot_b".to_string());
+
+        save_snapshot(project_root, &second).unwrap();
+        save_snapshot(project_root, &first).unwrap();
+
+        let all = load_all_snapshots(project_root).unwrap();
+        let ids: Vec<_> = all.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["snapshot_a", "snapshot_b"]);
+    }
+
+    #[test]
+    fn test_iter_snapshots_is_ordered_and_lazy() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let mut first = create_test_metadata();
+        first.id = SnapshotId::new("snapshot_a".to_string());
+        let mut second = create_test_metadata();
+        second.id = SnapshotId::new("snapshot_b".to_string());
+
+        save_snapshot(project_root, &second).unwrap();
+        save_snapshot(project_root, &first).unwrap();
+
+        let mut iter = iter_snapshots(project_root).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().id, first.id);
+
+        // Files are only read once the iterator reaches them
+        delete_snapshot(project_root, &second.id).unwrap();
+        assert!(matches!(
+            iter.next(),
+            Some(Err(MovsError::SnapshotNotFound(_)))
+        ));
+        assert!(iter.next().is_none());
+    }
+
     #[test]
     fn test_delete_snapshot() {
         let temp_dir = TempDir::new().unwrap();
@@ -180,5 +412,152 @@ mod tests {
         assert!(parsed.get("timestamp").is_some());
         assert!(parsed.get("message").is_some());
         assert!(parsed.get("files").is_some());
+        assert_eq!(parsed["schema_version"], SNAPSHOT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_load_snapshot_without_schema_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let metadata = create_test_metadata();
+        save_snapshot(project_root, &metadata).unwrap();
+
+        // Strip the field to simulate a snapshot written before versioning
+        let snapshot_path = get_snapshot_path(project_root, &metadata.id);
+        let mut parsed: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&snapshot_path).unwrap()).unwrap();
+        parsed.as_object_mut().unwrap().remove("schema_version");
+        fs::write(&snapshot_path, parsed.to_string()).unwrap();
+
+        let loaded = load_snapshot(project_root, &metadata.id).unwrap();
+        assert_eq!(loaded.schema_version, 1);
+    }
+
+    #[test]
+    fn test_load_snapshot_newer_schema() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let mut metadata = create_test_metadata();
+        metadata.schema_version = SNAPSHOT_SCHEMA_VERSION + 1;
+        save_snapshot(project_root, &metadata).unwrap();
+
+        let result = load_snapshot(project_root, &metadata.id);
+        match result {
+            Err(MovsError::ConfigError(msg)) => {
+                assert!(msg.contains(&(SNAPSHOT_SCHEMA_VERSION + 1).to_string()));
+            }
+            _ => panic!("Expected ConfigError"),
+        }
+    }
+
+    #[test]
+    fn test_load_snapshot_validated() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        // The test fixture uses short fake hashes
+        let metadata = create_test_metadata();
+        save_snapshot(project_root, &metadata).unwrap();
+
+        assert!(load_snapshot(project_root, &metadata.id).is_ok());
+        assert!(matches!(
+            load_snapshot_validated(project_root, &metadata.id),
+            Err(MovsError::StorageError(_))
+        ));
+    }
+
+    #[test]
+    fn test_content_digest_detects_edits() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let metadata = SnapshotMetadata::new(
+            SnapshotId::new("snapshot_digest".to_string()),
+            "Mixdown".to_string(),
+            None,
+            None,
+            vec![FileEntry::new(
+                PathBuf::from("mix.wav"),
+                crate::hash::hash_bytes(b"mix"),
+                3,
+                Utc::now(),
+            )],
+        );
+        save_snapshot(project_root, &metadata).unwrap();
+
+        let loaded = load_snapshot_validated(project_root, &metadata.id).unwrap();
+        assert_eq!(
+            loaded.content_digest,
+            Some(loaded.compute_content_digest().unwrap())
+        );
+
+        // Reformatting the file does not change the digest
+        let snapshot_path = get_snapshot_path(project_root, &metadata.id);
+        let mut parsed: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&snapshot_path).unwrap()).unwrap();
+        fs::write(&snapshot_path, parsed.to_string()).unwrap();
+        assert!(load_snapshot_validated(project_root, &metadata.id).is_ok());
+
+        parsed["files"][0]["size"] = serde_json::json!(4);
+        fs::write(&snapshot_path, parsed.to_string()).unwrap();
+        assert!(load_snapshot(project_root, &metadata.id).is_ok());
+        assert!(matches!(
+            load_snapshot_validated(project_root, &metadata.id),
+            Err(MovsError::SnapshotDigestMismatch { .. })
+        ));
+
+        // Removing the digest does not bypass the check
+        parsed.as_object_mut().unwrap().remove("content_digest");
+        fs::write(&snapshot_path, parsed.to_string()).unwrap();
+        assert!(matches!(
+            load_snapshot_validated(project_root, &metadata.id),
+            Err(MovsError::StorageError(_))
+        ));
+
+        // Snapshots from before digests existed are accepted without one
+        parsed["schema_version"] = serde_json::json!(1);
+        parsed["files"][0]["size"] = serde_json::json!(3);
+        fs::write(&snapshot_path, parsed.to_string()).unwrap();
+        assert!(load_snapshot_validated(project_root, &metadata.id).is_ok());
+    }
+
+    #[test]
+    fn test_compressed_metadata_roundtrip() {
+        use crate::metadata::config::save_config;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        config.compress_metadata = true;
+        save_config(project_root, &config).unwrap();
+
+        let metadata = create_test_metadata();
+        save_snapshot(project_root, &metadata).unwrap();
+
+        assert!(get_compressed_snapshot_path(project_root, &metadata.id).is_file());
+        assert!(!get_snapshot_path(project_root, &metadata.id).exists());
+        assert_eq!(list_snapshots(project_root).unwrap(), vec![metadata.id.clone()]);
+        assert_eq!(load_snapshot(project_root, &metadata.id).unwrap().files.len(), 2);
+
+        // Switching back rewrites as plain JSON without leaving a duplicate
+        config.compress_metadata = false;
+        save_config(project_root, &config).unwrap();
+        save_snapshot(project_root, &metadata).unwrap();
+
+        assert!(get_snapshot_path(project_root, &metadata.id).is_file());
+        assert!(!get_compressed_snapshot_path(project_root, &metadata.id).exists());
+
+        delete_snapshot(project_root, &metadata.id).unwrap();
+        assert!(list_snapshots(project_root).unwrap().is_empty());
     }
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::branches::advance_current_branch;
+use crate::metadata::lock::lock_for_write;
+use crate::metadata::{get_head_file, get_movs_dir, repository_exists};
+use crate::types::SnapshotId;
+use std::fs;
+use std::path::Path;
+
+/// Read the ID of the current snapshot
+///
+/// Returns `None` if no snapshot has been created yet.
+pub fn read_head(project_root: &Path) -> Result<Option<SnapshotId>> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    let head_path = get_head_file(project_root);
+    if !head_path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(head_path)?;
+    let id = content.trim();
+
+    if id.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(SnapshotId::new(id.to_string())))
+}
+
+/// Point HEAD at the given snapshot
+///
+/// If HEAD tracks a branch, the branch moves to the snapshot as well.
+pub fn write_head(project_root: &Path, snapshot_id: &SnapshotId) -> Result<()> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    let _lock = lock_for_write(project_root)?;
+    fs::write(get_head_file(project_root), format!("{}\n", snapshot_id))?;
+    advance_current_branch(project_root, snapshot_id)
+}
+
+/// Remove HEAD, e.g. after every snapshot was deleted
+pub fn clear_head(project_root: &Path) -> Result<()> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    let _lock = lock_for_write(project_root)?;
+    match fs::remove_file(get_head_file(project_root)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(MovsError::Io(e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_head_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        assert_eq!(read_head(project_root).unwrap(), None);
+
+        let id = SnapshotId::new("snapshot_1".to_string());
+        write_head(project_root, &id).unwrap();
+        assert_eq!(read_head(project_root).unwrap(), Some(id));
+
+        clear_head(project_root).unwrap();
+        assert_eq!(read_head(project_root).unwrap(), None);
+        clear_head(project_root).unwrap();
+    }
+
+    #[test]
+    fn test_head_missing_repository() {
+        let temp_dir = TempDir::new().unwrap();
+
+        assert!(matches!(
+            read_head(temp_dir.path()),
+            Err(MovsError::RepositoryNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,244 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::config::{load_config, save_config, write_config_unchecked};
+use crate::metadata::{get_movs_dir, repository_exists, MOVS_DIR};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+/// Move a repository from one project root to another
+///
+/// Snapshot entries and HEAD only hold paths relative to the project root,
+/// so moving the `.movs` directory is enough for every snapshot to load and
+/// restore at the new location. Any absolute path under `old_root` found in
+/// unrecognized config keys is rewritten to point below `new_root`.
+///
+/// The working files themselves are not moved. A relative `objects_dir`
+/// outside `.movs` therefore still names the store next to `old_root`, so
+/// it is rewritten to that store's absolute path.
+///
+/// # Arguments
+///
+/// * `old_root` - Current project root containing `.movs`
+/// * `new_root` - Existing directory that becomes the new project root
+pub fn relocate(old_root: &Path, new_root: &Path) -> Result<()> {
+    if !repository_exists(old_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(old_root)));
+    }
+    if repository_exists(new_root) {
+        return Err(MovsError::RepositoryAlreadyExists(get_movs_dir(new_root)));
+    }
+    if !new_root.is_dir() {
+        return Err(MovsError::InvalidPath(new_root.to_path_buf()));
+    }
+
+    let old_movs = get_movs_dir(old_root);
+    let new_movs = get_movs_dir(new_root);
+
+    // A rename is atomic; fall back to copying when crossing filesystems
+    if let Err(e) = fs::rename(&old_movs, &new_movs) {
+        if e.kind() != io::ErrorKind::CrossesDevices {
+            return Err(MovsError::Io(e));
+        }
+        copy_movs_dir(&old_movs, &new_movs)?;
+        fs::remove_dir_all(&old_movs)?;
+    }
+
+    let mut config = load_config(new_root)?;
+    let mut rewritten = false;
+    for value in config.extra.values_mut() {
+        rewritten |= rewrite_paths(value, old_root, new_root);
+    }
+    if rewritten {
+        save_config(new_root, &config)?;
+    }
+
+    if let Some(dir) = config.objects_dir.as_deref() {
+        if dir.is_relative() && !dir.starts_with(MOVS_DIR) {
+            let store = old_root.join(dir);
+            config.objects_dir = Some(fs::canonicalize(&store).unwrap_or(store));
+            // The store itself is unchanged, so skip `save_config`'s
+            // check against moving `objects_dir` of a non-empty repository
+            write_config_unchecked(new_root, &config)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Rewrite string values that are absolute paths under `old_root`
+fn rewrite_paths(value: &mut Value, old_root: &Path, new_root: &Path) -> bool {
+    match value {
+        Value::String(s) => match Path::new(s.as_str()).strip_prefix(old_root) {
+            Ok(relative) if Path::new(s.as_str()).is_absolute() => {
+                *s = new_root.join(relative).to_string_lossy().into_owned();
+                true
+            }
+            _ => false,
+        },
+        Value::Array(items) => items.iter_mut().fold(false, |changed, item| {
+            rewrite_paths(item, old_root, new_root) | changed
+        }),
+        Value::Object(map) => map.values_mut().fold(false, |changed, item| {
+            rewrite_paths(item, old_root, new_root) | changed
+        }),
+        _ => false,
+    }
+}
+
+/// Copy `from` to `to` through a staging directory next to `to`
+///
+/// A copy that fails part way is removed with the staging directory, so
+/// no partial repository is left at the destination.
+fn copy_movs_dir(from: &Path, to: &Path) -> Result<()> {
+    let parent = to
+        .parent()
+        .ok_or_else(|| MovsError::InvalidPath(to.to_path_buf()))?;
+    let staging = TempDir::new_in(parent)?;
+    let staged = staging.path().join(MOVS_DIR);
+
+    copy_dir(from, &staged)?;
+    fs::rename(&staged, to)?;
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    for entry in WalkDir::new(from) {
+        let entry = entry.map_err(|e| MovsError::Io(e.into()))?;
+        let target = to.join(
+            entry
+                .path()
+                .strip_prefix(from)
+                .map_err(|_| MovsError::InvalidPath(entry.path().to_path_buf()))?,
+        );
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::load_snapshot;
+    use crate::metadata::refs::read_head;
+    use crate::restore::restore_snapshot;
+    use crate::snapshot::create_snapshot;
+
+    #[test]
+    fn test_relocate_repository() {
+        let old_dir = TempDir::new().unwrap();
+        let new_dir = TempDir::new().unwrap();
+        let (old_root, new_root) = (old_dir.path(), new_dir.path());
+
+        init_repository(old_root).unwrap();
+        fs::create_dir(old_root.join("Samples")).unwrap();
+        fs::write(old_root.join("Samples/kick.wav"), b"kick").unwrap();
+        let id = create_snapshot(old_root, "First", None, &Default::default()).unwrap();
+
+        let mut config = load_config(old_root).unwrap();
+        config.extra.insert(
+            "sample_library".to_string(),
+            Value::String(old_root.join("Samples").to_string_lossy().into_owned()),
+        );
+        save_config(old_root, &config).unwrap();
+
+        relocate(old_root, new_root).unwrap();
+
+        assert!(!repository_exists(old_root));
+        assert_eq!(read_head(new_root).unwrap(), Some(id.clone()));
+        assert_eq!(load_snapshot(new_root, &id).unwrap().file_count(), 1);
+
+        restore_snapshot(new_root, &id, new_root, &Default::default()).unwrap();
+        assert_eq!(
+            fs::read(new_root.join("Samples/kick.wav")).unwrap(),
+            b"kick"
+        );
+
+        let config = load_config(new_root).unwrap();
+        assert_eq!(
+            config.extra["sample_library"],
+            Value::String(new_root.join("Samples").to_string_lossy().into_owned())
+        );
+    }
+
+    #[test]
+    fn test_relocate_keeps_relative_objects_dir() {
+        let dir = TempDir::new().unwrap();
+        let (old_root, new_root) = (dir.path().join("old"), dir.path().join("new"));
+        fs::create_dir(&old_root).unwrap();
+        fs::create_dir(&new_root).unwrap();
+        fs::create_dir(dir.path().join("store")).unwrap();
+
+        init_repository(&old_root).unwrap();
+        let mut config = load_config(&old_root).unwrap();
+        config.objects_dir = Some("../store".into());
+        save_config(&old_root, &config).unwrap();
+        fs::write(old_root.join("kick.wav"), b"kick").unwrap();
+        let id = create_snapshot(&old_root, "First", None, &Default::default()).unwrap();
+
+        relocate(&old_root, &new_root).unwrap();
+
+        let config = load_config(&new_root).unwrap();
+        assert_eq!(
+            config.objects_dir_in(&new_root),
+            fs::canonicalize(dir.path().join("store")).unwrap()
+        );
+
+        let target = TempDir::new().unwrap();
+        restore_snapshot(&new_root, &id, target.path(), &Default::default()).unwrap();
+        assert_eq!(fs::read(target.path().join("kick.wav")).unwrap(), b"kick");
+    }
+
+    #[test]
+    fn test_copy_movs_dir_leaves_only_the_copy() {
+        let old_dir = TempDir::new().unwrap();
+        let new_dir = TempDir::new().unwrap();
+        init_repository(old_dir.path()).unwrap();
+        fs::write(old_dir.path().join("song.als"), b"v1").unwrap();
+        create_snapshot(old_dir.path(), "v1", None, &Default::default()).unwrap();
+
+        copy_movs_dir(&get_movs_dir(old_dir.path()), &get_movs_dir(new_dir.path())).unwrap();
+
+        let names: Vec<_> = fs::read_dir(new_dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec![MOVS_DIR]);
+        assert_eq!(
+            read_head(new_dir.path()).unwrap(),
+            read_head(old_dir.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_copy_movs_dir_cleans_up_failed_copy() {
+        let new_dir = TempDir::new().unwrap();
+        let missing = new_dir.path().join("missing");
+
+        assert!(copy_movs_dir(&missing, &get_movs_dir(new_dir.path())).is_err());
+        assert_eq!(fs::read_dir(new_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_relocate_refuses_existing_repository() {
+        let old_dir = TempDir::new().unwrap();
+        let new_dir = TempDir::new().unwrap();
+        init_repository(old_dir.path()).unwrap();
+        init_repository(new_dir.path()).unwrap();
+
+        assert!(matches!(
+            relocate(old_dir.path(), new_dir.path()),
+            Err(MovsError::RepositoryAlreadyExists(_))
+        ));
+        assert!(repository_exists(old_dir.path()));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::error::Result;
+use crate::metadata::list_snapshots;
+use crate::metadata::persistence::{load_all_snapshots, load_snapshot};
+use crate::parallel::par_map;
+use crate::types::{FileHash, SnapshotId, SnapshotMetadata};
+use std::path::Path;
+
+/// Find all snapshots containing a file with the given content hash
+///
+/// Useful for tracing which versions of a session produced a given bounce.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `hash` - Content hash to search for
+pub fn find_snapshots_with_hash(project_root: &Path, hash: &FileHash) -> Result<Vec<SnapshotId>> {
+    Ok(load_all_snapshots(project_root)?
+        .into_iter()
+        .filter(|metadata| metadata.files.iter().any(|f| &f.hash == hash))
+        .map(|metadata| metadata.id)
+        .collect())
+}
+
+/// Find all snapshots containing a file at the given relative path
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `path` - Path relative to the project root
+pub fn find_snapshots_with_path(project_root: &Path, path: &Path) -> Result<Vec<SnapshotId>> {
+    Ok(load_all_snapshots(project_root)?
+        .into_iter()
+        .filter(|metadata| metadata.find_file(path).is_some())
+        .map(|metadata| metadata.id)
+        .collect())
+}
+
+/// Find snapshots whose message or description contains `query`
+///
+/// Snapshots are parsed in parallel and returned oldest first. Without
+/// `case_sensitive`, both sides are lowercased before comparing.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `query` - Text to look for
+/// * `case_sensitive` - Whether letter case must match
+pub fn search_snapshots(
+    project_root: &Path,
+    query: &str,
+    case_sensitive: bool,
+) -> Result<Vec<SnapshotMetadata>> {
+    if case_sensitive {
+        filter_snapshot_text(project_root, |text| text.contains(query))
+    } else {
+        let query = query.to_lowercase();
+        filter_snapshot_text(project_root, |text| text.to_lowercase().contains(&query))
+    }
+}
+
+/// Find snapshots whose message or description matches a regular expression
+///
+/// Like `search_snapshots`, but for patterns such as `(?i)kick|snare`.
+/// Returns `MovsError::InvalidPattern` if `pattern` does not compile.
+#[cfg(feature = "regex")]
+pub fn search_snapshots_regex(project_root: &Path, pattern: &str) -> Result<Vec<SnapshotMetadata>> {
+    let regex = regex::Regex::new(pattern)
+        .map_err(|e| crate::error::MovsError::InvalidPattern(e.to_string()))?;
+    filter_snapshot_text(project_root, |text| regex.is_match(text))
+}
+
+/// Load every snapshot in parallel and keep those whose text matches
+fn filter_snapshot_text(
+    project_root: &Path,
+    matches: impl Fn(&str) -> bool + Sync,
+) -> Result<Vec<SnapshotMetadata>> {
+    let loaded: Vec<Result<SnapshotMetadata>> = par_map(&list_snapshots(project_root)?, |id| {
+        load_snapshot(project_root, id)
+    });
+
+    let mut found = Vec::new();
+    for metadata in loaded {
+        let metadata = metadata?;
+        if matches(&metadata.message) || metadata.description.as_deref().is_some_and(&matches) {
+            found.push(metadata);
+        }
+    }
+
+    found.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::save_snapshot;
+    use crate::types::{FileEntry, SnapshotMetadata};
+    use chrono::Utc;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn save_test_snapshot(project_root: &Path, id: &str, files: Vec<(&str, Vec<u8>)>) {
+        let files = files
+            .into_iter()
+            .map(|(path, hash)| {
+                FileEntry::new(PathBuf::from(path), FileHash::new(hash), 10, Utc::now())
+            })
+            .collect();
+
+        let metadata = SnapshotMetadata::new(
+            SnapshotId::new(id.to_string()),
+            "Test snapshot".to_string(),
+            None,
+            None,
+            files,
+        );
+        save_snapshot(project_root, &metadata).unwrap();
+    }
+
+    #[test]
+    fn test_find_snapshots_with_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        save_test_snapshot(project_root, "snapshot_1", vec![("bounce.wav", vec![1, 1])]);
+        save_test_snapshot(project_root, "snapshot_2", vec![("bounce.wav", vec![2, 2])]);
+        save_test_snapshot(project_root, "snapshot_3", vec![("final.wav", vec![1, 1])]);
+
+        let found = find_snapshots_with_hash(project_root, &FileHash::new(vec![1, 1])).unwrap();
+        let ids: Vec<_> = found.iter().map(|id| id.as_str()).collect();
+        assert_eq!(ids, vec!["snapshot_1", "snapshot_3"]);
+
+        let none = find_snapshots_with_hash(project_root, &FileHash::new(vec![9, 9])).unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_find_snapshots_with_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        save_test_snapshot(project_root, "snapshot_1", vec![("drums.wav", vec![1])]);
+        save_test_snapshot(project_root, "snapshot_2", vec![("bass.wav", vec![2])]);
+        save_test_snapshot(project_root, "snapshot_3", vec![("drums.wav", vec![3])]);
+
+        let found = find_snapshots_with_path(project_root, Path::new("drums.wav")).unwrap();
+        let ids: Vec<_> = found.iter().map(|id| id.as_str()).collect();
+        assert_eq!(ids, vec!["snapshot_1", "snapshot_3"]);
+    }
+
+    fn save_message(project_root: &Path, id: &str, message: &str, description: Option<&str>) {
+        let mut metadata = SnapshotMetadata::new(
+            SnapshotId::new(id.to_string()),
+            message.to_string(),
+            None,
+            None,
+            Vec::new(),
+        );
+        metadata.description = description.map(str::to_string);
+        save_snapshot(project_root, &metadata).unwrap();
+    }
+
+    #[test]
+    fn test_search_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        save_message(project_root, "snapshot_1", "Fixed the Kick", None);
+        save_message(
+            project_root,
+            "snapshot_2",
+            "Vocal comp",
+            Some("kick is too loud"),
+        );
+        save_message(project_root, "snapshot_3", "Mix notes", None);
+
+        let ids = |found: Vec<SnapshotMetadata>| -> Vec<String> {
+            found.into_iter().map(|m| m.id.to_string()).collect()
+        };
+
+        assert_eq!(
+            ids(search_snapshots(project_root, "kick", false).unwrap()),
+            vec!["snapshot_1", "snapshot_2"]
+        );
+        assert_eq!(
+            ids(search_snapshots(project_root, "Kick", true).unwrap()),
+            vec!["snapshot_1"]
+        );
+        assert!(search_snapshots(project_root, "bass", false)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_search_snapshots_regex() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        save_message(project_root, "snapshot_1", "Fixed the kick", None);
+        save_message(project_root, "snapshot_2", "Snare swap", None);
+        save_message(project_root, "snapshot_3", "Mix notes", None);
+
+        let found = search_snapshots_regex(project_root, "(?i)kick|snare").unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(matches!(
+            search_snapshots_regex(project_root, "("),
+            Err(crate::error::MovsError::InvalidPattern(_))
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::list_snapshots;
+use crate::metadata::persistence::{delete_snapshot, load_snapshot, save_snapshot};
+use crate::metadata::refs::{read_head, write_head};
+use crate::types::{SnapshotId, SnapshotMetadata};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// A backend that stores snapshot metadata and HEAD
+///
+/// Together with `ObjectStore` this is everything snapshot logic needs, so
+/// a repository can live on disk or entirely in memory.
+pub trait MetadataStore: Send + Sync {
+    /// Persist a snapshot, replacing any snapshot with the same ID
+    fn save_snapshot(&self, metadata: &SnapshotMetadata) -> Result<()>;
+
+    /// Load a snapshot by ID
+    fn load_snapshot(&self, snapshot_id: &SnapshotId) -> Result<SnapshotMetadata>;
+
+    /// List all snapshot IDs, sorted
+    fn list_snapshots(&self) -> Result<Vec<SnapshotId>>;
+
+    /// Delete a snapshot by ID
+    fn delete_snapshot(&self, snapshot_id: &SnapshotId) -> Result<()>;
+
+    /// Read the ID of the current snapshot, if any
+    fn read_head(&self) -> Result<Option<SnapshotId>>;
+
+    /// Point HEAD at the given snapshot
+    fn write_head(&self, snapshot_id: &SnapshotId) -> Result<()>;
+}
+
+/// The default metadata store: JSON files and HEAD under `.movs`
+#[derive(Debug, Clone)]
+pub struct FsMetadataStore {
+    project_root: PathBuf,
+}
+
+impl FsMetadataStore {
+    /// Create a store for the repository at `project_root`
+    pub fn new(project_root: &Path) -> Self {
+        Self {
+            project_root: project_root.to_path_buf(),
+        }
+    }
+}
+
+impl MetadataStore for FsMetadataStore {
+    fn save_snapshot(&self, metadata: &SnapshotMetadata) -> Result<()> {
+        save_snapshot(&self.project_root, metadata)
+    }
+
+    fn load_snapshot(&self, snapshot_id: &SnapshotId) -> Result<SnapshotMetadata> {
+        load_snapshot(&self.project_root, snapshot_id)
+    }
+
+    fn list_snapshots(&self) -> Result<Vec<SnapshotId>> {
+        list_snapshots(&self.project_root)
+    }
+
+    fn delete_snapshot(&self, snapshot_id: &SnapshotId) -> Result<()> {
+        delete_snapshot(&self.project_root, snapshot_id)
+    }
+
+    fn read_head(&self) -> Result<Option<SnapshotId>> {
+        read_head(&self.project_root)
+    }
+
+    fn write_head(&self, snapshot_id: &SnapshotId) -> Result<()> {
+        write_head(&self.project_root, snapshot_id)
+    }
+}
+
+/// A metadata store kept entirely in memory, mainly for tests
+#[derive(Debug, Default)]
+pub struct MemoryMetadataStore {
+    snapshots: RwLock<BTreeMap<String, SnapshotMetadata>>,
+    head: RwLock<Option<SnapshotId>>,
+}
+
+impl MemoryMetadataStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn poisoned<T>(_: T) -> MovsError {
+    MovsError::StorageError("Metadata store lock poisoned".to_string())
+}
+
+impl MetadataStore for MemoryMetadataStore {
+    fn save_snapshot(&self, metadata: &SnapshotMetadata) -> Result<()> {
+        self.snapshots
+            .write()
+            .map_err(poisoned)?
+            .insert(metadata.id.as_str().to_string(), metadata.clone());
+        Ok(())
+    }
+
+    fn load_snapshot(&self, snapshot_id: &SnapshotId) -> Result<SnapshotMetadata> {
+        self.snapshots
+            .read()
+            .map_err(poisoned)?
+            .get(snapshot_id.as_str())
+            .cloned()
+            .ok_or_else(|| MovsError::SnapshotNotFound(snapshot_id.to_string()))
+    }
+
+    fn list_snapshots(&self) -> Result<Vec<SnapshotId>> {
+        Ok(self
+            .snapshots
+            .read()
+            .map_err(poisoned)?
+            .keys()
+            .map(|id| SnapshotId::new(id.clone()))
+            .collect())
+    }
+
+    fn delete_snapshot(&self, snapshot_id: &SnapshotId) -> Result<()> {
+        self.snapshots
+            .write()
+            .map_err(poisoned)?
+            .remove(snapshot_id.as_str())
+            .map(|_| ())
+            .ok_or_else(|| MovsError::SnapshotNotFound(snapshot_id.to_string()))
+    }
+
+    fn read_head(&self) -> Result<Option<SnapshotId>> {
+        Ok(self.head.read().map_err(poisoned)?.clone())
+    }
+
+    fn write_head(&self, snapshot_id: &SnapshotId) -> Result<()> {
+        *self.head.write().map_err(poisoned)? = Some(snapshot_id.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use tempfile::TempDir;
+
+    fn exercise(store: &dyn MetadataStore) {
+        assert_eq!(store.read_head().unwrap(), None);
+        assert!(store.list_snapshots().unwrap().is_empty());
+
+        let metadata = SnapshotMetadata::new(
+            SnapshotId::new("snapshot_1".to_string()),
+            "First".to_string(),
+            None,
+            None,
+            Vec::new(),
+        );
+        store.save_snapshot(&metadata).unwrap();
+        store.write_head(&metadata.id).unwrap();
+
+        assert_eq!(store.read_head().unwrap(), Some(metadata.id.clone()));
+        assert_eq!(store.list_snapshots().unwrap(), vec![metadata.id.clone()]);
+        assert_eq!(store.load_snapshot(&metadata.id).unwrap().message, "First");
+
+        store.delete_snapshot(&metadata.id).unwrap();
+        assert!(matches!(
+            store.load_snapshot(&metadata.id),
+            Err(MovsError::SnapshotNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_fs_metadata_store() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repository(temp_dir.path()).unwrap();
+
+        exercise(&FsMetadataStore::new(temp_dir.path()));
+    }
+
+    #[test]
+    fn test_memory_metadata_store() {
+        exercise(&MemoryMetadataStore::new());
+    }
+}
@@ -0,0 +1,34 @@
+use crate::types::{FileEntry, FileHash, SnapshotId};
+
+/// Receives structured events during long-running operations
+///
+/// Embedders implement this to drive progress bars or logging without
+/// polling. Every method has a no-op default, so implementors only override
+/// the events they care about.
+pub trait Observer {
+    /// A file was scanned and hashed during snapshot creation
+    fn on_file_scanned(&self, _entry: &FileEntry) {}
+
+    /// An object was handled by the store; `newly_stored` is false when it
+    /// was already present and deduplicated
+    fn on_object_stored(&self, _hash: &FileHash, _newly_stored: bool) {}
+
+    /// A snapshot was written and HEAD moved to it
+    fn on_snapshot_complete(&self, _snapshot_id: &SnapshotId) {}
+
+    /// A file was restored to the working tree
+    fn on_file_restored(&self, _entry: &FileEntry) {}
+
+    /// All files of a snapshot were restored
+    fn on_restore_complete(&self, _snapshot_id: &SnapshotId) {}
+
+    /// Something went wrong that did not stop the operation, e.g. the
+    /// operations log could not be written
+    fn on_warning(&self, _message: &str) {}
+}
+
+/// An observer that ignores every event
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullObserver;
+
+impl Observer for NullObserver {}
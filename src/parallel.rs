@@ -0,0 +1,21 @@
+/// Map `f` over `items`, on the rayon pool when the `parallel` feature is on
+///
+/// Results are in input order either way. Without the feature this is a
+/// plain serial loop, so the crate builds for targets without threads.
+pub(crate) fn par_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        items.par_iter().map(f).collect()
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.iter().map(f).collect()
+    }
+}
@@ -0,0 +1,232 @@
+use crate::error::{MovsError, Result};
+use crate::types::FileType;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Number of min/max pairs in a waveform preview
+pub const PREVIEW_BUCKETS: usize = 64;
+
+/// WAVE format tag for integer PCM samples
+const FORMAT_PCM: u16 = 1;
+
+/// WAVE format tag for IEEE float samples
+const FORMAT_FLOAT: u16 = 3;
+
+/// WAVE format tag indicating the real format is in the extension block
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Largest fmt chunk body in use (WAVE_FORMAT_EXTENSIBLE); longer chunks
+/// carry nothing the preview needs and the rest is skipped
+const MAX_FMT_LEN: u64 = 40;
+
+/// Compute a small waveform preview for a file
+///
+/// For WAV files this is `PREVIEW_BUCKETS` min/max peak pairs, interleaved
+/// as `[min0, max0, min1, max1, ...]`. Each value maps the sample range
+/// `-1.0..=1.0` onto `0..=255`, with 128 as silence; all channels are
+/// folded together. Other file types have no preview and return `None`.
+///
+/// The file is streamed once, so memory use does not depend on its length.
+/// Returns an error for WAV files that are malformed or use an unsupported
+/// sample format.
+pub fn compute_preview(path: &Path, kind: FileType) -> Result<Option<Vec<u8>>> {
+    match kind {
+        FileType::Wav => wav_preview(path).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Layout of the sample data in a WAV file
+struct WavFormat {
+    format: u16,
+    channels: u16,
+    bits_per_sample: u16,
+}
+
+fn wav_preview(path: &Path) -> Result<Vec<u8>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let invalid = |reason: &str| {
+        MovsError::StorageError(format!("Cannot preview '{}': {}", path.display(), reason))
+    };
+
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(invalid("not a RIFF/WAVE file"));
+    }
+
+    let mut format = None;
+    let data_len = loop {
+        let mut chunk = [0u8; 8];
+        if reader.read_exact(&mut chunk).is_err() {
+            return Err(invalid("no data chunk"));
+        }
+        let id = &chunk[0..4];
+        let len = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+
+        if id == b"fmt " {
+            if len < 16 {
+                return Err(invalid("truncated fmt chunk"));
+            }
+            let mut fmt = vec![0u8; len.min(MAX_FMT_LEN) as usize];
+            reader.read_exact(&mut fmt)?;
+            let mut tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+            if tag == FORMAT_EXTENSIBLE && fmt.len() >= 26 {
+                tag = u16::from_le_bytes([fmt[24], fmt[25]]);
+            }
+            format = Some(WavFormat {
+                format: tag,
+                channels: u16::from_le_bytes([fmt[2], fmt[3]]),
+                bits_per_sample: u16::from_le_bytes([fmt[14], fmt[15]]),
+            });
+            let rest = len - fmt.len() as u64 + len % 2;
+            reader.seek(SeekFrom::Current(rest as i64))?;
+        } else if id == b"data" {
+            break len;
+        } else {
+            // Chunks are padded to an even length
+            reader.seek(SeekFrom::Current((len + len % 2) as i64))?;
+        }
+    };
+
+    let format = format.ok_or_else(|| invalid("data chunk before fmt chunk"))?;
+    let bytes_per_sample = usize::from(format.bits_per_sample / 8);
+    let decode: fn(&[u8]) -> f32 = match (format.format, format.bits_per_sample) {
+        (FORMAT_PCM, 8) => |b| (f32::from(b[0]) - 128.0) / 128.0,
+        (FORMAT_PCM, 16) => |b| f32::from(i16::from_le_bytes([b[0], b[1]])) / 32768.0,
+        (FORMAT_PCM, 24) => {
+            |b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0
+        }
+        (FORMAT_PCM, 32) => {
+            |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0
+        }
+        (FORMAT_FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        _ => return Err(invalid("unsupported sample format")),
+    };
+    if format.channels == 0 {
+        return Err(invalid("zero channels"));
+    }
+
+    let total_samples = data_len / bytes_per_sample as u64;
+    let samples_per_bucket = total_samples.div_ceil(PREVIEW_BUCKETS as u64).max(1);
+    let mut peaks = vec![(0.0f32, 0.0f32); PREVIEW_BUCKETS];
+
+    let mut data = reader.take(data_len);
+    let mut buffer = vec![0u8; bytes_per_sample * 4096];
+    let mut pending = 0;
+    let mut index = 0u64;
+
+    loop {
+        let read = data.read(&mut buffer[pending..])?;
+        if read == 0 {
+            break;
+        }
+        let available = pending + read;
+        let whole = available - available % bytes_per_sample;
+
+        for sample in buffer[..whole].chunks_exact(bytes_per_sample) {
+            let value = decode(sample).clamp(-1.0, 1.0);
+            let bucket =
+                &mut peaks[((index / samples_per_bucket) as usize).min(PREVIEW_BUCKETS - 1)];
+            bucket.0 = bucket.0.min(value);
+            bucket.1 = bucket.1.max(value);
+            index += 1;
+        }
+
+        buffer.copy_within(whole..available, 0);
+        pending = available - whole;
+    }
+
+    let to_byte = |value: f32| ((value + 1.0) * 127.5).round() as u8;
+    Ok(peaks
+        .into_iter()
+        .flat_map(|(min, max)| [to_byte(min), to_byte(max)])
+        .collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Build a mono 16-bit PCM WAV file from samples
+    pub(crate) fn wav_16bit(samples: &[i16]) -> Vec<u8> {
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // channels
+        wav.extend_from_slice(&44_100u32.to_le_bytes());
+        wav.extend_from_slice(&(44_100u32 * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        wav
+    }
+
+    #[test]
+    fn test_wav_preview_peaks() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("kick.wav");
+
+        // Full-scale first half, silence second half
+        let mut samples = vec![0i16; PREVIEW_BUCKETS * 10];
+        for (i, sample) in samples.iter_mut().take(PREVIEW_BUCKETS * 5).enumerate() {
+            *sample = if i % 2 == 0 { i16::MAX } else { i16::MIN };
+        }
+        fs::write(&path, wav_16bit(&samples)).unwrap();
+
+        let preview = compute_preview(&path, FileType::Wav).unwrap().unwrap();
+        assert_eq!(preview.len(), PREVIEW_BUCKETS * 2);
+        assert_eq!(&preview[..2], &[0, 255]);
+        assert_eq!(&preview[preview.len() - 2..], &[128, 128]);
+    }
+
+    #[test]
+    fn test_preview_skips_other_types() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("song.als");
+        fs::write(&path, b"project").unwrap();
+
+        assert_eq!(compute_preview(&path, FileType::ProjectFile).unwrap(), None);
+    }
+
+    #[test]
+    fn test_preview_rejects_malformed_wav() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("broken.wav");
+        fs::write(&path, b"RIFF\0\0\0\0WAVEjunk").unwrap();
+
+        assert!(compute_preview(&path, FileType::Wav).is_err());
+
+        // A huge fmt length must not be allocated up front
+        let mut huge = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        huge.extend_from_slice(&[1, 0, 1, 0]);
+        fs::write(&path, &huge).unwrap();
+        assert!(compute_preview(&path, FileType::Wav).is_err());
+    }
+
+    #[test]
+    fn test_wav_preview_skips_long_fmt_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("long.wav");
+
+        // Grow the fmt chunk from 16 to 50 bytes of trailing padding
+        let mut wav = wav_16bit(&[16384; 128]);
+        wav[16..20].copy_from_slice(&50u32.to_le_bytes());
+        wav.splice(36..36, [0u8; 34]);
+        fs::write(&path, &wav).unwrap();
+
+        let preview = compute_preview(&path, FileType::Wav).unwrap().unwrap();
+        assert_eq!(preview.len(), PREVIEW_BUCKETS * 2);
+        assert!(preview.chunks(2).all(|pair| pair == [128, 191]));
+    }
+}
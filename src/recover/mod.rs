@@ -0,0 +1,620 @@
+use crate::error::{MovsError, Result};
+use crate::hash::hash_reader;
+use crate::metadata::config::load_config;
+use crate::metadata::get_movs_dir;
+use crate::metadata::persistence::save_snapshot;
+use crate::metadata::refs::{read_head, write_head};
+use crate::storage::delta::{
+    get_delta_path_with_config, list_delta_files, list_delta_objects, load_delta_object,
+};
+use crate::storage::pack::PackIndex;
+use crate::storage::{
+    get_object_path_with_config, list_loose_objects, object_exists_with_config, open_loose_object,
+    open_object_reader_with_config,
+};
+use crate::types::{FileEntry, FileHash, SnapshotId, SnapshotMetadata};
+use chrono::Utc;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory inside `.movs` holding objects set aside by `repair_objects`
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// An object whose content does not hash to the name it is stored under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectMismatch {
+    /// Hash derived from the object's file name or index entry
+    pub expected: FileHash,
+
+    /// Hash of the content actually stored
+    pub actual: FileHash,
+}
+
+/// Result of scanning the object store for recoverable content
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Objects whose content matches their hash
+    pub valid: Vec<FileHash>,
+
+    /// Objects whose content is corrupted
+    pub mismatched: Vec<ObjectMismatch>,
+
+    /// Objects that could not be read at all, with the error message
+    pub unreadable: Vec<(FileHash, String)>,
+}
+
+impl RecoveryReport {
+    /// Check if every object in the store is intact
+    pub fn is_clean(&self) -> bool {
+        self.mismatched.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+/// What `repair_objects` did with a misnamed object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepairAction {
+    /// Moved to the path matching its actual content hash
+    Relocated,
+
+    /// A correct copy already existed, so this one was moved to the given
+    /// path under `quarantine/`
+    Quarantined(PathBuf),
+
+    /// Left in place: packed objects cannot be moved individually
+    Unrepaired,
+}
+
+/// One discrepancy found and handled by `repair_objects`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectRepair {
+    /// The name and content that disagreed
+    pub mismatch: ObjectMismatch,
+
+    /// What was done about it
+    pub action: RepairAction,
+}
+
+/// Result of repairing the object store
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Number of objects re-hashed
+    pub checked: usize,
+
+    /// Every object whose name disagreed with its content
+    pub repairs: Vec<ObjectRepair>,
+
+    /// Objects that could not be read at all, with the error message
+    pub unreadable: Vec<(FileHash, String)>,
+}
+
+impl RepairReport {
+    /// Check if the store was already consistent
+    pub fn is_clean(&self) -> bool {
+        self.repairs.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+/// Get the directory misnamed duplicate objects are moved to
+pub fn get_quarantine_dir(project_root: &Path) -> PathBuf {
+    get_movs_dir(project_root).join(QUARANTINE_DIR)
+}
+
+/// Scan the object store and re-hash every object it holds
+///
+/// Loose, packed and delta objects are all included. Each object is read
+/// back and its content hashed, so the report distinguishes intact objects
+/// from corrupted or unreadable ones. Works without any snapshot metadata.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn scan_objects(project_root: &Path) -> Result<RecoveryReport> {
+    let config = load_config(project_root)?;
+    let mut report = RecoveryReport::default();
+
+    for hash in list_all_objects(project_root)? {
+        let actual = open_object_reader_with_config(project_root, &hash, &config)
+            .and_then(|mut reader| hash_reader(&mut reader));
+
+        match actual {
+            Ok(actual) if actual == hash => report.valid.push(hash),
+            Ok(actual) => report.mismatched.push(ObjectMismatch {
+                expected: hash,
+                actual,
+            }),
+            Err(e) => report.unreadable.push((hash, e.to_string())),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Re-hash a random sample of objects and return those that fail
+///
+/// Meant for a scheduled background scrub: each pass reads about
+/// `fraction` of the store, at least one object unless `fraction` is zero,
+/// so repeated passes catch bit rot without the cost of `scan_objects`.
+/// Corrupted and unreadable objects both count as failures.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `fraction` - Share of objects to check, from 0.0 to 1.0
+pub fn verify_random_sample(project_root: &Path, fraction: f32) -> Result<Vec<FileHash>> {
+    let seed = uuid::Uuid::new_v4().as_u64_pair().0;
+    verify_random_sample_seeded(project_root, fraction, seed)
+}
+
+/// Like `verify_random_sample`, but picks objects from a fixed seed
+///
+/// The same seed over the same store checks the same objects, which keeps
+/// test runs reproducible.
+pub fn verify_random_sample_seeded(
+    project_root: &Path,
+    fraction: f32,
+    seed: u64,
+) -> Result<Vec<FileHash>> {
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(MovsError::ConfigError(format!(
+            "Sample fraction must be between 0 and 1, got {}",
+            fraction
+        )));
+    }
+
+    let config = load_config(project_root)?;
+    let hashes = list_all_objects(project_root)?;
+    let count = ((hashes.len() as f64) * f64::from(fraction)).ceil() as usize;
+
+    let mut failed = Vec::new();
+    for index in sample_indices(hashes.len(), count.min(hashes.len()), seed) {
+        let hash = &hashes[index];
+        let intact = open_object_reader_with_config(project_root, hash, &config)
+            .and_then(|mut reader| hash_reader(&mut reader))
+            .is_ok_and(|actual| actual == *hash);
+        if !intact {
+            failed.push(hash.clone());
+        }
+    }
+
+    Ok(failed)
+}
+
+/// Every object in the store, loose, packed or delta, sorted by hash
+fn list_all_objects(project_root: &Path) -> Result<Vec<FileHash>> {
+    let mut seen = HashSet::new();
+    let mut hashes = Vec::new();
+    let loose = list_loose_objects(project_root)?
+        .into_iter()
+        .map(|(hash, _)| hash);
+    let packed = PackIndex::load(project_root)?
+        .iter()
+        .map(|(hash, _)| hash)
+        .collect::<Vec<_>>();
+    for hash in loose.chain(packed).chain(list_delta_objects(project_root)?) {
+        if seen.insert(hash.clone()) {
+            hashes.push(hash);
+        }
+    }
+    hashes.sort_by_key(|hash| hash.to_hex());
+    Ok(hashes)
+}
+
+/// Pick `count` distinct indices below `len`, in ascending order
+///
+/// A partial Fisher-Yates shuffle driven by splitmix64, which is plenty
+/// for spreading scrub passes evenly and needs no extra dependency.
+fn sample_indices(len: usize, count: usize, seed: u64) -> Vec<usize> {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+
+    let mut indices: Vec<usize> = (0..len).collect();
+    for i in 0..count {
+        let j = i + (next() % (len - i) as u64) as usize;
+        indices.swap(i, j);
+    }
+    indices.truncate(count);
+    indices.sort_unstable();
+    indices
+}
+
+/// Return the hash of every intact object in the store
+///
+/// A last-resort recovery path for repositories whose `snapshots/`
+/// directory was lost. Corrupted objects are left out; use `scan_objects`
+/// to see them.
+pub fn recover_objects(project_root: &Path) -> Result<Vec<FileHash>> {
+    Ok(scan_objects(project_root)?.valid)
+}
+
+/// Re-hash every object and fix those stored under the wrong name
+///
+/// A loose or delta object whose content hashes to something other than
+/// its name is moved to the path for its actual hash, so it can serve
+/// snapshots that reference that content. If an object with the actual
+/// hash already exists, the misnamed copy is moved to `quarantine/`
+/// instead. Nothing is ever deleted. Packed mismatches are reported but
+/// left in place, and the names they claimed stay missing; run
+/// `verify_snapshot` afterwards to see which snapshots are affected.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn repair_objects(project_root: &Path) -> Result<RepairReport> {
+    let config = load_config(project_root)?;
+    let mut report = RepairReport::default();
+
+    // Plain files first, so relocated loose objects can serve as delta bases
+    let mut candidates: Vec<(FileHash, Stored)> = list_loose_objects(project_root)?
+        .into_iter()
+        .map(|(hash, path)| (hash, Stored::Loose(path)))
+        .collect();
+    candidates.extend(
+        list_delta_files(project_root)?
+            .into_iter()
+            .map(|(hash, path)| (hash, Stored::Delta(path))),
+    );
+    candidates.extend(
+        PackIndex::load(project_root)?
+            .iter()
+            .map(|(hash, _)| (hash, Stored::Packed)),
+    );
+
+    for (hash, stored) in candidates {
+        report.checked += 1;
+
+        let actual = match &stored {
+            Stored::Loose(path) => {
+                open_loose_object(path).and_then(|mut reader| hash_reader(&mut reader))
+            }
+            Stored::Delta(_) => load_delta_object(project_root, &hash)
+                .and_then(|content| content.ok_or_else(|| missing_object(&hash)))
+                .map(|content| crate::hash::hash_bytes(&content)),
+            Stored::Packed => open_object_reader_with_config(project_root, &hash, &config)
+                .and_then(|mut reader| hash_reader(&mut reader)),
+        };
+        let actual = match actual {
+            Ok(actual) if actual == hash => continue,
+            Ok(actual) => actual,
+            Err(e) => {
+                report.unreadable.push((hash, e.to_string()));
+                continue;
+            }
+        };
+
+        let (path, target) = match stored {
+            Stored::Packed => {
+                report.repairs.push(ObjectRepair {
+                    mismatch: ObjectMismatch {
+                        expected: hash,
+                        actual,
+                    },
+                    action: RepairAction::Unrepaired,
+                });
+                continue;
+            }
+            Stored::Loose(path) => {
+                let target = get_object_path_with_config(project_root, &actual, &config);
+                (path, target)
+            }
+            Stored::Delta(path) => (
+                path,
+                get_delta_path_with_config(project_root, &actual, &config),
+            ),
+        };
+
+        let action = if object_exists_with_config(project_root, &actual, &config) {
+            RepairAction::Quarantined(quarantine(project_root, &path, &hash)?)
+        } else {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&path, &target)?;
+            RepairAction::Relocated
+        };
+
+        report.repairs.push(ObjectRepair {
+            mismatch: ObjectMismatch {
+                expected: hash,
+                actual,
+            },
+            action,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Where an object checked by `repair_objects` lives
+enum Stored {
+    Loose(PathBuf),
+    Delta(PathBuf),
+    Packed,
+}
+
+/// Move a suspect object file into the quarantine directory
+fn quarantine(project_root: &Path, path: &Path, hash: &FileHash) -> Result<PathBuf> {
+    let dir = get_quarantine_dir(project_root);
+    fs::create_dir_all(&dir)?;
+
+    let mut target = dir.join(hash.to_hex());
+    let mut n = 1;
+    while target.exists() {
+        target = dir.join(format!("{}.{}", hash.to_hex(), n));
+        n += 1;
+    }
+
+    fs::rename(path, &target)?;
+    Ok(target)
+}
+
+fn missing_object(hash: &FileHash) -> MovsError {
+    MovsError::StorageError(format!("Object not found: {}", hash))
+}
+
+/// Rebuild a snapshot from a known file layout over existing objects
+///
+/// Each `(path, hash)` pair must refer to an object present in the store;
+/// sizes are taken from the objects themselves. The snapshot is saved with
+/// the current HEAD as parent, and HEAD is set to it if the repository had
+/// none.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `entries` - Relative paths and the object hash each should contain
+/// * `message` - Message for the rebuilt snapshot
+pub fn reconstruct_snapshot<I>(
+    project_root: &Path,
+    entries: I,
+    message: &str,
+) -> Result<SnapshotMetadata>
+where
+    I: IntoIterator<Item = (PathBuf, FileHash)>,
+{
+    let config = load_config(project_root)?;
+    let now = Utc::now();
+    let mut files = Vec::new();
+
+    for (path, hash) in entries {
+        let mut reader =
+            open_object_reader_with_config(project_root, &hash, &config).map_err(|_| {
+                MovsError::StorageError(format!(
+                    "Cannot reconstruct '{}': object {} is missing",
+                    path.display(),
+                    hash.to_hex()
+                ))
+            })?;
+        let size = std::io::copy(&mut reader, &mut std::io::sink())?;
+        files.push(FileEntry::new(path, hash, size, now));
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let head = read_head(project_root)?;
+    let metadata = SnapshotMetadata::new(
+        SnapshotId::generate_with(config.id_scheme),
+        message.to_string(),
+        None,
+        head.clone(),
+        files,
+    );
+    metadata.validate()?;
+
+    save_snapshot(project_root, &metadata)?;
+    if head.is_none() {
+        write_head(project_root, &metadata.id)?;
+    }
+
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::load_snapshot;
+    use crate::restore::{restore_snapshot, RestoreOptions};
+    use crate::storage::object_exists;
+    use crate::storage::pack::pack_objects;
+    use crate::storage::{get_object_path, store_object_bytes};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_recover_objects_after_losing_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let packed = store_object_bytes(project_root, b"packed").unwrap();
+        pack_objects(project_root).unwrap();
+        let loose = store_object_bytes(project_root, b"loose").unwrap();
+
+        let mut expected = vec![packed, loose];
+        expected.sort_by_key(|hash| hash.to_hex());
+        assert_eq!(recover_objects(project_root).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_scan_objects_reports_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let good = store_object_bytes(project_root, b"good").unwrap();
+        let bad = store_object_bytes(project_root, b"bad").unwrap();
+        fs::write(get_object_path(project_root, &bad).unwrap(), b"bitrot").unwrap();
+
+        let report = scan_objects(project_root).unwrap();
+        assert!(!report.is_clean());
+        assert_eq!(report.valid, vec![good]);
+        assert_eq!(
+            report.mismatched,
+            vec![ObjectMismatch {
+                expected: bad,
+                actual: crate::hash::hash_bytes(b"bitrot"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_verify_random_sample() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        store_object_bytes(project_root, b"good").unwrap();
+        let bad = store_object_bytes(project_root, b"bad").unwrap();
+        fs::write(get_object_path(project_root, &bad).unwrap(), b"bitrot").unwrap();
+
+        assert_eq!(verify_random_sample(project_root, 1.0).unwrap(), vec![bad]);
+        assert!(verify_random_sample(project_root, 0.0).unwrap().is_empty());
+        assert!(matches!(
+            verify_random_sample(project_root, 1.5),
+            Err(MovsError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_sample_indices_are_reproducible() {
+        let sample = sample_indices(100, 10, 42);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+        assert!(sample.iter().all(|&i| i < 100));
+        assert_eq!(sample_indices(100, 10, 42), sample);
+        assert_ne!(sample_indices(100, 10, 7), sample);
+
+        assert_eq!(sample_indices(5, 5, 1), vec![0, 1, 2, 3, 4]);
+        assert!(sample_indices(0, 0, 1).is_empty());
+    }
+
+    #[test]
+    fn test_reconstruct_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let kick = store_object_bytes(project_root, b"kick").unwrap();
+        let song = store_object_bytes(project_root, b"project").unwrap();
+
+        let metadata = reconstruct_snapshot(
+            project_root,
+            vec![
+                (PathBuf::from("song.als"), song),
+                (PathBuf::from("Samples/kick.wav"), kick),
+            ],
+            "Recovered",
+        )
+        .unwrap();
+
+        assert_eq!(read_head(project_root).unwrap(), Some(metadata.id.clone()));
+        let loaded = load_snapshot(project_root, &metadata.id).unwrap();
+        assert_eq!(loaded.files.len(), 2);
+        assert_eq!(loaded.files[1].size, 7);
+
+        let target = temp_dir.path().join("restored");
+        restore_snapshot(
+            project_root,
+            &metadata.id,
+            &target,
+            &RestoreOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(fs::read(target.join("Samples/kick.wav")).unwrap(), b"kick");
+    }
+
+    #[test]
+    fn test_reconstruct_snapshot_missing_object() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let result = reconstruct_snapshot(
+            project_root,
+            vec![(PathBuf::from("gone.wav"), FileHash::new(vec![9; 32]))],
+            "Recovered",
+        );
+        assert!(matches!(result, Err(MovsError::StorageError(_))));
+    }
+
+    #[test]
+    fn test_repair_relocates_misnamed_object() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let kick = store_object_bytes(project_root, b"kick").unwrap();
+        let snare = crate::hash::hash_bytes(b"snare");
+        fs::write(get_object_path(project_root, &kick).unwrap(), b"snare").unwrap();
+
+        let report = repair_objects(project_root).unwrap();
+        assert_eq!(report.checked, 1);
+        assert_eq!(
+            report.repairs,
+            vec![ObjectRepair {
+                mismatch: ObjectMismatch {
+                    expected: kick.clone(),
+                    actual: snare.clone(),
+                },
+                action: RepairAction::Relocated,
+            }]
+        );
+        assert!(!object_exists(project_root, &kick).unwrap());
+        assert_eq!(
+            crate::storage::load_object(project_root, &snare).unwrap(),
+            b"snare"
+        );
+        assert!(repair_objects(project_root).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_repair_quarantines_duplicates() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let kick = store_object_bytes(project_root, b"kick").unwrap();
+        store_object_bytes(project_root, b"snare").unwrap();
+        fs::write(get_object_path(project_root, &kick).unwrap(), b"snare").unwrap();
+
+        let report = repair_objects(project_root).unwrap();
+        let [repair] = report.repairs.as_slice() else {
+            panic!("expected one repair, got {:?}", report.repairs);
+        };
+        let RepairAction::Quarantined(path) = &repair.action else {
+            panic!("expected quarantine, got {:?}", repair.action);
+        };
+
+        assert!(path.starts_with(get_quarantine_dir(project_root)));
+        assert_eq!(fs::read(path).unwrap(), b"snare");
+        assert!(!get_object_path(project_root, &kick).unwrap().exists());
+    }
+
+    #[test]
+    fn test_repair_leaves_compressed_objects_alone() {
+        use crate::metadata::config::save_config;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        config.compress_objects = true;
+        save_config(project_root, &config).unwrap();
+
+        let kick = store_object_bytes(project_root, &[3u8; 4096]).unwrap();
+        let report = repair_objects(project_root).unwrap();
+        assert_eq!(report.checked, 1);
+        assert!(report.is_clean());
+        assert_eq!(
+            crate::storage::load_object(project_root, &kick).unwrap(),
+            vec![3u8; 4096]
+        );
+    }
+}
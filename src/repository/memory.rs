@@ -0,0 +1,312 @@
+use crate::clock::SystemClock;
+use crate::diff::diff_snapshots;
+use crate::error::Result;
+use crate::gc::referenced_objects;
+use crate::hash::hash_bytes;
+use crate::metadata::store::{MemoryMetadataStore, MetadataStore};
+use crate::observer::NullObserver;
+use crate::restore::copy_object;
+use crate::snapshot::{prepare_snapshot, store_prepared, Prepared, SnapshotOptions, Stores};
+use crate::storage::store::{MemoryObjectStore, ObjectStore};
+use crate::tree::path::normalize_relative;
+use crate::types::{
+    classify_path, FileEntry, FileHash, IdScheme, SnapshotDiff, SnapshotId, SnapshotMetadata,
+};
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A repository kept entirely in memory
+///
+/// Implements both `ObjectStore` and `MetadataStore` with in-memory maps,
+/// so downstream crates can test against MOVS without touching the
+/// filesystem. Snapshots are created, restored, compared and collected by
+/// the same code as the on-disk repository; only the working tree is
+/// supplied as a list of `(path, content)` pairs instead of being scanned.
+#[derive(Debug, Default)]
+pub struct MemoryRepository {
+    objects: MemoryObjectStore,
+    metadata: MemoryMetadataStore,
+    id_scheme: IdScheme,
+}
+
+impl MemoryRepository {
+    /// Create an empty repository
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Snapshot the given files and move HEAD to the new snapshot
+    ///
+    /// Returns the parent's ID unchanged if nothing differs from it and
+    /// `options.allow_empty` is false. Like on disk, snapshotting no files
+    /// at all fails with `MovsError::EmptySnapshot` unless
+    /// `options.allow_empty` is set.
+    ///
+    /// Paths are normalized like scanned ones, so `./a.wav` and `a.wav` name
+    /// the same file and the later content wins. Absolute paths and paths
+    /// escaping the root fail with `MovsError::InvalidPath`.
+    pub fn create_snapshot<I>(
+        &self,
+        files: I,
+        message: &str,
+        author: Option<&str>,
+        options: &SnapshotOptions,
+    ) -> Result<SnapshotId>
+    where
+        I: IntoIterator<Item = (PathBuf, Vec<u8>)>,
+    {
+        let mut contents = BTreeMap::new();
+        for (path, content) in files {
+            contents.insert(normalize_relative(Path::new(""), &path)?, content);
+        }
+
+        let entries = contents
+            .iter()
+            .map(|(path, content)| {
+                FileEntry::new(
+                    path.clone(),
+                    hash_bytes(content),
+                    content.len() as u64,
+                    Utc::now(),
+                )
+                .with_file_type(classify_path(path))
+            })
+            .collect();
+
+        let (metadata, parent) = match prepare_snapshot(
+            &self.metadata,
+            entries,
+            message,
+            author,
+            options,
+            self.id_scheme,
+            &SystemClock,
+        )? {
+            Prepared::Unchanged(parent_id) => return Ok(parent_id),
+            Prepared::New { metadata, parent } => (metadata, parent),
+        };
+
+        let stores = Stores {
+            objects: &self.objects,
+            metadata: &self.metadata,
+        };
+        store_prepared(
+            stores,
+            &metadata,
+            parent.as_ref(),
+            options,
+            &NullObserver,
+            &mut |entry, _| self.objects.put(&contents[&entry.path]).map(|_| ()),
+        )?;
+        Ok(metadata.id)
+    }
+
+    /// Read back every file of a snapshot, verifying each checksum
+    pub fn restore(&self, snapshot_id: &SnapshotId) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+        let metadata = self.metadata.load_snapshot(snapshot_id)?;
+        let mut files = BTreeMap::new();
+
+        for entry in metadata.files.iter().filter(|entry| entry.is_file()) {
+            let mut content = Vec::new();
+            copy_object(&self.objects, entry, &mut content, true, &mut |_| {})?;
+            files.insert(entry.path.clone(), content);
+        }
+
+        Ok(files)
+    }
+
+    /// Delete the objects no snapshot references
+    ///
+    /// # Returns
+    ///
+    /// The number of objects removed
+    pub fn gc(&self) -> Result<usize> {
+        self.objects.retain(&referenced_objects(&self.metadata)?)
+    }
+
+    /// Compute the changes from one snapshot to another
+    pub fn diff(&self, old: &SnapshotId, new: &SnapshotId) -> Result<SnapshotDiff> {
+        Ok(diff_snapshots(
+            &self.metadata.load_snapshot(old)?,
+            &self.metadata.load_snapshot(new)?,
+        ))
+    }
+}
+
+impl ObjectStore for MemoryRepository {
+    fn put(&self, content: &[u8]) -> Result<FileHash> {
+        self.objects.put(content)
+    }
+
+    fn get(&self, hash: &FileHash) -> Result<Vec<u8>> {
+        self.objects.get(hash)
+    }
+
+    fn contains(&self, hash: &FileHash) -> bool {
+        self.objects.contains(hash)
+    }
+
+    fn iter_hashes(&self) -> Result<Box<dyn Iterator<Item = FileHash> + '_>> {
+        self.objects.iter_hashes()
+    }
+}
+
+impl MetadataStore for MemoryRepository {
+    fn save_snapshot(&self, metadata: &SnapshotMetadata) -> Result<()> {
+        self.metadata.save_snapshot(metadata)
+    }
+
+    fn load_snapshot(&self, snapshot_id: &SnapshotId) -> Result<SnapshotMetadata> {
+        self.metadata.load_snapshot(snapshot_id)
+    }
+
+    fn list_snapshots(&self) -> Result<Vec<SnapshotId>> {
+        self.metadata.list_snapshots()
+    }
+
+    fn delete_snapshot(&self, snapshot_id: &SnapshotId) -> Result<()> {
+        self.metadata.delete_snapshot(snapshot_id)
+    }
+
+    fn read_head(&self) -> Result<Option<SnapshotId>> {
+        self.metadata.read_head()
+    }
+
+    fn write_head(&self, snapshot_id: &SnapshotId) -> Result<()> {
+        self.metadata.write_head(snapshot_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::MovsError;
+
+    fn files(list: &[(&str, &[u8])]) -> Vec<(PathBuf, Vec<u8>)> {
+        list.iter()
+            .map(|(path, content)| (PathBuf::from(path), content.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_snapshot_restore_diff_in_memory() {
+        let repo = MemoryRepository::new();
+        let options = SnapshotOptions::default();
+
+        let first = repo
+            .create_snapshot(
+                files(&[("song.als", b"v1"), ("vox.wav", b"vox")]),
+                "First",
+                None,
+                &options,
+            )
+            .unwrap();
+        let second = repo
+            .create_snapshot(
+                files(&[("song.als", b"v2"), ("bass.wav", b"bass")]),
+                "Second",
+                Some("Producer"),
+                &options,
+            )
+            .unwrap();
+
+        assert_eq!(repo.read_head().unwrap(), Some(second.clone()));
+        assert_eq!(
+            repo.load_snapshot(&second).unwrap().parent,
+            Some(first.clone())
+        );
+
+        let restored = repo.restore(&first).unwrap();
+        assert_eq!(restored[&PathBuf::from("song.als")], b"v1");
+        assert_eq!(restored[&PathBuf::from("vox.wav")], b"vox");
+
+        let diff = repo.diff(&first, &second).unwrap();
+        assert_eq!(diff.summary(), "1 added, 1 modified, 1 removed");
+    }
+
+    #[test]
+    fn test_unchanged_snapshot_is_skipped() {
+        let repo = MemoryRepository::new();
+        let options = SnapshotOptions::default();
+
+        let first = repo
+            .create_snapshot(files(&[("a.wav", b"a")]), "First", None, &options)
+            .unwrap();
+        let again = repo
+            .create_snapshot(files(&[("a.wav", b"a")]), "Again", None, &options)
+            .unwrap();
+
+        assert_eq!(first, again);
+        assert_eq!(repo.list_snapshots().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_empty_snapshot_rejected_in_memory() {
+        let repo = MemoryRepository::new();
+
+        let result = repo.create_snapshot(Vec::new(), "Empty", None, &SnapshotOptions::default());
+        assert!(matches!(result, Err(MovsError::EmptySnapshot { .. })));
+
+        let options = SnapshotOptions {
+            allow_empty: true,
+            ..SnapshotOptions::default()
+        };
+        assert!(repo
+            .create_snapshot(Vec::new(), "Empty", None, &options)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_gc_in_memory() {
+        let repo = MemoryRepository::new();
+        let options = SnapshotOptions::default();
+
+        let first = repo
+            .create_snapshot(files(&[("a.wav", b"v1")]), "First", None, &options)
+            .unwrap();
+        repo.create_snapshot(files(&[("a.wav", b"v2")]), "Second", None, &options)
+            .unwrap();
+        assert_eq!(repo.gc().unwrap(), 0);
+
+        repo.delete_snapshot(&first).unwrap();
+        assert_eq!(repo.gc().unwrap(), 1);
+        assert!(!repo.contains(&hash_bytes(b"v1")));
+        assert!(repo.contains(&hash_bytes(b"v2")));
+    }
+
+    #[test]
+    fn test_paths_normalized_in_memory() {
+        let repo = MemoryRepository::new();
+        let options = SnapshotOptions::default();
+
+        let id = repo
+            .create_snapshot(
+                files(&[("./a.wav", b"old"), ("Stems/../a.wav", b"new")]),
+                "Normalized",
+                None,
+                &options,
+            )
+            .unwrap();
+        let snapshot = repo.load_snapshot(&id).unwrap();
+        assert_eq!(snapshot.files.len(), 1);
+        assert_eq!(snapshot.files[0].path, PathBuf::from("a.wav"));
+        assert_eq!(repo.restore(&id).unwrap()[&PathBuf::from("a.wav")], b"new");
+
+        let result = repo.create_snapshot(files(&[("../a.wav", b"a")]), "Escape", None, &options);
+        assert!(matches!(result, Err(MovsError::InvalidPath(_))));
+    }
+
+    #[test]
+    fn test_case_collisions_rejected_in_memory() {
+        let repo = MemoryRepository::new();
+
+        let result = repo.create_snapshot(
+            files(&[("Kick.wav", b"a"), ("kick.wav", b"b")]),
+            "Collide",
+            None,
+            &SnapshotOptions::default(),
+        );
+        assert!(matches!(result, Err(MovsError::PathCollision { .. })));
+    }
+}
@@ -0,0 +1,349 @@
+use crate::error::{MovsError, Result};
+use crate::gc::referenced_objects;
+use crate::metadata::config::validate_objects_dir;
+use crate::metadata::lock::lock_for_write;
+use crate::metadata::migration::check_format_version;
+use crate::metadata::store::{FsMetadataStore, MetadataStore};
+use crate::metadata::{get_movs_dir, init_repository, repository_exists};
+use crate::observer::NullObserver;
+use crate::restore::{restore_snapshot_inner, RestoreOptions, RestoreReport};
+use crate::snapshot::{SnapshotBuilder, Stores};
+use crate::stats::{repository_stats, RepoStats};
+use crate::storage::store::{FsObjectStore, ObjectStore};
+use crate::types::SnapshotId;
+use std::path::{Path, PathBuf};
+
+pub mod memory;
+
+pub use memory::MemoryRepository;
+
+/// An opened MOVS repository
+///
+/// Bundles the project root with the object and metadata store backends,
+/// so callers can swap in a different store without touching snapshot logic:
+/// `snapshot`, `restore` and `gc` run the regular code paths against
+/// whichever stores the repository was opened with.
+pub struct Repository {
+    root: PathBuf,
+    objects: Box<dyn ObjectStore>,
+    metadata: Box<dyn MetadataStore>,
+}
+
+impl Repository {
+    /// Open an existing repository backed by the filesystem object store
+    pub fn open(project_root: &Path) -> Result<Self> {
+        Self::with_object_store(project_root, Box::new(FsObjectStore::new(project_root)?))
+    }
+
+    /// Open the repository at `project_root`, initializing it if there is none
+    ///
+    /// An existing repository, including its config, is left untouched.
+    /// There is no separate existence check: initialization is attempted
+    /// first and relies on `init_repository` creating `.movs` exclusively,
+    /// so when two processes race only one initializes and the other opens
+    /// the result. The loser may open the repository while the winner is
+    /// still writing its config; operations that read the config fall back
+    /// to defaults until it appears.
+    pub fn open_or_init(project_root: &Path) -> Result<Self> {
+        match init_repository(project_root) {
+            Ok(()) | Err(MovsError::RepositoryAlreadyExists(_)) => Self::open(project_root),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Open an existing repository with a custom object store
+    ///
+    /// Fails with `MovsError::ConfigError` if the repository was created by
+    /// a newer version of MOVS with an incompatible format, or if the config
+    /// names an external `objects_dir` that does not exist or is not
+    /// writable. Older formats open fine.
+    pub fn with_object_store(project_root: &Path, objects: Box<dyn ObjectStore>) -> Result<Self> {
+        if !repository_exists(project_root) {
+            return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+        }
+        check_format_version(project_root)?;
+        validate_objects_dir(project_root)?;
+
+        Ok(Self {
+            root: project_root.to_path_buf(),
+            objects,
+            metadata: Box::new(FsMetadataStore::new(project_root)),
+        })
+    }
+
+    /// Get the project root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the object store backend
+    pub fn objects(&self) -> &dyn ObjectStore {
+        self.objects.as_ref()
+    }
+
+    /// Get the metadata store backend
+    pub fn metadata(&self) -> &dyn MetadataStore {
+        self.metadata.as_ref()
+    }
+
+    /// Start a snapshot of the working tree, stored through this
+    /// repository's backends
+    ///
+    /// Configure it like any `SnapshotBuilder`, then call `create`.
+    pub fn snapshot<'a>(&'a self, message: &'a str) -> SnapshotBuilder<'a> {
+        SnapshotBuilder::new(&self.root, message).with_stores(Stores {
+            objects: self.objects.as_ref(),
+            metadata: self.metadata.as_ref(),
+        })
+    }
+
+    /// Restore a snapshot into `target_dir`, reading content from this
+    /// repository's object store
+    ///
+    /// Behaves like `restore::restore_snapshot`.
+    pub fn restore(
+        &self,
+        snapshot_id: &SnapshotId,
+        target_dir: &Path,
+        options: &RestoreOptions,
+    ) -> Result<RestoreReport> {
+        restore_snapshot_inner(
+            self.objects.as_ref(),
+            &self.root,
+            snapshot_id,
+            target_dir,
+            options,
+            &NullObserver,
+            &mut |_| {},
+        )
+    }
+
+    /// Delete the objects no snapshot references
+    ///
+    /// Snapshots are read from the metadata store and objects deleted
+    /// through the object store, all under the repository lock. Fails if
+    /// the object store cannot delete objects.
+    ///
+    /// # Returns
+    ///
+    /// The number of objects removed
+    pub fn gc(&self) -> Result<usize> {
+        let _lock = lock_for_write(&self.root)?;
+        self.objects
+            .retain(&referenced_objects(self.metadata.as_ref())?)
+    }
+
+    /// Compute aggregate statistics for the repository
+    ///
+    /// Disk usage is measured on the filesystem store under `.movs/objects`.
+    pub fn stats(&self) -> Result<RepoStats> {
+        repository_stats(&self.root)
+    }
+}
+
+impl std::fmt::Debug for Repository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Repository")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::storage::object_exists;
+    use crate::storage::store::MemoryObjectStore;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_open_requires_repository() {
+        let temp_dir = TempDir::new().unwrap();
+
+        assert!(matches!(
+            Repository::open(temp_dir.path()),
+            Err(MovsError::RepositoryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_open_checks_format_version() {
+        use crate::metadata::{get_config_file, REPO_FORMAT_VERSION};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let config_file = get_config_file(project_root);
+        let mut config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&config_file).unwrap()).unwrap();
+
+        // Older formats stay readable
+        config["format_version"] = serde_json::json!(1);
+        std::fs::write(&config_file, config.to_string()).unwrap();
+        assert!(Repository::open(project_root).is_ok());
+
+        config["format_version"] = serde_json::json!(REPO_FORMAT_VERSION + 1);
+        std::fs::write(&config_file, config.to_string()).unwrap();
+        let err = Repository::open(project_root).err().unwrap();
+        assert!(matches!(err, MovsError::ConfigError(_)));
+        assert!(err.to_string().contains("newer than the supported version"));
+    }
+
+    #[test]
+    fn test_open_or_init() {
+        use crate::metadata::config::{load_config, save_config};
+
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::open_or_init(temp_dir.path()).unwrap();
+        assert!(repository_exists(repo.root()));
+
+        let mut config = load_config(temp_dir.path()).unwrap();
+        config.delta_objects = true;
+        save_config(temp_dir.path(), &config).unwrap();
+
+        // Reopening keeps the existing config
+        Repository::open_or_init(temp_dir.path()).unwrap();
+        assert!(load_config(temp_dir.path()).unwrap().delta_objects);
+    }
+
+    #[test]
+    fn test_shared_object_store() {
+        use crate::metadata::config::{load_config, save_config};
+        use crate::snapshot::create_snapshot;
+        use std::fs;
+
+        let temp_dir = TempDir::new().unwrap();
+        let mastering = temp_dir.path().join("mastering");
+        let mixing = temp_dir.path().join("mixing");
+        fs::create_dir(&mastering).unwrap();
+        fs::create_dir(&mixing).unwrap();
+        init_repository(&mastering).unwrap();
+        init_repository(&mixing).unwrap();
+
+        // The mixing copy borrows the mastering copy's store
+        let mut config = load_config(&mixing).unwrap();
+        config.objects_dir = Some(PathBuf::from("../mastering/.movs/objects"));
+        save_config(&mixing, &config).unwrap();
+
+        fs::write(mastering.join("song.als"), b"shared").unwrap();
+        create_snapshot(&mastering, "master", None, &Default::default()).unwrap();
+        let repo = Repository::open(&mixing).unwrap();
+        assert!(repo.objects().contains(&crate::hash::hash_bytes(b"shared")));
+
+        fs::write(mixing.join("mix.als"), b"mix only").unwrap();
+        create_snapshot(&mixing, "mix", None, &Default::default()).unwrap();
+        assert!(object_exists(&mastering, &crate::hash::hash_bytes(b"mix only")).unwrap());
+        assert!(!mixing
+            .join(".movs/objects")
+            .read_dir()
+            .unwrap()
+            .any(|_| true));
+
+        // A vanished store is caught at open time
+        fs::remove_dir_all(mastering.join(".movs")).unwrap();
+        assert!(matches!(
+            Repository::open(&mixing),
+            Err(MovsError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_objects_dir_cannot_change_with_objects() {
+        use crate::metadata::config::{load_config, save_config};
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir(&root).unwrap();
+        init_repository(&root).unwrap();
+        Repository::open(&root)
+            .unwrap()
+            .objects()
+            .put(b"vox")
+            .unwrap();
+
+        let mut config = load_config(&root).unwrap();
+        config.objects_dir = Some(temp_dir.path().to_path_buf());
+        assert!(matches!(
+            save_config(&root, &config),
+            Err(MovsError::ConfigError(_))
+        ));
+
+        config.objects_dir = Some(temp_dir.path().join("missing"));
+        assert!(matches!(
+            save_config(&root, &config),
+            Err(MovsError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_open_uses_filesystem_store() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repository(temp_dir.path()).unwrap();
+
+        let repo = Repository::open(temp_dir.path()).unwrap();
+        let hash = repo.objects().put(b"vox").unwrap();
+        assert!(object_exists(temp_dir.path(), &hash).unwrap());
+    }
+
+    #[test]
+    fn test_custom_object_store() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repository(temp_dir.path()).unwrap();
+
+        let repo =
+            Repository::with_object_store(temp_dir.path(), Box::new(MemoryObjectStore::new()))
+                .unwrap();
+        let hash = repo.objects().put(b"vox").unwrap();
+
+        assert!(repo.objects().contains(&hash));
+        assert!(!object_exists(temp_dir.path(), &hash).unwrap());
+    }
+
+    #[test]
+    fn test_custom_object_store_runs_snapshot_restore_and_gc() {
+        use crate::metadata::list_snapshots;
+        use std::fs;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox").unwrap();
+
+        let repo = Repository::with_object_store(project_root, Box::new(MemoryObjectStore::new()))
+            .unwrap();
+        let (first, result) = repo.snapshot("First").create().unwrap();
+        assert_eq!(result.new_objects, 1);
+
+        let vox = crate::hash::hash_bytes(b"vox");
+        assert!(repo.objects().contains(&vox));
+        assert!(!object_exists(project_root, &vox).unwrap());
+
+        fs::write(project_root.join("vox.wav"), b"vox v2").unwrap();
+        repo.snapshot("Second").create().unwrap();
+
+        let restored = TempDir::new().unwrap();
+        repo.restore(&first, restored.path(), &RestoreOptions::default())
+            .unwrap();
+        assert_eq!(fs::read(restored.path().join("vox.wav")).unwrap(), b"vox");
+
+        crate::metadata::persistence::delete_snapshot(project_root, &first).unwrap();
+        assert_eq!(list_snapshots(project_root).unwrap().len(), 1);
+        assert_eq!(repo.gc().unwrap(), 1);
+        assert!(!repo.objects().contains(&vox));
+    }
+
+    #[test]
+    fn test_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repository(temp_dir.path()).unwrap();
+
+        let repo = Repository::open(temp_dir.path()).unwrap();
+        repo.objects().put(b"vox").unwrap();
+
+        let stats = repo.stats().unwrap();
+        assert_eq!(stats.snapshot_count, 0);
+        assert_eq!(stats.object_count, 1);
+    }
+}
@@ -0,0 +1,1137 @@
+use crate::error::{MovsError, Result};
+use crate::hash::{hash_bytes, hash_file};
+use crate::log::{self as oplog, Operation};
+use crate::metadata::persistence::load_snapshot;
+use crate::metadata::refs::read_head;
+use crate::observer::{NullObserver, Observer};
+use crate::storage::store::{FsObjectStore, ObjectStore};
+use crate::tree::path::{fs_path, normalize_relative};
+use crate::types::{EntryKind, FileEntry, FileHash, SnapshotId};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tempfile::NamedTempFile;
+
+mod reflink;
+
+const COPY_CHUNK_SIZE: usize = 1024 * 1024; // 1 MB
+
+/// Options controlling how a snapshot is restored
+#[derive(Debug, Clone)]
+pub struct RestoreOptions {
+    /// Overwrite files with local changes that were never snapshotted
+    ///
+    /// When false, a destination file whose content matches neither the
+    /// snapshot being restored nor HEAD is left alone and reported as a
+    /// conflict instead of being clobbered.
+    pub overwrite_modified: bool,
+
+    /// Hash restored content and check it against the snapshot (default true)
+    ///
+    /// Turning this off speeds up restores to trusted local disks. Packed,
+    /// delta and compressed objects are still verified, since they are
+    /// reassembled or decoded from other data on read, and so are objects
+    /// hardlinked to a working file; only plain loose objects are copied
+    /// unchecked.
+    pub verify: bool,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            overwrite_modified: false,
+            verify: true,
+        }
+    }
+}
+
+/// Outcome of a restore
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// Paths that were restored
+    pub restored: Vec<PathBuf>,
+
+    /// Paths skipped because they hold uncommitted local changes
+    pub conflicts: Vec<PathBuf>,
+}
+
+impl RestoreReport {
+    /// Check if every entry was restored
+    pub fn is_complete(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Progress of a restore, passed to the callback of
+/// `restore_snapshot_with_progress`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestoreProgress<'a> {
+    /// Path of the entry being restored, relative to the project root
+    pub path: &'a Path,
+
+    /// Position of the entry in the snapshot, starting at 0
+    pub file_index: usize,
+
+    /// Number of entries in the snapshot
+    pub file_count: usize,
+
+    /// Bytes of this entry written so far
+    pub bytes_written: u64,
+
+    /// Size of this entry
+    pub file_size: u64,
+
+    /// Bytes written so far across the whole restore
+    pub total_bytes_written: u64,
+
+    /// Combined size of every entry in the snapshot
+    pub total_bytes: u64,
+}
+
+impl RestoreProgress<'_> {
+    /// Check if this is the final report for the entry
+    pub fn is_file_complete(&self) -> bool {
+        self.bytes_written == self.file_size
+    }
+}
+
+/// Restore all files of a snapshot into a target directory
+///
+/// Each file is streamed from the object store in fixed-size chunks, so
+/// memory use stays flat regardless of file size. Content is hashed while
+/// copying and checked against the snapshot's recorded hash. Files in the
+/// target directory that are not part of the snapshot are left untouched,
+/// so restoring a scoped snapshot only touches files within its scope.
+///
+/// Unless `options.overwrite_modified` is set, existing files whose content
+/// differs from both the snapshot and HEAD are skipped and returned as
+/// conflicts, mirroring git's refusal to clobber local changes.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `snapshot_id` - ID of the snapshot to restore
+/// * `target_dir` - Directory to restore files into
+/// * `options` - Restore options
+pub fn restore_snapshot(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    target_dir: &Path,
+    options: &RestoreOptions,
+) -> Result<RestoreReport> {
+    restore_snapshot_with_observer(
+        project_root,
+        snapshot_id,
+        target_dir,
+        options,
+        &NullObserver,
+    )
+}
+
+/// Restore a snapshot, reporting progress to an observer
+///
+/// Behaves exactly like `restore_snapshot`, but emits `on_file_restored`
+/// after each restored entry and `on_restore_complete` at the end.
+pub fn restore_snapshot_with_observer(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    target_dir: &Path,
+    options: &RestoreOptions,
+    observer: &dyn Observer,
+) -> Result<RestoreReport> {
+    restore_snapshot_inner(
+        &FsObjectStore::new(project_root)?,
+        project_root,
+        snapshot_id,
+        target_dir,
+        options,
+        observer,
+        &mut |_| {},
+    )
+}
+
+/// Restore a snapshot, calling `progress` as content is written
+///
+/// Behaves exactly like `restore_snapshot_with_observer`, with `observer`
+/// receiving the same events alongside `progress`. `progress` is called
+/// once per restored entry, with `bytes_written` equal to `file_size`.
+/// Files copied in more than one chunk also report each chunk as it is
+/// written, so a progress bar keeps moving through a multi-gigabyte stem.
+/// Entries skipped as conflicts are not reported, so the final
+/// `total_bytes_written` can fall short of `total_bytes`.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `snapshot_id` - ID of the snapshot to restore
+/// * `target_dir` - Directory to restore files into
+/// * `options` - Restore options
+/// * `observer` - Receives restore events; pass `&NullObserver` for none
+/// * `progress` - Called with the progress after each chunk and entry
+pub fn restore_snapshot_with_progress(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    target_dir: &Path,
+    options: &RestoreOptions,
+    observer: &dyn Observer,
+    mut progress: impl FnMut(RestoreProgress<'_>),
+) -> Result<RestoreReport> {
+    restore_snapshot_inner(
+        &FsObjectStore::new(project_root)?,
+        project_root,
+        snapshot_id,
+        target_dir,
+        options,
+        observer,
+        &mut progress,
+    )
+}
+
+/// Restore a snapshot, reading its content from `objects`
+pub(crate) fn restore_snapshot_inner(
+    objects: &dyn ObjectStore,
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    target_dir: &Path,
+    options: &RestoreOptions,
+    observer: &dyn Observer,
+    progress: &mut dyn FnMut(RestoreProgress<'_>),
+) -> Result<RestoreReport> {
+    let metadata = load_snapshot(project_root, snapshot_id)?;
+
+    let head_files: HashMap<PathBuf, FileHash> = if options.overwrite_modified {
+        HashMap::new()
+    } else {
+        match read_head(project_root)? {
+            Some(head) => load_snapshot(project_root, &head)?
+                .files
+                .into_iter()
+                .map(|entry| (entry.path, entry.hash))
+                .collect(),
+            None => HashMap::new(),
+        }
+    };
+
+    let mut report = RestoreReport::default();
+    let symlinks = symlink_paths(metadata.files.iter().map(|entry| (entry, &entry.path)));
+    let file_count = metadata.files.len();
+    let total_bytes: u64 = metadata.files.iter().map(|entry| entry.size).sum();
+    let mut total_bytes_written = 0;
+
+    for (file_index, entry) in metadata.files.iter().enumerate() {
+        let report_progress = |bytes_written| RestoreProgress {
+            path: &entry.path,
+            file_index,
+            file_count,
+            bytes_written,
+            file_size: entry.size,
+            total_bytes_written: total_bytes_written + bytes_written,
+            total_bytes,
+        };
+
+        // Scoped snapshots never write outside their subtree
+        if let Some(scope) = &metadata.scope {
+            if !entry.path.starts_with(scope) {
+                continue;
+            }
+        }
+        let dest = restore_destination(target_dir, &entry.path, &symlinks)?;
+
+        if !options.overwrite_modified {
+            if let Some(local) = local_hash(&dest)? {
+                let committed = head_files.get(&entry.path);
+                if local != entry.hash && committed != Some(&local) {
+                    report.conflicts.push(entry.path.clone());
+                    continue;
+                }
+            }
+        }
+
+        restore_entry(objects, entry, &dest, options.verify, &mut |written| {
+            progress(report_progress(written))
+        })?;
+        progress(report_progress(entry.size));
+        total_bytes_written += entry.size;
+        observer.on_file_restored(entry);
+        report.restored.push(entry.path.clone());
+    }
+
+    oplog::record(
+        project_root,
+        Operation::Restore,
+        Some(snapshot_id),
+        None,
+        observer,
+    );
+    observer.on_restore_complete(snapshot_id);
+    Ok(report)
+}
+
+/// Restore a snapshot into `dest`, relocating or skipping entries
+///
+/// `remap` is called with each entry's snapshot path. Returning `None`
+/// skips the entry; returning `Some(path)` writes it to that path under
+/// `dest`, so `|p| Some(p.to_path_buf())` is a plain restore. Remapped
+/// paths are normalized like snapshot paths and must stay inside `dest`.
+/// Existing files are overwritten, and content is always verified against
+/// the snapshot's hash wherever it lands.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `snapshot_id` - ID of the snapshot to restore
+/// * `dest` - Directory to restore files into
+/// * `remap` - Maps each snapshot path to its destination, or `None` to skip
+///
+/// # Returns
+///
+/// A report listing the destination paths written, relative to `dest`
+pub fn restore_into(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    dest: &Path,
+    remap: impl Fn(&Path) -> Option<PathBuf>,
+) -> Result<RestoreReport> {
+    let metadata = load_snapshot(project_root, snapshot_id)?;
+    let objects = FsObjectStore::new(project_root)?;
+    let mut report = RestoreReport::default();
+
+    let mut targets = Vec::new();
+    for entry in &metadata.files {
+        if let Some(target) = remap(&entry.path) {
+            targets.push((entry, normalize_relative(dest, &target)?));
+        }
+    }
+    let symlinks = symlink_paths(targets.iter().map(|(entry, target)| (*entry, target)));
+
+    for (entry, target) in &targets {
+        let target_path = restore_destination(dest, target, &symlinks)?;
+
+        restore_entry(&objects, entry, &target_path, true, &mut |_| {})?;
+        report.restored.push(target.clone());
+    }
+
+    oplog::record(
+        project_root,
+        Operation::Restore,
+        Some(snapshot_id),
+        None,
+        &NullObserver,
+    );
+    Ok(report)
+}
+
+/// Destination paths of the symlink entries among `entries`
+fn symlink_paths<'a>(
+    entries: impl Iterator<Item = (&'a FileEntry, &'a PathBuf)>,
+) -> HashSet<&'a Path> {
+    entries
+        .filter(|(entry, _)| matches!(entry.kind, EntryKind::Symlink { .. }))
+        .map(|(_, path)| path.as_path())
+        .collect()
+}
+
+/// Join a snapshot path onto the directory it is restored into
+///
+/// Snapshot files can be edited or imported, so their paths are not
+/// trusted: every component must be a plain name, and no parent directory
+/// may be one of the snapshot's own symlinks, given as `symlinks`, which
+/// could redirect the write outside `target_dir` once restored.
+fn restore_destination(
+    target_dir: &Path,
+    path: &Path,
+    symlinks: &HashSet<&Path>,
+) -> Result<PathBuf> {
+    let invalid = || MovsError::InvalidPath(path.to_path_buf());
+
+    if path.as_os_str().is_empty()
+        || !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        || path
+            .ancestors()
+            .skip(1)
+            .any(|parent| symlinks.contains(parent))
+    {
+        return Err(invalid());
+    }
+
+    Ok(target_dir.join(path))
+}
+
+/// Hash whatever currently sits at `dest`, as a snapshot would record it
+///
+/// Returns `None` if nothing is there or it is a directory.
+fn local_hash(dest: &Path) -> Result<Option<FileHash>> {
+    let fs_metadata = match dest.symlink_metadata() {
+        Ok(fs_metadata) => fs_metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(MovsError::Io(e)),
+    };
+
+    if fs_metadata.file_type().is_symlink() {
+        let target = fs::read_link(dest)?;
+        Ok(Some(hash_bytes(target.to_string_lossy().as_bytes())))
+    } else if fs_metadata.is_file() {
+        Ok(Some(hash_file(dest)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Restore a single file entry to the given destination path
+///
+/// Content is written to a temporary file next to the destination and only
+/// moved into place once its hash has been verified. Symlink entries are
+/// recreated as links and directory entries as empty directories.
+pub fn restore_file(project_root: &Path, entry: &FileEntry, dest: &Path) -> Result<()> {
+    restore_entry(
+        &FsObjectStore::new(project_root)?,
+        entry,
+        dest,
+        true,
+        &mut |_| {},
+    )
+}
+
+/// Restore one entry, calling `on_chunk` with the bytes written so far
+/// after each chunk of a file that is copied in pieces
+///
+/// Content is verified when `verify` is set, and regardless of it unless
+/// the store holds the object as a plain local file.
+fn restore_entry(
+    objects: &dyn ObjectStore,
+    entry: &FileEntry,
+    dest: &Path,
+    verify: bool,
+    on_chunk: &mut dyn FnMut(u64),
+) -> Result<()> {
+    let dest = &fs_path(dest)?;
+    let parent = dest
+        .parent()
+        .ok_or_else(|| MovsError::InvalidPath(dest.to_path_buf()))?;
+    fs::create_dir_all(parent)?;
+
+    match &entry.kind {
+        EntryKind::File => {}
+        EntryKind::Symlink { target } => return restore_symlink(target, dest),
+        EntryKind::Directory => {
+            fs::create_dir_all(dest)?;
+            return Ok(());
+        }
+    }
+
+    let mut writer = NamedTempFile::new_in(parent)?;
+    let local = objects.local_path(&entry.hash)?;
+    let reflinked = match &local {
+        Some(source) => reflink_object(source, entry, writer.as_file(), verify)?,
+        None => false,
+    };
+    if !reflinked {
+        copy_object(
+            objects,
+            entry,
+            &mut writer,
+            verify || local.is_none(),
+            on_chunk,
+        )?;
+    }
+
+    writer.as_file().set_modified(entry.modified.into())?;
+    writer.persist(dest).map_err(|e| MovsError::Io(e.error))?;
+
+    Ok(())
+}
+
+/// Stream a file's content from a snapshot into a writer
+///
+/// Nothing touches the disk besides reading the object, which suits
+/// previewing an old version in a player. The content is hashed while it
+/// is copied; if it does not match the snapshot, `ChecksumMismatch` is
+/// returned after the writer has already received the bad bytes, so
+/// callers that cannot take that back should buffer the output.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `snapshot_id` - Snapshot to read from
+/// * `path` - Path of the file within the snapshot
+/// * `writer` - Destination for the file's content
+///
+/// # Errors
+///
+/// Returns `MovsError::FileNotFoundInSnapshot` if the snapshot has no
+/// entry at `path`, and `MovsError::RestoreError` if the entry is a
+/// directory or symlink.
+pub fn read_file_from_snapshot(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    path: &Path,
+    mut writer: impl Write,
+) -> Result<()> {
+    let metadata = load_snapshot(project_root, snapshot_id)?;
+    let entry = metadata
+        .find_file(path)
+        .ok_or_else(|| MovsError::FileNotFoundInSnapshot(path.to_path_buf()))?;
+
+    if !entry.is_file() {
+        return Err(MovsError::RestoreError(format!(
+            "'{}' is not a regular file",
+            entry.path.display()
+        )));
+    }
+
+    copy_object(
+        &FsObjectStore::new(project_root)?,
+        entry,
+        &mut writer,
+        true,
+        &mut |_| {},
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Clone an object's local file into `dest` without copying its data, if
+/// possible
+///
+/// `source` comes from `ObjectStore::local_path`. Returns false when the
+/// filesystem cannot reflink, in which case `dest` is still empty. With
+/// `verify`, the object is hashed after cloning, which reads it but still
+/// avoids writing a second copy.
+fn reflink_object(source: &Path, entry: &FileEntry, dest: &fs::File, verify: bool) -> Result<bool> {
+    let Ok(src) = fs::File::open(source) else {
+        return Ok(false);
+    };
+    if !reflink::reflink(&src, dest) {
+        return Ok(false);
+    }
+
+    if verify {
+        let actual = hash_file(source)?;
+        if actual != entry.hash {
+            return Err(MovsError::ChecksumMismatch {
+                path: entry.path.clone(),
+                expected: entry.hash.to_hex(),
+                actual: actual.to_hex(),
+            });
+        }
+    }
+
+    Ok(true)
+}
+
+/// Copy a file entry's object into `writer`, optionally verifying its hash
+///
+/// `on_chunk` is called with the bytes written so far after every chunk
+/// that leaves the copy unfinished.
+pub(crate) fn copy_object(
+    objects: &dyn ObjectStore,
+    entry: &FileEntry,
+    writer: &mut impl Write,
+    verify: bool,
+    on_chunk: &mut dyn FnMut(u64),
+) -> Result<()> {
+    let mut reader = objects.open(&entry.hash)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE];
+    let mut written = 0;
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+
+        if verify {
+            hasher.update(&buffer[..bytes_read]);
+        }
+        writer.write_all(&buffer[..bytes_read])?;
+
+        written += bytes_read as u64;
+        if written < entry.size {
+            on_chunk(written);
+        }
+    }
+
+    let actual = FileHash::new(hasher.finalize().to_vec());
+    if verify && actual != entry.hash {
+        return Err(MovsError::ChecksumMismatch {
+            path: entry.path.clone(),
+            expected: entry.hash.to_hex(),
+            actual: actual.to_hex(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Recreate a symlink, replacing whatever is currently at `dest`
+fn restore_symlink(target: &Path, dest: &Path) -> Result<()> {
+    if dest.symlink_metadata().is_ok() {
+        fs::remove_file(dest)?;
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, dest)?;
+
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_file(target, dest)?;
+
+    #[cfg(not(any(unix, windows)))]
+    return Err(MovsError::RestoreError(format!(
+        "Symlinks are not supported on this platform: {}",
+        dest.display()
+    )));
+
+    #[cfg(any(unix, windows))]
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::save_snapshot;
+    use crate::storage::{get_object_path, store_object_bytes};
+    use crate::types::SnapshotMetadata;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn save_snapshot_with(project_root: &Path, files: &[(&str, &[u8])]) -> SnapshotId {
+        let entries = files
+            .iter()
+            .map(|(path, content)| {
+                let hash = store_object_bytes(project_root, content).unwrap();
+                FileEntry::new(PathBuf::from(path), hash, content.len() as u64, Utc::now())
+            })
+            .collect();
+
+        let metadata = SnapshotMetadata::new(
+            SnapshotId::generate(),
+            "Test snapshot".to_string(),
+            None,
+            None,
+            entries,
+        );
+        save_snapshot(project_root, &metadata).unwrap();
+        metadata.id
+    }
+
+    #[test]
+    fn test_restore_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = save_snapshot_with(
+            project_root,
+            &[("song.als", b"project data"), ("Samples/kick.wav", b"kick")],
+        );
+
+        let target = TempDir::new().unwrap();
+        restore_snapshot(project_root, &id, target.path(), &Default::default()).unwrap();
+
+        assert_eq!(
+            fs::read(target.path().join("song.als")).unwrap(),
+            b"project data"
+        );
+        assert_eq!(
+            fs::read(target.path().join("Samples/kick.wav")).unwrap(),
+            b"kick"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_restore_recreates_symlinks() {
+        use crate::snapshot::create_snapshot;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("kick.wav"), b"kick").unwrap();
+        std::os::unix::fs::symlink("kick.wav", project_root.join("link.wav")).unwrap();
+
+        let id = create_snapshot(project_root, "Links", None, &Default::default()).unwrap();
+
+        let target = TempDir::new().unwrap();
+        restore_snapshot(project_root, &id, target.path(), &Default::default()).unwrap();
+
+        let link = target.path().join("link.wav");
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("kick.wav"));
+        assert_eq!(fs::read(&link).unwrap(), b"kick");
+    }
+
+    #[test]
+    fn test_restore_with_observer() {
+        use std::cell::RefCell;
+
+        struct Recorder(RefCell<Vec<PathBuf>>, RefCell<bool>);
+
+        impl Observer for Recorder {
+            fn on_file_restored(&self, entry: &FileEntry) {
+                self.0.borrow_mut().push(entry.path.clone());
+            }
+
+            fn on_restore_complete(&self, _snapshot_id: &SnapshotId) {
+                *self.1.borrow_mut() = true;
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = save_snapshot_with(project_root, &[("a.wav", b"a"), ("b.wav", b"b")]);
+
+        let target = TempDir::new().unwrap();
+        let recorder = Recorder(RefCell::new(Vec::new()), RefCell::new(false));
+        restore_snapshot_with_observer(
+            project_root,
+            &id,
+            target.path(),
+            &Default::default(),
+            &recorder,
+        )
+        .unwrap();
+
+        assert_eq!(
+            *recorder.0.borrow(),
+            vec![PathBuf::from("a.wav"), PathBuf::from("b.wav")]
+        );
+        assert!(*recorder.1.borrow());
+    }
+
+    #[test]
+    fn test_restore_with_progress() {
+        use crate::metadata::config::{load_config, save_config};
+        use std::cell::Cell;
+
+        struct Counter(Cell<usize>);
+
+        impl Observer for Counter {
+            fn on_file_restored(&self, _entry: &FileEntry) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        // Compressed objects are never reflinked, so the stem is copied in chunks
+        let mut config = load_config(project_root).unwrap();
+        config.compress_objects = true;
+        save_config(project_root, &config).unwrap();
+
+        let stem_size = COPY_CHUNK_SIZE * 2 + 512;
+        fs::write(project_root.join("a.als"), b"project").unwrap();
+        fs::write(project_root.join("stem.wav"), vec![7u8; stem_size]).unwrap();
+        let id = crate::snapshot::create_snapshot(project_root, "Stem", None, &Default::default())
+            .unwrap();
+
+        let target = TempDir::new().unwrap();
+        let mut events = Vec::new();
+        let counter = Counter(Cell::new(0));
+        restore_snapshot_with_progress(
+            project_root,
+            &id,
+            target.path(),
+            &Default::default(),
+            &counter,
+            |progress| {
+                assert_eq!(progress.file_count, 2);
+                assert_eq!(progress.total_bytes, stem_size as u64 + 7);
+                events.push((
+                    progress.path.to_path_buf(),
+                    progress.file_index,
+                    progress.bytes_written,
+                    progress.total_bytes_written,
+                ));
+            },
+        )
+        .unwrap();
+
+        let chunk = COPY_CHUNK_SIZE as u64;
+        let stem = PathBuf::from("stem.wav");
+        assert_eq!(
+            events,
+            vec![
+                (PathBuf::from("a.als"), 0, 7, 7),
+                (stem.clone(), 1, chunk, 7 + chunk),
+                (stem.clone(), 1, chunk * 2, 7 + chunk * 2),
+                (stem, 1, stem_size as u64, 7 + stem_size as u64),
+            ]
+        );
+        assert_eq!(counter.0.get(), 2);
+        assert_eq!(
+            fs::read(target.path().join("stem.wav")).unwrap().len(),
+            stem_size
+        );
+    }
+
+    #[test]
+    fn test_restore_detects_corrupted_object() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = save_snapshot_with(project_root, &[("song.als", b"project data")]);
+
+        // Tamper with the stored object
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        fs::write(
+            get_object_path(project_root, &metadata.files[0].hash).unwrap(),
+            b"garbage",
+        )
+        .unwrap();
+
+        let target = TempDir::new().unwrap();
+        let result = restore_snapshot(project_root, &id, target.path(), &Default::default());
+        assert!(matches!(result, Err(MovsError::ChecksumMismatch { .. })));
+
+        // Corrupted content never reaches the destination
+        assert!(!target.path().join("song.als").exists());
+    }
+
+    #[test]
+    fn test_read_file_from_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = save_snapshot_with(
+            project_root,
+            &[("Bounces/mix.wav", b"old mix"), ("song.als", b"project")],
+        );
+
+        let mut content = Vec::new();
+        read_file_from_snapshot(
+            project_root,
+            &id,
+            Path::new("./Bounces/mix.wav"),
+            &mut content,
+        )
+        .unwrap();
+        assert_eq!(content, b"old mix");
+
+        let result =
+            read_file_from_snapshot(project_root, &id, Path::new("missing.wav"), Vec::new());
+        assert!(matches!(result, Err(MovsError::FileNotFoundInSnapshot(_))));
+
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        fs::write(
+            get_object_path(project_root, &metadata.files[1].hash).unwrap(),
+            b"garbage",
+        )
+        .unwrap();
+        let result = read_file_from_snapshot(project_root, &id, Path::new("song.als"), Vec::new());
+        assert!(matches!(result, Err(MovsError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_restore_without_verify() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = save_snapshot_with(project_root, &[("song.als", b"project data")]);
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        fs::write(
+            get_object_path(project_root, &metadata.files[0].hash).unwrap(),
+            b"garbage",
+        )
+        .unwrap();
+
+        let options = RestoreOptions {
+            verify: false,
+            ..Default::default()
+        };
+        let target = TempDir::new().unwrap();
+        restore_snapshot(project_root, &id, target.path(), &options).unwrap();
+
+        // Loose objects are trusted and copied as-is
+        assert_eq!(
+            fs::read(target.path().join("song.als")).unwrap(),
+            b"garbage"
+        );
+    }
+
+    #[test]
+    fn test_restore_compressed_objects() {
+        use crate::metadata::config::{load_config, save_config};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        config.compress_objects = true;
+        save_config(project_root, &config).unwrap();
+
+        fs::write(project_root.join("vox.wav"), vec![1u8; 4096]).unwrap();
+        let id =
+            crate::snapshot::create_snapshot(project_root, "Compressed", None, &Default::default())
+                .unwrap();
+
+        let options = RestoreOptions {
+            verify: false,
+            ..Default::default()
+        };
+        let target = TempDir::new().unwrap();
+        restore_snapshot(project_root, &id, target.path(), &options).unwrap();
+        assert_eq!(
+            fs::read(target.path().join("vox.wav")).unwrap(),
+            vec![1u8; 4096]
+        );
+    }
+
+    #[test]
+    fn test_restore_without_verify_still_checks_compressed_objects() {
+        use crate::metadata::config::{load_config, save_config};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        config.compress_objects = true;
+        save_config(project_root, &config).unwrap();
+
+        let id = save_snapshot_with(project_root, &[("a.wav", b"aaaa"), ("b.wav", b"bbbb")]);
+
+        // Swap in another object's compressed content
+        fs::copy(
+            get_object_path(project_root, &hash_bytes(b"aaaa")).unwrap(),
+            get_object_path(project_root, &hash_bytes(b"bbbb")).unwrap(),
+        )
+        .unwrap();
+
+        let options = RestoreOptions {
+            verify: false,
+            ..Default::default()
+        };
+        let target = TempDir::new().unwrap();
+        assert!(matches!(
+            restore_snapshot(project_root, &id, target.path(), &options),
+            Err(MovsError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_restore_recreates_empty_dirs() {
+        use crate::snapshot::{create_snapshot, SnapshotOptions};
+        use crate::tree::ScanOptions;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::create_dir(project_root.join("Recorded")).unwrap();
+        let options = SnapshotOptions {
+            scan: ScanOptions {
+                track_empty_dirs: true,
+                ..ScanOptions::default()
+            },
+            ..SnapshotOptions::default()
+        };
+        let id = create_snapshot(project_root, "Session", None, &options).unwrap();
+
+        let target = TempDir::new().unwrap();
+        restore_snapshot(project_root, &id, target.path(), &RestoreOptions::default()).unwrap();
+        assert!(target.path().join("Recorded").is_dir());
+        assert!(target.path().join("song.als").is_file());
+    }
+
+    #[test]
+    fn test_restore_without_verify_still_checks_packed_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = save_snapshot_with(project_root, &[("song.als", b"project data")]);
+        crate::storage::pack::pack_objects(project_root).unwrap();
+
+        let pack_dir = crate::storage::pack::get_pack_dir(project_root).unwrap();
+        fs::write(pack_dir.join("pack-000001.pack"), b"corrupted!!!").unwrap();
+
+        let options = RestoreOptions {
+            verify: false,
+            ..Default::default()
+        };
+        let target = TempDir::new().unwrap();
+        let result = restore_snapshot(project_root, &id, target.path(), &options);
+        assert!(matches!(result, Err(MovsError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_restore_protects_local_changes() {
+        use crate::snapshot::{create_snapshot, SnapshotOptions};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox v1").unwrap();
+        let first =
+            create_snapshot(project_root, "First", None, &SnapshotOptions::default()).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        create_snapshot(project_root, "Second", None, &SnapshotOptions::default()).unwrap();
+
+        // Uncommitted edit on top of HEAD
+        fs::write(project_root.join("vox.wav"), b"vox local").unwrap();
+
+        let report = restore_snapshot(
+            project_root,
+            &first,
+            project_root,
+            &RestoreOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.conflicts, vec![PathBuf::from("vox.wav")]);
+        assert_eq!(report.restored, vec![PathBuf::from("song.als")]);
+        assert!(!report.is_complete());
+
+        // Committed content was rolled back, local work survived
+        assert_eq!(fs::read(project_root.join("song.als")).unwrap(), b"v1");
+        assert_eq!(
+            fs::read(project_root.join("vox.wav")).unwrap(),
+            b"vox local"
+        );
+
+        let options = RestoreOptions {
+            overwrite_modified: true,
+            ..Default::default()
+        };
+        let report = restore_snapshot(project_root, &first, project_root, &options).unwrap();
+        assert!(report.is_complete());
+        assert_eq!(fs::read(project_root.join("vox.wav")).unwrap(), b"vox v1");
+    }
+
+    #[test]
+    fn test_restore_into_remaps_and_skips() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = save_snapshot_with(
+            project_root,
+            &[
+                ("song.als", b"project"),
+                ("Samples/kick.wav", b"kick"),
+                ("Backup/song.als", b"old"),
+            ],
+        );
+
+        let target = TempDir::new().unwrap();
+        let report = restore_into(project_root, &id, target.path(), |path| {
+            if path.starts_with("Backup") {
+                None
+            } else if let Ok(rest) = path.strip_prefix("Samples") {
+                Some(Path::new("Library").join(rest))
+            } else {
+                Some(path.to_path_buf())
+            }
+        })
+        .unwrap();
+
+        assert_eq!(report.restored.len(), 2);
+        assert_eq!(
+            fs::read(target.path().join("Library/kick.wav")).unwrap(),
+            b"kick"
+        );
+        assert_eq!(
+            fs::read(target.path().join("song.als")).unwrap(),
+            b"project"
+        );
+        assert!(!target.path().join("Samples").exists());
+        assert!(!target.path().join("Backup").exists());
+    }
+
+    #[test]
+    fn test_restore_into_rejects_escaping_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = save_snapshot_with(project_root, &[("song.als", b"project")]);
+        let target = TempDir::new().unwrap();
+        let result = restore_into(project_root, &id, target.path(), |_| {
+            Some(PathBuf::from("../outside.als"))
+        });
+
+        assert!(matches!(result, Err(MovsError::InvalidPath(_))));
+    }
+
+    #[test]
+    fn test_restore_rejects_escaping_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        let hash = store_object_bytes(project_root, b"payload").unwrap();
+        let entry = |path: &str| FileEntry::new(PathBuf::from(path), hash.clone(), 7, Utc::now());
+
+        let outside = temp_dir.path().join("outside");
+        let cases = vec![
+            vec![entry("../escape.wav")],
+            vec![entry("Samples/../../escape.wav")],
+            vec![entry(&outside.join("escape.wav").to_string_lossy())],
+            // A file placed behind one of the snapshot's own symlinks
+            vec![
+                entry("link").with_kind(EntryKind::Symlink {
+                    target: outside.clone(),
+                }),
+                entry("link/escape.wav"),
+            ],
+        ];
+
+        for files in cases {
+            let metadata = SnapshotMetadata::new(
+                SnapshotId::generate(),
+                "Tampered".to_string(),
+                None,
+                None,
+                files,
+            );
+            save_snapshot(project_root, &metadata).unwrap();
+
+            let target = TempDir::new().unwrap();
+            assert!(matches!(
+                restore_snapshot(
+                    project_root,
+                    &metadata.id,
+                    target.path(),
+                    &Default::default()
+                ),
+                Err(MovsError::InvalidPath(_))
+            ));
+        }
+        assert!(!outside.exists());
+        assert!(!temp_dir.path().join("escape.wav").exists());
+    }
+
+    #[test]
+    fn test_restore_into_verifies_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let id = save_snapshot_with(project_root, &[("song.als", b"project")]);
+        let hash = crate::hash::hash_bytes(b"project");
+        fs::write(get_object_path(project_root, &hash).unwrap(), b"garbage").unwrap();
+
+        let target = TempDir::new().unwrap();
+        let result = restore_into(project_root, &id, target.path(), |path| {
+            Some(Path::new("elsewhere").join(path))
+        });
+        assert!(matches!(result, Err(MovsError::ChecksumMismatch { .. })));
+    }
+}
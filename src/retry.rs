@@ -0,0 +1,94 @@
+use crate::metadata::config::RetryPolicy;
+use std::io;
+use std::thread;
+
+/// Check if an I/O error is likely transient and worth retrying
+///
+/// `NotFound` and `PermissionDenied` are never retried since they will not
+/// resolve on their own.
+pub(crate) fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ResourceBusy
+    )
+}
+
+/// Run an I/O operation, retrying transient failures with exponential backoff
+///
+/// The operation is attempted at most `policy.attempts` times. Non-transient
+/// errors are returned immediately.
+pub(crate) fn with_retry<T>(
+    policy: &RetryPolicy,
+    mut op: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let attempts = policy.attempts.max(1);
+    let mut delay = policy.backoff();
+    let mut attempt = 1;
+
+    loop {
+        match op() {
+            Err(e) if attempt < attempts && is_transient(&e) => {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            backoff_ms: 1,
+        }
+    }
+
+    #[test]
+    fn test_retries_transient_errors() {
+        let mut calls = 0;
+        let result = with_retry(&policy(3), || {
+            calls += 1;
+            if calls < 3 {
+                Err(io::Error::from(io::ErrorKind::TimedOut))
+            } else {
+                Ok(calls)
+            }
+        });
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_gives_up_after_attempts() {
+        let mut calls = 0;
+        let result: io::Result<()> = with_retry(&policy(4), || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::Interrupted))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(calls, 4);
+    }
+
+    #[test]
+    fn test_does_not_retry_permanent_errors() {
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::PermissionDenied] {
+            let mut calls = 0;
+            let result: io::Result<()> = with_retry(&policy(5), || {
+                calls += 1;
+                Err(io::Error::from(kind))
+            });
+
+            assert_eq!(result.unwrap_err().kind(), kind);
+            assert_eq!(calls, 1);
+        }
+    }
+}
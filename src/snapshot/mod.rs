@@ -0,0 +1,1739 @@
+use crate::clock::{Clock, SystemClock};
+use crate::diff::snapshots_identical;
+use crate::error::{MovsError, Result};
+use crate::log::{self as oplog, Operation};
+use crate::metadata::config::load_config;
+use crate::metadata::lock::lock_for_write;
+use crate::metadata::refs::read_head;
+use crate::metadata::store::{FsMetadataStore, MetadataStore};
+use crate::metadata::{get_movs_dir, repository_exists, MOVS_DIR};
+use crate::observer::{NullObserver, Observer};
+use crate::preview::compute_preview;
+use crate::storage::store::{FsObjectStore, ObjectStore};
+use crate::tree::path::normalize_scope;
+use crate::tree::{status, ScanOptions, ScanOutcome, ScanTiming, WorkingTree, IGNORE_FILE};
+use crate::types::{
+    EntryKind, FileEntry, FileHash, IdScheme, SnapshotDiff, SnapshotId, SnapshotMetadata,
+};
+use chrono::{DateTime, Local, TimeZone};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+/// Options controlling snapshot creation
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotOptions {
+    /// Create a new snapshot even if nothing changed since the parent, or
+    /// if the project contains no files at all
+    ///
+    /// Without this, snapshotting an empty project (or one where every file
+    /// is ignored) fails with `MovsError::EmptySnapshot`, which usually
+    /// means the command was run in the wrong directory.
+    pub allow_empty: bool,
+
+    /// Allow paths that differ only by case (e.g. `Track.wav` and `track.wav`)
+    ///
+    /// Such snapshots lose files when restored on macOS or Windows, so they
+    /// are rejected with `MovsError::PathCollision` unless this is set.
+    pub allow_case_collisions: bool,
+
+    /// How the working tree is traversed, e.g. whether symlinks are followed
+    pub scan: ScanOptions,
+
+    /// Only snapshot this subdirectory of the project
+    ///
+    /// Files outside it are neither scanned nor recorded; paths stay
+    /// relative to the project root. The scope must be an existing
+    /// directory inside the project and outside `.movs`.
+    pub scope: Option<PathBuf>,
+
+    /// Record this snapshot as the parent instead of HEAD
+    ///
+    /// The parent must exist, and is also what the new snapshot is compared
+    /// against to detect that nothing changed. HEAD still moves to the new
+    /// snapshot.
+    pub parent: Option<SnapshotId>,
+
+    /// Refuse to create the snapshot if it would store more new bytes
+    ///
+    /// The files are scanned and hashed first, then the bytes that would be
+    /// written are estimated: content already in the store, or repeated
+    /// within this snapshot, is not counted. Delta and compressed objects
+    /// may end up smaller on disk, so the estimate is an upper bound.
+    /// Nothing is written when the budget is exceeded: the snapshot fails
+    /// with `MovsError::BudgetExceeded`, carrying the estimate, so a tool can
+    /// ask the user and retry with a larger budget.
+    pub max_new_bytes: Option<u64>,
+}
+
+/// Find entries whose paths differ only by letter case
+///
+/// Returns each colliding pair; when more than two paths collide, every
+/// later path is paired with the first one encountered.
+pub fn detect_case_collisions(entries: &[FileEntry]) -> Vec<(PathBuf, PathBuf)> {
+    let mut seen: HashMap<String, &PathBuf> = HashMap::new();
+    let mut collisions = Vec::new();
+
+    for entry in entries {
+        let folded = entry.path.to_string_lossy().to_lowercase();
+        match seen.get(&folded) {
+            Some(first) => collisions.push(((*first).clone(), entry.path.clone())),
+            None => {
+                seen.insert(folded, &entry.path);
+            }
+        }
+    }
+
+    collisions
+}
+
+/// Scan a project directory and hash every file
+///
+/// The `.movs` directory is always skipped and `.movsignore` rules are
+/// honored with `.gitignore` precedence. The directory walk runs in
+/// parallel; entries are returned sorted by their path relative to the
+/// project root.
+pub fn scan_directory(project_root: &Path) -> Result<Vec<FileEntry>> {
+    scan_directory_with_options(project_root, &ScanOptions::default())
+}
+
+/// Scan a project directory with explicit traversal options
+///
+/// With `follow_symlinks` set, linked files and directories are recorded
+/// by their content and a symlink cycle fails with `MovsError::StorageError`.
+pub fn scan_directory_with_options(
+    project_root: &Path,
+    options: &ScanOptions,
+) -> Result<Vec<FileEntry>> {
+    WorkingTree::open(project_root)?
+        .with_scan_options(*options)
+        .hash_all()
+}
+
+/// Scan a project directory, skipping files that cannot be read
+///
+/// Unlike `scan_directory`, a single locked or unreadable file does not
+/// abort the scan; it is recorded in `ScanOutcome::errors` with the reason
+/// and the caller decides whether to proceed with the partial entries.
+pub fn scan_directory_lenient(project_root: &Path) -> Result<ScanOutcome> {
+    WorkingTree::open(project_root)?.hash_all_lenient()
+}
+
+/// Create a new snapshot of the project's current state
+///
+/// Scans the working tree, stores any new file content in the object store,
+/// writes the snapshot metadata and moves HEAD to the new snapshot. The
+/// parent is the snapshot HEAD pointed at before.
+///
+/// Writes happen in an order that keeps a crash from leaving a partial
+/// snapshot: objects are stored first, then the metadata is written to a
+/// temporary file and renamed into place, and HEAD moves last. After a
+/// crash the snapshot is either fully present or absent, and
+/// `gc::cleanup_incomplete` removes the orphaned objects.
+///
+/// When `delta_objects` is enabled in the repository config, changed files
+/// are stored as deltas against their version in the parent snapshot.
+///
+/// If the tree is identical to the parent snapshot and
+/// `options.allow_empty` is false, nothing is written and the parent's ID
+/// is returned.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `message` - Message describing this version
+/// * `author` - Optional author name
+/// * `options` - Snapshot creation options
+pub fn create_snapshot(
+    project_root: &Path,
+    message: &str,
+    author: Option<&str>,
+    options: &SnapshotOptions,
+) -> Result<SnapshotId> {
+    SnapshotBuilder::new(project_root, message)
+        .with_author(author)
+        .with_options(options)
+        .create()
+        .map(|(id, _)| id)
+}
+
+/// Where the time went while creating a snapshot
+///
+/// Durations are in milliseconds. A slow `hash_ms` relative to
+/// `bytes_hashed` points at disk reads; a slow `store_ms` at object copies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotTiming {
+    /// Walking the working tree
+    pub scan_ms: u64,
+
+    /// Reading and hashing file content
+    pub hash_ms: u64,
+
+    /// Copying new content into the object store
+    pub store_ms: u64,
+
+    /// Writing snapshot metadata and HEAD
+    pub write_ms: u64,
+
+    /// Total size of all hashed files
+    pub bytes_hashed: u64,
+
+    /// Total size of content newly added to the object store
+    pub bytes_stored: u64,
+}
+
+/// How much a new snapshot added to the object store
+///
+/// Counts cover regular files and symlinks. A file whose content was
+/// already stored, by an earlier snapshot or by another file in this one,
+/// counts as deduplicated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotResult {
+    /// Objects written to the store by this snapshot
+    pub new_objects: usize,
+
+    /// Entries whose content was already in the store
+    pub deduped_objects: usize,
+
+    /// Total size of the newly written objects
+    pub new_bytes: u64,
+
+    /// Total size of every entry in the snapshot
+    pub logical_bytes: u64,
+
+    /// How long each phase took
+    pub timing: SnapshotTiming,
+}
+
+/// Creates a snapshot with any combination of options, observer and clock
+///
+/// `create_snapshot` covers the common case. The builder adds progress
+/// events, a custom clock, and a `SnapshotResult` describing what the
+/// snapshot stored and how long it took. Every setting is optional and
+/// they combine freely.
+pub struct SnapshotBuilder<'a> {
+    project_root: &'a Path,
+    message: &'a str,
+    author: Option<&'a str>,
+    options: SnapshotOptions,
+    observer: &'a dyn Observer,
+    clock: &'a dyn Clock,
+    files: Option<Vec<FileEntry>>,
+    stores: Option<Stores<'a>>,
+}
+
+/// The backends a snapshot's objects and metadata are written to
+#[derive(Clone, Copy)]
+pub(crate) struct Stores<'a> {
+    pub objects: &'a dyn ObjectStore,
+    pub metadata: &'a dyn MetadataStore,
+}
+
+impl<'a> SnapshotBuilder<'a> {
+    /// Start a snapshot of the project at `project_root` with default options
+    pub fn new(project_root: &'a Path, message: &'a str) -> Self {
+        Self {
+            project_root,
+            message,
+            author: None,
+            options: SnapshotOptions::default(),
+            observer: &NullObserver,
+            clock: &SystemClock,
+            files: None,
+            stores: None,
+        }
+    }
+
+    /// Record who created the snapshot
+    pub fn with_author(mut self, author: Option<&'a str>) -> Self {
+        self.author = author;
+        self
+    }
+
+    /// Use these options instead of the defaults
+    pub fn with_options(mut self, options: &SnapshotOptions) -> Self {
+        self.options = options.clone();
+        self
+    }
+
+    /// Report progress to an observer
+    ///
+    /// It receives `on_file_scanned` for every scanned entry,
+    /// `on_object_stored` for every stored or deduplicated object, and
+    /// `on_snapshot_complete` once the snapshot is written.
+    pub fn with_observer(mut self, observer: &'a dyn Observer) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Take the snapshot's ID and timestamp from `clock`
+    ///
+    /// With `IdScheme::Timestamp` and a `FixedClock`, the snapshot's ID and
+    /// timestamp are deterministic; a monotonic clock guarantees that later
+    /// snapshots sort after earlier ones.
+    pub fn with_clock(mut self, clock: &'a dyn Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Snapshot these already hashed entries instead of scanning the tree
+    ///
+    /// The entries must cover the whole tree as a scan would have found it.
+    pub(crate) fn with_files(mut self, files: Vec<FileEntry>) -> Self {
+        self.files = Some(files);
+        self
+    }
+
+    /// Write to these stores instead of the filesystem stores in `.movs`
+    pub(crate) fn with_stores(mut self, stores: Stores<'a>) -> Self {
+        self.stores = Some(stores);
+        self
+    }
+
+    /// Create the snapshot
+    ///
+    /// Behaves like `create_snapshot`, and also returns what the snapshot
+    /// added to the store. If nothing changed, the parent's ID is returned,
+    /// every entry counts as deduplicated, and `store_ms`, `write_ms` and
+    /// `bytes_stored` are zero.
+    pub fn create(self) -> Result<(SnapshotId, SnapshotResult)> {
+        create_snapshot_inner(self)
+    }
+}
+
+/// Create a new snapshot and report how long each phase took
+///
+/// Behaves exactly like `create_snapshot`. If nothing changed, the parent's
+/// ID is returned and `store_ms`, `write_ms` and `bytes_stored` are zero.
+/// A shorthand for `SnapshotBuilder::create`, keeping only the timing.
+pub fn create_snapshot_timed(
+    project_root: &Path,
+    message: &str,
+    author: Option<&str>,
+    options: &SnapshotOptions,
+) -> Result<(SnapshotId, SnapshotTiming)> {
+    SnapshotBuilder::new(project_root, message)
+        .with_author(author)
+        .with_options(options)
+        .create()
+        .map(|(id, result)| (id, result.timing))
+}
+
+/// Create a new snapshot and report how much storage it actually used
+///
+/// Behaves exactly like `create_snapshot`. If nothing changed, the parent's
+/// ID is returned and every entry counts as deduplicated. A shorthand for
+/// `SnapshotBuilder::create`.
+pub fn create_snapshot_with_result(
+    project_root: &Path,
+    message: &str,
+    author: Option<&str>,
+    options: &SnapshotOptions,
+) -> Result<(SnapshotId, SnapshotResult)> {
+    SnapshotBuilder::new(project_root, message)
+        .with_author(author)
+        .with_options(options)
+        .create()
+}
+
+/// Create a new snapshot unless it would add more than `max_new_bytes`
+///
+/// Behaves exactly like `create_snapshot` with
+/// `SnapshotOptions::max_new_bytes` set.
+pub fn create_snapshot_budgeted(
+    project_root: &Path,
+    message: &str,
+    author: Option<&str>,
+    max_new_bytes: u64,
+) -> Result<SnapshotId> {
+    let options = SnapshotOptions {
+        max_new_bytes: Some(max_new_bytes),
+        ..SnapshotOptions::default()
+    };
+    create_snapshot(project_root, message, author, &options)
+}
+
+/// Create a new snapshot, reporting progress to an observer
+///
+/// Behaves exactly like `create_snapshot` with
+/// `SnapshotBuilder::with_observer`.
+pub fn create_snapshot_with_observer(
+    project_root: &Path,
+    message: &str,
+    author: Option<&str>,
+    options: &SnapshotOptions,
+    observer: &dyn Observer,
+) -> Result<SnapshotId> {
+    SnapshotBuilder::new(project_root, message)
+        .with_author(author)
+        .with_options(options)
+        .with_observer(observer)
+        .create()
+        .map(|(id, _)| id)
+}
+
+/// Create a new snapshot, taking its ID and timestamp from `clock`
+///
+/// Behaves exactly like `create_snapshot` with
+/// `SnapshotBuilder::with_clock`.
+pub fn create_snapshot_with_clock(
+    project_root: &Path,
+    message: &str,
+    author: Option<&str>,
+    options: &SnapshotOptions,
+    clock: &dyn Clock,
+) -> Result<SnapshotId> {
+    SnapshotBuilder::new(project_root, message)
+        .with_author(author)
+        .with_options(options)
+        .with_clock(clock)
+        .create()
+        .map(|(id, _)| id)
+}
+
+/// Snapshot the working tree only if it differs from HEAD
+///
+/// The everyday entry point for tools: "no changes" means every path holds
+/// the same content as in HEAD, so files that were merely touched or
+/// re-saved unchanged do not produce a snapshot. Uses default options; an
+/// empty project still fails with `MovsError::EmptySnapshot`.
+///
+/// # Returns
+///
+/// The new snapshot's ID, or `None` if nothing changed
+pub fn commit(
+    project_root: &Path,
+    message: &str,
+    author: Option<&str>,
+) -> Result<Option<SnapshotId>> {
+    commit_files(project_root, message, author, None)
+}
+
+/// `commit`, over already hashed entries if `files` is given
+///
+/// The entries must cover the whole tree as a scan would have found it.
+pub(crate) fn commit_files(
+    project_root: &Path,
+    message: &str,
+    author: Option<&str>,
+    files: Option<Vec<FileEntry>>,
+) -> Result<Option<SnapshotId>> {
+    let head = read_head(project_root)?;
+    let mut builder = SnapshotBuilder::new(project_root, message).with_author(author);
+    if let Some(files) = files {
+        builder = builder.with_files(files);
+    }
+    let (id, _) = builder.create()?;
+    Ok((head.as_ref() != Some(&id)).then_some(id))
+}
+
+/// Create a snapshot whose message is expanded from a template
+///
+/// Meant for auto-save daemons. The template may contain these
+/// placeholders, computed from the working tree's diff against HEAD:
+///
+/// - `{date}` - local date, e.g. `2024-01-01`
+/// - `{time}` - local time, e.g. `14:05:09`
+/// - `{changed}` - number of added, modified and removed files
+/// - `{added}`, `{modified}`, `{removed}` - number of files of each kind
+///
+/// Unknown placeholders are left as written. Otherwise behaves like
+/// `create_snapshot` with default options and no author.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `template` - Message template, e.g. `"Auto-save {date} - {changed} files changed"`
+pub fn create_auto_snapshot(project_root: &Path, template: &str) -> Result<SnapshotId> {
+    let diff = status(project_root)?;
+    let message = expand_message_template(template, &diff, &Local::now());
+    create_snapshot(project_root, &message, None, &SnapshotOptions::default())
+}
+
+/// Substitute the placeholders documented on `create_auto_snapshot`
+fn expand_message_template<Tz>(template: &str, diff: &SnapshotDiff, now: &DateTime<Tz>) -> String
+where
+    Tz: TimeZone,
+    Tz::Offset: std::fmt::Display,
+{
+    let mut message = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let value = match &rest[1..end] {
+            "date" => now.format("%Y-%m-%d").to_string(),
+            "time" => now.format("%H:%M:%S").to_string(),
+            "changed" => diff.total_changes().to_string(),
+            "added" => diff.added.len().to_string(),
+            "modified" => diff.modified.len().to_string(),
+            "removed" => diff.removed.len().to_string(),
+            _ => {
+                // Not a placeholder; keep the brace and look again after it
+                message.push('{');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        message.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+
+    message.push_str(rest);
+    message
+}
+
+/// Create a snapshot as configured by `builder`, scanning the tree unless
+/// it already holds hashed entries
+fn create_snapshot_inner(builder: SnapshotBuilder<'_>) -> Result<(SnapshotId, SnapshotResult)> {
+    let SnapshotBuilder {
+        project_root,
+        message,
+        author,
+        ref options,
+        observer,
+        clock,
+        files,
+        stores,
+    } = builder;
+    let mut timing = SnapshotTiming::default();
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+    // Held from storing the first object until HEAD moves, so `gc` never
+    // sees staged objects as garbage
+    let _lock = lock_for_write(project_root)?;
+
+    let config = load_config(project_root)?;
+    let mut tree = WorkingTree::open(project_root)?.with_scan_options(options.scan);
+    let scoped;
+    let options = match &options.scope {
+        Some(scope) => {
+            tree = tree.with_scope(scope)?;
+            scoped = SnapshotOptions {
+                scope: tree.scope().map(Path::to_path_buf),
+                ..options.clone()
+            };
+            &scoped
+        }
+        None => options,
+    };
+    let (files, scan_timing) = match files {
+        Some(files) => (files, ScanTiming::default()),
+        None => tree.hash_all_timed()?,
+    };
+    timing.scan_ms = millis(scan_timing.walk);
+    timing.hash_ms = millis(scan_timing.hash);
+    timing.bytes_hashed = files
+        .iter()
+        .filter(|entry| entry.is_file())
+        .map(|entry| entry.size)
+        .sum();
+
+    for entry in &files {
+        observer.on_file_scanned(entry);
+    }
+
+    let stored_entries = files
+        .iter()
+        .filter(|entry| entry.kind != EntryKind::Directory)
+        .count();
+    let logical_bytes = files
+        .iter()
+        .filter(|entry| entry.kind != EntryKind::Directory)
+        .map(|entry| entry.size)
+        .sum();
+
+    let fs_objects;
+    let fs_metadata;
+    let stores = match stores {
+        Some(stores) => stores,
+        None => {
+            fs_objects = FsObjectStore::new(project_root)?;
+            fs_metadata = FsMetadataStore::new(project_root);
+            Stores {
+                objects: &fs_objects,
+                metadata: &fs_metadata,
+            }
+        }
+    };
+
+    let prepared = prepare_snapshot(
+        stores.metadata,
+        files,
+        message,
+        author,
+        options,
+        config.id_scheme,
+        clock,
+    );
+    let (mut metadata, parent_metadata) = match prepared {
+        Ok(Prepared::Unchanged(parent_id)) => {
+            let result = SnapshotResult {
+                deduped_objects: stored_entries,
+                logical_bytes,
+                timing,
+                ..SnapshotResult::default()
+            };
+            return Ok((parent_id, result));
+        }
+        Ok(Prepared::New { metadata, parent }) => (metadata, parent),
+        Err(MovsError::EmptySnapshot { .. }) => {
+            let scanned = match &options.scope {
+                Some(scope) => project_root.join(scope),
+                None => project_root.to_path_buf(),
+            };
+            return Err(MovsError::EmptySnapshot {
+                all_ignored: has_untracked_content(&scanned)?,
+                root: scanned,
+            });
+        }
+        Err(e) => return Err(e),
+    };
+
+    if config.compute_previews {
+        attach_previews(
+            project_root,
+            &mut metadata.files,
+            parent_metadata.as_ref(),
+            observer,
+        );
+    }
+
+    let mut result = store_prepared(
+        stores,
+        &metadata,
+        parent_metadata.as_ref(),
+        options,
+        observer,
+        &mut |entry, base| {
+            stores
+                .objects
+                .put_file(&entry.hash, &project_root.join(&entry.path), base)
+        },
+    )?;
+    oplog::record(
+        project_root,
+        Operation::Snapshot,
+        Some(&metadata.id),
+        author,
+        observer,
+    );
+    observer.on_snapshot_complete(&metadata.id);
+
+    result.timing = SnapshotTiming {
+        store_ms: result.timing.store_ms,
+        write_ms: result.timing.write_ms,
+        bytes_stored: result.timing.bytes_stored,
+        ..timing
+    };
+    Ok((metadata.id, result))
+}
+
+/// Store a prepared snapshot's new objects, then save it and move HEAD
+///
+/// Shared by every backend. Symlink targets are stored as they are;
+/// `store_content` stores a file's content and is only called for content
+/// not in the store yet. It is passed the hash of the file's version in
+/// `parent` when that is stored, to encode a delta against. Fails with
+/// `MovsError::BudgetExceeded` before writing anything if the new content
+/// exceeds `options.max_new_bytes`.
+pub(crate) fn store_prepared(
+    stores: Stores<'_>,
+    metadata: &SnapshotMetadata,
+    parent: Option<&SnapshotMetadata>,
+    options: &SnapshotOptions,
+    observer: &dyn Observer,
+    store_content: &mut dyn FnMut(&FileEntry, Option<&FileHash>) -> Result<()>,
+) -> Result<SnapshotResult> {
+    let Stores {
+        objects,
+        metadata: store,
+    } = stores;
+    let mut result = SnapshotResult::default();
+
+    // Previous versions of each path, used as delta bases
+    let delta_bases: HashMap<&Path, &FileHash> = parent
+        .map(|parent| {
+            parent
+                .files
+                .iter()
+                .filter(|entry| entry.is_file())
+                .map(|entry| (entry.path.as_path(), &entry.hash))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // List the store once, so existence checks for the whole run are set
+    // lookups instead of one stat per file. The run holds the repository
+    // lock, so the listing cannot go stale before the snapshot is written.
+    let store_start = Instant::now();
+    let mut stored: HashSet<FileHash> = objects.iter_hashes()?.collect();
+
+    if let Some(budget) = options.max_new_bytes {
+        let new_bytes = estimate_new_bytes(&metadata.files, &stored);
+        if new_bytes > budget {
+            return Err(MovsError::BudgetExceeded { new_bytes, budget });
+        }
+    }
+
+    for entry in &metadata.files {
+        if entry.kind == EntryKind::Directory {
+            continue;
+        }
+        result.logical_bytes += entry.size;
+
+        let newly_stored = !stored.contains(&entry.hash);
+        if newly_stored {
+            match &entry.kind {
+                EntryKind::Symlink { target } => {
+                    objects.put(target.to_string_lossy().as_bytes())?;
+                }
+                _ => {
+                    let base = delta_bases
+                        .get(entry.path.as_path())
+                        .copied()
+                        .filter(|base| stored.contains(*base));
+                    store_content(entry, base)?;
+                }
+            }
+            stored.insert(entry.hash.clone());
+            result.new_objects += 1;
+            result.new_bytes += entry.size;
+        } else {
+            result.deduped_objects += 1;
+        }
+
+        observer.on_object_stored(&entry.hash, newly_stored);
+    }
+    result.timing.bytes_stored = result.new_bytes;
+    result.timing.store_ms = millis(store_start.elapsed());
+
+    let write_start = Instant::now();
+    store.save_snapshot(metadata)?;
+    store.write_head(&metadata.id)?;
+    result.timing.write_ms = millis(write_start.elapsed());
+
+    Ok(result)
+}
+
+/// Compute waveform previews for WAV entries
+///
+/// Previews of files whose content is unchanged since the parent are copied
+/// over instead of recomputed. A file that cannot be previewed is reported
+/// through `Observer::on_warning` and stored without one.
+fn attach_previews(
+    project_root: &Path,
+    files: &mut [FileEntry],
+    parent: Option<&SnapshotMetadata>,
+    observer: &dyn Observer,
+) {
+    let previous: HashMap<&FileHash, &Vec<u8>> = parent
+        .map(|parent| {
+            parent
+                .files
+                .iter()
+                .filter_map(|entry| entry.preview.as_ref().map(|p| (&entry.hash, p)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for entry in files.iter_mut() {
+        let Some(kind) = entry.file_type.filter(|_| entry.is_file()) else {
+            continue;
+        };
+        if let Some(preview) = previous.get(&entry.hash) {
+            entry.preview = Some((*preview).clone());
+            continue;
+        }
+
+        match compute_preview(&project_root.join(&entry.path), kind) {
+            Ok(preview) => entry.preview = preview,
+            Err(e) => {
+                observer.on_warning(&format!("No preview for '{}': {}", entry.path.display(), e))
+            }
+        }
+    }
+}
+
+/// Outcome of the backend-independent part of snapshot creation
+#[allow(clippy::large_enum_variant)] // built once per snapshot, never stored
+pub(crate) enum Prepared {
+    /// Nothing changed since the parent, which should be returned as-is
+    Unchanged(SnapshotId),
+
+    /// A new snapshot whose objects must be stored before it is saved
+    New {
+        metadata: SnapshotMetadata,
+        parent: Option<SnapshotMetadata>,
+    },
+}
+
+/// Total size of the entries whose content is not in the store yet
+///
+/// Each distinct hash counts once, however many paths share it.
+fn estimate_new_bytes(files: &[FileEntry], stored: &HashSet<FileHash>) -> u64 {
+    let mut seen = HashSet::new();
+    files
+        .iter()
+        .filter(|entry| entry.kind != EntryKind::Directory)
+        .filter(|entry| !stored.contains(&entry.hash) && seen.insert(&entry.hash))
+        .map(|entry| entry.size)
+        .sum()
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Check if the project holds anything besides `.movs` and `.movsignore`
+///
+/// Only files and symlinks count; directories are walked but a tree of
+/// empty directories is not content. Symlinks are not followed.
+fn has_untracked_content(project_root: &Path) -> Result<bool> {
+    let walker = WalkDir::new(project_root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() > 1 || entry.file_name() != MOVS_DIR);
+
+    for entry in walker {
+        let entry = entry.map_err(|e| MovsError::Io(e.into()))?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        if entry.depth() > 1 || entry.file_name() != IGNORE_FILE {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Check that `id` does not already occur in `parent`'s ancestry
+///
+/// A fresh ID can only appear there if it collides with an existing
+/// snapshot, but an explicit parent must never turn history into a loop.
+/// Ancestors that have been deleted end the walk.
+fn check_no_cycle(
+    store: &dyn MetadataStore,
+    parent: &SnapshotMetadata,
+    id: &SnapshotId,
+) -> Result<()> {
+    let mut seen = HashSet::new();
+    let mut current = Some(parent.id.clone());
+
+    while let Some(ancestor) = current {
+        if ancestor == *id || !seen.insert(ancestor.clone()) {
+            return Err(MovsError::InvalidSnapshotId(format!(
+                "using {} as parent would create a cycle in the history",
+                parent.id
+            )));
+        }
+        current = match store.load_snapshot(&ancestor) {
+            Ok(metadata) => metadata.parent,
+            Err(MovsError::SnapshotNotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+    }
+
+    Ok(())
+}
+
+/// Validate scanned entries and build the new snapshot's metadata
+///
+/// Shared by every backend: rejects empty snapshots and case collisions,
+/// resolves the parent from HEAD and detects no-op snapshots. Nothing is
+/// written. An empty snapshot's error names the scope, or an empty path,
+/// as its root and leaves `all_ignored` unset; callers that know the
+/// directory that was scanned fill both in.
+pub(crate) fn prepare_snapshot(
+    store: &dyn MetadataStore,
+    files: Vec<FileEntry>,
+    message: &str,
+    author: Option<&str>,
+    options: &SnapshotOptions,
+    id_scheme: IdScheme,
+    clock: &dyn Clock,
+) -> Result<Prepared> {
+    let scope = match &options.scope {
+        Some(scope) => Some(normalize_scope(Path::new(""), scope)?),
+        None => None,
+    };
+    let files = match &scope {
+        Some(scope) => files
+            .into_iter()
+            .filter(|entry| entry.path.starts_with(scope))
+            .collect(),
+        None => files,
+    };
+
+    let only_ignore_file = files
+        .iter()
+        .all(|entry| entry.path == Path::new(IGNORE_FILE));
+    if only_ignore_file && !options.allow_empty {
+        return Err(MovsError::EmptySnapshot {
+            root: scope.unwrap_or_default(),
+            all_ignored: false,
+        });
+    }
+
+    if !options.allow_case_collisions {
+        if let Some((first, second)) = detect_case_collisions(&files).into_iter().next() {
+            return Err(MovsError::PathCollision { first, second });
+        }
+    }
+
+    let parent_id = match &options.parent {
+        Some(parent_id) => Some(parent_id.clone()),
+        None => store.read_head()?,
+    };
+    let parent = match parent_id {
+        Some(parent_id) => Some(store.load_snapshot(&parent_id)?),
+        None => None,
+    };
+
+    let now = clock.now();
+    let id = SnapshotId::generate_at(id_scheme, now);
+    if let (Some(_), Some(parent)) = (&options.parent, &parent) {
+        check_no_cycle(store, parent, &id)?;
+    }
+
+    let mut metadata = SnapshotMetadata::new(
+        id,
+        message.to_string(),
+        author.map(str::to_string),
+        parent.as_ref().map(|parent| parent.id.clone()),
+        files,
+    );
+    metadata.timestamp = now;
+    metadata.scope = scope;
+
+    if let Some(parent) = &parent {
+        if !options.allow_empty
+            && parent.scope == metadata.scope
+            && snapshots_identical(parent, &metadata)
+        {
+            return Ok(Prepared::Unchanged(parent.id.clone()));
+        }
+    }
+
+    Ok(Prepared::New { metadata, parent })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::metadata::persistence::load_snapshot;
+    use crate::storage::object_exists;
+    use std::cell::RefCell;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_expand_message_template() {
+        use chrono::{TimeZone, Utc};
+
+        let diff = SnapshotDiff {
+            added: vec![PathBuf::from("a.wav"), PathBuf::from("b.wav")],
+            modified: vec![PathBuf::from("song.als")],
+            removed: vec![],
+        };
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 9, 30, 0).unwrap();
+
+        assert_eq!(
+            expand_message_template(
+                "Auto-save {date} {time} - {changed} files changed",
+                &diff,
+                &now
+            ),
+            "Auto-save 2024-01-01 09:30:00 - 3 files changed"
+        );
+        assert_eq!(
+            expand_message_template("+{added} ~{modified} -{removed}", &diff, &now),
+            "+2 ~1 -0"
+        );
+        // Unknown and unterminated placeholders stay literal
+        assert_eq!(
+            expand_message_template("{take} {{added}} {date", &diff, &now),
+            "{take} {2} {date"
+        );
+    }
+
+    #[test]
+    fn test_explicit_parent() {
+        use crate::metadata::persistence::save_snapshot;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let v1 = create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        create_snapshot(project_root, "v2", None, &Default::default()).unwrap();
+
+        fs::write(project_root.join("song.als"), b"alt").unwrap();
+        let options = SnapshotOptions {
+            parent: Some(v1.clone()),
+            ..Default::default()
+        };
+        let alt = create_snapshot(project_root, "alt", None, &options).unwrap();
+        assert_eq!(load_snapshot(project_root, &alt).unwrap().parent, Some(v1));
+        assert_eq!(read_head(project_root).unwrap(), Some(alt));
+
+        let options = SnapshotOptions {
+            parent: Some(SnapshotId::new("missing".to_string())),
+            ..Default::default()
+        };
+        assert!(matches!(
+            create_snapshot(project_root, "orphan", None, &options),
+            Err(MovsError::SnapshotNotFound(_))
+        ));
+
+        // A parent whose ancestry already loops is rejected
+        let a = SnapshotId::new("loop_a".to_string());
+        let b = SnapshotId::new("loop_b".to_string());
+        for (id, parent) in [(&a, &b), (&b, &a)] {
+            let metadata = SnapshotMetadata::new(
+                id.clone(),
+                "loop".to_string(),
+                None,
+                Some(parent.clone()),
+                Vec::new(),
+            );
+            save_snapshot(project_root, &metadata).unwrap();
+        }
+        let options = SnapshotOptions {
+            parent: Some(a),
+            allow_empty: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            create_snapshot(project_root, "cycle", None, &options),
+            Err(MovsError::InvalidSnapshotId(_))
+        ));
+    }
+
+    #[test]
+    fn test_commit_only_when_content_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let first = commit(project_root, "v1", None).unwrap().unwrap();
+        assert_eq!(commit(project_root, "again", None).unwrap(), None);
+
+        // Re-saving identical content is not a change
+        let file = fs::File::options()
+            .write(true)
+            .open(project_root.join("song.als"))
+            .unwrap();
+        file.set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(commit(project_root, "touched", None).unwrap(), None);
+
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        let second = commit(project_root, "v2", Some("mia")).unwrap().unwrap();
+        let metadata = load_snapshot(project_root, &second).unwrap();
+        assert_eq!(metadata.parent, Some(first));
+        assert_eq!(read_head(project_root).unwrap(), Some(second));
+    }
+
+    #[test]
+    fn test_scoped_snapshot() {
+        use crate::restore::restore_snapshot;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::create_dir_all(project_root.join("Song A/Samples")).unwrap();
+        fs::create_dir(project_root.join("Song B")).unwrap();
+        fs::write(project_root.join("Song A/a.als"), b"a").unwrap();
+        fs::write(project_root.join("Song A/Samples/kick.wav"), b"kick").unwrap();
+        fs::write(project_root.join("Song B/b.als"), b"b").unwrap();
+
+        let options = SnapshotOptions {
+            scope: Some(project_root.join("Song A")),
+            ..Default::default()
+        };
+        let id = create_snapshot(project_root, "Song A only", None, &options).unwrap();
+
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        assert_eq!(metadata.scope, Some(PathBuf::from("Song A")));
+        let paths: Vec<_> = metadata.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("Song A/Samples/kick.wav"),
+                PathBuf::from("Song A/a.als")
+            ]
+        );
+        assert!(!object_exists(project_root, &crate::hash::hash_bytes(b"b")).unwrap());
+
+        // Unchanged scope is a no-op
+        assert_eq!(
+            create_snapshot(project_root, "again", None, &options).unwrap(),
+            id
+        );
+
+        // Restoring only writes inside the scope
+        let target = TempDir::new().unwrap();
+        fs::create_dir(target.path().join("Song B")).unwrap();
+        fs::write(target.path().join("Song B/b.als"), b"local").unwrap();
+        let report =
+            restore_snapshot(project_root, &id, target.path(), &Default::default()).unwrap();
+        assert_eq!(report.restored.len(), 2);
+        assert_eq!(
+            fs::read(target.path().join("Song B/b.als")).unwrap(),
+            b"local"
+        );
+    }
+
+    #[test]
+    fn test_invalid_scopes_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+
+        for scope in [
+            ".movs",
+            ".movs/objects",
+            "../elsewhere",
+            "missing",
+            "song.als",
+        ] {
+            let options = SnapshotOptions {
+                scope: Some(PathBuf::from(scope)),
+                ..Default::default()
+            };
+            assert!(
+                matches!(
+                    create_snapshot(project_root, "bad", None, &options),
+                    Err(MovsError::InvalidPath(_))
+                ),
+                "{}",
+                scope
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_snapshot_with_fixed_clock() {
+        use crate::clock::FixedClock;
+        use chrono::{TimeZone, Utc};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let clock = FixedClock::new(start);
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let (first, _) = SnapshotBuilder::new(project_root, "v1")
+            .with_clock(&clock)
+            .create()
+            .unwrap();
+        assert_eq!(first.as_str(), "snapshot_20240101_120000_000000000");
+        assert_eq!(
+            load_snapshot(project_root, &first).unwrap().timestamp,
+            start
+        );
+
+        clock.advance(chrono::Duration::minutes(5));
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        let (second, _) = SnapshotBuilder::new(project_root, "v2")
+            .with_clock(&clock)
+            .create()
+            .unwrap();
+        assert_eq!(
+            second.timestamp(),
+            Some(start + chrono::Duration::minutes(5))
+        );
+    }
+
+    #[test]
+    fn test_create_auto_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        fs::write(project_root.join("kick.wav"), b"kick").unwrap();
+        create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        fs::remove_file(project_root.join("kick.wav")).unwrap();
+        let id = create_auto_snapshot(
+            project_root,
+            "Auto-save: {changed} changed, {removed} removed, {x}",
+        )
+        .unwrap();
+
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        assert_eq!(metadata.message, "Auto-save: 2 changed, 1 removed, {x}");
+    }
+
+    #[test]
+    fn test_scan_directory_skips_movs() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        fs::create_dir(project_root.join("Samples")).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::write(project_root.join("Samples/kick.wav"), b"kick").unwrap();
+
+        let entries = scan_directory(project_root).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.clone()).collect();
+
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("Samples/kick.wav"), PathBuf::from("song.als")]
+        );
+        assert_eq!(entries[1].size, 7);
+    }
+
+    #[test]
+    fn test_create_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+
+        let id =
+            create_snapshot(project_root, "First", Some("Producer"), &Default::default()).unwrap();
+
+        assert_eq!(read_head(project_root).unwrap(), Some(id.clone()));
+
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        assert_eq!(metadata.message, "First");
+        assert_eq!(metadata.author.as_deref(), Some("Producer"));
+        assert_eq!(metadata.parent, None);
+        assert_eq!(metadata.file_count(), 1);
+        assert!(object_exists(project_root, &metadata.files[0].hash).unwrap());
+    }
+
+    #[test]
+    fn test_create_snapshot_skips_unchanged() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+
+        let first = create_snapshot(project_root, "First", None, &Default::default()).unwrap();
+
+        // Nothing changed: the parent is returned and no snapshot is written
+        let again = create_snapshot(project_root, "Again", None, &Default::default()).unwrap();
+        assert_eq!(again, first);
+        assert_eq!(
+            crate::metadata::list_snapshots(project_root).unwrap().len(),
+            1
+        );
+
+        // Explicitly allowed: a new snapshot parented to the first
+        let options = SnapshotOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+        let forced = create_snapshot(project_root, "Forced", None, &options).unwrap();
+        assert_ne!(forced, first);
+        assert_eq!(
+            load_snapshot(project_root, &forced).unwrap().parent,
+            Some(first.clone())
+        );
+
+        // A real change creates a new snapshot
+        fs::write(project_root.join("song.als"), b"project v2").unwrap();
+        let changed = create_snapshot(project_root, "Changed", None, &Default::default()).unwrap();
+        assert_ne!(changed, forced);
+        assert_eq!(
+            load_snapshot(project_root, &changed).unwrap().parent,
+            Some(forced)
+        );
+    }
+
+    #[test]
+    fn test_detect_case_collisions() {
+        let entry = |path: &str| {
+            FileEntry::new(
+                PathBuf::from(path),
+                crate::types::FileHash::new(vec![1]),
+                1,
+                chrono::Utc::now(),
+            )
+        };
+
+        let entries = vec![
+            entry("Track.wav"),
+            entry("track.wav"),
+            entry("Drums/kick.wav"),
+            entry("drums/snare.wav"),
+            entry("TRACK.WAV"),
+        ];
+
+        assert_eq!(
+            detect_case_collisions(&entries),
+            vec![
+                (PathBuf::from("Track.wav"), PathBuf::from("track.wav")),
+                (PathBuf::from("Track.wav"), PathBuf::from("TRACK.WAV")),
+            ]
+        );
+        assert!(detect_case_collisions(&entries[2..4]).is_empty());
+    }
+
+    #[test]
+    fn test_create_snapshot_rejects_case_collisions() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("Track.wav"), b"upper").unwrap();
+        fs::write(project_root.join("track.wav"), b"lower").unwrap();
+
+        // Only meaningful on case-sensitive filesystems
+        if fs::read(project_root.join("Track.wav")).unwrap() != b"upper" {
+            return;
+        }
+
+        let result = create_snapshot(project_root, "Collide", None, &Default::default());
+        assert!(matches!(result, Err(MovsError::PathCollision { .. })));
+
+        let options = SnapshotOptions {
+            allow_case_collisions: true,
+            ..Default::default()
+        };
+        assert!(create_snapshot(project_root, "Collide", None, &options).is_ok());
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: RefCell<Vec<String>>,
+    }
+
+    impl Observer for RecordingObserver {
+        fn on_file_scanned(&self, entry: &FileEntry) {
+            self.events
+                .borrow_mut()
+                .push(format!("scanned {}", entry.path.display()));
+        }
+
+        fn on_object_stored(&self, _hash: &crate::types::FileHash, newly_stored: bool) {
+            self.events
+                .borrow_mut()
+                .push(format!("stored new={}", newly_stored));
+        }
+
+        fn on_snapshot_complete(&self, _snapshot_id: &SnapshotId) {
+            self.events.borrow_mut().push("complete".to_string());
+        }
+    }
+
+    #[test]
+    fn test_create_snapshot_with_observer() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("a.wav"), b"same").unwrap();
+        fs::write(project_root.join("b.wav"), b"same").unwrap();
+
+        let observer = RecordingObserver::default();
+        SnapshotBuilder::new(project_root, "First")
+            .with_observer(&observer)
+            .create()
+            .unwrap();
+
+        assert_eq!(
+            *observer.events.borrow(),
+            vec![
+                "scanned a.wav",
+                "scanned b.wav",
+                "stored new=true",
+                "stored new=false",
+                "complete",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_builder_combines_settings() {
+        use crate::clock::FixedClock;
+        use chrono::{TimeZone, Utc};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("a.wav"), b"kick").unwrap();
+
+        let clock = FixedClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap());
+        let observer = RecordingObserver::default();
+        let options = SnapshotOptions {
+            max_new_bytes: Some(4),
+            ..SnapshotOptions::default()
+        };
+        let (id, result) = SnapshotBuilder::new(project_root, "First")
+            .with_author(Some("Producer"))
+            .with_options(&options)
+            .with_observer(&observer)
+            .with_clock(&clock)
+            .create()
+            .unwrap();
+
+        assert_eq!(id.as_str(), "snapshot_20240101_120000_000000000");
+        assert_eq!(result.new_bytes, 4);
+        assert_eq!(result.timing.bytes_stored, 4);
+        assert_eq!(observer.events.borrow().last().unwrap(), "complete");
+        assert_eq!(
+            load_snapshot(project_root, &id).unwrap().author.as_deref(),
+            Some("Producer")
+        );
+    }
+
+    #[test]
+    fn test_create_snapshot_missing_repository() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let result = create_snapshot(temp_dir.path(), "msg", None, &Default::default());
+        assert!(matches!(result, Err(MovsError::RepositoryNotFound(_))));
+    }
+
+    #[test]
+    fn test_create_snapshot_stores_deltas() {
+        use crate::metadata::config::save_config;
+        use crate::storage::delta::get_delta_path;
+        use crate::storage::load_object;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        let mut config = load_config(project_root).unwrap();
+        config.delta_objects = true;
+        save_config(project_root, &config).unwrap();
+
+        let mut content: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(project_root.join("song.als"), &content).unwrap();
+        create_snapshot(project_root, "First", None, &Default::default()).unwrap();
+
+        content[10_000] ^= 0xff;
+        fs::write(project_root.join("song.als"), &content).unwrap();
+        let id = create_snapshot(project_root, "Second", None, &Default::default()).unwrap();
+
+        let hash = &load_snapshot(project_root, &id).unwrap().files[0].hash;
+        assert!(get_delta_path(project_root, hash).unwrap().is_file());
+        assert_eq!(load_object(project_root, hash).unwrap(), content);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_directory_lenient_skips_unreadable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        let locked = project_root.join("locked.wav");
+        fs::write(&locked, b"locked").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Permission bits are not enforced for privileged users
+        if fs::File::open(&locked).is_ok() {
+            return;
+        }
+
+        assert!(scan_directory(project_root).is_err());
+
+        let outcome = scan_directory_lenient(project_root).unwrap();
+        assert!(!outcome.is_complete());
+        assert_eq!(outcome.entries.len(), 1);
+        assert_eq!(outcome.entries[0].path, PathBuf::from("song.als"));
+        assert_eq!(outcome.errors[0].0, PathBuf::from("locked.wav"));
+    }
+
+    #[test]
+    fn test_scan_directory_lenient_complete() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+
+        let outcome = scan_directory_lenient(project_root).unwrap();
+        assert!(outcome.is_complete());
+        assert_eq!(outcome.entries.len(), 1);
+        assert_eq!(
+            outcome.entries[0].hash,
+            scan_directory(project_root).unwrap()[0].hash
+        );
+    }
+
+    #[test]
+    fn test_create_snapshot_rejects_empty_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let result = create_snapshot(project_root, "Empty", None, &Default::default());
+        assert!(matches!(
+            result,
+            Err(MovsError::EmptySnapshot {
+                all_ignored: false,
+                ..
+            })
+        ));
+
+        let options = SnapshotOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+        let id = create_snapshot(project_root, "Empty", None, &options).unwrap();
+        assert!(load_snapshot(project_root, &id).unwrap().files.is_empty());
+    }
+
+    #[test]
+    fn test_create_snapshot_rejects_fully_ignored_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join(IGNORE_FILE), "*.wav\n").unwrap();
+        fs::write(project_root.join("kick.wav"), b"kick").unwrap();
+
+        let err = create_snapshot(project_root, "Ignored", None, &Default::default()).unwrap_err();
+        assert!(matches!(
+            err,
+            MovsError::EmptySnapshot {
+                all_ignored: true,
+                ..
+            }
+        ));
+        assert!(err.to_string().contains(".movsignore"));
+    }
+
+    #[test]
+    fn test_empty_directories_are_not_ignored_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::create_dir_all(project_root.join("Samples/Drums")).unwrap();
+        fs::create_dir(project_root.join("Bounces")).unwrap();
+
+        let err = create_snapshot(project_root, "Empty", None, &Default::default()).unwrap_err();
+        assert!(matches!(
+            err,
+            MovsError::EmptySnapshot {
+                all_ignored: false,
+                ..
+            }
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_follows_symlinked_library() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        fs::write(library.path().join("808.wav"), b"boom").unwrap();
+        std::os::unix::fs::symlink(library.path(), project_root.join("Library")).unwrap();
+
+        let unfollowed = scan_directory(project_root).unwrap();
+        assert_eq!(unfollowed.len(), 1);
+        assert!(!unfollowed[0].is_file());
+
+        let options = ScanOptions {
+            follow_symlinks: true,
+            ..ScanOptions::default()
+        };
+        let followed = scan_directory_with_options(project_root, &options).unwrap();
+        assert_eq!(followed.len(), 1);
+        assert_eq!(followed[0].path, PathBuf::from("Library/808.wav"));
+        assert_eq!(followed[0].hash, crate::hash::hash_bytes(b"boom"));
+        assert!(followed[0].is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_detects_symlink_loops() {
+        let options = ScanOptions {
+            follow_symlinks: true,
+            ..ScanOptions::default()
+        };
+
+        // A link that resolves to itself
+        let temp_dir = TempDir::new().unwrap();
+        std::os::unix::fs::symlink("self", temp_dir.path().join("self")).unwrap();
+        assert!(matches!(
+            scan_directory_with_options(temp_dir.path(), &options),
+            Err(MovsError::StorageError(_))
+        ));
+
+        // A directory link pointing back at its ancestor
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("Samples")).unwrap();
+        std::os::unix::fs::symlink("..", temp_dir.path().join("Samples/up")).unwrap();
+        assert!(matches!(
+            scan_directory_with_options(temp_dir.path(), &options),
+            Err(MovsError::StorageError(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_following_keeps_dangling_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        std::os::unix::fs::symlink("missing.wav", temp_dir.path().join("gone.wav")).unwrap();
+
+        let options = ScanOptions {
+            follow_symlinks: true,
+            ..ScanOptions::default()
+        };
+        let entries = scan_directory_with_options(temp_dir.path(), &options).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(entries[0].kind, EntryKind::Symlink { .. }));
+    }
+
+    #[test]
+    fn test_previews_follow_config_flag() {
+        use crate::metadata::config::{load_config, save_config};
+        use crate::preview::tests::wav_16bit;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(
+            project_root.join("kick.wav"),
+            wav_16bit(&[0, 1000, -1000, 0]),
+        )
+        .unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+
+        let id = create_snapshot(project_root, "Off", None, &Default::default()).unwrap();
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        assert!(metadata.files.iter().all(|entry| entry.preview.is_none()));
+
+        let mut config = load_config(project_root).unwrap();
+        config.compute_previews = true;
+        save_config(project_root, &config).unwrap();
+
+        let options = SnapshotOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+        let id = create_snapshot(project_root, "On", None, &options).unwrap();
+        let metadata = load_snapshot(project_root, &id).unwrap();
+        let kick = metadata
+            .files
+            .iter()
+            .find(|entry| entry.path == Path::new("kick.wav"))
+            .unwrap();
+        let song = metadata
+            .files
+            .iter()
+            .find(|entry| entry.path == Path::new("song.als"))
+            .unwrap();
+        assert!(kick.preview.is_some());
+        assert!(song.preview.is_none());
+    }
+
+    #[test]
+    fn test_snapshot_builder_reports_timing() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::write(project_root.join("kick.wav"), b"kick").unwrap();
+
+        let (id, result) = SnapshotBuilder::new(project_root, "First")
+            .create()
+            .unwrap();
+        assert_eq!(read_head(project_root).unwrap(), Some(id.clone()));
+        assert_eq!(result.timing.bytes_hashed, 11);
+        assert_eq!(result.timing.bytes_stored, 11);
+
+        // Unchanged content is hashed again but nothing new is stored
+        fs::write(project_root.join("copy.wav"), b"kick").unwrap();
+        let (_, result) = SnapshotBuilder::new(project_root, "Second")
+            .create()
+            .unwrap();
+        assert_eq!(result.timing.bytes_hashed, 15);
+        assert_eq!(result.timing.bytes_stored, 0);
+    }
+
+    #[test]
+    fn test_snapshot_builder_reports_storage() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::write(project_root.join("kick.wav"), b"kick").unwrap();
+        fs::write(project_root.join("kick copy.wav"), b"kick").unwrap();
+
+        let (_, result) = SnapshotBuilder::new(project_root, "First")
+            .create()
+            .unwrap();
+        assert_eq!(
+            (
+                result.new_objects,
+                result.deduped_objects,
+                result.new_bytes,
+                result.logical_bytes
+            ),
+            (2, 1, 11, 15)
+        );
+
+        fs::write(project_root.join("song.als"), b"project v2").unwrap();
+        let (_, result) = SnapshotBuilder::new(project_root, "Second")
+            .create()
+            .unwrap();
+        assert_eq!(result.new_objects, 1);
+        assert_eq!(result.deduped_objects, 2);
+        assert_eq!(result.new_bytes, 10);
+
+        let (_, result) = SnapshotBuilder::new(project_root, "Same").create().unwrap();
+        assert_eq!(result.new_objects, 0);
+        assert_eq!(result.deduped_objects, 3);
+        assert_eq!(result.logical_bytes, 18);
+    }
+
+    #[test]
+    fn test_create_snapshot_with_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::write(project_root.join("kick.wav"), b"kick").unwrap();
+        fs::write(project_root.join("kick copy.wav"), b"kick").unwrap();
+        let budget = |max_new_bytes| SnapshotOptions {
+            max_new_bytes: Some(max_new_bytes),
+            ..SnapshotOptions::default()
+        };
+
+        // Duplicated content counts once: 7 + 4 bytes
+        match create_snapshot(project_root, "First", None, &budget(10)) {
+            Err(MovsError::BudgetExceeded { new_bytes, budget }) => {
+                assert_eq!((new_bytes, budget), (11, 10))
+            }
+            other => panic!("expected the budget to be exceeded, got {:?}", other),
+        }
+        assert!(crate::metadata::list_snapshots(project_root)
+            .unwrap()
+            .is_empty());
+        assert!(!object_exists(project_root, &crate::hash::hash_bytes(b"project")).unwrap());
+
+        create_snapshot(project_root, "First", None, &budget(11)).unwrap();
+
+        // Only the changed file counts against the budget afterwards
+        fs::write(project_root.join("song.als"), b"project v2").unwrap();
+        create_snapshot(project_root, "Second", None, &budget(10)).unwrap();
+    }
+}
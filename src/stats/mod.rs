@@ -0,0 +1,264 @@
+use crate::diff::diff_entries;
+use crate::error::{MovsError, Result};
+use crate::metadata::persistence::{iter_snapshots, load_all_snapshots};
+use crate::metadata::{get_movs_dir, get_objects_dir, repository_exists};
+use crate::storage::pack::{PackIndex, PACK_DIR};
+use crate::types::{SnapshotId, SnapshotMetadata};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Disk usage of a repository after deduplication
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiskUsage {
+    /// Bytes actually occupied by the object store on disk
+    pub stored_bytes: u64,
+
+    /// Sum of the sizes of every file in every snapshot
+    pub logical_bytes: u64,
+
+    /// `logical_bytes / stored_bytes`; values above 1.0 mean dedup is saving space
+    pub dedup_ratio: f64,
+}
+
+impl DiskUsage {
+    /// Bytes saved by deduplication
+    pub fn saved_bytes(&self) -> u64 {
+        self.logical_bytes.saturating_sub(self.stored_bytes)
+    }
+}
+
+/// Report how much disk the repository uses compared to its logical size
+///
+/// The stored size is measured by walking `objects/` rather than trusting
+/// snapshot metadata, so it includes pack files and any orphaned objects.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn repository_disk_usage(project_root: &Path) -> Result<DiskUsage> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    let stored_bytes = object_store_size(project_root)?;
+    let mut logical_bytes = 0;
+    for metadata in iter_snapshots(project_root)? {
+        logical_bytes += metadata?.total_size();
+    }
+
+    Ok(DiskUsage {
+        stored_bytes,
+        logical_bytes,
+        dedup_ratio: dedup_ratio(logical_bytes, stored_bytes),
+    })
+}
+
+/// Aggregate statistics about a repository, for dashboards
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepoStats {
+    /// Number of snapshots
+    pub snapshot_count: usize,
+
+    /// Number of distinct objects in the store (loose, packed and delta)
+    pub object_count: usize,
+
+    /// Disk usage and dedup ratio
+    pub disk_usage: DiskUsage,
+
+    /// Timestamp of the oldest snapshot
+    pub oldest: Option<DateTime<Utc>>,
+
+    /// Timestamp of the newest snapshot
+    pub newest: Option<DateTime<Utc>>,
+
+    /// Mean churn percentage of each snapshot relative to its parent
+    ///
+    /// Snapshots without a parent are not counted; `0.0` if there are none.
+    pub average_churn: f64,
+}
+
+/// Compute aggregate statistics for a repository
+///
+/// Snapshot metadata is loaded once and the object store is walked once;
+/// both passes feed every field of the report.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn repository_stats(project_root: &Path) -> Result<RepoStats> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    let snapshots = load_all_snapshots(project_root)?;
+    let store = scan_object_store(project_root)?;
+    let packed = PackIndex::load(project_root)?.len();
+
+    let logical_bytes = snapshots.iter().map(|metadata| metadata.total_size()).sum();
+
+    let by_id: HashMap<&SnapshotId, &SnapshotMetadata> = snapshots
+        .iter()
+        .map(|metadata| (&metadata.id, metadata))
+        .collect();
+    let churns: Vec<f64> = snapshots
+        .iter()
+        .filter_map(|metadata| {
+            let parent = by_id.get(metadata.parent.as_ref()?)?;
+            let diff = diff_entries(&parent.files, &metadata.files);
+            Some(diff.as_percentages(parent.file_count()).churn)
+        })
+        .collect();
+    let average_churn = if churns.is_empty() {
+        0.0
+    } else {
+        churns.iter().sum::<f64>() / churns.len() as f64
+    };
+
+    Ok(RepoStats {
+        snapshot_count: snapshots.len(),
+        object_count: store.loose_objects + packed,
+        disk_usage: DiskUsage {
+            stored_bytes: store.bytes,
+            logical_bytes,
+            dedup_ratio: dedup_ratio(logical_bytes, store.bytes),
+        },
+        oldest: snapshots.iter().map(|metadata| metadata.timestamp).min(),
+        newest: snapshots.iter().map(|metadata| metadata.timestamp).max(),
+        average_churn,
+    })
+}
+
+/// Total size of all files under `objects/`
+pub fn object_store_size(project_root: &Path) -> Result<u64> {
+    Ok(scan_object_store(project_root)?.bytes)
+}
+
+/// Result of a single walk over `objects/`
+struct StoreScan {
+    bytes: u64,
+    loose_objects: usize,
+}
+
+fn scan_object_store(project_root: &Path) -> Result<StoreScan> {
+    let objects_dir = get_objects_dir(project_root)?;
+    let pack_dir = objects_dir.join(PACK_DIR);
+    let mut scan = StoreScan {
+        bytes: 0,
+        loose_objects: 0,
+    };
+
+    for entry in WalkDir::new(&objects_dir) {
+        let entry = entry.map_err(|e| MovsError::Io(e.into()))?;
+        if entry.file_type().is_file() {
+            scan.bytes += entry.metadata().map_err(|e| MovsError::Io(e.into()))?.len();
+
+            // Pack files hold many objects; those are counted via the pack index
+            if !entry.path().starts_with(&pack_dir) {
+                scan.loose_objects += 1;
+            }
+        }
+    }
+
+    Ok(scan)
+}
+
+fn dedup_ratio(logical_bytes: u64, stored_bytes: u64) -> f64 {
+    if stored_bytes == 0 {
+        return 1.0;
+    }
+
+    logical_bytes as f64 / stored_bytes as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::snapshot::{create_snapshot, SnapshotOptions};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_empty_repository_usage() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let usage = repository_disk_usage(project_root).unwrap();
+        assert_eq!(
+            usage,
+            DiskUsage {
+                stored_bytes: 0,
+                logical_bytes: 0,
+                dedup_ratio: 1.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_dedup_usage() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        // Two identical 1000-byte files in one snapshot
+        fs::write(project_root.join("a.wav"), vec![1u8; 1000]).unwrap();
+        fs::write(project_root.join("b.wav"), vec![1u8; 1000]).unwrap();
+        create_snapshot(project_root, "First", None, &Default::default()).unwrap();
+
+        // A second snapshot of the same content
+        let options = SnapshotOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+        create_snapshot(project_root, "Second", None, &options).unwrap();
+
+        let usage = repository_disk_usage(project_root).unwrap();
+        assert_eq!(usage.stored_bytes, 1000);
+        assert_eq!(usage.logical_bytes, 4000);
+        assert_eq!(usage.dedup_ratio, 4.0);
+        assert_eq!(usage.saved_bytes(), 3000);
+    }
+
+    #[test]
+    fn test_usage_missing_repository() {
+        let temp_dir = TempDir::new().unwrap();
+
+        assert!(matches!(
+            repository_disk_usage(temp_dir.path()),
+            Err(MovsError::RepositoryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_repository_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let empty = repository_stats(project_root).unwrap();
+        assert_eq!(empty.snapshot_count, 0);
+        assert_eq!(empty.oldest, None);
+
+        fs::write(project_root.join("a.wav"), b"a").unwrap();
+        fs::write(project_root.join("b.wav"), b"b").unwrap();
+        create_snapshot(project_root, "First", None, &SnapshotOptions::default()).unwrap();
+
+        fs::write(project_root.join("a.wav"), b"a2").unwrap();
+        create_snapshot(project_root, "Second", None, &SnapshotOptions::default()).unwrap();
+
+        let stats = repository_stats(project_root).unwrap();
+        assert_eq!(stats.snapshot_count, 2);
+        assert_eq!(stats.object_count, 3);
+        assert!(stats.oldest.unwrap() <= stats.newest.unwrap());
+        assert_eq!(stats.average_churn, 50.0);
+        assert_eq!(stats.disk_usage.stored_bytes, 4);
+        assert_eq!(stats.disk_usage.logical_bytes, 5);
+        assert_eq!(
+            stats.disk_usage,
+            repository_disk_usage(project_root).unwrap()
+        );
+    }
+}
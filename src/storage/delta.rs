@@ -0,0 +1,699 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::config::RepositoryConfig;
+use crate::metadata::get_objects_dir;
+use crate::storage::naming::{decode_object_name, encode_object_name};
+use crate::storage::{
+    load_object, load_settings, object_exists_with_config, store_object_bytes_with_config,
+    store_object_with_config,
+};
+use crate::tree::path::fs_path;
+use crate::types::{FileHash, HashAlgorithm, ObjectEncoding};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// Subdirectory of `objects/` holding delta-encoded objects
+pub const DELTA_DIR: &str = "delta";
+
+/// Maximum number of deltas applied to reconstruct one object
+///
+/// Bounds reconstruction cost; objects whose base chain would grow beyond
+/// this are stored in full instead.
+pub const MAX_DELTA_DEPTH: usize = 16;
+
+const DELTA_MAGIC: &[u8; 4] = b"MVD1";
+const BLOCK_SIZE: usize = 16;
+const OP_COPY: u8 = 1;
+const OP_INSERT: u8 = 2;
+
+/// Bytes of new content read at a time while encoding
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Longest literal run buffered before it is written as an insert
+const MAX_INSERT: usize = 1024 * 1024;
+
+/// Encode `new` as a binary delta against `old`
+///
+/// The delta is a sequence of copy (offset, length into `old`) and insert
+/// (literal bytes) instructions. Matches are found by indexing `old` in
+/// fixed-size blocks and extending each hit as far as the bytes agree, so
+/// edits in the middle of a large file cost little more than the edit.
+pub fn delta_encode(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::with_capacity(new.len() / 4 + 16);
+    encode_stream(old, new, new.len() as u64, &mut delta, None)
+        .expect("writing to a Vec cannot fail");
+    delta
+}
+
+/// Reconstruct the new content from a base and a delta produced by `delta_encode`
+pub fn delta_decode(old: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    decode_stream(old, delta, &mut output)?;
+    Ok(output)
+}
+
+/// What `encode_stream` read from `new`
+struct Encoded {
+    hash: FileHash,
+    len: u64,
+}
+
+/// Streaming core of `delta_encode`
+///
+/// `new` is read in chunks, so besides `old` only a bounded window of it is
+/// held in memory; long literal runs are flushed as several inserts. `len`
+/// goes into the header, and the caller checks it against what was read.
+/// Returns `None` as soon as the delta reaches `limit` bytes.
+fn encode_stream(
+    old: &[u8],
+    new: impl Read,
+    len: u64,
+    out: &mut impl Write,
+    limit: Option<u64>,
+) -> io::Result<Option<Encoded>> {
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    for offset in (0..old.len().saturating_sub(BLOCK_SIZE - 1)).step_by(BLOCK_SIZE) {
+        blocks
+            .entry(&old[offset..offset + BLOCK_SIZE])
+            .or_insert(offset);
+    }
+
+    let mut out = CountingWriter {
+        inner: out,
+        written: 0,
+    };
+    out.write_all(DELTA_MAGIC)?;
+    out.write_all(&len.to_le_bytes())?;
+
+    let mut input = HashingReader {
+        inner: new,
+        hasher: Sha256::new(),
+        len: 0,
+    };
+    // Bytes of `new` not yet encoded; `window[..pending]` has no match
+    let mut window = Vec::new();
+    let mut pending = 0;
+    let mut eof = false;
+    let over_limit = |out: &CountingWriter<_>| limit.is_some_and(|limit| out.written >= limit);
+
+    loop {
+        if window.len() < pending + BLOCK_SIZE && !eof {
+            eof = !input.fill(&mut window)?;
+            continue;
+        }
+        if pending == window.len() {
+            break;
+        }
+
+        let hit = window
+            .get(pending..pending + BLOCK_SIZE)
+            .and_then(|block| blocks.get(block).copied());
+
+        match hit {
+            Some(start) => {
+                write_insert(&mut out, &window[..pending])?;
+                window.drain(..pending);
+                pending = 0;
+
+                let mut matched = 0;
+                loop {
+                    let n = window
+                        .iter()
+                        .zip(&old[start + matched..])
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    matched += n;
+                    window.drain(..n);
+                    if !window.is_empty() || start + matched == old.len() || eof {
+                        break;
+                    }
+                    eof = !input.fill(&mut window)?;
+                }
+
+                out.write_all(&[OP_COPY])?;
+                out.write_all(&(start as u64).to_le_bytes())?;
+                out.write_all(&(matched as u64).to_le_bytes())?;
+            }
+            None => {
+                pending += 1;
+                if pending == MAX_INSERT {
+                    write_insert(&mut out, &window[..pending])?;
+                    window.drain(..pending);
+                    pending = 0;
+                }
+            }
+        }
+
+        if over_limit(&out) {
+            return Ok(None);
+        }
+    }
+
+    write_insert(&mut out, &window[..pending])?;
+    if over_limit(&out) {
+        return Ok(None);
+    }
+
+    Ok(Some(Encoded {
+        hash: FileHash::new(input.hasher.finalize().to_vec()),
+        len: input.len,
+    }))
+}
+
+/// Streaming core of `delta_decode`, writing the content to `out`
+fn decode_stream(old: &[u8], mut delta: impl Read, out: &mut impl Write) -> Result<()> {
+    let malformed = || MovsError::StorageError("Malformed delta".to_string());
+
+    let mut magic = [0u8; 4];
+    delta.read_exact(&mut magic).map_err(|_| malformed())?;
+    if &magic != DELTA_MAGIC {
+        return Err(malformed());
+    }
+    let expected_len = read_u64(&mut delta).ok_or_else(malformed)?;
+    let mut written: u64 = 0;
+
+    loop {
+        let mut op = [0u8];
+        match delta.read(&mut op) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(MovsError::Io(e)),
+        }
+
+        match op[0] {
+            OP_COPY => {
+                let offset = read_u64(&mut delta).ok_or_else(malformed)?;
+                let len = read_u64(&mut delta).ok_or_else(malformed)?;
+                let start = usize::try_from(offset).map_err(|_| malformed())?;
+                let end = start
+                    .checked_add(usize::try_from(len).map_err(|_| malformed())?)
+                    .ok_or_else(malformed)?;
+                out.write_all(old.get(start..end).ok_or_else(malformed)?)?;
+                written += len;
+            }
+            OP_INSERT => {
+                let len = read_u64(&mut delta).ok_or_else(malformed)?;
+                if io::copy(&mut (&mut delta).take(len), out)? != len {
+                    return Err(malformed());
+                }
+                written += len;
+            }
+            _ => return Err(malformed()),
+        }
+    }
+
+    if written != expected_len {
+        return Err(malformed());
+    }
+
+    Ok(())
+}
+
+/// Get the path to the delta object directory
+pub fn get_delta_dir(project_root: &Path) -> Result<PathBuf> {
+    Ok(get_objects_dir(project_root)?.join(DELTA_DIR))
+}
+
+/// Get the path where a delta object with the given hash is stored
+///
+/// The file name uses the configured `object_encoding`, read on every call.
+pub fn get_delta_path(project_root: &Path, hash: &FileHash) -> Result<PathBuf> {
+    let config = crate::storage::load_settings(project_root)?;
+    Ok(get_delta_path_with_config(project_root, hash, &config))
+}
+
+/// `get_delta_path` with an already loaded config
+pub(crate) fn get_delta_path_with_config(
+    project_root: &Path,
+    hash: &FileHash,
+    config: &RepositoryConfig,
+) -> PathBuf {
+    config
+        .objects_dir_in(project_root)
+        .join(DELTA_DIR)
+        .join(encode_object_name(hash, config.object_encoding))
+}
+
+/// Locate a delta object, trying the configured name encoding first
+///
+/// Falls back to the other encoding, like `find_loose_object`, so deltas
+/// stay readable while `migrate_object_encoding` is interrupted halfway.
+pub(crate) fn find_delta_object(
+    project_root: &Path,
+    hash: &FileHash,
+    config: &RepositoryConfig,
+) -> Option<PathBuf> {
+    let configured = get_delta_path_with_config(project_root, hash, config);
+    if configured.is_file() {
+        return Some(configured);
+    }
+
+    let delta_dir = configured.parent()?;
+    [ObjectEncoding::Hex, ObjectEncoding::Base32]
+        .map(|encoding| delta_dir.join(encode_object_name(hash, encoding)))
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+/// Store `content` as a delta against the object `base`
+///
+/// Falls back to a full object when the base chain is already at
+/// `MAX_DELTA_DEPTH` or the delta would not be smaller than the content.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `hash` - Content hash of `content`
+/// * `content` - The new version of the object
+/// * `base` - Hash of an existing object to encode against, e.g. the parent version of the file
+///
+/// # Returns
+///
+/// `true` if the object was stored as a delta
+pub fn store_delta_object(
+    project_root: &Path,
+    hash: &FileHash,
+    content: &[u8],
+    base: &FileHash,
+) -> Result<bool> {
+    let config = load_settings(project_root)?;
+    match write_delta(
+        project_root,
+        hash,
+        base,
+        &config,
+        content.len() as u64,
+        content,
+    )? {
+        DeltaWrite::Stored => Ok(true),
+        DeltaWrite::Declined => {
+            store_object_bytes_with_config(project_root, content, &config)?;
+            Ok(false)
+        }
+        DeltaWrite::Mismatch => Err(MovsError::StorageError(format!(
+            "Content does not hash to {}",
+            hash
+        ))),
+    }
+}
+
+/// `store_delta_object` for a working file, streamed from disk
+///
+/// Only the base is held in memory. Returns
+/// `MovsError::FileChangedDuringRead` if the file no longer hashes to
+/// `hash` by the time it has been read.
+pub(crate) fn store_delta_file(
+    project_root: &Path,
+    hash: &FileHash,
+    source: &Path,
+    base: &FileHash,
+    config: &RepositoryConfig,
+) -> Result<bool> {
+    let file = File::open(fs_path(source)?)?;
+    let len = file.metadata()?.len();
+
+    match write_delta(project_root, hash, base, config, len, file)? {
+        DeltaWrite::Stored => Ok(true),
+        DeltaWrite::Declined => {
+            store_object_with_config(project_root, hash, source, config)?;
+            Ok(false)
+        }
+        DeltaWrite::Mismatch => Err(MovsError::FileChangedDuringRead(source.to_path_buf())),
+    }
+}
+
+/// Outcome of `write_delta`
+enum DeltaWrite {
+    /// The delta object was published
+    Stored,
+
+    /// A delta is not worth it, or the object already exists
+    Declined,
+
+    /// The content read does not hash to the expected hash
+    Mismatch,
+}
+
+/// Encode `source` against `base` into a delta object
+///
+/// The delta is written to a temporary file as it is encoded, then decoded
+/// again and checked against `hash` before being renamed into place, so
+/// neither a source changing mid-read nor a bad delta is ever published.
+fn write_delta(
+    project_root: &Path,
+    hash: &FileHash,
+    base: &FileHash,
+    config: &RepositoryConfig,
+    len: u64,
+    source: impl Read,
+) -> Result<DeltaWrite> {
+    if object_exists_with_config(project_root, hash, config)
+        || delta_depth(project_root, base)? + 1 > MAX_DELTA_DEPTH
+    {
+        return Ok(DeltaWrite::Declined);
+    }
+
+    let old = load_object(project_root, base)?;
+    let path = get_delta_path_with_config(project_root, hash, config);
+    let delta_dir = path.parent().expect("delta path has a parent");
+    fs::create_dir_all(delta_dir)?;
+
+    let mut temp = NamedTempFile::new_in(delta_dir)?;
+    let header = format!("{}\n", base.to_hex());
+    temp.write_all(header.as_bytes())?;
+
+    let mut writer = BufWriter::new(temp.as_file_mut());
+    let encoded = encode_stream(&old, source, len, &mut writer, Some(len))?;
+    writer.flush()?;
+    drop(writer);
+
+    let Some(encoded) = encoded else {
+        return Ok(DeltaWrite::Declined);
+    };
+    if encoded.hash != *hash || encoded.len != len {
+        return Ok(DeltaWrite::Mismatch);
+    }
+
+    let file = temp.as_file_mut();
+    file.seek(SeekFrom::Start(header.len() as u64))?;
+    let mut hasher = Sha256::new();
+    decode_stream(&old, BufReader::new(&*file), &mut hasher)?;
+    if FileHash::new(hasher.finalize().to_vec()) != *hash {
+        return Err(MovsError::StorageError(format!(
+            "Delta for {} does not reconstruct the object",
+            hash
+        )));
+    }
+
+    temp.persist(&path).map_err(|e| MovsError::Io(e.error))?;
+    Ok(DeltaWrite::Stored)
+}
+
+/// List the hashes of all delta-encoded objects
+pub fn list_delta_objects(project_root: &Path) -> Result<Vec<FileHash>> {
+    Ok(list_delta_files(project_root)?
+        .into_iter()
+        .map(|(hash, _)| hash)
+        .collect())
+}
+
+/// List every delta object with the path it is stored at, in either name
+/// encoding
+pub(crate) fn list_delta_files(project_root: &Path) -> Result<Vec<(FileHash, PathBuf)>> {
+    let delta_dir = get_delta_dir(project_root)?;
+    if !delta_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut deltas = Vec::new();
+    for entry in fs::read_dir(delta_dir)? {
+        let entry = entry?;
+        if let Some(hash) = decode_object_name(&entry.file_name().to_string_lossy()) {
+            deltas.push((hash, entry.path()));
+        }
+    }
+
+    deltas.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    Ok(deltas)
+}
+
+/// Reconstruct a delta object, or `None` if `hash` is not stored as a delta
+///
+/// Bases are resolved recursively through `load_object`; chains longer than
+/// `MAX_DELTA_DEPTH` are rejected.
+pub(crate) fn load_delta_object(project_root: &Path, hash: &FileHash) -> Result<Option<Vec<u8>>> {
+    load_delta_at_depth(project_root, hash, 0)
+}
+
+fn load_delta_at_depth(
+    project_root: &Path,
+    hash: &FileHash,
+    depth: usize,
+) -> Result<Option<Vec<u8>>> {
+    let Some((base, delta)) = read_delta_file(project_root, hash)? else {
+        return Ok(None);
+    };
+
+    if depth >= MAX_DELTA_DEPTH {
+        return Err(MovsError::StorageError(format!(
+            "Delta chain for {} exceeds {} levels",
+            hash, MAX_DELTA_DEPTH
+        )));
+    }
+
+    let base_content = match load_delta_at_depth(project_root, &base, depth + 1)? {
+        Some(content) => content,
+        None => load_object(project_root, &base)?,
+    };
+
+    delta_decode(&base_content, &delta).map(Some)
+}
+
+/// The object a delta is encoded against, or `None` if `hash` is not a delta
+pub(crate) fn delta_base(project_root: &Path, hash: &FileHash) -> Result<Option<FileHash>> {
+    Ok(read_delta_file(project_root, hash)?.map(|(base, _)| base))
+}
+
+/// Number of deltas that must be applied to reconstruct `hash`
+fn delta_depth(project_root: &Path, hash: &FileHash) -> Result<usize> {
+    let mut depth = 0;
+    let mut current = hash.clone();
+
+    while let Some((base, _)) = read_delta_file(project_root, &current)? {
+        depth += 1;
+        if depth > MAX_DELTA_DEPTH {
+            break;
+        }
+        current = base;
+    }
+
+    Ok(depth)
+}
+
+fn read_delta_file(project_root: &Path, hash: &FileHash) -> Result<Option<(FileHash, Vec<u8>)>> {
+    let config = crate::storage::load_settings(project_root)?;
+    let Some(path) = find_delta_object(project_root, hash, &config) else {
+        return Ok(None);
+    };
+
+    let raw = fs::read(&path)?;
+    let newline = raw
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or_else(|| MovsError::StorageError(format!("Malformed delta object: {}", hash)))?;
+    let base = FileHash::from_hex_checked(
+        &String::from_utf8_lossy(&raw[..newline]),
+        HashAlgorithm::Sha256,
+    )
+    .map_err(|_| MovsError::StorageError(format!("Malformed delta object: {}", hash)))?;
+
+    Ok(Some((base, raw[newline + 1..].to_vec())))
+}
+
+fn write_insert(out: &mut impl Write, literal: &[u8]) -> io::Result<()> {
+    if literal.is_empty() {
+        return Ok(());
+    }
+
+    out.write_all(&[OP_INSERT])?;
+    out.write_all(&(literal.len() as u64).to_le_bytes())?;
+    out.write_all(literal)
+}
+
+fn read_u64(reader: &mut impl Read) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Reader that hashes and counts everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    /// Append the next chunk to `window`, returning `false` at end of input
+    fn fill(&mut self, window: &mut Vec<u8>) -> io::Result<bool> {
+        let start = window.len();
+        window.resize(start + READ_CHUNK, 0);
+        let read = loop {
+            match self.inner.read(&mut window[start..]) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    window.truncate(start);
+                    return Err(e);
+                }
+            }
+        };
+        window.truncate(start + read);
+
+        self.hasher.update(&window[start..]);
+        self.len += read as u64;
+        Ok(read > 0)
+    }
+}
+
+/// Writer that counts the bytes written through it
+struct CountingWriter<'a, W> {
+    inner: &'a mut W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_bytes;
+    use crate::metadata::init_repository;
+    use crate::storage::{object_exists, store_object_bytes};
+    use tempfile::TempDir;
+
+    fn project_bytes(len: usize, seed: u8) -> Vec<u8> {
+        (0..len)
+            .map(|i| {
+                (i as u8)
+                    .wrapping_mul(31)
+                    .wrapping_add(seed ^ (i / 7) as u8)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_delta_roundtrip() {
+        let old = project_bytes(10_000, 1);
+        let mut new = old.clone();
+        new[5_000..5_010].copy_from_slice(b"edited bar");
+        new.extend_from_slice(b"appended clip");
+
+        let delta = delta_encode(&old, &new);
+        assert!(delta.len() < 200);
+        assert_eq!(delta_decode(&old, &delta).unwrap(), new);
+    }
+
+    #[test]
+    fn test_delta_edge_cases() {
+        for (old, new) in [
+            (&b""[..], &b""[..]),
+            (b"", b"brand new"),
+            (b"everything removed", b""),
+            (b"short", b"shorter"),
+        ] {
+            assert_eq!(delta_decode(old, &delta_encode(old, new)).unwrap(), new);
+        }
+    }
+
+    #[test]
+    fn test_delta_roundtrip_across_chunks() {
+        // Matches and literal runs spanning read chunks and the insert limit
+        let old = project_bytes(3 * MAX_INSERT, 5);
+        let mut new = old[READ_CHUNK / 2..].to_vec();
+        new[READ_CHUNK..READ_CHUNK + 4].copy_from_slice(b"edit");
+        new.extend(
+            project_bytes(MAX_INSERT + READ_CHUNK + 7, 9)
+                .iter()
+                .map(|b| !b),
+        );
+
+        let delta = delta_encode(&old, &new);
+        assert_eq!(delta_decode(&old, &delta).unwrap(), new);
+    }
+
+    #[test]
+    fn test_delta_decode_rejects_garbage() {
+        assert!(delta_decode(b"base", b"not a delta").is_err());
+
+        let mut delta = delta_encode(b"0123456789abcdef0123", b"0123456789abcdef");
+        delta.truncate(delta.len() - 3);
+        assert!(delta_decode(b"0123456789abcdef0123", &delta).is_err());
+    }
+
+    #[test]
+    fn test_store_and_load_delta_chain() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let mut content = project_bytes(50_000, 3);
+        let mut base = store_object_bytes(project_root, &content).unwrap();
+        let mut versions = Vec::new();
+
+        for i in 0..MAX_DELTA_DEPTH + 2 {
+            content[i * 100] ^= 0xff;
+            let hash = hash_bytes(&content);
+            store_delta_object(project_root, &hash, &content, &base).unwrap();
+            versions.push((hash.clone(), content.clone()));
+            base = hash;
+        }
+
+        for (hash, expected) in &versions {
+            assert!(object_exists(project_root, hash).unwrap());
+            assert_eq!(&load_object(project_root, hash).unwrap(), expected);
+        }
+
+        // Chains are cut at the depth limit by storing a full object
+        assert!(list_delta_objects(project_root).unwrap().len() <= MAX_DELTA_DEPTH + 1);
+        assert!(versions
+            .iter()
+            .all(|(hash, _)| delta_depth(project_root, hash).unwrap() <= MAX_DELTA_DEPTH));
+    }
+
+    #[test]
+    fn test_store_delta_file_checks_the_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        let config = load_settings(project_root).unwrap();
+
+        let content = project_bytes(50_000, 4);
+        let base = store_object_bytes(project_root, &content).unwrap();
+        let mut edited = content.clone();
+        edited[1_000] ^= 0xff;
+        let source = project_root.join("vox.wav");
+        fs::write(&source, &edited).unwrap();
+
+        // The file no longer matches the hash it was scanned with
+        let stale = hash_bytes(b"scanned earlier");
+        assert!(matches!(
+            store_delta_file(project_root, &stale, &source, &base, &config),
+            Err(MovsError::FileChangedDuringRead(_))
+        ));
+        assert!(list_delta_objects(project_root).unwrap().is_empty());
+
+        let hash = hash_bytes(&edited);
+        assert!(store_delta_file(project_root, &hash, &source, &base, &config).unwrap());
+        assert_eq!(load_object(project_root, &hash).unwrap(), edited);
+    }
+
+    #[test]
+    fn test_store_delta_falls_back_when_not_smaller() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let base = store_object_bytes(project_root, b"unrelated").unwrap();
+        let content = b"tiny";
+        let hash = hash_bytes(content);
+
+        assert!(!store_delta_object(project_root, &hash, content, &base).unwrap());
+        assert!(!get_delta_path(project_root, &hash).unwrap().exists());
+        assert_eq!(load_object(project_root, &hash).unwrap(), content);
+    }
+}
@@ -0,0 +1,1167 @@
+use crate::error::{MovsError, Result};
+use crate::hash::{hash_bytes, hash_reader};
+use crate::metadata::config::{
+    load_config, validate_fanout_depth, RepositoryConfig, FANOUT_DEPTHS,
+};
+use crate::metadata::get_objects_dir;
+use crate::metadata::lock::lock_for_write;
+use crate::retry::with_retry;
+use crate::tree::path::fs_path;
+use crate::types::{classify_path, FileHash, FileType, ObjectEncoding, StorageMode};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+use walkdir::WalkDir;
+
+pub mod delta;
+pub mod naming;
+pub mod pack;
+pub mod store;
+
+use delta::{find_delta_object, list_delta_objects, load_delta_object, DELTA_DIR};
+use pack::{get_pack_dir_with_config, open_packed_object, PackIndex, PACK_DIR};
+
+pub use naming::{decode_object_name, encode_object_name};
+
+const BUFFER_SIZE: usize = 1024 * 1024; // 1 MB
+
+/// Header marking an object stored gzip-compressed
+///
+/// Compressed objects are this header followed by a gzip stream. Content
+/// that happens to start with the header is always stored compressed, so
+/// an object without it is never mistaken for a compressed one.
+const COMPRESSED_MAGIC: &[u8] = b"\0MOVSGZ\x01";
+
+/// Get the path where an object with the given hash is stored
+///
+/// Objects are fanned out git-style by two characters per level, using
+/// the repository's configured `fanout_depth`: `.movs/objects/ab/cdef...`
+/// for one level, `.movs/objects/ab/cd/ef...` for two. The file name is
+/// the hash in the configured `object_encoding`. The config is read on
+/// every call to resolve `objects_dir`, the depth and the encoding, so the
+/// path always follows the repository's current layout.
+pub fn get_object_path(project_root: &Path, hash: &FileHash) -> Result<PathBuf> {
+    Ok(get_object_path_with_config(
+        project_root,
+        hash,
+        &load_settings(project_root)?,
+    ))
+}
+
+/// Get the path where an object is stored for an explicit fan-out depth and name encoding
+pub fn get_object_path_with_layout(
+    project_root: &Path,
+    hash: &FileHash,
+    depth: u8,
+    encoding: ObjectEncoding,
+) -> Result<PathBuf> {
+    Ok(object_path_in(
+        &get_objects_dir(project_root)?,
+        hash,
+        depth,
+        encoding,
+    ))
+}
+
+/// Lay out an object's path below an already resolved objects directory
+fn object_path_in(
+    objects_dir: &Path,
+    hash: &FileHash,
+    depth: u8,
+    encoding: ObjectEncoding,
+) -> PathBuf {
+    let name = encode_object_name(hash, encoding);
+    let mut path = objects_dir.to_path_buf();
+    let mut rest = name.as_str();
+
+    for _ in 0..depth {
+        if rest.len() <= 2 {
+            break;
+        }
+        let (prefix, tail) = rest.split_at(2);
+        path.push(prefix);
+        rest = tail;
+    }
+
+    path.join(rest)
+}
+
+/// `get_object_path` with an already loaded config
+pub(crate) fn get_object_path_with_config(
+    project_root: &Path,
+    hash: &FileHash,
+    config: &RepositoryConfig,
+) -> PathBuf {
+    object_path_in(
+        &config.objects_dir_in(project_root),
+        hash,
+        config.fanout_depth,
+        config.object_encoding,
+    )
+}
+
+/// Check if an object exists in the store, either loose, packed or as a delta
+pub fn object_exists(project_root: &Path, hash: &FileHash) -> Result<bool> {
+    Ok(object_exists_with_config(
+        project_root,
+        hash,
+        &load_settings(project_root)?,
+    ))
+}
+
+/// `object_exists` with an already loaded config
+pub(crate) fn object_exists_with_config(
+    project_root: &Path,
+    hash: &FileHash,
+    config: &RepositoryConfig,
+) -> bool {
+    let packs = PackIndex::load_in(&get_pack_dir_with_config(project_root, config));
+    object_exists_in(project_root, hash, config, &packs.unwrap_or_default())
+}
+
+/// `object_exists` with an already loaded config and pack index
+pub(crate) fn object_exists_in(
+    project_root: &Path,
+    hash: &FileHash,
+    config: &RepositoryConfig,
+    packs: &PackIndex,
+) -> bool {
+    find_loose_object(project_root, hash, config).is_some()
+        || packs.contains(hash)
+        || find_delta_object(project_root, hash, config).is_some()
+}
+
+/// Locate a loose object, trying the configured layout first
+///
+/// Falls back to every other fan-out depth and name encoding, so objects
+/// stay readable while `migrate_fanout_depth` or `migrate_object_encoding`
+/// is interrupted halfway.
+pub(crate) fn find_loose_object(
+    project_root: &Path,
+    hash: &FileHash,
+    config: &RepositoryConfig,
+) -> Option<PathBuf> {
+    let objects_dir = config.objects_dir_in(project_root);
+    let configured = object_path_in(
+        &objects_dir,
+        hash,
+        config.fanout_depth,
+        config.object_encoding,
+    );
+    if configured.is_file() {
+        return Some(configured);
+    }
+
+    FANOUT_DEPTHS
+        .flat_map(|depth| [ObjectEncoding::Hex, ObjectEncoding::Base32].map(|e| (depth, e)))
+        .map(|(depth, encoding)| object_path_in(&objects_dir, hash, depth, encoding))
+        .find(|path| path.is_file())
+}
+
+/// Load the repository config for storage operations
+///
+/// A repository without a config file yet, e.g. one still being
+/// initialized, uses the defaults. A config that exists but cannot be read
+/// is an error: guessing would send objects to the wrong layout or
+/// `objects_dir`.
+pub(crate) fn load_settings(project_root: &Path) -> Result<RepositoryConfig> {
+    match load_config(project_root) {
+        Err(MovsError::RepositoryNotFound(_)) => Ok(RepositoryConfig::default()),
+        Err(MovsError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+            Ok(RepositoryConfig::default())
+        }
+        result => result,
+    }
+}
+
+/// List every loose (unpacked) object with its on-disk path
+///
+/// The hash is reconstructed from the fan-out directories and file name, so
+/// objects are found regardless of the fan-out depth and name encoding they
+/// were written with. Pack files, delta objects and anything that is not a
+/// valid object name are skipped.
+pub fn list_loose_objects(project_root: &Path) -> Result<Vec<(FileHash, PathBuf)>> {
+    let objects_dir = get_objects_dir(project_root)?;
+    let mut objects = iter_loose_objects(objects_dir)?.collect::<Result<Vec<_>>>()?;
+    objects.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(objects)
+}
+
+/// Iterate over every object stored in a file of its own, with its path
+///
+/// Objects are found lazily as the store is walked, so a backup tool can
+/// copy them one at a time without listing the whole store first. Loose
+/// objects come first, then delta objects, whose files hold a delta rather
+/// than the full content. Pack files, pack indexes, temporary files and
+/// anything else that is not a valid object name are skipped; packed
+/// objects have no file of their own, so back up `objects/pack/` whole.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn iter_objects(
+    project_root: &Path,
+) -> Result<impl Iterator<Item = Result<(FileHash, PathBuf)>>> {
+    let objects_dir = load_settings(project_root)?.objects_dir_in(project_root);
+    let delta_dir = objects_dir.join(DELTA_DIR);
+    let deltas = delta_dir
+        .is_dir()
+        .then(|| WalkDir::new(delta_dir).min_depth(1).max_depth(1))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| match entry {
+            Ok(entry) if entry.file_type().is_file() => {
+                decode_object_name(&entry.file_name().to_string_lossy())
+                    .map(|hash| Ok((hash, entry.into_path())))
+            }
+            Ok(_) => None,
+            Err(e) => Some(Err(MovsError::Io(e.into()))),
+        });
+
+    Ok(iter_loose_objects(objects_dir)?.chain(deltas))
+}
+
+/// Lazily walk the loose objects, in directory order
+fn iter_loose_objects(
+    objects_dir: PathBuf,
+) -> Result<impl Iterator<Item = Result<(FileHash, PathBuf)>>> {
+    // Fail up front, rather than on the first item, if there is no store
+    fs::metadata(&objects_dir)?;
+
+    let walker = WalkDir::new(&objects_dir)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| {
+            !(e.depth() == 1 && (e.file_name() == PACK_DIR || e.file_name() == DELTA_DIR))
+        });
+
+    Ok(walker.filter_map(move |entry| {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(MovsError::Io(e.into()))),
+        };
+        if !entry.file_type().is_file() || entry.depth() < 2 {
+            return None;
+        }
+
+        let relative = entry.path().strip_prefix(&objects_dir).ok()?;
+        let name: String = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+
+        decode_object_name(&name).map(|hash| Ok((hash, entry.into_path())))
+    }))
+}
+
+/// Move every loose object to the layout for a new fan-out depth
+///
+/// This is the only supported way to change `fanout_depth` on a repository
+/// that already holds objects; `save_config` rejects the change otherwise.
+/// The repository lock is held throughout. Objects are moved first and
+/// the config updated last. Listing finds
+/// objects at any depth, and lookups fall back to the other depths when an
+/// object is not where the config expects it, so objects stay readable
+/// after an interrupted migration, which can simply be run again.
+///
+/// # Returns
+///
+/// The number of objects moved
+pub fn migrate_fanout_depth(project_root: &Path, depth: u8) -> Result<usize> {
+    validate_fanout_depth(depth)?;
+    let _lock = lock_for_write(project_root)?;
+    let mut config = load_config(project_root)?;
+    let moved = relayout_objects(project_root, depth, config.object_encoding)?;
+
+    config.fanout_depth = depth;
+    crate::metadata::config::write_config_unchecked(project_root, &config)?;
+
+    Ok(moved)
+}
+
+/// Rename every loose and delta object for a new name encoding
+///
+/// This is the only supported way to change `object_encoding` on a
+/// repository that already holds objects; `save_config` rejects the change
+/// otherwise. Like `migrate_fanout_depth`, it holds the repository lock
+/// and renames objects before the config is updated and listing recognizes both encodings, so an
+/// interrupted migration can simply be run again. Packed objects are
+/// unaffected, since they have no file names of their own.
+///
+/// # Returns
+///
+/// The number of objects renamed
+pub fn migrate_object_encoding(project_root: &Path, encoding: ObjectEncoding) -> Result<usize> {
+    let _lock = lock_for_write(project_root)?;
+    let mut config = load_config(project_root)?;
+    let moved = relayout_objects(project_root, config.fanout_depth, encoding)?;
+
+    config.object_encoding = encoding;
+    crate::metadata::config::write_config_unchecked(project_root, &config)?;
+
+    Ok(moved)
+}
+
+/// Move loose and delta objects to where the given layout expects them
+fn relayout_objects(project_root: &Path, depth: u8, encoding: ObjectEncoding) -> Result<usize> {
+    let objects_dir = get_objects_dir(project_root)?;
+    let mut moved = 0;
+
+    for (hash, path) in list_loose_objects(project_root)? {
+        let target = object_path_in(&objects_dir, &hash, depth, encoding);
+        if target == path {
+            continue;
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&path, &target)?;
+        moved += 1;
+
+        // Drop fan-out directories left empty by the move
+        let mut dir = path.parent();
+        while let Some(current) = dir.filter(|d| *d != objects_dir) {
+            if fs::remove_dir(current).is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+
+    let delta_dir = objects_dir.join(DELTA_DIR);
+    for hash in list_delta_objects(project_root)? {
+        let target = delta_dir.join(encode_object_name(&hash, encoding));
+        if target.is_file() {
+            continue;
+        }
+
+        for other in [ObjectEncoding::Hex, ObjectEncoding::Base32] {
+            let path = delta_dir.join(encode_object_name(&hash, other));
+            if path.is_file() {
+                fs::rename(&path, &target)?;
+                moved += 1;
+                break;
+            }
+        }
+    }
+
+    Ok(moved)
+}
+
+/// Store a file's content in the object store under its hash
+///
+/// The content is written to a temporary file first and renamed into place,
+/// so a crash never leaves a partially written object behind. Transient I/O
+/// errors are retried according to the repository's retry policy. With
+/// `StorageMode::Hardlink` eligible files are linked instead of copied; see
+/// `StorageMode` for when that happens.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `hash` - Content hash of the source file
+/// * `source` - File whose content should be stored
+///
+/// # Returns
+///
+/// `true` if the object was newly written, `false` if it already existed
+pub fn store_object(project_root: &Path, hash: &FileHash, source: &Path) -> Result<bool> {
+    store_object_with_config(project_root, hash, source, &load_settings(project_root)?)
+}
+
+/// `store_object` with an already loaded config, for callers storing many objects
+pub(crate) fn store_object_with_config(
+    project_root: &Path,
+    hash: &FileHash,
+    source: &Path,
+    config: &RepositoryConfig,
+) -> Result<bool> {
+    if object_exists_with_config(project_root, hash, config) {
+        return Ok(false);
+    }
+
+    let source = fs_path(source)?;
+    let compress = config.compress_objects && classify_path(&source).is_compressible();
+    if config.storage_mode == StorageMode::Hardlink
+        && !compress
+        && link_object(project_root, hash, &source, config)?
+    {
+        return Ok(true);
+    }
+    write_object(project_root, hash, config, compress, || File::open(&source))?;
+
+    Ok(true)
+}
+
+/// Try to hardlink a working file into the store
+///
+/// Returns `false` when the file should be copied instead: project files,
+/// which DAWs rewrite in place, files whose inode is already shared with
+/// another path, any link failure such as crossing filesystems, and files
+/// whose linked content no longer hashes to `hash` or would be read back
+/// as compressed. A kept link is made read-only, so a program rewriting
+/// the working file in place fails instead of changing the object.
+fn link_object(
+    project_root: &Path,
+    hash: &FileHash,
+    source: &Path,
+    config: &RepositoryConfig,
+) -> Result<bool> {
+    if !matches!(
+        classify_path(source),
+        FileType::Wav | FileType::Aiff | FileType::Midi
+    ) {
+        return Ok(false);
+    }
+    if link_count(&fs::metadata(source)?) != Some(1) {
+        return Ok(false);
+    }
+
+    let object_path = fs_path(&get_object_path_with_config(project_root, hash, config))?;
+    if let Some(parent) = object_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match fs::hard_link(source, &object_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(true),
+        Err(_) => return Ok(false),
+    }
+
+    // Check the linked inode itself, since the file may have changed since
+    // it was hashed; a linked object is read back as is, so it must not
+    // look compressed either
+    let mut linked = File::open(&object_path)?;
+    if is_compressed_object(&mut linked)? || hash_reader(&mut linked)? != *hash {
+        fs::remove_file(&object_path)?;
+        return Ok(false);
+    }
+
+    let mut permissions = linked.metadata()?.permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&object_path, permissions)?;
+    Ok(true)
+}
+
+/// Number of hardlinks to a file, where the platform reports it
+fn link_count(metadata: &fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.nlink())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Store in-memory content in the object store, returning its hash
+pub fn store_object_bytes(project_root: &Path, content: &[u8]) -> Result<FileHash> {
+    store_object_bytes_with_config(project_root, content, &load_settings(project_root)?)
+}
+
+/// `store_object_bytes` with an already loaded config
+pub(crate) fn store_object_bytes_with_config(
+    project_root: &Path,
+    content: &[u8],
+    config: &RepositoryConfig,
+) -> Result<FileHash> {
+    let hash = hash_bytes(content);
+
+    if !object_exists_with_config(project_root, &hash, config) {
+        write_object(project_root, &hash, config, config.compress_objects, || {
+            Ok(content)
+        })?;
+    }
+
+    Ok(hash)
+}
+
+/// Load an object's full content into memory
+///
+/// Prefer `open_object_reader` for large objects.
+pub fn load_object(project_root: &Path, hash: &FileHash) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    open_object_reader(project_root, hash)?.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// Open a buffered reader over an object's content
+///
+/// This streams the object from disk, keeping memory use flat regardless of
+/// the object's size. The pack index is consulted first, then loose objects.
+/// Delta objects are reconstructed in memory by applying their base chain.
+pub fn open_object_reader(project_root: &Path, hash: &FileHash) -> Result<impl Read> {
+    open_object_reader_with_config(project_root, hash, &load_settings(project_root)?)
+}
+
+/// `open_object_reader` with an already loaded config, for callers reading many objects
+pub(crate) fn open_object_reader_with_config(
+    project_root: &Path,
+    hash: &FileHash,
+    config: &RepositoryConfig,
+) -> Result<impl Read> {
+    let packs = PackIndex::load_in(&get_pack_dir_with_config(project_root, config))?;
+    open_object_reader_in(project_root, hash, config, &packs)
+}
+
+/// `open_object_reader` with an already loaded config and pack index
+pub(crate) fn open_object_reader_in(
+    project_root: &Path,
+    hash: &FileHash,
+    config: &RepositoryConfig,
+    packs: &PackIndex,
+) -> Result<impl Read> {
+    if let Some(location) = packs.get(hash) {
+        let pack_dir = get_pack_dir_with_config(project_root, config);
+        let reader = open_packed_object(&pack_dir, location)?;
+        return decode_object(reader);
+    }
+
+    let path = match find_loose_object(project_root, hash, config) {
+        Some(path) => fs_path(&path)?,
+        None => {
+            if let Some(content) = load_delta_object(project_root, hash)? {
+                return Ok(Box::new(io::Cursor::new(content)) as Box<dyn Read>);
+            }
+            fs_path(&get_object_path_with_config(project_root, hash, config))?
+        }
+    };
+
+    let file = with_retry(&config.retry, || File::open(&path)).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => MovsError::StorageError(format!("Object not found: {}", hash)),
+        _ => MovsError::Io(e),
+    })?;
+
+    // A hardlinked object shares its inode with a working file that may
+    // have been rewritten in place despite being read-only
+    if is_linked_object(&file)? {
+        let reader = VerifyingReader::new(BufReader::with_capacity(BUFFER_SIZE, file), hash, None);
+        return decode_object(reader);
+    }
+
+    decode_object(BufReader::with_capacity(BUFFER_SIZE, file))
+}
+
+/// Check if an object file shares its inode with another path, which is
+/// how `StorageMode::Hardlink` stores working files
+pub(crate) fn is_linked_object(file: &File) -> io::Result<bool> {
+    Ok(link_count(&file.metadata()?).is_some_and(|links| links > 1))
+}
+
+/// Open an object for reading, failing if its content does not match
+///
+/// Unlike `open_object_reader`, the returned reader errors as soon as it
+/// would yield more than `len` bytes, and at the end of the content unless
+/// exactly `len` bytes hashing to `hash` were read. Use it where the
+/// consumer has already committed to the recorded size, e.g. an archive
+/// header.
+pub(crate) fn open_verified_object_reader(
+    project_root: &Path,
+    hash: &FileHash,
+    len: u64,
+) -> Result<impl Read> {
+    let reader = open_object_reader(project_root, hash)?;
+    Ok(VerifyingReader::new(reader, hash, Some(len)))
+}
+
+/// Reader that fails at the end of its input unless the content read
+/// hashes to the expected object hash, and has the expected length if set
+struct VerifyingReader<R> {
+    inner: R,
+    hasher: Option<Sha256>,
+    expected: FileHash,
+    expected_len: Option<u64>,
+    len: u64,
+}
+
+impl<R: Read> VerifyingReader<R> {
+    fn new(inner: R, expected: &FileHash, expected_len: Option<u64>) -> Self {
+        Self {
+            inner,
+            hasher: Some(Sha256::new()),
+            expected: expected.clone(),
+            expected_len,
+            len: 0,
+        }
+    }
+
+    fn mismatch(&self, reason: String) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Object {} is corrupt: {}", self.expected, reason),
+        )
+    }
+}
+
+impl<R: Read> Read for VerifyingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        if bytes_read > 0 || buf.is_empty() {
+            self.len += bytes_read as u64;
+            if let Some(expected_len) = self.expected_len.filter(|&l| self.len > l) {
+                return Err(self.mismatch(format!("longer than {} bytes", expected_len)));
+            }
+            if let Some(hasher) = &mut self.hasher {
+                hasher.update(&buf[..bytes_read]);
+            }
+            return Ok(bytes_read);
+        }
+
+        if let Some(hasher) = self.hasher.take() {
+            if let Some(expected_len) = self.expected_len.filter(|&l| self.len != l) {
+                return Err(
+                    self.mismatch(format!("{} bytes instead of {}", self.len, expected_len))
+                );
+            }
+            let actual = FileHash::new(hasher.finalize().to_vec());
+            if actual != self.expected {
+                return Err(self.mismatch(format!("content hashes to {}", actual)));
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// Open a loose object file by path, yielding its original content
+///
+/// Unlike `open_object_reader`, the object is not looked up by hash, so
+/// this also reads objects stored under the wrong name.
+pub(crate) fn open_loose_object(path: &Path) -> Result<Box<dyn Read>> {
+    let file = File::open(fs_path(path)?)?;
+    decode_object(BufReader::with_capacity(BUFFER_SIZE, file))
+}
+
+/// Check if an object file is stored compressed, leaving it at its start
+pub(crate) fn is_compressed_object(file: &mut File) -> io::Result<bool> {
+    let head = read_head(&mut *file)?;
+    file.rewind()?;
+    Ok(head == COMPRESSED_MAGIC)
+}
+
+/// Wrap a reader over a stored object so it yields the original content
+fn decode_object(mut reader: impl Read + 'static) -> Result<Box<dyn Read>> {
+    let head = read_head(&mut reader)?;
+    if head == COMPRESSED_MAGIC {
+        return Ok(Box::new(GzDecoder::new(reader)));
+    }
+    Ok(Box::new(io::Cursor::new(head).chain(reader)))
+}
+
+/// Read up to the length of `COMPRESSED_MAGIC` from the start of an object
+fn read_head(reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(COMPRESSED_MAGIC.len());
+    reader
+        .take(COMPRESSED_MAGIC.len() as u64)
+        .read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// Write an object atomically, gzip-compressing it if `compress` is set
+fn write_object<R: Read>(
+    project_root: &Path,
+    hash: &FileHash,
+    config: &RepositoryConfig,
+    compress: bool,
+    open_source: impl Fn() -> io::Result<R>,
+) -> Result<()> {
+    let object_path = fs_path(&get_object_path_with_config(project_root, hash, config))?;
+    let parent = object_path
+        .parent()
+        .ok_or_else(|| MovsError::InvalidPath(object_path.clone()))?;
+    fs::create_dir_all(parent)?;
+
+    with_retry(&config.retry, || {
+        let mut reader = open_source()?;
+        let head = read_head(&mut reader)?;
+        let compress = compress || head == COMPRESSED_MAGIC;
+        let mut reader = io::Cursor::new(head).chain(reader);
+
+        let mut temp = NamedTempFile::new_in(parent)?;
+        if compress {
+            temp.write_all(COMPRESSED_MAGIC)?;
+            let mut encoder = GzEncoder::new(&mut temp, Compression::default());
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?;
+        } else {
+            io::copy(&mut reader, &mut temp)?;
+        }
+        temp.persist(&object_path).map_err(|e| e.error)?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_file;
+    use crate::metadata::init_repository;
+    use crate::storage::delta::get_delta_path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_object_path_fanout() {
+        let hash = FileHash::from_hex("abcdef12").unwrap();
+        let path = get_object_path(Path::new("/project"), &hash).unwrap();
+
+        assert_eq!(path, PathBuf::from("/project/.movs/objects/ab/cdef12"));
+    }
+
+    #[test]
+    fn test_store_and_load_object() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let source = project_root.join("kick.wav");
+        fs::write(&source, b"kick drum samples").unwrap();
+        let hash = hash_file(&source).unwrap();
+
+        assert!(!object_exists(project_root, &hash).unwrap());
+        assert!(store_object(project_root, &hash, &source).unwrap());
+        assert!(object_exists(project_root, &hash).unwrap());
+
+        // Storing again is a no-op
+        assert!(!store_object(project_root, &hash, &source).unwrap());
+
+        assert_eq!(
+            load_object(project_root, &hash).unwrap(),
+            b"kick drum samples"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_storage_mode() {
+        use crate::metadata::config::save_config;
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        config.storage_mode = StorageMode::Hardlink;
+        save_config(project_root, &config).unwrap();
+
+        let sample = project_root.join("kick.wav");
+        let project = project_root.join("song.als");
+        fs::write(&sample, b"kick drum samples").unwrap();
+        fs::write(&project, b"project").unwrap();
+
+        let sample_hash = hash_file(&sample).unwrap();
+        let project_hash = hash_file(&project).unwrap();
+        assert!(store_object(project_root, &sample_hash, &sample).unwrap());
+        assert!(store_object(project_root, &project_hash, &project).unwrap());
+
+        let inode = |path: &Path| fs::metadata(path).unwrap().ino();
+        assert_eq!(
+            inode(&get_object_path(project_root, &sample_hash).unwrap()),
+            inode(&sample)
+        );
+        assert_ne!(
+            inode(&get_object_path(project_root, &project_hash).unwrap()),
+            inode(&project)
+        );
+
+        // A sample already linked elsewhere is copied
+        let shared = project_root.join("snare.wav");
+        fs::write(&shared, b"snare").unwrap();
+        fs::hard_link(&shared, project_root.join("snare copy.wav")).unwrap();
+        let shared_hash = hash_file(&shared).unwrap();
+        store_object(project_root, &shared_hash, &shared).unwrap();
+        assert_ne!(
+            inode(&get_object_path(project_root, &shared_hash).unwrap()),
+            inode(&shared)
+        );
+
+        // A sample changed since it was hashed is copied, not linked
+        let hat = project_root.join("hat.wav");
+        fs::write(&hat, b"hat v2").unwrap();
+        let stale_hash = hash_bytes(b"hat v1");
+        store_object(project_root, &stale_hash, &hat).unwrap();
+        assert_ne!(
+            inode(&get_object_path(project_root, &stale_hash).unwrap()),
+            inode(&hat)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlinked_objects_are_protected() {
+        use crate::metadata::config::save_config;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        config.storage_mode = StorageMode::Hardlink;
+        save_config(project_root, &config).unwrap();
+
+        let sample = project_root.join("kick.wav");
+        fs::write(&sample, b"kick").unwrap();
+        let hash = hash_file(&sample).unwrap();
+        store_object(project_root, &hash, &sample).unwrap();
+        assert!(fs::metadata(&sample).unwrap().permissions().readonly());
+
+        // Rewriting the working file in place anyway is caught on read
+        fs::set_permissions(&sample, fs::Permissions::from_mode(0o644)).unwrap();
+        fs::write(&sample, b"snare").unwrap();
+        assert!(matches!(
+            load_object(project_root, &hash),
+            Err(MovsError::Io(e)) if e.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+
+    #[test]
+    fn test_store_object_bytes() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let hash = store_object_bytes(project_root, b"").unwrap();
+        assert_eq!(
+            hash.to_hex(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert!(load_object(project_root, &hash).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compressed_objects() {
+        use crate::metadata::config::save_config;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        config.compress_objects = true;
+        save_config(project_root, &config).unwrap();
+
+        let content = vec![0u8; 64 * 1024];
+        let wav = project_root.join("silence.wav");
+        let flac = project_root.join("silence.flac");
+        fs::write(&wav, &content).unwrap();
+        fs::write(&flac, [&b"fLaC"[..], &content].concat()).unwrap();
+
+        // PCM audio is compressed
+        let wav_hash = hash_file(&wav).unwrap();
+        store_object(project_root, &wav_hash, &wav).unwrap();
+        let stored = fs::read(get_object_path(project_root, &wav_hash).unwrap()).unwrap();
+        assert!(stored.starts_with(COMPRESSED_MAGIC));
+        assert!(stored.len() < content.len());
+        assert_eq!(load_object(project_root, &wav_hash).unwrap(), content);
+
+        // Already compressed audio is stored as is
+        let flac_hash = hash_file(&flac).unwrap();
+        store_object(project_root, &flac_hash, &flac).unwrap();
+        assert_eq!(
+            fs::read(get_object_path(project_root, &flac_hash).unwrap()).unwrap(),
+            fs::read(&flac).unwrap()
+        );
+        assert_eq!(
+            load_object(project_root, &flac_hash).unwrap(),
+            fs::read(&flac).unwrap()
+        );
+
+        // Objects stay readable after compression is turned off
+        config.compress_objects = false;
+        save_config(project_root, &config).unwrap();
+        assert_eq!(load_object(project_root, &wav_hash).unwrap(), content);
+    }
+
+    #[test]
+    fn test_content_resembling_compressed_header() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        for content in [
+            COMPRESSED_MAGIC,
+            &COMPRESSED_MAGIC[..3],
+            b"\0MOVSGZ\x01 and more",
+        ] {
+            let hash = store_object_bytes(project_root, content).unwrap();
+            assert_eq!(load_object(project_root, &hash).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn test_open_object_reader_streams_large_object() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        // Larger than the reader's buffer
+        let content = vec![0x5A; 3 * BUFFER_SIZE + 17];
+        let hash = store_object_bytes(project_root, &content).unwrap();
+
+        let mut reader = open_object_reader(project_root, &hash).unwrap();
+        let mut chunk = vec![0u8; 4096];
+        let mut total = 0;
+        loop {
+            let n = reader.read(&mut chunk).unwrap();
+            if n == 0 {
+                break;
+            }
+            assert!(chunk[..n].iter().all(|&b| b == 0x5A));
+            total += n;
+        }
+        assert_eq!(total, content.len());
+    }
+
+    #[test]
+    fn test_iter_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let base = vec![7u8; 4096];
+        let mut edited = base.clone();
+        edited[100] = 8;
+        let a = store_object_bytes(project_root, &base).unwrap();
+        let b = hash_bytes(&edited);
+        assert!(delta::store_delta_object(project_root, &b, &edited, &a).unwrap());
+        pack::pack_objects(project_root).unwrap();
+        let c = store_object_bytes(project_root, b"loose").unwrap();
+        fs::write(
+            get_objects_dir(project_root).unwrap().join("stray.tmp"),
+            b"",
+        )
+        .unwrap();
+
+        let objects: Vec<_> = iter_objects(project_root)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            objects,
+            vec![
+                (c.clone(), get_object_path(project_root, &c).unwrap()),
+                (b.clone(), delta::get_delta_path(project_root, &b).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_loose_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let a = store_object_bytes(project_root, b"a").unwrap();
+        let b = store_object_bytes(project_root, b"b").unwrap();
+
+        let mut hashes: Vec<_> = list_loose_objects(project_root)
+            .unwrap()
+            .into_iter()
+            .map(|(hash, _)| hash)
+            .collect();
+        hashes.sort_by(|x, y| x.as_bytes().cmp(y.as_bytes()));
+
+        let mut expected = vec![a, b];
+        expected.sort_by(|x, y| x.as_bytes().cmp(y.as_bytes()));
+        assert_eq!(hashes, expected);
+    }
+
+    #[test]
+    fn test_missing_object() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let hash = FileHash::new(vec![0xAA; 32]);
+        assert!(matches!(
+            open_object_reader(project_root, &hash),
+            Err(MovsError::StorageError(_))
+        ));
+    }
+
+    #[test]
+    fn test_object_path_two_level_fanout() {
+        let hash = FileHash::from_hex("abcdef12").unwrap();
+        let path =
+            get_object_path_with_layout(Path::new("/project"), &hash, 2, ObjectEncoding::Hex)
+                .unwrap();
+
+        assert_eq!(path, PathBuf::from("/project/.movs/objects/ab/cd/ef12"));
+    }
+
+    #[test]
+    fn test_store_and_load_with_two_level_fanout() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        config.fanout_depth = 2;
+        crate::metadata::config::save_config(project_root, &config).unwrap();
+
+        let hash = store_object_bytes(project_root, b"snare").unwrap();
+        let path = get_object_path(project_root, &hash).unwrap();
+        assert_eq!(
+            path,
+            get_object_path_with_layout(project_root, &hash, 2, ObjectEncoding::Hex).unwrap()
+        );
+        assert!(path.is_file());
+        assert_eq!(load_object(project_root, &hash).unwrap(), b"snare");
+        assert_eq!(
+            list_loose_objects(project_root).unwrap(),
+            vec![(hash, path)]
+        );
+    }
+
+    #[test]
+    fn test_changing_fanout_depth_requires_migration() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let hash = store_object_bytes(project_root, b"hat").unwrap();
+
+        let mut config = load_config(project_root).unwrap();
+        config.fanout_depth = 2;
+        assert!(matches!(
+            crate::metadata::config::save_config(project_root, &config),
+            Err(MovsError::ConfigError(_))
+        ));
+
+        assert_eq!(migrate_fanout_depth(project_root, 2).unwrap(), 1);
+        assert_eq!(load_config(project_root).unwrap().fanout_depth, 2);
+        assert!(
+            get_object_path_with_layout(project_root, &hash, 2, ObjectEncoding::Hex)
+                .unwrap()
+                .is_file()
+        );
+        assert!(!get_objects_dir(project_root)
+            .unwrap()
+            .join(&hash.to_hex()[..2])
+            .join(&hash.to_hex()[2..])
+            .exists());
+        assert_eq!(load_object(project_root, &hash).unwrap(), b"hat");
+
+        // Running again is a no-op
+        assert_eq!(migrate_fanout_depth(project_root, 2).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_objects_readable_during_interrupted_migration() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        // Moved to the new depth, but the config was never updated
+        let hash = store_object_bytes(project_root, b"hat").unwrap();
+        let moved =
+            get_object_path_with_layout(project_root, &hash, 2, ObjectEncoding::Hex).unwrap();
+        fs::create_dir_all(moved.parent().unwrap()).unwrap();
+        fs::rename(get_object_path(project_root, &hash).unwrap(), &moved).unwrap();
+
+        assert!(object_exists(project_root, &hash).unwrap());
+        assert_eq!(load_object(project_root, &hash).unwrap(), b"hat");
+        assert_eq!(store_object_bytes(project_root, b"hat").unwrap(), hash);
+        assert!(!get_object_path(project_root, &hash).unwrap().exists());
+    }
+
+    #[test]
+    fn test_invalid_config_is_an_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        let hash = store_object_bytes(project_root, b"hat").unwrap();
+
+        fs::write(crate::metadata::get_config_file(project_root), "{ not json").unwrap();
+        assert!(matches!(
+            store_object_bytes(project_root, b"snare"),
+            Err(MovsError::ConfigError(_))
+        ));
+        assert!(matches!(
+            load_object(project_root, &hash),
+            Err(MovsError::ConfigError(_))
+        ));
+        assert!(object_exists(project_root, &hash).is_err());
+    }
+
+    #[test]
+    fn test_base32_object_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let hex_hash = store_object_bytes(project_root, b"hat").unwrap();
+        let base = store_object_bytes(project_root, &[7u8; 4096]).unwrap();
+        let mut edited = vec![7u8; 4096];
+        edited[100] = 8;
+        let delta_hash = hash_bytes(&edited);
+        assert!(delta::store_delta_object(project_root, &delta_hash, &edited, &base).unwrap());
+
+        let mut config = load_config(project_root).unwrap();
+        config.object_encoding = ObjectEncoding::Base32;
+        assert!(matches!(
+            crate::metadata::config::save_config(project_root, &config),
+            Err(MovsError::ConfigError(_))
+        ));
+
+        assert_eq!(
+            migrate_object_encoding(project_root, ObjectEncoding::Base32).unwrap(),
+            3
+        );
+        let name = encode_object_name(&hex_hash, ObjectEncoding::Base32);
+        let path = get_object_path(project_root, &hex_hash).unwrap();
+        assert_eq!(
+            path,
+            get_objects_dir(project_root)
+                .unwrap()
+                .join(&name[..2])
+                .join(&name[2..])
+        );
+        assert!(path.is_file());
+        assert!(get_delta_path(project_root, &delta_hash).unwrap().is_file());
+
+        // Store, load, listing and gc all agree on the new names
+        let new_hash = store_object_bytes(project_root, b"ride").unwrap();
+        assert!(get_object_path(project_root, &new_hash).unwrap().is_file());
+        assert_eq!(load_object(project_root, &hex_hash).unwrap(), b"hat");
+        assert_eq!(load_object(project_root, &delta_hash).unwrap(), edited);
+        assert_eq!(list_loose_objects(project_root).unwrap().len(), 3);
+        assert_eq!(list_delta_objects(project_root).unwrap(), vec![delta_hash]);
+        assert_eq!(crate::gc::gc(project_root).unwrap().objects_removed, 4);
+
+        assert_eq!(
+            migrate_object_encoding(project_root, ObjectEncoding::Base32).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_invalid_fanout_depth_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        assert!(matches!(
+            migrate_fanout_depth(project_root, 3),
+            Err(MovsError::ConfigError(_))
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::types::{FileHash, HashAlgorithm, ObjectEncoding};
+
+/// RFC 4648 base32 alphabet, lowercased so names are stable on any filesystem
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Encode a hash as an object file name
+///
+/// Hex names are 64 characters for SHA-256; base32 names are 52. Both use
+/// only lowercase letters and digits, so they are safe on case-insensitive
+/// filesystems.
+pub fn encode_object_name(hash: &FileHash, encoding: ObjectEncoding) -> String {
+    match encoding {
+        ObjectEncoding::Hex => hash.to_hex(),
+        ObjectEncoding::Base32 => base32_encode(hash.as_bytes()),
+    }
+}
+
+/// Decode an object file name written with either encoding
+///
+/// The two encodings of a SHA-256 hash differ in length, so a name is
+/// unambiguous and objects are found whichever encoding wrote them. This
+/// lets an interrupted `migrate_object_encoding` simply be run again.
+/// Returns `None` for anything that is not a valid object name.
+pub fn decode_object_name(name: &str) -> Option<FileHash> {
+    if let Ok(hash) = FileHash::from_hex_checked(name, HashAlgorithm::Sha256) {
+        return Some(hash);
+    }
+
+    base32_decode(name)
+        .filter(|bytes| bytes.len() == HashAlgorithm::Sha256.digest_len())
+        .map(FileHash::new)
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+/// Decode unpadded lowercase base32, rejecting non-canonical input
+fn base32_decode(name: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(name.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in name.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+
+    // Leftover bits must be zero padding, so each hash has exactly one name
+    (buffer == 0 && bits < 5).then_some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_bytes;
+
+    #[test]
+    fn test_base32_rfc4648_vectors() {
+        for (input, expected) in [
+            ("", ""),
+            ("f", "my"),
+            ("fo", "mzxq"),
+            ("foo", "mzxw6"),
+            ("foob", "mzxw6yq"),
+            ("fooba", "mzxw6ytb"),
+            ("foobar", "mzxw6ytboi"),
+        ] {
+            assert_eq!(base32_encode(input.as_bytes()), expected);
+            assert_eq!(base32_decode(expected).unwrap(), input.as_bytes());
+        }
+
+        assert_eq!(base32_decode("MZXW6"), None);
+        assert_eq!(base32_decode("mz"), None);
+        assert_eq!(base32_decode("mzxw6yr"), None);
+    }
+
+    #[test]
+    fn test_object_names_roundtrip() {
+        let hash = hash_bytes(b"kick");
+
+        let hex = encode_object_name(&hash, ObjectEncoding::Hex);
+        let base32 = encode_object_name(&hash, ObjectEncoding::Base32);
+        assert_eq!(hex.len(), 64);
+        assert_eq!(base32.len(), 52);
+
+        assert_eq!(decode_object_name(&hex), Some(hash.clone()));
+        assert_eq!(decode_object_name(&base32), Some(hash));
+        assert_eq!(decode_object_name("pack"), None);
+        assert_eq!(decode_object_name(&base32[..51]), None);
+    }
+}
@@ -0,0 +1,437 @@
+use crate::error::{MovsError, Result};
+use crate::gc::GcReport;
+use crate::metadata::config::RepositoryConfig;
+use crate::metadata::get_objects_dir;
+use crate::metadata::lock::lock_for_write;
+use crate::storage::list_loose_objects;
+use crate::types::{FileHash, HashAlgorithm};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tempfile::NamedTempFile;
+
+/// Subdirectory of `objects/` holding pack files and their index
+pub const PACK_DIR: &str = "pack";
+
+/// Pack index file name
+pub const PACK_INDEX_FILE: &str = "index.json";
+
+/// Loose objects up to this size are consolidated into packs
+pub const PACK_THRESHOLD: u64 = 64 * 1024; // 64 KB
+
+/// Location of an object inside a pack file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackLocation {
+    /// Pack file name within the pack directory
+    pub pack: String,
+
+    /// Byte offset of the object within the pack
+    pub offset: u64,
+
+    /// Length of the object in bytes
+    pub len: u64,
+}
+
+/// Index mapping object hashes to their location in pack files
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackIndex {
+    entries: BTreeMap<String, PackLocation>,
+}
+
+impl PackIndex {
+    /// Load the pack index, returning an empty index if none exists yet
+    pub fn load(project_root: &Path) -> Result<Self> {
+        Self::load_in(&get_pack_dir(project_root)?)
+    }
+
+    /// Load the index of the packs in `pack_dir`
+    pub(crate) fn load_in(pack_dir: &Path) -> Result<Self> {
+        let path = pack_dir.join(PACK_INDEX_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Atomically write the pack index
+    pub fn save(&self, project_root: &Path) -> Result<()> {
+        let pack_dir = get_pack_dir(project_root)?;
+        fs::create_dir_all(&pack_dir)?;
+
+        let mut temp = NamedTempFile::new_in(&pack_dir)?;
+        temp.write_all(serde_json::to_string(self)?.as_bytes())?;
+        temp.persist(pack_dir.join(PACK_INDEX_FILE))
+            .map_err(|e| MovsError::Io(e.error))?;
+
+        Ok(())
+    }
+
+    /// Look up where an object is packed
+    pub fn get(&self, hash: &FileHash) -> Option<&PackLocation> {
+        self.entries.get(&hash.to_hex())
+    }
+
+    /// Check if an object is packed
+    pub fn contains(&self, hash: &FileHash) -> bool {
+        self.entries.contains_key(&hash.to_hex())
+    }
+
+    /// Number of packed objects
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if no objects are packed
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over all packed object hashes and their locations
+    pub fn iter(&self) -> impl Iterator<Item = (FileHash, &PackLocation)> {
+        self.entries.iter().filter_map(|(hex, loc)| {
+            FileHash::from_hex_checked(hex, HashAlgorithm::Sha256)
+                .ok()
+                .map(|hash| (hash, loc))
+        })
+    }
+
+    fn insert(&mut self, hash: &FileHash, location: PackLocation) {
+        self.entries.insert(hash.to_hex(), location);
+    }
+}
+
+/// Summary of a packing run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackStats {
+    /// Number of loose objects moved into the new pack
+    pub objects_packed: usize,
+
+    /// Total bytes of object content packed
+    pub bytes_packed: u64,
+
+    /// The pack file written, if any objects were packed
+    pub pack_file: Option<PathBuf>,
+}
+
+/// Get the path to the pack directory
+pub fn get_pack_dir(project_root: &Path) -> Result<PathBuf> {
+    Ok(get_objects_dir(project_root)?.join(PACK_DIR))
+}
+
+/// `get_pack_dir` with an already loaded config
+pub(crate) fn get_pack_dir_with_config(project_root: &Path, config: &RepositoryConfig) -> PathBuf {
+    config.objects_dir_in(project_root).join(PACK_DIR)
+}
+
+/// A pack index loaded on first use and reloaded only when its file changes
+///
+/// Looking an object up then costs one `stat` of the index file rather
+/// than parsing the whole index, which matters when every object of a
+/// restore is looked up in turn.
+#[derive(Debug, Default)]
+pub(crate) struct PackIndexCache {
+    cached: Mutex<Option<(Option<IndexStamp>, Arc<PackIndex>)>>,
+}
+
+/// Size and modification time of a pack index file
+type IndexStamp = (u64, SystemTime);
+
+impl PackIndexCache {
+    /// The current index of the packs in `pack_dir`
+    pub(crate) fn get(&self, pack_dir: &Path) -> Result<Arc<PackIndex>> {
+        let stamp = match fs::metadata(pack_dir.join(PACK_INDEX_FILE)) {
+            Ok(metadata) => Some((metadata.len(), metadata.modified()?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cached_stamp, index)) = cached.as_ref() {
+            if *cached_stamp == stamp {
+                return Ok(Arc::clone(index));
+            }
+        }
+
+        let index = Arc::new(PackIndex::load_in(pack_dir)?);
+        *cached = Some((stamp, Arc::clone(&index)));
+        Ok(index)
+    }
+}
+
+impl Clone for PackIndexCache {
+    fn clone(&self) -> Self {
+        let cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+        Self {
+            cached: Mutex::new(cached.clone()),
+        }
+    }
+}
+
+/// Consolidate small loose objects into a new pack file
+///
+/// Every loose object no larger than `PACK_THRESHOLD` is appended to a new
+/// `.pack` file and recorded in the pack index. The pack is synced to disk
+/// and the index written before any loose object is removed, so a crash at
+/// any point leaves every object readable. The repository lock is held
+/// throughout, so concurrent runs and `gc` never save an index that lacks
+/// each other's objects.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+pub fn pack_objects(project_root: &Path) -> Result<PackStats> {
+    let _lock = lock_for_write(project_root)?;
+    let mut index = PackIndex::load(project_root)?;
+    let mut candidates = Vec::new();
+
+    for (hash, path) in list_loose_objects(project_root)? {
+        if !index.contains(&hash) && fs::metadata(&path)?.len() <= PACK_THRESHOLD {
+            candidates.push((hash, path));
+        }
+    }
+
+    if candidates.is_empty() {
+        return Ok(PackStats::default());
+    }
+
+    let pack_dir = get_pack_dir(project_root)?;
+    fs::create_dir_all(&pack_dir)?;
+
+    let (pack_name, mut pack) = create_pack(&pack_dir)?;
+    let pack_path = pack_dir.join(&pack_name);
+    let mut stats = PackStats::default();
+    let mut offset = 0;
+
+    for (hash, path) in &candidates {
+        let content = fs::read(path)?;
+        pack.write_all(&content)?;
+
+        let len = content.len() as u64;
+        index.insert(
+            hash,
+            PackLocation {
+                pack: pack_name.clone(),
+                offset,
+                len,
+            },
+        );
+
+        offset += len;
+        stats.objects_packed += 1;
+        stats.bytes_packed += len;
+    }
+
+    pack.sync_all()?;
+    index.save(project_root)?;
+
+    for (_, path) in &candidates {
+        fs::remove_file(path)?;
+    }
+
+    stats.pack_file = Some(pack_path);
+    Ok(stats)
+}
+
+/// Rewrite the packs holding any of `dead`, leaving those objects out
+///
+/// The live objects of every affected pack are copied into one new pack,
+/// which is synced before the index is switched over to it; only then are
+/// the old packs deleted. A crash at any point leaves every live object
+/// readable. The caller holds the repository lock.
+pub(crate) fn remove_packed(project_root: &Path, dead: &HashSet<FileHash>) -> Result<GcReport> {
+    let mut index = PackIndex::load(project_root)?;
+    let affected: BTreeSet<String> = index
+        .iter()
+        .filter(|(hash, _)| dead.contains(hash))
+        .map(|(_, location)| location.pack.clone())
+        .collect();
+    if affected.is_empty() {
+        return Ok(GcReport::default());
+    }
+
+    let pack_dir = get_pack_dir(project_root)?;
+    let mut report = GcReport::default();
+    let mut kept = Vec::new();
+    for (hash, location) in index.iter() {
+        if !affected.contains(&location.pack) {
+            continue;
+        }
+        if dead.contains(&hash) {
+            report.objects_removed += 1;
+            report.bytes_freed += location.len;
+        } else {
+            kept.push((hash, location.clone()));
+        }
+    }
+    index
+        .entries
+        .retain(|_, location| !affected.contains(&location.pack));
+
+    if !kept.is_empty() {
+        let (pack_name, mut pack) = create_pack(&pack_dir)?;
+        let mut offset = 0;
+
+        for (hash, location) in kept {
+            let len = io::copy(&mut open_packed_object(&pack_dir, &location)?, &mut pack)?;
+            if len != location.len {
+                return Err(MovsError::StorageError(format!(
+                    "Pack file '{}' is truncated",
+                    location.pack
+                )));
+            }
+            index.insert(
+                &hash,
+                PackLocation {
+                    pack: pack_name.clone(),
+                    offset,
+                    len,
+                },
+            );
+            offset += len;
+        }
+
+        pack.sync_all()?;
+    }
+    index.save(project_root)?;
+
+    for pack in affected {
+        match fs::remove_file(pack_dir.join(pack)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Open a reader over a packed object's bytes
+pub(crate) fn open_packed_object(pack_dir: &Path, location: &PackLocation) -> Result<impl Read> {
+    let mut file = File::open(pack_dir.join(&location.pack))?;
+    file.seek(SeekFrom::Start(location.offset))?;
+
+    Ok(BufReader::new(file).take(location.len))
+}
+
+/// Create a new, empty pack file, returning its name
+///
+/// Packs are numbered one past the highest existing number, so a pack
+/// removed by `gc` never frees a name that is still in use, and an
+/// existing pack is never opened for writing.
+fn create_pack(pack_dir: &Path) -> Result<(String, File)> {
+    let mut highest = 0;
+    for entry in fs::read_dir(pack_dir)? {
+        let name = entry?.file_name();
+        let number = name.to_str().and_then(|name| {
+            name.strip_prefix("pack-")?
+                .strip_suffix(".pack")?
+                .parse()
+                .ok()
+        });
+        highest = highest.max(number.unwrap_or(0));
+    }
+
+    let name = format!("pack-{:06}.pack", highest + 1);
+    let file = File::options()
+        .write(true)
+        .create_new(true)
+        .open(pack_dir.join(&name))?;
+    Ok((name, file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::storage::{get_object_path, load_object, object_exists, store_object_bytes};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pack_small_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let small: Vec<_> = (0..20)
+            .map(|i| store_object_bytes(project_root, format!("midi {}", i).as_bytes()).unwrap())
+            .collect();
+        let large =
+            store_object_bytes(project_root, &vec![7u8; PACK_THRESHOLD as usize + 1]).unwrap();
+
+        let stats = pack_objects(project_root).unwrap();
+        assert_eq!(stats.objects_packed, 20);
+        assert!(stats.pack_file.unwrap().exists());
+
+        // Small objects are no longer loose but still readable
+        for (i, hash) in small.iter().enumerate() {
+            assert!(!get_object_path(project_root, hash).unwrap().exists());
+            assert!(object_exists(project_root, hash).unwrap());
+            assert_eq!(
+                load_object(project_root, hash).unwrap(),
+                format!("midi {}", i).as_bytes()
+            );
+        }
+
+        // Large objects stay loose
+        assert!(get_object_path(project_root, &large).unwrap().exists());
+        assert_eq!(
+            load_object(project_root, &large).unwrap().len(),
+            PACK_THRESHOLD as usize + 1
+        );
+    }
+
+    #[test]
+    fn test_pack_is_incremental() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let first = store_object_bytes(project_root, b"first").unwrap();
+        assert_eq!(pack_objects(project_root).unwrap().objects_packed, 1);
+
+        // Nothing new to pack
+        assert_eq!(pack_objects(project_root).unwrap(), PackStats::default());
+
+        let second = store_object_bytes(project_root, b"second").unwrap();
+        let stats = pack_objects(project_root).unwrap();
+        assert_eq!(stats.objects_packed, 1);
+
+        let index = PackIndex::load(project_root).unwrap();
+        assert_eq!(index.len(), 2);
+        assert_ne!(
+            index.get(&first).unwrap().pack,
+            index.get(&second).unwrap().pack
+        );
+        assert_eq!(load_object(project_root, &first).unwrap(), b"first");
+        assert_eq!(load_object(project_root, &second).unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_pack_names_are_not_reused() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+
+        let first = store_object_bytes(project_root, b"first").unwrap();
+        let first_pack = pack_objects(project_root).unwrap().pack_file.unwrap();
+        let second = store_object_bytes(project_root, b"second").unwrap();
+        pack_objects(project_root).unwrap();
+
+        // Dropping the only object of the first pack deletes that pack
+        let report = remove_packed(project_root, &HashSet::from([first])).unwrap();
+        assert_eq!(report.objects_removed, 1);
+        assert!(!first_pack.exists());
+
+        // Counting packs would now pick the second pack's name again
+        let third = store_object_bytes(project_root, b"third").unwrap();
+        let stats = pack_objects(project_root).unwrap();
+        assert!(stats.pack_file.unwrap().ends_with("pack-000003.pack"));
+        assert_eq!(load_object(project_root, &second).unwrap(), b"second");
+        assert_eq!(load_object(project_root, &third).unwrap(), b"third");
+    }
+}
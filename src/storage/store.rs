@@ -0,0 +1,355 @@
+use crate::error::{MovsError, Result};
+use crate::gc::remove_unreachable;
+use crate::hash::hash_bytes;
+use crate::metadata::config::RepositoryConfig;
+use crate::storage::delta::{list_delta_objects, store_delta_file};
+use crate::storage::pack::{get_pack_dir_with_config, PackIndex, PackIndexCache};
+use crate::storage::{
+    find_loose_object, is_compressed_object, is_linked_object, list_loose_objects, load_settings,
+    object_exists_in, open_object_reader_in, store_object_bytes_with_config,
+    store_object_with_config,
+};
+use crate::tree::path::fs_path;
+use crate::types::FileHash;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// A backend that stores content-addressed objects
+///
+/// Snapshot logic only needs the first four operations, so objects can live
+/// on the local filesystem, in memory, or in a remote blob store. The
+/// others have defaults built on those four; stores override them to
+/// stream large files or to avoid copies.
+pub trait ObjectStore: Send + Sync {
+    /// Store content and return its hash; storing existing content is a no-op
+    fn put(&self, content: &[u8]) -> Result<FileHash>;
+
+    /// Read back the content stored under `hash`
+    fn get(&self, hash: &FileHash) -> Result<Vec<u8>>;
+
+    /// Check if an object is present
+    fn contains(&self, hash: &FileHash) -> bool;
+
+    /// Iterate over the hashes of every stored object
+    fn iter_hashes(&self) -> Result<Box<dyn Iterator<Item = FileHash> + '_>>;
+
+    /// Store the content of the file at `path`, already hashed as `hash`
+    ///
+    /// `base` is a stored object holding an earlier version of the file,
+    /// which stores supporting deltas may encode the content against. The
+    /// default reads the whole file and fails with
+    /// `MovsError::FileChangedDuringRead` if it no longer hashes to `hash`.
+    fn put_file(&self, hash: &FileHash, path: &Path, base: Option<&FileHash>) -> Result<()> {
+        let _ = base;
+        if self.put(&fs::read(fs_path(path)?)?)? != *hash {
+            return Err(MovsError::FileChangedDuringRead(path.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    /// Open a reader over the content stored under `hash`
+    fn open(&self, hash: &FileHash) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(io::Cursor::new(self.get(hash)?)))
+    }
+
+    /// Path of a local file holding exactly the content stored under `hash`
+    ///
+    /// Restores clone such files instead of copying them, and trust their
+    /// content without hashing it unless asked to verify. The default has
+    /// no such files.
+    fn local_path(&self, hash: &FileHash) -> Result<Option<PathBuf>> {
+        let _ = hash;
+        Ok(None)
+    }
+
+    /// Delete every object not in `live`, returning how many were removed
+    ///
+    /// The default cannot delete objects and fails.
+    fn retain(&self, live: &HashSet<FileHash>) -> Result<usize> {
+        let _ = live;
+        Err(MovsError::StorageError(
+            "This object store does not support deleting objects".to_string(),
+        ))
+    }
+}
+
+/// The default object store: loose files and packs under `.movs/objects`
+#[derive(Debug, Clone)]
+pub struct FsObjectStore {
+    project_root: PathBuf,
+    config: RepositoryConfig,
+    packs: PackIndexCache,
+}
+
+impl FsObjectStore {
+    /// Create a store for the repository at `project_root`
+    ///
+    /// The repository config, which decides the object layout, compression
+    /// and delta encoding, is read once here; a config that cannot be read
+    /// is an error. The pack index is cached and only reloaded when its
+    /// file changes.
+    pub fn new(project_root: &Path) -> Result<Self> {
+        Ok(Self {
+            project_root: project_root.to_path_buf(),
+            config: load_settings(project_root)?,
+            packs: PackIndexCache::default(),
+        })
+    }
+
+    fn pack_index(&self) -> Result<Arc<PackIndex>> {
+        self.packs
+            .get(&get_pack_dir_with_config(&self.project_root, &self.config))
+    }
+}
+
+impl ObjectStore for FsObjectStore {
+    fn put(&self, content: &[u8]) -> Result<FileHash> {
+        store_object_bytes_with_config(&self.project_root, content, &self.config)
+    }
+
+    fn get(&self, hash: &FileHash) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+        self.open(hash)?.read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    fn contains(&self, hash: &FileHash) -> bool {
+        let packs = self.pack_index().unwrap_or_default();
+        object_exists_in(&self.project_root, hash, &self.config, &packs)
+    }
+
+    fn put_file(&self, hash: &FileHash, path: &Path, base: Option<&FileHash>) -> Result<()> {
+        match base {
+            Some(base) if self.config.delta_objects => {
+                store_delta_file(&self.project_root, hash, path, base, &self.config)?;
+            }
+            _ => {
+                store_object_with_config(&self.project_root, hash, path, &self.config)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn open(&self, hash: &FileHash) -> Result<Box<dyn Read + '_>> {
+        let packs = self.pack_index()?;
+        Ok(Box::new(open_object_reader_in(
+            &self.project_root,
+            hash,
+            &self.config,
+            &packs,
+        )?))
+    }
+
+    /// Plain loose objects only: packed, delta, compressed and hardlinked
+    /// objects are not stored as their content
+    fn local_path(&self, hash: &FileHash) -> Result<Option<PathBuf>> {
+        if self.pack_index()?.contains(hash) {
+            return Ok(None);
+        }
+
+        let Some(path) = find_loose_object(&self.project_root, hash, &self.config) else {
+            return Ok(None);
+        };
+        let path = fs_path(&path)?;
+        match File::open(&path) {
+            Ok(mut file) => {
+                let plain = !is_compressed_object(&mut file)? && !is_linked_object(&file)?;
+                Ok(plain.then_some(path))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(MovsError::Io(e)),
+        }
+    }
+
+    /// Keeps the delta bases of live objects as well; fails with
+    /// `MovsError::ConfigError` for an external `objects_dir`, like `gc`
+    fn retain(&self, live: &HashSet<FileHash>) -> Result<usize> {
+        Ok(remove_unreachable(&self.project_root, live.iter().cloned())?.objects_removed)
+    }
+
+    fn iter_hashes(&self) -> Result<Box<dyn Iterator<Item = FileHash> + '_>> {
+        let loose = list_loose_objects(&self.project_root)?
+            .into_iter()
+            .map(|(hash, _)| hash);
+        let packed: Vec<_> = self.pack_index()?.iter().map(|(hash, _)| hash).collect();
+        let deltas = list_delta_objects(&self.project_root)?;
+
+        Ok(Box::new(loose.chain(packed).chain(deltas)))
+    }
+}
+
+/// An object store kept entirely in memory, mainly for tests
+#[derive(Debug, Default)]
+pub struct MemoryObjectStore {
+    objects: RwLock<HashMap<FileHash, Vec<u8>>>,
+}
+
+impl MemoryObjectStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ObjectStore for MemoryObjectStore {
+    fn put(&self, content: &[u8]) -> Result<FileHash> {
+        let hash = hash_bytes(content);
+        self.objects
+            .write()
+            .map_err(|_| MovsError::StorageError("Object store lock poisoned".to_string()))?
+            .entry(hash.clone())
+            .or_insert_with(|| content.to_vec());
+        Ok(hash)
+    }
+
+    fn get(&self, hash: &FileHash) -> Result<Vec<u8>> {
+        self.objects
+            .read()
+            .map_err(|_| MovsError::StorageError("Object store lock poisoned".to_string()))?
+            .get(hash)
+            .cloned()
+            .ok_or_else(|| MovsError::StorageError(format!("Object not found: {}", hash.to_hex())))
+    }
+
+    fn contains(&self, hash: &FileHash) -> bool {
+        self.objects
+            .read()
+            .is_ok_and(|objects| objects.contains_key(hash))
+    }
+
+    fn iter_hashes(&self) -> Result<Box<dyn Iterator<Item = FileHash> + '_>> {
+        let hashes: Vec<_> = self
+            .objects
+            .read()
+            .map_err(|_| MovsError::StorageError("Object store lock poisoned".to_string()))?
+            .keys()
+            .cloned()
+            .collect();
+
+        Ok(Box::new(hashes.into_iter()))
+    }
+
+    fn retain(&self, live: &HashSet<FileHash>) -> Result<usize> {
+        let mut objects = self
+            .objects
+            .write()
+            .map_err(|_| MovsError::StorageError("Object store lock poisoned".to_string()))?;
+        let before = objects.len();
+        objects.retain(|hash, _| live.contains(hash));
+        Ok(before - objects.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::storage::pack::pack_objects;
+    use tempfile::TempDir;
+
+    fn exercise(store: &dyn ObjectStore, dir: &Path) {
+        let hash = store.put(b"kick").unwrap();
+        assert_eq!(store.put(b"kick").unwrap(), hash);
+        assert!(store.contains(&hash));
+        assert_eq!(store.get(&hash).unwrap(), b"kick");
+
+        let missing = hash_bytes(b"missing");
+        assert!(!store.contains(&missing));
+        assert!(store.get(&missing).is_err());
+
+        let file = dir.join("snare.wav");
+        fs::write(&file, b"snare").unwrap();
+        let snare = hash_bytes(b"snare");
+        store.put_file(&snare, &file, None).unwrap();
+        let mut content = Vec::new();
+        store
+            .open(&snare)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, b"snare");
+
+        assert_eq!(store.retain(&HashSet::from([hash.clone()])).unwrap(), 1);
+        assert!(store.contains(&hash));
+        assert!(!store.contains(&snare));
+    }
+
+    #[test]
+    fn test_fs_object_store() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repository(temp_dir.path()).unwrap();
+
+        exercise(
+            &FsObjectStore::new(temp_dir.path()).unwrap(),
+            temp_dir.path(),
+        );
+    }
+
+    #[test]
+    fn test_memory_object_store() {
+        let temp_dir = TempDir::new().unwrap();
+        exercise(&MemoryObjectStore::new(), temp_dir.path());
+    }
+
+    #[test]
+    fn test_put_file_checks_the_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("snare.wav");
+        fs::write(&file, b"re-bounced").unwrap();
+
+        let result = MemoryObjectStore::new().put_file(&hash_bytes(b"snare"), &file, None);
+        assert!(matches!(result, Err(MovsError::FileChangedDuringRead(_))));
+    }
+
+    #[test]
+    fn test_fs_local_path_only_for_plain_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repository(temp_dir.path()).unwrap();
+
+        let store = FsObjectStore::new(temp_dir.path()).unwrap();
+        let packed = store.put(b"packed").unwrap();
+        pack_objects(temp_dir.path()).unwrap();
+        let loose = store.put(b"loose").unwrap();
+
+        let path = store.local_path(&loose).unwrap().unwrap();
+        assert_eq!(fs::read(path).unwrap(), b"loose");
+        assert!(store.local_path(&packed).unwrap().is_none());
+        assert!(store.local_path(&hash_bytes(b"missing")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_fs_store_reloads_changed_pack_index() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repository(temp_dir.path()).unwrap();
+
+        let store = FsObjectStore::new(temp_dir.path()).unwrap();
+        let first = store.put(b"first").unwrap();
+        assert!(store.contains(&first));
+
+        // Packing behind the store's back moves the object out of its cached view
+        pack_objects(temp_dir.path()).unwrap();
+        assert!(store.contains(&first));
+        assert_eq!(store.get(&first).unwrap(), b"first");
+
+        let second = store.put(b"second").unwrap();
+        pack_objects(temp_dir.path()).unwrap();
+        assert_eq!(store.get(&second).unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_fs_iter_hashes_includes_packed() {
+        let temp_dir = TempDir::new().unwrap();
+        init_repository(temp_dir.path()).unwrap();
+
+        let store = FsObjectStore::new(temp_dir.path()).unwrap();
+        let packed = store.put(b"packed").unwrap();
+        pack_objects(temp_dir.path()).unwrap();
+        let loose = store.put(b"loose").unwrap();
+
+        let hashes: HashSet<_> = store.iter_hashes().unwrap().collect();
+        assert_eq!(hashes, HashSet::from([packed, loose]));
+    }
+}
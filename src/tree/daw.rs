@@ -0,0 +1,117 @@
+use crate::error::Result;
+use crate::tree::ignore::IgnoreRules;
+use std::fs;
+use std::path::Path;
+
+/// A digital audio workstation whose project layout MOVS knows about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DawKind {
+    /// Ableton Live (`.als` sets)
+    Ableton,
+
+    /// Logic Pro (`.logicx` packages)
+    Logic,
+
+    /// Pro Tools (`.ptx` / `.ptf` sessions)
+    ProTools,
+}
+
+impl DawKind {
+    /// Every supported DAW
+    pub const ALL: [DawKind; 3] = [DawKind::Ableton, DawKind::Logic, DawKind::ProTools];
+
+    /// File extensions of this DAW's project files, lowercase
+    fn project_extensions(self) -> &'static [&'static str] {
+        match self {
+            DawKind::Ableton => &["als"],
+            DawKind::Logic => &["logicx", "logic"],
+            DawKind::ProTools => &["ptx", "ptf"],
+        }
+    }
+}
+
+/// Built-in ignore patterns for files a DAW regenerates on its own
+///
+/// These cover waveform caches, automatic backups and freeze renders, which
+/// can dwarf the actual project and are rebuilt when missing. Backup
+/// folders are anchored to the project root, where the DAW writes them, so
+/// a user's own `Samples/Backup/` is still snapshotted.
+pub fn default_ignores_for(daw: DawKind) -> Vec<String> {
+    let patterns: &[&str] = match daw {
+        DawKind::Ableton => &["/Backup/", "*.asd"],
+        DawKind::Logic => &["Freeze Files/", "Freeze Files.nosync/"],
+        DawKind::ProTools => &["WaveCache.wfm", "/Session File Backups/"],
+    };
+
+    patterns.iter().map(|p| p.to_string()).collect()
+}
+
+/// Detect which DAWs have project files directly in `root`
+///
+/// Returns each DAW at most once, in `DawKind::ALL` order.
+pub fn detect_daws(root: &Path) -> Result<Vec<DawKind>> {
+    let mut found = Vec::new();
+
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+            continue;
+        };
+        let ext = ext.to_ascii_lowercase();
+
+        for daw in DawKind::ALL {
+            if daw.project_extensions().contains(&ext.as_str()) && !found.contains(&daw) {
+                found.push(daw);
+            }
+        }
+    }
+
+    found.sort_by_key(|daw| DawKind::ALL.iter().position(|d| d == daw));
+    Ok(found)
+}
+
+/// Add the default ignores for every DAW detected in `root`
+pub(crate) fn add_detected_ignores(rules: &mut IgnoreRules, root: &Path) -> Result<()> {
+    for daw in detect_daws(root)? {
+        for pattern in default_ignores_for(daw) {
+            rules.add_pattern(&pattern);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_daws() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        assert!(detect_daws(root).unwrap().is_empty());
+
+        fs::write(root.join("Session.PTX"), b"").unwrap();
+        fs::write(root.join("song.als"), b"").unwrap();
+        fs::write(root.join("song v2.als"), b"").unwrap();
+
+        assert_eq!(
+            detect_daws(root).unwrap(),
+            vec![DawKind::Ableton, DawKind::ProTools]
+        );
+    }
+
+    #[test]
+    fn test_default_ignores_match_caches() {
+        let mut rules = IgnoreRules::new();
+        for pattern in default_ignores_for(DawKind::Ableton) {
+            rules.add_pattern(&pattern);
+        }
+
+        assert!(rules.is_ignored(Path::new("Samples/kick.wav.asd"), false));
+        assert!(rules.is_ignored(Path::new("Backup"), true));
+        assert!(!rules.is_ignored(Path::new("Samples/Backup"), true));
+        assert!(!rules.is_ignored(Path::new("Samples/kick.wav"), false));
+    }
+}
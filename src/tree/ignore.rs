@@ -0,0 +1,226 @@
+use crate::error::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the per-project ignore file
+pub const IGNORE_FILE: &str = ".movsignore";
+
+/// Ignore rules from `.movsignore` files and built-in patterns
+///
+/// Patterns follow `.gitignore` syntax exactly, as implemented by the
+/// `ignore` crate: `#` comments, `!` negation, `*`, `**` and `?`, a
+/// trailing `/` for directories only, and a leading or inner `/` to anchor
+/// a pattern to the directory holding the ignore file. Precedence also
+/// matches git: a `.movsignore` in a subdirectory overrides its parents,
+/// later lines override earlier ones, and patterns added with
+/// `add_pattern` (such as the built-in DAW ignores) rank below every file.
+#[derive(Debug, Clone)]
+pub struct IgnoreRules {
+    /// Lines added with `add_pattern`, kept to rebuild `patterns`
+    lines: Vec<String>,
+
+    /// Matcher for `lines`, relative to the project root
+    patterns: Gitignore,
+
+    /// Matchers for ignore files with their directory, deepest first
+    files: Vec<(PathBuf, Gitignore)>,
+}
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            patterns: Gitignore::empty(),
+            files: Vec::new(),
+        }
+    }
+}
+
+impl IgnoreRules {
+    /// Create an empty rule set that ignores nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse rules from the contents of an ignore file
+    pub fn parse(content: &str) -> Self {
+        let mut rules = Self::new();
+        for line in content.lines() {
+            rules.add_pattern(line);
+        }
+        rules
+    }
+
+    /// Load rules from `.movsignore` in the project root, if present
+    ///
+    /// Ignore files in subdirectories are picked up by the working tree
+    /// walk as it enters each directory; see `add_ignore_file`.
+    pub fn load(project_root: &Path) -> Result<Self> {
+        let mut rules = Self::new();
+        rules.load_ignore_file(project_root, Path::new(""))?;
+        Ok(rules)
+    }
+
+    /// Add a single pattern line, relative to the project root
+    ///
+    /// Lines that are not valid patterns are skipped.
+    pub fn add_pattern(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+
+        self.lines.push(line.to_string());
+        match build_matcher(self.lines.iter().map(String::as_str)) {
+            Some(patterns) => self.patterns = patterns,
+            None => {
+                self.lines.pop();
+            }
+        }
+    }
+
+    /// Add the contents of an ignore file found in `dir`, relative to the root
+    ///
+    /// Its patterns apply to paths under `dir` and take precedence over
+    /// files in parent directories.
+    pub fn add_ignore_file(&mut self, dir: &Path, content: &str) {
+        let Some(matcher) = build_matcher(content.lines()) else {
+            return;
+        };
+        if matcher.is_empty() {
+            return;
+        }
+
+        let depth = dir.components().count();
+        let at = self
+            .files
+            .iter()
+            .position(|(other, _)| other.components().count() < depth)
+            .unwrap_or(self.files.len());
+        self.files.insert(at, (dir.to_path_buf(), matcher));
+    }
+
+    /// Read `dir`'s `.movsignore`, if any, into these rules
+    pub(crate) fn load_ignore_file(&mut self, project_root: &Path, dir: &Path) -> Result<()> {
+        let path = project_root.join(dir).join(IGNORE_FILE);
+        if path.is_file() {
+            self.add_ignore_file(dir, &fs::read_to_string(path)?);
+        }
+        Ok(())
+    }
+
+    /// Check if there are no rules
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.files.is_empty()
+    }
+
+    /// Check if a path relative to the project root is ignored
+    ///
+    /// A path inside an ignored directory is ignored too.
+    pub fn is_ignored(&self, relative_path: &Path, is_dir: bool) -> bool {
+        for (dir, matcher) in &self.files {
+            let Ok(path) = relative_path.strip_prefix(dir) else {
+                continue;
+            };
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+
+            match matcher.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+
+        self.patterns
+            .matched_path_or_any_parents(relative_path, is_dir)
+            .is_ignore()
+    }
+}
+
+/// Build a matcher from gitignore lines, or `None` if a line is invalid
+///
+/// The matcher is rooted at `.`, so paths are matched as given, relative
+/// to the directory the lines apply to.
+fn build_matcher<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(".");
+    for line in lines {
+        // Skip invalid lines individually, like git does
+        let _ = builder.add_line(None, line);
+    }
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitignore_semantics() {
+        let rules = IgnoreRules::parse(
+            "*.wav\n\
+             !keep.wav\n\
+             Samples/*.aif\n\
+             **/cache\n",
+        );
+
+        assert!(rules.is_ignored(Path::new("Bounces/mix.wav"), false));
+        assert!(!rules.is_ignored(Path::new("Bounces/keep.wav"), false));
+        assert!(rules.is_ignored(Path::new("Samples/kick.aif"), false));
+        assert!(!rules.is_ignored(Path::new("Samples/Drums/kick.aif"), false));
+        assert!(rules.is_ignored(Path::new("a/b/cache"), true));
+        assert!(rules.is_ignored(Path::new("cache"), true));
+        // Everything inside an ignored directory is ignored
+        assert!(rules.is_ignored(Path::new("a/cache/data.bin"), false));
+    }
+
+    #[test]
+    fn test_nested_ignore_files() {
+        let mut rules = IgnoreRules::parse("*.asd\n");
+        rules.add_ignore_file(Path::new(""), "*.tmp\n!kick.asd\n");
+        rules.add_ignore_file(Path::new("Song A"), "/Renders/\n!*.tmp\n");
+
+        // The root file beats built-in patterns
+        assert!(rules.is_ignored(Path::new("snare.asd"), false));
+        assert!(!rules.is_ignored(Path::new("kick.asd"), false));
+
+        // The nested file beats the root file, and is anchored to its directory
+        assert!(rules.is_ignored(Path::new("Song B/take.tmp"), false));
+        assert!(!rules.is_ignored(Path::new("Song A/take.tmp"), false));
+        assert!(rules.is_ignored(Path::new("Song A/Renders"), true));
+        assert!(!rules.is_ignored(Path::new("Renders"), true));
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let rules = IgnoreRules::parse(
+            "# Ableton analysis files\n\
+             *.asd\n\
+             \n\
+             Backup/\n\
+             /Renders/draft.wav\n",
+        );
+
+        assert!(rules.is_ignored(Path::new("kick.asd"), false));
+        assert!(rules.is_ignored(Path::new("Samples/kick.asd"), false));
+        assert!(!rules.is_ignored(Path::new("kick.wav"), false));
+
+        // Directory-only pattern
+        assert!(rules.is_ignored(Path::new("Backup"), true));
+        assert!(!rules.is_ignored(Path::new("Backup"), false));
+
+        // Anchored pattern
+        assert!(rules.is_ignored(Path::new("Renders/draft.wav"), false));
+        assert!(!rules.is_ignored(Path::new("Old/Renders/draft.wav"), false));
+    }
+
+    #[test]
+    fn test_empty_rules() {
+        let rules = IgnoreRules::parse("# only a comment\n\n");
+        assert!(rules.is_empty());
+        assert!(!rules.is_ignored(Path::new("anything"), false));
+    }
+}
@@ -0,0 +1,1243 @@
+use crate::diff::diff_entries;
+use crate::error::{MovsError, Result};
+use crate::hash::{hash_bytes, hash_files_parallel};
+use crate::metadata::config::{default_max_file_size, load_config};
+use crate::metadata::persistence::load_snapshot;
+use crate::metadata::refs::read_head;
+use crate::metadata::{get_movs_dir, repository_exists, MOVS_DIR};
+use crate::types::{
+    classify_path, EntryKind, FileEntry, FileHash, SnapshotDiff, SnapshotId, SnapshotMetadata,
+};
+use ::ignore::{DirEntry, WalkBuilder, WalkState};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+pub mod daw;
+pub mod ignore;
+pub mod path;
+pub(crate) mod quick_cache;
+
+pub use daw::{default_ignores_for, detect_daws, DawKind};
+pub use ignore::{IgnoreRules, IGNORE_FILE};
+pub use path::normalize_relative;
+use path::{fs_path, fs_walk_root};
+
+use quick_cache::QuickHashCache;
+
+/// Result of a scan that skips unreadable files instead of failing
+#[derive(Debug, Default)]
+pub struct ScanOutcome {
+    /// Entries that were hashed successfully, sorted by path
+    pub entries: Vec<FileEntry>,
+
+    /// Paths that were skipped, relative to the root, with the reason
+    pub errors: Vec<(PathBuf, MovsError)>,
+}
+
+impl ScanOutcome {
+    /// Check if every tracked file was scanned
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Options controlling how a working tree is traversed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Dereference symlinks and record the content they point to
+    ///
+    /// Useful for shared sample libraries linked into a project. Symlink
+    /// cycles are detected and reported as `MovsError::StorageError`.
+    /// Dangling links are still recorded as symlinks. When false (the
+    /// default), symlinks are recorded as links and never followed.
+    pub follow_symlinks: bool,
+
+    /// Record directories with no tracked files as `EntryKind::Directory`
+    ///
+    /// Some DAWs expect folders such as `Recorded/` to exist even when
+    /// empty. A directory whose only contents are ignored counts as empty.
+    /// Directories that contain tracked files are implied by their files
+    /// and never recorded.
+    pub track_empty_dirs: bool,
+}
+
+/// Time spent in each phase of a working tree scan
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ScanTiming {
+    /// Directory traversal and stat calls
+    pub walk: Duration,
+
+    /// Reading and hashing file content
+    pub hash: Duration,
+}
+
+/// The scanned working tree of a project
+///
+/// Centralizes the traversal policy shared by everything that walks a
+/// project: the `.movs` directory is always skipped and `.movsignore`
+/// rules are honored, including files in subdirectories. Directories are
+/// walked in parallel by the `ignore` crate's walker.
+#[derive(Debug, Clone)]
+pub struct WorkingTree {
+    root: PathBuf,
+    ignore: IgnoreRules,
+    options: ScanOptions,
+    scope: Option<PathBuf>,
+    max_file_size: Option<u64>,
+}
+
+impl WorkingTree {
+    /// Open the working tree at `root`, loading its `.movsignore` if present
+    ///
+    /// Built-in ignores for any DAW detected in `root` (see
+    /// `default_ignores_for`) are added on top, unless the repository config
+    /// sets `daw_default_ignores` to false. Directories that are not
+    /// repositories always get them. The file size limit is also taken from
+    /// the config.
+    pub fn open(root: &Path) -> Result<Self> {
+        let mut ignore = IgnoreRules::load(root)?;
+        let config = load_config(root).ok();
+
+        if config
+            .as_ref()
+            .is_none_or(|config| config.daw_default_ignores)
+        {
+            daw::add_detected_ignores(&mut ignore, root)?;
+        }
+
+        let mut tree = Self::with_ignore_rules(root, ignore);
+        if let Some(config) = config {
+            tree.max_file_size = config.max_file_size;
+        }
+        Ok(tree)
+    }
+
+    /// Create a working tree with explicit ignore rules
+    pub fn with_ignore_rules(root: &Path, ignore: IgnoreRules) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            ignore,
+            options: ScanOptions::default(),
+            scope: None,
+            max_file_size: default_max_file_size(),
+        }
+    }
+
+    /// Refuse to hash files larger than `limit` bytes, or lift the limit with `None`
+    pub fn with_max_file_size(mut self, limit: Option<u64>) -> Self {
+        self.max_file_size = limit;
+        self
+    }
+
+    /// Use the given traversal options
+    pub fn with_scan_options(mut self, options: ScanOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Only walk the given subdirectory of the root
+    ///
+    /// Paths are still reported relative to the root. Fails with
+    /// `MovsError::InvalidPath` if the scope is outside the root, inside
+    /// `.movs`, or not an existing directory.
+    pub fn with_scope(mut self, scope: &Path) -> Result<Self> {
+        let scope = path::normalize_scope(&self.root, scope)?;
+        if !self.root.join(&scope).is_dir() {
+            return Err(MovsError::InvalidPath(scope));
+        }
+        self.scope = Some(scope);
+        Ok(self)
+    }
+
+    /// Get the root directory of the tree
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the subdirectory traversal is limited to, relative to the root
+    pub fn scope(&self) -> Option<&Path> {
+        self.scope.as_deref()
+    }
+
+    /// Get the ignore rules applied during traversal
+    pub fn ignore_rules(&self) -> &IgnoreRules {
+        &self.ignore
+    }
+
+    /// List all tracked files and symlinks as paths relative to the root, sorted
+    ///
+    /// Symlinks are only followed if `ScanOptions::follow_symlinks` is set.
+    pub fn entries(&self) -> Result<Vec<PathBuf>> {
+        self.walk(None)
+    }
+
+    /// Hash every tracked file in parallel
+    ///
+    /// Regular files are hashed by content. Unless following is enabled,
+    /// symlinks are not followed; their hash is computed over the link's
+    /// target path instead. Entries are returned sorted by relative path.
+    pub fn hash_all(&self) -> Result<Vec<FileEntry>> {
+        Ok(self
+            .scan(false, &mut ScanTiming::default(), None, None)?
+            .entries)
+    }
+
+    /// `hash_all`, also reporting how long traversal and hashing took
+    pub(crate) fn hash_all_timed(&self) -> Result<(Vec<FileEntry>, ScanTiming)> {
+        let mut timing = ScanTiming::default();
+        let entries = self.scan(false, &mut timing, None, None)?.entries;
+        Ok((entries, timing))
+    }
+
+    /// Hash every tracked file, collecting per-file errors instead of failing
+    ///
+    /// Files or directories that cannot be read (e.g. locked by another
+    /// program or lacking permissions) are skipped and reported in
+    /// `ScanOutcome::errors` together with the reason.
+    pub fn hash_all_lenient(&self) -> Result<ScanOutcome> {
+        self.scan(true, &mut ScanTiming::default(), None, None)
+    }
+
+    /// Hash only the given paths, exactly as `hash_all` would hash them
+    ///
+    /// Paths are relative to the root. Directories are walked, and paths
+    /// that no longer exist, are ignored or lie outside the scope are left
+    /// out, so the result is what `hash_all` returns for those paths.
+    pub fn hash_paths(&self, paths: &[PathBuf]) -> Result<Vec<FileEntry>> {
+        Ok(self
+            .scan(false, &mut ScanTiming::default(), None, Some(paths))?
+            .entries)
+    }
+
+    /// Hash every tracked file, reusing SHA-256 hashes from a quick-hash cache
+    fn hash_all_cached(&self, cache: &mut QuickHashCache) -> Result<Vec<FileEntry>> {
+        Ok(self
+            .scan(false, &mut ScanTiming::default(), Some(cache), None)?
+            .entries)
+    }
+
+    fn walk(&self, errors: Option<&mut Vec<(PathBuf, MovsError)>>) -> Result<Vec<PathBuf>> {
+        let follow = self.options.follow_symlinks;
+        let lenient = errors.is_some();
+        // Entry paths are built from the walk root, so an extended root
+        // keeps deeply nested entries reachable on Windows
+        let root = fs_walk_root(&self.root)?;
+        let start = match &self.scope {
+            Some(scope) => scope
+                .iter()
+                .fold(root.clone(), |path, part| path.join(part)),
+            None => root.clone(),
+        };
+        // Ignore files below the root are read as the walk reaches them
+        let mut ignore = self.ignore.clone();
+        if let Some(scope) = &self.scope {
+            for dir in scope.ancestors().filter(|d| !d.as_os_str().is_empty()) {
+                ignore.load_ignore_file(&self.root, dir)?;
+            }
+        }
+
+        let filter = Arc::new(WalkFilter {
+            root: root.clone(),
+            skip_movs: self.scope.is_none(),
+            ignore: RwLock::new(ignore),
+            error: Mutex::new(None),
+        });
+        let found = Mutex::new(WalkOutput::default());
+
+        let walk_filter = Arc::clone(&filter);
+        WalkBuilder::new(start)
+            .standard_filters(false)
+            .follow_links(follow)
+            .filter_entry(move |entry| walk_filter.accepts(entry))
+            .build_parallel()
+            .run(|| {
+                Box::new(|entry| {
+                    let step = self.visit(&root, lenient, entry);
+                    let mut found = found.lock().unwrap_or_else(|e| e.into_inner());
+                    match step {
+                        Ok(Some(Visited::Path(path))) => found.paths.push(path),
+                        Ok(Some(Visited::Dir(path))) => found.dirs.push(path),
+                        Ok(Some(Visited::Error(path, e))) => found.errors.push((path, e)),
+                        Ok(None) => {}
+                        Err(e) => {
+                            found.fatal.get_or_insert(e);
+                            return WalkState::Quit;
+                        }
+                    }
+                    WalkState::Continue
+                })
+            });
+
+        let found = found.into_inner().unwrap_or_else(|e| e.into_inner());
+        let filter_error = filter
+            .error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(e) = found.fatal.or(filter_error) {
+            return Err(e);
+        }
+        if let Some(errors) = errors {
+            errors.extend(found.errors);
+        }
+        let mut paths = found.paths;
+        let dirs = found.dirs;
+
+        // Only directories with nothing tracked below them are recorded
+        let occupied: HashSet<&Path> = paths
+            .iter()
+            .chain(&dirs)
+            .flat_map(|path| path.ancestors().skip(1))
+            .collect();
+        let empty_dirs: Vec<PathBuf> = dirs
+            .iter()
+            .filter(|dir| !occupied.contains(dir.as_path()))
+            .cloned()
+            .collect();
+        paths.extend(empty_dirs);
+
+        paths.sort();
+        paths.dedup();
+        Ok(paths)
+    }
+
+    /// Classify one item produced by the walker
+    ///
+    /// `Ok(None)` means the item is skipped; an error aborts the walk.
+    fn visit(
+        &self,
+        root: &Path,
+        lenient: bool,
+        entry: std::result::Result<DirEntry, ::ignore::Error>,
+    ) -> Result<Option<Visited>> {
+        // Non-UTF-8 names are rejected here, before anything is hashed
+        let normalized = |relative: &Path, visited: fn(PathBuf) -> Visited| match normalize_relative(
+            &self.root, relative,
+        ) {
+            Ok(path) => Ok(Some(visited(path))),
+            Err(e) if lenient => Ok(Some(Visited::Error(relative.to_path_buf(), e))),
+            Err(e) => Err(e),
+        };
+
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                if self.options.follow_symlinks {
+                    if let Some(dangling) = check_followed_error(root, &e)? {
+                        return normalized(&dangling, Visited::Path);
+                    }
+                }
+                return match walk_error_path(&e) {
+                    Some(path) if lenient && e.depth().is_some_and(|depth| depth > 0) => {
+                        let relative = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+                        Ok(Some(Visited::Error(
+                            relative,
+                            MovsError::Io(walk_io_error(&e)),
+                        )))
+                    }
+                    _ => Err(MovsError::Io(walk_io_error(&e))),
+                };
+            }
+        };
+        if entry.depth() == 0 {
+            return Ok(None);
+        }
+
+        let Some(file_type) = entry.file_type() else {
+            return Ok(None);
+        };
+        let relative = entry
+            .path()
+            .strip_prefix(root)
+            .map_err(|_| MovsError::InvalidPath(entry.path().to_path_buf()))?;
+
+        if file_type.is_dir() {
+            if !self.options.track_empty_dirs {
+                return Ok(None);
+            }
+            return normalized(relative, Visited::Dir);
+        }
+        if !file_type.is_file() && !file_type.is_symlink() {
+            return Ok(None);
+        }
+        normalized(relative, Visited::Path)
+    }
+
+    /// Resolve paths given to `hash_paths` into the tracked files they cover
+    fn expand_paths(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let mut expanded = Vec::new();
+
+        for path in paths {
+            // The root itself stands for the whole tree
+            if path.components().all(|c| c == Component::CurDir) {
+                return self.walk(None);
+            }
+            let relative = normalize_relative(&self.root, path)?;
+            if relative.starts_with(MOVS_DIR) {
+                continue;
+            }
+            if self
+                .scope
+                .as_ref()
+                .is_some_and(|s| !relative.starts_with(s))
+            {
+                continue;
+            }
+
+            let absolute = fs_path(&self.root.join(&relative))?;
+            let Ok(fs_metadata) = absolute.symlink_metadata() else {
+                continue;
+            };
+            let is_dir =
+                fs_metadata.is_dir() || (self.options.follow_symlinks && absolute.is_dir());
+
+            // Ignore files in the path's ancestors apply as in a full walk
+            let mut ignore = self.ignore.clone();
+            for dir in relative.ancestors().skip(1) {
+                if !dir.as_os_str().is_empty() {
+                    ignore.load_ignore_file(&self.root, dir)?;
+                }
+            }
+            if ignore.is_ignored(&relative, is_dir) {
+                continue;
+            }
+
+            if is_dir {
+                let inner = self.clone().with_scope(&relative)?.walk(None)?;
+                if inner.is_empty() && self.options.track_empty_dirs {
+                    expanded.push(relative);
+                } else {
+                    expanded.extend(inner);
+                }
+            } else {
+                expanded.push(relative);
+            }
+        }
+
+        expanded.sort();
+        expanded.dedup();
+        Ok(expanded)
+    }
+
+    fn scan(
+        &self,
+        lenient: bool,
+        timing: &mut ScanTiming,
+        cache: Option<&mut QuickHashCache>,
+        only: Option<&[PathBuf]>,
+    ) -> Result<ScanOutcome> {
+        let walk_start = Instant::now();
+        let mut outcome = ScanOutcome::default();
+        let mut files = Vec::new();
+
+        // In strict mode the first error aborts the scan
+        let record = |errors: &mut Vec<_>, path: PathBuf, error: MovsError| {
+            if lenient {
+                errors.push((path, error));
+                Ok(())
+            } else {
+                Err(error)
+            }
+        };
+
+        let paths = match only {
+            Some(only) => self.expand_paths(only)?,
+            None if lenient => self.walk(Some(&mut outcome.errors))?,
+            None => self.walk(None)?,
+        };
+
+        for relative in paths {
+            let absolute = match fs_path(&self.root.join(&relative)) {
+                Ok(absolute) => absolute,
+                Err(e) => {
+                    record(&mut outcome.errors, relative, e)?;
+                    continue;
+                }
+            };
+            let stat = match self.options.follow_symlinks {
+                true => std::fs::metadata(&absolute).or_else(|_| absolute.symlink_metadata()),
+                false => absolute.symlink_metadata(),
+            };
+            let result = stat.and_then(|fs_metadata| {
+                let modified = DateTime::<Utc>::from(fs_metadata.modified()?);
+                Ok((fs_metadata, modified))
+            });
+            let (fs_metadata, modified) = match result {
+                Ok(stat) => stat,
+                Err(e) => {
+                    record(&mut outcome.errors, relative, MovsError::Io(e))?;
+                    continue;
+                }
+            };
+
+            if fs_metadata.is_dir() {
+                outcome.entries.push(
+                    FileEntry::new(relative, hash_bytes(b""), 0, modified)
+                        .with_kind(EntryKind::Directory),
+                );
+            } else if fs_metadata.file_type().is_symlink() {
+                match symlink_entry(relative.clone(), &absolute, modified) {
+                    Ok(entry) => outcome.entries.push(entry),
+                    Err(e) => record(&mut outcome.errors, relative, e)?,
+                }
+            } else if let Err(e) = check_file_size(&relative, fs_metadata.len(), self.max_file_size)
+            {
+                record(&mut outcome.errors, relative, e)?;
+            } else {
+                files.push((relative, absolute, fs_metadata.len(), modified));
+            }
+        }
+
+        timing.walk = walk_start.elapsed();
+        let hash_start = Instant::now();
+
+        let hashed: Vec<Result<FileHash>> = match cache {
+            Some(cache) => {
+                let keyed: Vec<_> = files
+                    .iter()
+                    .map(|(relative, absolute, size, _)| {
+                        (relative.clone(), absolute.clone(), *size)
+                    })
+                    .collect();
+                cache.hash_files(&keyed)
+            }
+            None => hash_files_parallel(files.iter().map(|(_, absolute, _, _)| absolute.as_path()))
+                .into_iter()
+                .map(|(_, hash)| hash)
+                .collect(),
+        };
+        for ((relative, _, size, modified), hash) in files.into_iter().zip(hashed) {
+            match hash {
+                Ok(hash) => {
+                    let file_type = classify_path(&relative);
+                    outcome.entries.push(
+                        FileEntry::new(relative, hash, size, modified).with_file_type(file_type),
+                    )
+                }
+                Err(e) => record(&mut outcome.errors, relative, e)?,
+            }
+        }
+
+        timing.hash = hash_start.elapsed();
+
+        outcome.entries.sort_by(|a, b| a.path.cmp(&b.path));
+        outcome.errors.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(outcome)
+    }
+
+    /// Compute the changes from a snapshot to the current tree
+    pub fn diff_against_snapshot(&self, snapshot: &SnapshotMetadata) -> Result<SnapshotDiff> {
+        Ok(diff_entries(&snapshot.files, &self.hash_all()?))
+    }
+}
+
+/// Ignore and `.movs` filtering shared by the walker threads
+///
+/// A directory's `.movsignore` is read when the directory is accepted,
+/// which happens before any of its children are filtered.
+struct WalkFilter {
+    root: PathBuf,
+    skip_movs: bool,
+    ignore: RwLock<IgnoreRules>,
+    error: Mutex<Option<MovsError>>,
+}
+
+impl WalkFilter {
+    fn accepts(&self, entry: &DirEntry) -> bool {
+        if self.skip_movs && entry.depth() == 1 && entry.file_name() == MOVS_DIR {
+            return false;
+        }
+        let Ok(relative) = entry.path().strip_prefix(&self.root) else {
+            return false;
+        };
+        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+
+        let ignore = self.ignore.read().unwrap_or_else(|e| e.into_inner());
+        if ignore.is_ignored(relative, is_dir) {
+            return false;
+        }
+        drop(ignore);
+
+        if is_dir {
+            let mut ignore = self.ignore.write().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = ignore.load_ignore_file(&self.root, relative) {
+                self.error
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get_or_insert(e);
+            }
+        }
+        true
+    }
+}
+
+/// What a walk collected, merged from every walker thread
+#[derive(Default)]
+struct WalkOutput {
+    paths: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+    errors: Vec<(PathBuf, MovsError)>,
+    fatal: Option<MovsError>,
+}
+
+/// A walked item worth keeping
+enum Visited {
+    /// A file or symlink, relative to the root
+    Path(PathBuf),
+    /// A directory, relative to the root
+    Dir(PathBuf),
+    /// An unreadable path, kept by lenient scans
+    Error(PathBuf, MovsError),
+}
+
+/// Get the path a walk error is about, if any
+fn walk_error_path(e: &::ignore::Error) -> Option<&Path> {
+    match e {
+        ::ignore::Error::WithPath { path, .. } => Some(path),
+        ::ignore::Error::WithDepth { err, .. } | ::ignore::Error::WithLineNumber { err, .. } => {
+            walk_error_path(err)
+        }
+        ::ignore::Error::Loop { child, .. } => Some(child),
+        _ => None,
+    }
+}
+
+/// Get the ancestor a symlink loop points back to, if `e` is a loop
+fn walk_loop_ancestor(e: &::ignore::Error) -> Option<&Path> {
+    match e {
+        ::ignore::Error::WithPath { err, .. }
+        | ::ignore::Error::WithDepth { err, .. }
+        | ::ignore::Error::WithLineNumber { err, .. } => walk_loop_ancestor(err),
+        ::ignore::Error::Loop { ancestor, .. } => Some(ancestor),
+        _ => None,
+    }
+}
+
+/// Convert a walk error, keeping the I/O error kind where there is one
+fn walk_io_error(e: &::ignore::Error) -> std::io::Error {
+    match e.io_error() {
+        Some(io) => std::io::Error::new(io.kind(), e.to_string()),
+        None => std::io::Error::other(e.to_string()),
+    }
+}
+
+/// Classify a walk error that occurred while following symlinks
+///
+/// Cycles, whether through a directory link pointing at one of its
+/// ancestors or a link resolving to itself, become `StorageError`.
+/// Dangling links are returned as relative paths to record as plain
+/// symlinks. `None` means the error is handled like any other.
+fn check_followed_error(root: &Path, e: &::ignore::Error) -> Result<Option<PathBuf>> {
+    let Some(path) = walk_error_path(e) else {
+        return Ok(None);
+    };
+    let relative = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+
+    if let Some(ancestor) = walk_loop_ancestor(e) {
+        return Err(MovsError::StorageError(format!(
+            "Symlink loop detected: '{}' points back to '{}'",
+            relative.display(),
+            ancestor.display()
+        )));
+    }
+
+    let is_symlink = path
+        .symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink());
+    if !is_symlink {
+        return Ok(None);
+    }
+
+    match std::fs::canonicalize(path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Some(relative)),
+        Err(_) if std::fs::metadata(path).is_err() => Err(MovsError::StorageError(format!(
+            "Symlink loop detected at '{}'",
+            relative.display()
+        ))),
+        _ => Ok(None),
+    }
+}
+
+/// Reject a file larger than the configured limit before it is read
+fn check_file_size(relative: &Path, size: u64, limit: Option<u64>) -> Result<()> {
+    match limit {
+        Some(limit) if size > limit => Err(MovsError::StorageError(format!(
+            "'{}' is {} bytes, larger than the {} byte limit for hashed files",
+            relative.display(),
+            size,
+            limit
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Build the entry for a symlink, hashing its target path
+fn symlink_entry(relative: PathBuf, absolute: &Path, modified: DateTime<Utc>) -> Result<FileEntry> {
+    let target = std::fs::read_link(absolute)?;
+    let target_bytes = target.to_string_lossy();
+
+    Ok(FileEntry::new(
+        relative,
+        hash_bytes(target_bytes.as_bytes()),
+        target_bytes.len() as u64,
+        modified,
+    )
+    .with_kind(EntryKind::Symlink { target }))
+}
+
+/// Compute the uncommitted changes in the project relative to HEAD
+///
+/// If no snapshot exists yet, every tracked file is reported as added.
+/// With `quick_hash_status` enabled in the config, files whose xxHash64
+/// matches the previous status run reuse their recorded SHA-256 instead of
+/// being hashed again; see `hash::quick_hash`.
+pub fn status(project_root: &Path) -> Result<SnapshotDiff> {
+    if !repository_exists(project_root) {
+        return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+    }
+
+    let tree = WorkingTree::open(project_root)?;
+    let current = if load_config(project_root)?.quick_hash_status {
+        let mut cache = QuickHashCache::load(project_root);
+        let entries = tree.hash_all_cached(&mut cache)?;
+        cache.save(project_root)?;
+        entries
+    } else {
+        tree.hash_all()?
+    };
+
+    let previous = match read_head(project_root)? {
+        Some(head) => load_snapshot(project_root, &head)?.files,
+        None => Vec::new(),
+    };
+    Ok(diff_entries(&previous, &current))
+}
+
+/// List paths that might have changed since a snapshot, without hashing
+///
+/// Only each file's size and modification time are compared with the
+/// snapshot, which makes this cheap enough to poll from a background
+/// watcher. The check is deliberately conservative: a file that was touched
+/// or rewritten with identical content is still reported, so callers must
+/// treat the result as candidates and confirm by hashing (which snapshot
+/// creation does anyway). Content changes that preserve both size and
+/// mtime are not detected. Added and removed paths are always reported.
+///
+/// Only the snapshot's scope is walked, so a scoped snapshot never reports
+/// files outside it. Symlinks are not followed; use
+/// `changed_paths_since_with_options` for snapshots taken with other
+/// scan options.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `snapshot_id` - Snapshot to compare against
+pub fn changed_paths_since(project_root: &Path, snapshot_id: &SnapshotId) -> Result<Vec<PathBuf>> {
+    changed_paths_since_with_options(project_root, snapshot_id, &ScanOptions::default())
+}
+
+/// `changed_paths_since`, walking the tree with explicit scan options
+///
+/// Pass the options the snapshot was created with. With `follow_symlinks`
+/// set, linked files are compared by the size and mtime of their target,
+/// as the snapshot recorded them.
+pub fn changed_paths_since_with_options(
+    project_root: &Path,
+    snapshot_id: &SnapshotId,
+    options: &ScanOptions,
+) -> Result<Vec<PathBuf>> {
+    let snapshot = load_snapshot(project_root, snapshot_id)?;
+    let mut tree = WorkingTree::open(project_root)?.with_scan_options(*options);
+    if let Some(scope) = &snapshot.scope {
+        tree = tree.with_scope(scope)?;
+    }
+    let mut recorded: HashMap<&Path, &FileEntry> = snapshot
+        .files
+        .iter()
+        .map(|entry| (entry.path.as_path(), entry))
+        .collect();
+
+    let mut changed = Vec::new();
+
+    for relative in tree.entries()? {
+        let Some(entry) = recorded.remove(relative.as_path()) else {
+            changed.push(relative);
+            continue;
+        };
+
+        let absolute = fs_path(&project_root.join(&relative))?;
+        let fs_metadata = match options.follow_symlinks {
+            true => std::fs::metadata(&absolute).or_else(|_| absolute.symlink_metadata())?,
+            false => absolute.symlink_metadata()?,
+        };
+        if fs_metadata.is_dir() {
+            if entry.kind != EntryKind::Directory {
+                changed.push(relative);
+            }
+            continue;
+        }
+        let modified = DateTime::<Utc>::from(fs_metadata.modified()?);
+        let size = if fs_metadata.file_type().is_symlink() {
+            std::fs::read_link(&absolute)?.to_string_lossy().len() as u64
+        } else {
+            fs_metadata.len()
+        };
+
+        if size != entry.size || modified != entry.modified {
+            changed.push(relative);
+        }
+    }
+
+    changed.extend(
+        recorded
+            .into_values()
+            .filter(|entry| entry.kind != EntryKind::Directory)
+            .map(|entry| entry.path.clone()),
+    );
+    changed.sort();
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use crate::snapshot::{create_snapshot, SnapshotOptions};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_entries_honor_ignore_rules() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        fs::create_dir_all(root.join("Backup")).unwrap();
+        fs::write(root.join(IGNORE_FILE), "*.asd\nBackup/\n").unwrap();
+        fs::write(root.join("song.als"), b"project").unwrap();
+        fs::write(root.join("kick.wav.asd"), b"analysis").unwrap();
+        fs::write(root.join("Backup/song.als"), b"old").unwrap();
+
+        let tree = WorkingTree::open(root).unwrap();
+        assert_eq!(
+            tree.entries().unwrap(),
+            vec![PathBuf::from(IGNORE_FILE), PathBuf::from("song.als")]
+        );
+    }
+
+    #[test]
+    fn test_parallel_walk_applies_each_ignore_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        // Enough sibling directories to spread over the walker threads
+        let mut expected = Vec::new();
+        for i in 0..32 {
+            let dir = PathBuf::from(format!("Track {:02}/Takes", i));
+            fs::create_dir_all(root.join(&dir)).unwrap();
+            fs::write(root.join(&dir).join(IGNORE_FILE), "*.tmp\n").unwrap();
+            fs::write(root.join(&dir).join("take.tmp"), b"scratch").unwrap();
+            fs::write(root.join(&dir).join("take.wav"), b"take").unwrap();
+            expected.push(dir.join(IGNORE_FILE));
+            expected.push(dir.join("take.wav"));
+        }
+        expected.sort();
+
+        let tree = WorkingTree::with_ignore_rules(root, IgnoreRules::new());
+        assert_eq!(tree.entries().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_nested_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        fs::create_dir_all(root.join("Song/Renders")).unwrap();
+        fs::write(root.join(IGNORE_FILE), "*.wav\n").unwrap();
+        fs::write(root.join("Song").join(IGNORE_FILE), "!stems/**\nRenders/\n").unwrap();
+        fs::write(root.join("Song/Renders/mix.als"), b"render").unwrap();
+        fs::create_dir_all(root.join("Song/stems/drums")).unwrap();
+        fs::write(root.join("Song/stems/drums/kick.wav"), b"kick").unwrap();
+        fs::write(root.join("Song/bounce.wav"), b"bounce").unwrap();
+
+        let tree = WorkingTree::open(root).unwrap();
+        let expected = vec![
+            PathBuf::from(IGNORE_FILE),
+            PathBuf::from("Song").join(IGNORE_FILE),
+            PathBuf::from("Song/stems/drums/kick.wav"),
+        ];
+        assert_eq!(tree.entries().unwrap(), expected);
+
+        let scoped = WorkingTree::open(root)
+            .unwrap()
+            .with_scope(Path::new("Song/stems"))
+            .unwrap();
+        assert_eq!(
+            scoped.entries().unwrap(),
+            vec![PathBuf::from("Song/stems/drums/kick.wav")]
+        );
+    }
+
+    #[test]
+    fn test_hash_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        fs::create_dir_all(root.join("Samples/Drums")).unwrap();
+        fs::write(root.join(IGNORE_FILE), "*.asd\n").unwrap();
+        fs::write(root.join("song.als"), b"project").unwrap();
+        fs::write(root.join("Samples/Drums/kick.wav"), b"kick").unwrap();
+        fs::write(root.join("Samples/kick.wav.asd"), b"analysis").unwrap();
+
+        let tree = WorkingTree::open(root).unwrap();
+        let entries = tree
+            .hash_paths(&[
+                PathBuf::from("Samples"),
+                PathBuf::from("Samples/kick.wav.asd"),
+                PathBuf::from("deleted.wav"),
+                PathBuf::from(".movs/HEAD"),
+            ])
+            .unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("Samples/Drums/kick.wav")]);
+        assert_eq!(entries[0].hash, hash_bytes(b"kick"));
+    }
+
+    #[test]
+    fn test_check_file_size() {
+        let path = Path::new("huge.wav");
+
+        assert!(check_file_size(path, u64::MAX, None).is_ok());
+        assert!(check_file_size(path, 1024, Some(1024)).is_ok());
+        let err = check_file_size(path, u64::MAX, Some(u32::MAX as u64)).unwrap_err();
+        assert!(matches!(err, MovsError::StorageError(_)));
+        assert!(err.to_string().contains("huge.wav"));
+    }
+
+    #[test]
+    fn test_max_file_size() {
+        use crate::metadata::config::save_config;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        fs::write(root.join("song.als"), b"project").unwrap();
+        fs::write(root.join("take.wav"), vec![0u8; 64]).unwrap();
+
+        let mut config = load_config(root).unwrap();
+        config.max_file_size = Some(16);
+        save_config(root, &config).unwrap();
+
+        let tree = WorkingTree::open(root).unwrap();
+        assert!(matches!(tree.hash_all(), Err(MovsError::StorageError(_))));
+
+        let outcome = tree.hash_all_lenient().unwrap();
+        assert_eq!(outcome.entries.len(), 1);
+        assert_eq!(outcome.errors[0].0, PathBuf::from("take.wav"));
+
+        let unlimited = WorkingTree::open(root).unwrap().with_max_file_size(None);
+        assert_eq!(unlimited.hash_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_default_daw_ignores() {
+        use crate::metadata::config::save_config;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        fs::create_dir_all(root.join("Backup")).unwrap();
+        fs::write(root.join("song.als"), b"project").unwrap();
+        fs::write(root.join("kick.wav.asd"), b"analysis").unwrap();
+        fs::write(root.join("Backup/song.als"), b"old").unwrap();
+
+        let tree = WorkingTree::open(root).unwrap();
+        assert_eq!(tree.entries().unwrap(), vec![PathBuf::from("song.als")]);
+
+        let mut config = load_config(root).unwrap();
+        config.daw_default_ignores = false;
+        save_config(root, &config).unwrap();
+
+        let tree = WorkingTree::open(root).unwrap();
+        assert_eq!(tree.entries().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_track_empty_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::create_dir(root.join("Recorded")).unwrap();
+        fs::create_dir_all(root.join("Takes/Comped")).unwrap();
+        fs::create_dir(root.join("Samples")).unwrap();
+        fs::write(root.join("Samples/kick.wav"), b"kick").unwrap();
+        fs::create_dir(root.join("Freeze")).unwrap();
+        fs::write(root.join("Freeze/vox.tmp"), b"frozen").unwrap();
+        fs::write(root.join(".movsignore"), "*.tmp\n").unwrap();
+
+        let tree = WorkingTree::open(root).unwrap();
+        assert!(tree
+            .hash_all()
+            .unwrap()
+            .iter()
+            .all(|entry| entry.kind != EntryKind::Directory));
+
+        let options = ScanOptions {
+            track_empty_dirs: true,
+            ..ScanOptions::default()
+        };
+        let entries = tree.with_scan_options(options).hash_all().unwrap();
+        let dirs: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.kind == EntryKind::Directory)
+            .map(|entry| (entry.path.clone(), entry.size))
+            .collect();
+        assert_eq!(
+            dirs,
+            vec![
+                (PathBuf::from("Freeze"), 0),
+                (PathBuf::from("Recorded"), 0),
+                (PathBuf::from("Takes/Comped"), 0),
+            ]
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_hash_all_deeply_nested() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let mut relative = PathBuf::new();
+        for i in 0..12 {
+            relative.push(format!("Session Folder Level {:02} With Long Name", i));
+        }
+        relative.push("Bounced Stem - Lead Vocal Comp Final.wav");
+        let absolute = fs_path(&root.join(&relative)).unwrap();
+        fs::create_dir_all(absolute.parent().unwrap()).unwrap();
+        fs::write(&absolute, b"vocal").unwrap();
+
+        let entries = WorkingTree::open(root).unwrap().hash_all().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, relative);
+        assert_eq!(entries[0].hash, hash_bytes(b"vocal"));
+    }
+
+    #[test]
+    fn test_hash_all() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::create_dir(root.join("Samples")).unwrap();
+        fs::write(root.join("Samples/kick.wav"), b"kick").unwrap();
+
+        let entries = WorkingTree::open(root).unwrap().hash_all().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, PathBuf::from("Samples/kick.wav"));
+        assert_eq!(entries[0].size, 4);
+        assert_eq!(entries[0].file_type, Some(crate::types::FileType::Wav));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_file_names_are_rejected() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let name = OsStr::from_bytes(b"kick\xff.wav");
+        fs::write(root.join("snare.wav"), b"snare").unwrap();
+        if fs::write(root.join(name), b"kick").is_err() {
+            // The filesystem itself enforces UTF-8 names
+            return;
+        }
+
+        let tree = WorkingTree::open(root).unwrap();
+        assert!(matches!(tree.hash_all(), Err(MovsError::InvalidPath(p)) if p == Path::new(name)));
+
+        let outcome = tree.hash_all_lenient().unwrap();
+        assert_eq!(outcome.entries.len(), 1);
+        assert_eq!(outcome.entries[0].path, PathBuf::from("snare.wav"));
+        assert_eq!(outcome.errors.len(), 1);
+        assert_eq!(outcome.errors[0].0, Path::new(name));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hash_all_records_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let library = TempDir::new().unwrap();
+        fs::write(library.path().join("kick.wav"), b"shared kick").unwrap();
+        std::os::unix::fs::symlink(library.path().join("kick.wav"), root.join("kick.wav")).unwrap();
+
+        let entries = WorkingTree::open(root).unwrap().hash_all().unwrap();
+        assert_eq!(entries.len(), 1);
+
+        let target = library.path().join("kick.wav");
+        assert_eq!(
+            entries[0].kind,
+            EntryKind::Symlink {
+                target: target.clone()
+            }
+        );
+        assert_eq!(
+            entries[0].hash,
+            hash_bytes(target.to_string_lossy().as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_status() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        fs::write(root.join("song.als"), b"project").unwrap();
+        fs::write(root.join("vox.wav"), b"vox").unwrap();
+
+        // Before any snapshot everything is new
+        assert_eq!(status(root).unwrap().added.len(), 2);
+
+        create_snapshot(root, "First", None, &Default::default()).unwrap();
+        assert!(!status(root).unwrap().has_changes());
+
+        fs::write(root.join("song.als"), b"project v2").unwrap();
+        fs::remove_file(root.join("vox.wav")).unwrap();
+        fs::write(root.join("bass.wav"), b"bass").unwrap();
+
+        let diff = status(root).unwrap();
+        assert_eq!(diff.added, vec![PathBuf::from("bass.wav")]);
+        assert_eq!(diff.modified, vec![PathBuf::from("song.als")]);
+        assert_eq!(diff.removed, vec![PathBuf::from("vox.wav")]);
+    }
+
+    #[test]
+    fn test_status_with_quick_hash_cache() {
+        use crate::metadata::config::save_config;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        let mut config = load_config(root).unwrap();
+        config.quick_hash_status = true;
+        save_config(root, &config).unwrap();
+
+        fs::write(root.join("song.als"), b"project").unwrap();
+        create_snapshot(root, "First", None, &Default::default()).unwrap();
+
+        assert!(!status(root).unwrap().has_changes());
+        assert!(quick_cache::get_quick_cache_path(root).is_file());
+        assert!(!status(root).unwrap().has_changes());
+
+        fs::write(root.join("song.als"), b"project v2").unwrap();
+        assert_eq!(
+            status(root).unwrap().modified,
+            vec![PathBuf::from("song.als")]
+        );
+    }
+
+    #[test]
+    fn test_changed_paths_since() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        fs::write(root.join("song.als"), b"project").unwrap();
+        fs::write(root.join("vox.wav"), b"vox").unwrap();
+        fs::write(root.join("keep.wav"), b"keep").unwrap();
+
+        let id = create_snapshot(root, "First", None, &Default::default()).unwrap();
+        assert!(changed_paths_since(root, &id).unwrap().is_empty());
+
+        fs::write(root.join("song.als"), b"project v2").unwrap();
+        fs::remove_file(root.join("vox.wav")).unwrap();
+        fs::write(root.join("bass.wav"), b"bass").unwrap();
+
+        // Same content, new mtime: reported as a possible change
+        let touched = fs::File::options()
+            .write(true)
+            .open(root.join("keep.wav"))
+            .unwrap();
+        touched
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+
+        assert_eq!(
+            changed_paths_since(root, &id).unwrap(),
+            vec![
+                PathBuf::from("bass.wav"),
+                PathBuf::from("keep.wav"),
+                PathBuf::from("song.als"),
+                PathBuf::from("vox.wav"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changed_paths_since_honors_scope() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        fs::create_dir(root.join("Stems")).unwrap();
+        fs::write(root.join("Stems/vox.wav"), b"vox").unwrap();
+        fs::write(root.join("song.als"), b"project").unwrap();
+
+        let options = SnapshotOptions {
+            scope: Some(PathBuf::from("Stems")),
+            ..Default::default()
+        };
+        let id = create_snapshot(root, "Stems", None, &options).unwrap();
+        assert!(changed_paths_since(root, &id).unwrap().is_empty());
+
+        fs::write(root.join("Stems/bass.wav"), b"bass").unwrap();
+        assert_eq!(
+            changed_paths_since(root, &id).unwrap(),
+            vec![PathBuf::from("Stems/bass.wav")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_changed_paths_since_follows_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        init_repository(root).unwrap();
+        fs::write(library.path().join("808.wav"), b"boom").unwrap();
+        std::os::unix::fs::symlink(library.path(), root.join("Library")).unwrap();
+
+        let scan = ScanOptions {
+            follow_symlinks: true,
+            ..ScanOptions::default()
+        };
+        let options = SnapshotOptions {
+            scan,
+            ..Default::default()
+        };
+        let id = create_snapshot(root, "Linked", None, &options).unwrap();
+        assert!(changed_paths_since_with_options(root, &id, &scan)
+            .unwrap()
+            .is_empty());
+
+        fs::write(library.path().join("808.wav"), b"boom boom").unwrap();
+        assert_eq!(
+            changed_paths_since_with_options(root, &id, &scan).unwrap(),
+            vec![PathBuf::from("Library/808.wav")]
+        );
+    }
+}
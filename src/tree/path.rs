@@ -0,0 +1,282 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::MOVS_DIR;
+use std::path::{Component, Path, PathBuf};
+
+/// Normalize a path to the canonical form used for snapshot entries
+///
+/// Absolute paths are made relative to `root`. On Windows backslashes are
+/// treated as separators; elsewhere they are legal in file names and kept.
+/// `.` components are dropped and `..` components are resolved lexically,
+/// so `./audio.wav`, `audio.wav` and `Samples/../audio.wav` all map to the
+/// same entry. The filesystem is never consulted.
+///
+/// Entries are stored as JSON strings, so paths must be valid UTF-8; other
+/// paths are rejected rather than recorded lossily.
+///
+/// # Errors
+///
+/// Returns `MovsError::InvalidPath` if the path is not valid UTF-8, lies
+/// outside `root`, escapes it through `..`, or refers to the root itself.
+pub fn normalize_relative(root: &Path, path: &Path) -> Result<PathBuf> {
+    let invalid = || MovsError::InvalidPath(path.to_path_buf());
+
+    let raw = path.to_str().ok_or_else(invalid)?;
+    let unified = if cfg!(windows) && raw.contains('\\') {
+        PathBuf::from(raw.replace('\\', "/"))
+    } else {
+        path.to_path_buf()
+    };
+
+    let relative = if unified.is_absolute() {
+        unified.strip_prefix(root).map_err(|_| invalid())?
+    } else {
+        unified.as_path()
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err(invalid());
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return Err(invalid()),
+        }
+    }
+
+    if normalized.as_os_str().is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(normalized)
+}
+
+/// Normalize the subdirectory a scoped snapshot is limited to
+///
+/// Like `normalize_relative`, but also rejects scopes inside `.movs`.
+pub(crate) fn normalize_scope(root: &Path, scope: &Path) -> Result<PathBuf> {
+    let normalized = normalize_relative(root, scope)?;
+    if normalized.starts_with(MOVS_DIR) {
+        return Err(MovsError::InvalidPath(scope.to_path_buf()));
+    }
+    Ok(normalized)
+}
+
+/// Longest path the classic Win32 APIs accept, including the terminator
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// Longest path the extended-length (`\\?\`) Win32 APIs accept
+#[cfg(windows)]
+const MAX_EXTENDED_PATH: usize = 32_767;
+
+/// Longest single path component NTFS accepts
+#[cfg(windows)]
+const MAX_COMPONENT: usize = 255;
+
+/// Prepare a path for filesystem calls, working around Windows' `MAX_PATH`
+///
+/// On Windows, paths at or beyond `MAX_PATH` are made absolute and given the
+/// `\\?\` extended-length prefix (`\\?\UNC\` for network shares) so that
+/// deeply nested session folders can still be read and written. Shorter
+/// paths, and every path on other platforms, are returned unchanged.
+///
+/// # Errors
+///
+/// Returns `MovsError::InvalidPath` if the path cannot be used even in
+/// extended form, e.g. a component longer than 255 characters.
+pub fn fs_path(path: &Path) -> Result<PathBuf> {
+    #[cfg(windows)]
+    {
+        extended_length_path(path, false)
+    }
+
+    #[cfg(not(windows))]
+    {
+        Ok(path.to_path_buf())
+    }
+}
+
+/// Prepare the root of a directory walk for filesystem calls
+///
+/// Like `fs_path`, but on Windows the root is always put in extended-length
+/// form, however short it is: the walk builds every entry's path from it,
+/// and entries deep below a short root can still exceed `MAX_PATH`.
+pub(crate) fn fs_walk_root(path: &Path) -> Result<PathBuf> {
+    #[cfg(windows)]
+    {
+        extended_length_path(path, true)
+    }
+
+    #[cfg(not(windows))]
+    {
+        Ok(path.to_path_buf())
+    }
+}
+
+#[cfg(windows)]
+fn extended_length_path(path: &Path, always: bool) -> Result<PathBuf> {
+    let invalid = || MovsError::InvalidPath(path.to_path_buf());
+
+    let raw = path.as_os_str().to_string_lossy();
+    if raw.starts_with(r"\\?\") || (!always && raw.encode_utf16().count() < MAX_PATH) {
+        return Ok(path.to_path_buf());
+    }
+
+    // Extended paths bypass normalization, so resolve `.`/`..` and `/` first
+    let absolute = std::path::absolute(path).map_err(|_| invalid())?;
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            Component::Normal(part)
+                if part.to_string_lossy().encode_utf16().count() > MAX_COMPONENT =>
+            {
+                return Err(invalid());
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+
+    let normalized = normalized.to_string_lossy().replace('/', "\\");
+    let extended = match normalized.strip_prefix(r"\\") {
+        Some(unc) => format!(r"\\?\UNC\{}", unc),
+        None => format!(r"\\?\{}", normalized),
+    };
+
+    if extended.encode_utf16().count() >= MAX_EXTENDED_PATH {
+        return Err(invalid());
+    }
+
+    Ok(PathBuf::from(extended))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_equivalent_spellings() {
+        let root = Path::new("/project");
+
+        for path in [
+            "audio.wav",
+            "./audio.wav",
+            "Samples/../audio.wav",
+            "/project/audio.wav",
+            "/project/./audio.wav",
+        ] {
+            assert_eq!(
+                normalize_relative(root, Path::new(path)).unwrap(),
+                PathBuf::from("audio.wav"),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_normalize_unifies_separators() {
+        assert_eq!(
+            normalize_relative(Path::new("/project"), Path::new("Samples\\Drums\\kick.wav"))
+                .unwrap(),
+            PathBuf::from("Samples/Drums/kick.wav")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_normalize_keeps_backslashes_in_names() {
+        assert_eq!(
+            normalize_relative(Path::new("/project"), Path::new("Samples/kick\\808.wav")).unwrap(),
+            Path::new("Samples").join("kick\\808.wav")
+        );
+    }
+
+    #[test]
+    fn test_normalize_rejects_escaping_paths() {
+        let root = Path::new("/project");
+
+        for path in [
+            "../outside.wav",
+            "Samples/../../x.wav",
+            "/elsewhere/a.wav",
+            ".",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    normalize_relative(root, Path::new(path)),
+                    Err(MovsError::InvalidPath(_))
+                ),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_normalize_rejects_non_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"Samples/kick\xff.wav"));
+        assert!(matches!(
+            normalize_relative(Path::new("/project"), path),
+            Err(MovsError::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalize_scope() {
+        let root = Path::new("/project");
+
+        assert_eq!(
+            normalize_scope(root, Path::new("/project/Song A/")).unwrap(),
+            PathBuf::from("Song A")
+        );
+        for scope in [".movs", ".movs/objects", "..", "/elsewhere"] {
+            assert!(
+                normalize_scope(root, Path::new(scope)).is_err(),
+                "{}",
+                scope
+            );
+        }
+    }
+
+    #[test]
+    fn test_fs_path_leaves_short_paths_alone() {
+        let path = Path::new("project").join("Samples").join("kick.wav");
+        assert_eq!(fs_path(&path).unwrap(), path);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_fs_path_deeply_nested() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut path = temp_dir.path().to_path_buf();
+        for i in 0..12 {
+            path.push(format!("Session Folder Level {:02} With Long Name", i));
+        }
+        path.push("Bounced Stem - Lead Vocal Comp Final.wav");
+        assert!(path.as_os_str().len() > 260);
+
+        let extended = fs_path(&path).unwrap();
+        assert!(extended.to_string_lossy().starts_with(r"\\?\"));
+
+        std::fs::create_dir_all(fs_path(path.parent().unwrap()).unwrap()).unwrap();
+        std::fs::write(&extended, b"vocal").unwrap();
+        assert_eq!(std::fs::read(&extended).unwrap(), b"vocal");
+
+        let too_long = temp_dir.path().join("x".repeat(300)).join("a".repeat(300));
+        assert!(matches!(fs_path(&too_long), Err(MovsError::InvalidPath(_))));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::error::Result;
+use crate::hash::{hash_files_parallel, quick_hash};
+use crate::metadata::get_movs_dir;
+use crate::parallel::par_map;
+use crate::types::{FileHash, HashAlgorithm};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// Change-detection cache file name inside `.movs`
+pub const QUICK_CACHE_FILE: &str = "quick_cache.json";
+
+/// What was known about a file the last time it was fully hashed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedFile {
+    size: u64,
+    quick: u64,
+    hash: String,
+}
+
+/// Cache mapping each file's xxHash64 to its SHA-256 hash
+///
+/// Used by `status` when `quick_hash_status` is enabled: a file whose size
+/// and quick hash match the cache reuses the recorded SHA-256 instead of
+/// being hashed again, so only changed files pay for the cryptographic
+/// hash. The cache is a local optimization only; snapshot creation always
+/// hashes content with SHA-256, and quick hashes never name objects.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct QuickHashCache {
+    files: BTreeMap<String, CachedFile>,
+}
+
+impl QuickHashCache {
+    /// Load the cache, treating a missing or unreadable file as empty
+    pub fn load(project_root: &Path) -> Self {
+        let files = fs::read_to_string(get_quick_cache_path(project_root))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { files }
+    }
+
+    /// Persist the cache atomically
+    pub fn save(&self, project_root: &Path) -> Result<()> {
+        let json = serde_json::to_string(&self.files)?;
+        let mut temp = NamedTempFile::new_in(get_movs_dir(project_root))?;
+        temp.write_all(json.as_bytes())?;
+        temp.persist(get_quick_cache_path(project_root))
+            .map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// Hash files, reusing cached SHA-256 hashes where the quick hash matches
+    ///
+    /// `files` holds each file's path relative to the root, its absolute
+    /// path and its size. Results are returned in the same order. Entries
+    /// for files not in `files` are dropped from the cache.
+    pub fn hash_files(&mut self, files: &[(PathBuf, PathBuf, u64)]) -> Vec<Result<FileHash>> {
+        let quick: Vec<Result<u64>> = par_map(files, |(_, absolute, _)| quick_hash(absolute));
+
+        let mut results: Vec<Option<Result<FileHash>>> = Vec::with_capacity(files.len());
+        let mut misses = Vec::new();
+
+        for (i, ((relative, absolute, size), quick)) in files.iter().zip(&quick).enumerate() {
+            let quick = match quick {
+                Ok(quick) => *quick,
+                Err(_) => {
+                    results.push(None);
+                    misses.push((i, absolute.as_path()));
+                    continue;
+                }
+            };
+
+            let cached = relative
+                .to_str()
+                .and_then(|key| self.files.get(key))
+                .filter(|cached| cached.size == *size && cached.quick == quick)
+                .and_then(|cached| {
+                    FileHash::from_hex_checked(&cached.hash, HashAlgorithm::Sha256).ok()
+                });
+
+            match cached {
+                Some(hash) => results.push(Some(Ok(hash))),
+                None => {
+                    results.push(None);
+                    misses.push((i, absolute.as_path()));
+                }
+            }
+        }
+
+        let hashed = hash_files_parallel(misses.iter().map(|(_, path)| *path));
+        for ((i, _), (_, hash)) in misses.into_iter().zip(hashed) {
+            results[i] = Some(hash);
+        }
+
+        let mut updated = BTreeMap::new();
+        for (((relative, _, size), quick), result) in files.iter().zip(quick).zip(&results) {
+            if let (Some(key), Ok(quick), Some(Ok(hash))) = (relative.to_str(), quick, result) {
+                updated.insert(
+                    key.to_string(),
+                    CachedFile {
+                        size: *size,
+                        quick,
+                        hash: hash.to_hex(),
+                    },
+                );
+            }
+        }
+        self.files = updated;
+
+        results
+            .into_iter()
+            .map(|result| result.expect("every file is either cached or hashed"))
+            .collect()
+    }
+}
+
+/// Get the path to the change-detection cache
+pub fn get_quick_cache_path(project_root: &Path) -> PathBuf {
+    get_movs_dir(project_root).join(QUICK_CACHE_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_bytes;
+    use crate::metadata::init_repository;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cache_reuses_unchanged_hashes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        init_repository(root).unwrap();
+        fs::write(root.join("kick.wav"), b"kick").unwrap();
+
+        let files = vec![(PathBuf::from("kick.wav"), root.join("kick.wav"), 4)];
+        let mut cache = QuickHashCache::load(root);
+        let first = cache.hash_files(&files);
+        assert_eq!(first[0].as_ref().unwrap(), &hash_bytes(b"kick"));
+        cache.save(root).unwrap();
+
+        // Poison the cached hash: a hit must return it without rehashing
+        let mut cache = QuickHashCache::load(root);
+        cache.files.get_mut("kick.wav").unwrap().hash = hash_bytes(b"cached").to_hex();
+        let second = cache.hash_files(&files);
+        assert_eq!(second[0].as_ref().unwrap(), &hash_bytes(b"cached"));
+
+        // Changed content misses the cache
+        fs::write(root.join("kick.wav"), b"kic2").unwrap();
+        let third = cache.hash_files(&files);
+        assert_eq!(third[0].as_ref().unwrap(), &hash_bytes(b"kic2"));
+    }
+}
@@ -1,8 +1,15 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Timelike, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::path::{Path, PathBuf};
 
+/// Length in bytes of a SHA-256 hash
+pub const HASH_LEN: usize = 32;
+
+/// Number of hex characters in an abbreviated hash, as in git
+pub const SHORT_HASH_LEN: usize = 12;
+
 /// Represents a cryptographic hash of file content
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FileHash {
@@ -26,11 +33,84 @@ impl FileHash {
         hex::encode(&self.bytes)
     }
 
+    /// Get the first `n` hex characters, for compact display
+    ///
+    /// Returns the full hex string if `n` is at least its length.
+    pub fn short(&self, n: usize) -> String {
+        let mut hex = self.to_hex();
+        hex.truncate(n);
+        hex
+    }
+
+    /// Get the hash abbreviated to `SHORT_HASH_LEN` hex characters
+    pub fn short_default(&self) -> String {
+        self.short(SHORT_HASH_LEN)
+    }
+
+    /// Check if the hex form of this hash starts with `hex_prefix`
+    ///
+    /// The prefix is matched case-insensitively and may have an odd
+    /// length. An empty prefix matches every hash.
+    pub fn matches_prefix(&self, hex_prefix: &str) -> bool {
+        let hex = self.to_hex();
+        hex.len() >= hex_prefix.len()
+            && hex.as_bytes()[..hex_prefix.len()].eq_ignore_ascii_case(hex_prefix.as_bytes())
+    }
+
     /// Create FileHash from hex string
+    ///
+    /// Accepts any even-length hex string; use `from_hex_checked` when the
+    /// hash must be a valid digest of a particular algorithm.
     pub fn from_hex(hex_str: &str) -> Result<Self, hex::FromHexError> {
         let bytes = hex::decode(hex_str)?;
         Ok(Self::new(bytes))
     }
+
+    /// Create FileHash from hex string, checking the digest length
+    ///
+    /// Returns `MovsError::InvalidHash` if the string is not hex or does not
+    /// decode to exactly `algorithm.digest_len()` bytes.
+    pub fn from_hex_checked(hex_str: &str, algorithm: HashAlgorithm) -> crate::Result<Self> {
+        let hash = Self::from_hex(hex_str).map_err(|e| {
+            crate::MovsError::InvalidHash(format!("'{}' is not valid hex: {}", hex_str, e))
+        })?;
+
+        if hash.bytes.len() != algorithm.digest_len() {
+            return Err(crate::MovsError::InvalidHash(format!(
+                "'{}' is {} bytes, but a {} digest is {} bytes",
+                hex_str,
+                hash.bytes.len(),
+                algorithm.name(),
+                algorithm.digest_len()
+            )));
+        }
+
+        Ok(hash)
+    }
+}
+
+/// Hash algorithm used for content addressing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    /// SHA-256, the only algorithm currently used for objects
+    #[default]
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Length of a digest in bytes
+    pub fn digest_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => HASH_LEN,
+        }
+    }
+
+    /// Human-readable name of the algorithm
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "SHA-256",
+        }
+    }
 }
 
 impl fmt::Display for FileHash {
@@ -39,8 +119,64 @@ impl fmt::Display for FileHash {
     }
 }
 
+/// Strategy used to generate new snapshot IDs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdScheme {
+    /// `snapshot_YYYYMMDD_HHMMSS_fffffffff` (sorts chronologically)
+    #[default]
+    Timestamp,
+
+    /// `snapshot_<uuid-v4>` (globally unique, no ordering)
+    Uuid,
+
+    /// Timestamp followed by a random suffix, safe when merging repos across machines
+    TimestampPlusRandom,
+}
+
+/// How file content is placed into the object store
+///
+/// `Hardlink` makes the initial snapshot of a large sample library nearly
+/// free in both time and space, but the object and the working file then
+/// share one inode: a program that rewrites the file in place, rather than
+/// writing a new file and renaming it over the old one, would change the
+/// stored object too. To limit that risk only audio and MIDI files are
+/// linked, since DAWs rewrite project files on every save, and linked
+/// files are made read-only so in-place writes fail. Linked objects are
+/// also verified against their hash whenever they are read. Files that
+/// are already hardlinked elsewhere, files on another filesystem and
+/// platforms without link counts all fall back to copying.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageMode {
+    /// Copy content into a new object file
+    #[default]
+    Copy,
+
+    /// Hardlink eligible working files into the store, copying otherwise
+    Hardlink,
+}
+
+/// How object file names are derived from content hashes
+///
+/// Base32 names are shorter (52 instead of 64 characters for SHA-256),
+/// which helps with path length limits on deeply nested stores. Both
+/// encodings are lowercase and safe on case-insensitive filesystems.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectEncoding {
+    /// Lowercase hexadecimal, as written by every earlier version
+    #[default]
+    Hex,
+
+    /// Unpadded lowercase RFC 4648 base32
+    Base32,
+}
+
 /// Unique identifier for a snapshot
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+///
+/// IDs order lexicographically by their string. That is chronological for
+/// the timestamp scheme, but arbitrary for UUID IDs.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct SnapshotId(String);
 
 impl SnapshotId {
@@ -51,14 +187,66 @@ impl SnapshotId {
 
     /// Generate a new unique snapshot ID based on timestamp
     pub fn generate() -> Self {
-        let now = Utc::now();
-        Self(format!("snapshot_{}", now.format("%Y%m%d_%H%M%S_%f")))
+        Self::generate_with(IdScheme::Timestamp)
+    }
+
+    /// Generate a new unique snapshot ID using the given scheme
+    pub fn generate_with(scheme: IdScheme) -> Self {
+        Self::generate_at(scheme, Utc::now())
+    }
+
+    /// Generate a snapshot ID for the given creation time
+    ///
+    /// With `IdScheme::Timestamp` the ID is fully determined by `now`.
+    pub fn generate_at(scheme: IdScheme, now: DateTime<Utc>) -> Self {
+        match scheme {
+            IdScheme::Timestamp => Self(format!("snapshot_{}", now.format("%Y%m%d_%H%M%S_%f"))),
+            IdScheme::Uuid => Self(format!("snapshot_{}", uuid::Uuid::new_v4())),
+            IdScheme::TimestampPlusRandom => {
+                let random = uuid::Uuid::new_v4().simple().to_string();
+                Self(format!(
+                    "snapshot_{}_{}",
+                    now.format("%Y%m%d_%H%M%S_%f"),
+                    &random[..8]
+                ))
+            }
+        }
     }
 
     /// Get the inner string value
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Extract the creation time embedded in a timestamp-scheme ID
+    ///
+    /// Parses `snapshot_YYYYMMDD_HHMMSS_fffffffff`, optionally followed by
+    /// the random suffix of `IdScheme::TimestampPlusRandom`. Returns `None`
+    /// for UUID IDs or anything else that does not match.
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        let mut parts = self.0.strip_prefix("snapshot_")?.split('_');
+        let (date, time, fraction) = (parts.next()?, parts.next()?, parts.next()?);
+
+        if let Some(suffix) = parts.next() {
+            if suffix.len() != 8 || !suffix.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+        }
+        if parts.next().is_some()
+            || fraction.is_empty()
+            || fraction.len() > 9
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let seconds =
+            chrono::NaiveDateTime::parse_from_str(&format!("{}{}", date, time), "%Y%m%d%H%M%S")
+                .ok()?;
+        let nanos: u32 = format!("{:0<9}", fraction).parse().ok()?;
+
+        Some(seconds.with_nanosecond(nanos)?.and_utc())
+    }
 }
 
 impl fmt::Display for SnapshotId {
@@ -73,6 +261,120 @@ impl From<String> for SnapshotId {
     }
 }
 
+/// The kind of filesystem object a `FileEntry` describes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntryKind {
+    /// A regular file; the hash is of its content
+    #[default]
+    File,
+
+    /// A symbolic link; the hash is of the target path string
+    Symlink { target: PathBuf },
+
+    /// A directory; carries no content
+    Directory,
+}
+
+/// Advisory content category of a file, inferred from its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FileType {
+    /// WAV / Broadcast WAV audio
+    Wav,
+
+    /// AIFF audio
+    Aiff,
+
+    /// Standard MIDI file
+    Midi,
+
+    /// Lossy or lossless compressed audio: FLAC, MP3, Ogg, Opus, AAC
+    CompressedAudio,
+
+    /// A DAW project or session file
+    ProjectFile,
+
+    /// Anything else
+    Other,
+}
+
+impl FileType {
+    /// Check if general-purpose compression is likely to shrink this content
+    ///
+    /// Already compressed audio is not worth the CPU time.
+    pub fn is_compressible(self) -> bool {
+        self != FileType::CompressedAudio
+    }
+}
+
+/// DAW project extensions: Ableton, FL Studio, Logic, Pro Tools, Cubase,
+/// Reaper, Studio One, Bitwig
+const PROJECT_EXTENSIONS: &[&str] = &[
+    "als",
+    "flp",
+    "logicx",
+    "ptx",
+    "ptf",
+    "cpr",
+    "rpp",
+    "song",
+    "bwproject",
+];
+
+/// Classify a path by its extension, case-insensitively
+pub fn classify_path(path: &Path) -> FileType {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return FileType::Other;
+    };
+
+    match ext.to_ascii_lowercase().as_str() {
+        "wav" | "wave" | "bwf" => FileType::Wav,
+        "aif" | "aiff" | "aifc" => FileType::Aiff,
+        "mid" | "midi" => FileType::Midi,
+        "flac" | "mp3" | "ogg" | "oga" | "opus" | "m4a" | "aac" => FileType::CompressedAudio,
+        ext if PROJECT_EXTENSIONS.contains(&ext) => FileType::ProjectFile,
+        _ => FileType::Other,
+    }
+}
+
+/// Write `value` as compact JSON with every object's keys in sorted order
+fn write_canonical_json(value: &serde_json::Value, out: &mut String) -> serde_json::Result<()> {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::to_string(key)?);
+                out.push(':');
+                write_canonical_json(&map[key], out)?;
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(item, out)?;
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&serde_json::to_string(scalar)?),
+    }
+    Ok(())
+}
+
+/// Grouping key for a path's extension: lowercase, `""` if there is none
+fn extension_key(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
 /// Represents a file entry in a snapshot
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
@@ -87,6 +389,18 @@ pub struct FileEntry {
 
     /// Last modified time (from file system)
     pub modified: DateTime<Utc>,
+
+    /// Kind of entry (missing in snapshots written before symlink support)
+    #[serde(default)]
+    pub kind: EntryKind,
+
+    /// Advisory content category, set during scanning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<FileType>,
+
+    /// Small waveform thumbnail, see `preview::compute_preview`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<Vec<u8>>,
 }
 
 impl FileEntry {
@@ -96,13 +410,71 @@ impl FileEntry {
             hash,
             size,
             modified,
+            kind: EntryKind::File,
+            file_type: None,
+            preview: None,
         }
     }
+
+    /// Set the kind of this entry
+    pub fn with_kind(mut self, kind: EntryKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Set the content category of this entry
+    pub fn with_file_type(mut self, file_type: FileType) -> Self {
+        self.file_type = Some(file_type);
+        self
+    }
+
+    /// Attach a waveform preview to this entry
+    pub fn with_preview(mut self, preview: Vec<u8>) -> Self {
+        self.preview = Some(preview);
+        self
+    }
+
+    /// Check if this entry is a regular file
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+
+    /// Check if two entries hold the same content, wherever they live
+    ///
+    /// Only the hashes are compared. `FileEntry` deliberately has no
+    /// `PartialEq`, so callers say whether they mean content or identity.
+    pub fn same_content(&self, other: &FileEntry) -> bool {
+        self.hash == other.hash
+    }
+
+    /// Check if two entries are at the same path, whatever their content
+    pub fn same_path(&self, other: &FileEntry) -> bool {
+        self.path == other.path
+    }
+}
+
+/// Current schema version written into snapshot metadata files
+///
+/// Bump this whenever the on-disk shape of `SnapshotMetadata` changes in a
+/// way older clients cannot read.
+///
+/// Version 2 snapshots always carry a `content_digest`.
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 2;
+
+/// First schema version whose snapshots must carry a `content_digest`
+pub const DIGEST_SCHEMA_VERSION: u32 = 2;
+
+fn default_schema_version() -> u32 {
+    1
 }
 
 /// Metadata about a snapshot version
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SnapshotMetadata {
+    /// Schema version of this metadata (missing in files written before versioning)
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+
     /// Unique identifier for this snapshot
     pub id: SnapshotId,
 
@@ -120,6 +492,28 @@ pub struct SnapshotMetadata {
 
     /// List of all files in this snapshot
     pub files: Vec<FileEntry>,
+
+    /// Longer free-form notes, e.g. mix decisions or plugin chains
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Free-form key/value annotations
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+
+    /// Subdirectory this snapshot is limited to, if it is a scoped snapshot
+    ///
+    /// A scoped snapshot only records files under this path, relative to
+    /// the project root, and restoring it only touches that subtree.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<PathBuf>,
+
+    /// Hex SHA-256 of every other field, set when the snapshot is saved
+    ///
+    /// Snapshots written before digests were introduced have none; from
+    /// `DIGEST_SCHEMA_VERSION` on it is required.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_digest: Option<String>,
 }
 
 impl SnapshotMetadata {
@@ -131,12 +525,17 @@ impl SnapshotMetadata {
         files: Vec<FileEntry>,
     ) -> Self {
         Self {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
             id,
             timestamp: Utc::now(),
             message,
             author,
             parent,
             files,
+            description: None,
+            metadata: BTreeMap::new(),
+            scope: None,
+            content_digest: None,
         }
     }
 
@@ -151,24 +550,277 @@ impl SnapshotMetadata {
     }
 
     /// Find a file entry by path
+    ///
+    /// The path is normalized first, so `./audio.wav` finds `audio.wav`.
     pub fn find_file(&self, path: &Path) -> Option<&FileEntry> {
+        let path = crate::tree::normalize_relative(Path::new(""), path).ok()?;
         self.files.iter().find(|f| f.path == path)
     }
+
+    /// Group regular files by lowercase extension
+    ///
+    /// Files without an extension are grouped under `""`. Directories and
+    /// symlinks are left out.
+    pub fn files_by_extension(&self) -> HashMap<String, Vec<&FileEntry>> {
+        let mut groups: HashMap<String, Vec<&FileEntry>> = HashMap::new();
+        for entry in self.files.iter().filter(|f| f.is_file()) {
+            groups
+                .entry(extension_key(&entry.path))
+                .or_default()
+                .push(entry);
+        }
+        groups
+    }
+
+    /// Total size of regular files per lowercase extension
+    ///
+    /// Uses the same grouping as `files_by_extension`.
+    pub fn size_by_extension(&self) -> HashMap<String, u64> {
+        let mut sizes = HashMap::new();
+        for entry in self.files.iter().filter(|f| f.is_file()) {
+            *sizes.entry(extension_key(&entry.path)).or_insert(0) += entry.size;
+        }
+        sizes
+    }
+
+    /// Compute the digest stored in `content_digest`
+    ///
+    /// Hashes the metadata serialized as compact JSON with object keys
+    /// explicitly sorted, leaving out `content_digest` itself, so the result
+    /// depends neither on field order or formatting in the file nor on how
+    /// serde_json orders map keys in this build.
+    pub fn compute_content_digest(&self) -> crate::error::Result<String> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(object) = value.as_object_mut() {
+            object.remove("content_digest");
+        }
+        let mut canonical = String::new();
+        write_canonical_json(&value, &mut canonical)?;
+        Ok(crate::hash::hash_bytes(canonical.as_bytes()).to_hex())
+    }
+
+    /// Check that `content_digest` matches the content
+    ///
+    /// Snapshots older than `DIGEST_SCHEMA_VERSION` pass without a digest;
+    /// newer ones must have one, so stripping it does not skip the check. A
+    /// mismatch means the file was edited outside MOVS or corrupted, even if
+    /// every object is intact.
+    pub fn verify_content_digest(&self) -> crate::error::Result<()> {
+        let Some(expected) = &self.content_digest else {
+            if self.schema_version >= DIGEST_SCHEMA_VERSION {
+                return Err(crate::error::MovsError::StorageError(format!(
+                    "Snapshot '{}' is missing its content digest",
+                    self.id
+                )));
+            }
+            return Ok(());
+        };
+
+        let actual = self.compute_content_digest()?;
+        if &actual != expected {
+            return Err(crate::error::MovsError::SnapshotDigestMismatch {
+                id: self.id.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the snapshot for internal consistency
+    ///
+    /// Rejects empty IDs, a snapshot that is its own parent, empty, absolute
+    /// or `..`-containing paths, duplicate paths, and hashes that are not
+    /// SHA-256 sized. The error names the first problem found.
+    pub fn validate(&self) -> crate::error::Result<()> {
+        use crate::error::MovsError;
+        use std::collections::HashSet;
+        use std::path::Component;
+
+        let invalid = |problem: String| {
+            Err(MovsError::StorageError(format!(
+                "Invalid snapshot '{}': {}",
+                self.id, problem
+            )))
+        };
+
+        if self.id.as_str().is_empty() {
+            return invalid("empty snapshot ID".to_string());
+        }
+        if self.parent.as_ref() == Some(&self.id) {
+            return invalid("snapshot is its own parent".to_string());
+        }
+        if self.schema_version == 0 || self.schema_version > SNAPSHOT_SCHEMA_VERSION {
+            return invalid(format!(
+                "unsupported schema version {}",
+                self.schema_version
+            ));
+        }
+
+        let mut seen = HashSet::new();
+        for entry in &self.files {
+            let path = entry.path.display();
+
+            if entry.path.as_os_str().is_empty() {
+                return invalid("entry with an empty path".to_string());
+            }
+            if !entry
+                .path
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+            {
+                return invalid(format!("path '{}' is not relative to the project", path));
+            }
+            if let Some(scope) = self.scope.as_ref().filter(|s| !entry.path.starts_with(s)) {
+                return invalid(format!(
+                    "path '{}' is outside the snapshot scope '{}'",
+                    path,
+                    scope.display()
+                ));
+            }
+            if !seen.insert(&entry.path) {
+                return invalid(format!("duplicate path '{}'", path));
+            }
+            if entry.hash.as_bytes().len() != HASH_LEN {
+                return invalid(format!(
+                    "hash of '{}' is {} bytes, expected {}",
+                    path,
+                    entry.hash.as_bytes().len(),
+                    HASH_LEN
+                ));
+            }
+            if entry.kind == EntryKind::Directory && entry.size != 0 {
+                return invalid(format!("directory '{}' has a non-zero size", path));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Incrementally assembles `SnapshotMetadata`
+///
+/// For tools that produce file entries one at a time, e.g. while streaming
+/// files through a hasher. Adding an entry for a path already present
+/// replaces it, and entries are sorted by path on `build`.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotBuilder {
+    message: String,
+    author: Option<String>,
+    parent: Option<SnapshotId>,
+    id_scheme: IdScheme,
+    files: BTreeMap<PathBuf, FileEntry>,
+}
+
+impl SnapshotBuilder {
+    /// Create an empty builder with no message, author or parent
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the snapshot message
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    /// Set the snapshot author
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Set the parent snapshot
+    pub fn parent(mut self, parent: SnapshotId) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Set the scheme used to generate the ID on `build`
+    pub fn id_scheme(mut self, scheme: IdScheme) -> Self {
+        self.id_scheme = scheme;
+        self
+    }
+
+    /// Add a file entry, replacing any earlier entry for the same path
+    pub fn add_file(&mut self, entry: FileEntry) -> &mut Self {
+        self.files.insert(entry.path.clone(), entry);
+        self
+    }
+
+    /// Number of distinct paths added so far
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Produce the metadata with a freshly generated ID and timestamp
+    pub fn build(self) -> SnapshotMetadata {
+        SnapshotMetadata::new(
+            SnapshotId::generate_with(self.id_scheme),
+            self.message,
+            self.author,
+            self.parent,
+            self.files.into_values().collect(),
+        )
+    }
+}
+
+impl Extend<FileEntry> for SnapshotBuilder {
+    fn extend<I: IntoIterator<Item = FileEntry>>(&mut self, entries: I) {
+        for entry in entries {
+            self.add_file(entry);
+        }
+    }
 }
 
 /// Represents changes between two snapshots
-#[derive(Debug, Clone)]
+///
+/// Serializes to `{"added": [...], "modified": [...], "removed": [...]}` with
+/// every path written using `/` separators, so a diff cached on one platform
+/// reads back identically on another.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SnapshotDiff {
     /// Files added in the new snapshot
+    #[serde(with = "slash_paths")]
     pub added: Vec<PathBuf>,
 
     /// Files modified between snapshots
+    #[serde(with = "slash_paths")]
     pub modified: Vec<PathBuf>,
 
     /// Files removed in the new snapshot
+    #[serde(with = "slash_paths")]
     pub removed: Vec<PathBuf>,
 }
 
+/// Serde helpers writing relative paths as `/`-separated strings
+mod slash_paths {
+    use serde::de::Deserializer;
+    use serde::ser::{Error, SerializeSeq, Serializer};
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    pub fn serialize<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(paths.len()))?;
+        for path in paths {
+            let parts = path
+                .components()
+                .map(|c| c.as_os_str().to_str())
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| S::Error::custom(format!("path is not valid UTF-8: {:?}", path)))?;
+            seq.serialize_element(&parts.join("/"))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<PathBuf>, D::Error> {
+        let paths = Vec::<String>::deserialize(deserializer)?;
+        Ok(paths.into_iter().map(PathBuf::from).collect())
+    }
+}
+
 impl SnapshotDiff {
     pub fn new() -> Self {
         Self {
@@ -187,6 +839,56 @@ impl SnapshotDiff {
     pub fn total_changes(&self) -> usize {
         self.added.len() + self.modified.len() + self.removed.len()
     }
+
+    /// One-line human readable summary, e.g. `"3 added, 1 modified, 0 removed"`
+    ///
+    /// Returns `"no changes"` when the diff is empty.
+    pub fn summary(&self) -> String {
+        if !self.has_changes() {
+            return "no changes".to_string();
+        }
+
+        format!(
+            "{} added, {} modified, {} removed",
+            self.added.len(),
+            self.modified.len(),
+            self.removed.len()
+        )
+    }
+
+    /// Express the diff as percentages of the number of files in the base snapshot
+    ///
+    /// If the base snapshot is empty, all percentages are `0.0`.
+    pub fn as_percentages(&self, total_files_in_base: usize) -> DiffPercentages {
+        if total_files_in_base == 0 {
+            return DiffPercentages::default();
+        }
+
+        let percent = |count: usize| count as f64 / total_files_in_base as f64 * 100.0;
+
+        DiffPercentages {
+            added: percent(self.added.len()),
+            modified: percent(self.modified.len()),
+            removed: percent(self.removed.len()),
+            churn: percent(self.total_changes()),
+        }
+    }
+}
+
+/// Percentage breakdown of a `SnapshotDiff` relative to its base snapshot
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffPercentages {
+    /// Added files as a percentage of the base file count
+    pub added: f64,
+
+    /// Modified files as a percentage of the base file count
+    pub modified: f64,
+
+    /// Removed files as a percentage of the base file count
+    pub removed: f64,
+
+    /// All changed files as a percentage of the base file count
+    pub churn: f64,
 }
 
 impl Default for SnapshotDiff {
@@ -198,31 +900,182 @@ impl Default for SnapshotDiff {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hash::hash_bytes;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_file_hash_short() {
+        let hash = FileHash::from_hex("0123456789abcdef0123").unwrap();
+
+        assert_eq!(hash.short(4), "0123");
+        assert_eq!(hash.short_default(), "0123456789ab");
+        assert_eq!(hash.short(100), hash.to_hex());
+        assert_eq!(hash.short(0), "");
+
+        assert!(hash.matches_prefix("0123456"));
+        assert!(hash.matches_prefix("0123456789ABCDEF"));
+        assert!(hash.matches_prefix(""));
+        assert!(!hash.matches_prefix("1"));
+        assert!(!hash.matches_prefix("0123456789abcdef01234"));
+    }
 
     #[test]
     fn test_file_hash_hex_conversion() {
         let bytes = vec![0x12, 0x34, 0x56, 0x78];
         let hash = FileHash::new(bytes.clone());
-        
+
         assert_eq!(hash.to_hex(), "12345678");
         assert_eq!(hash.as_bytes(), &bytes);
-        
+
         let from_hex = FileHash::from_hex("12345678").unwrap();
         assert_eq!(hash, from_hex);
     }
 
+    #[test]
+    fn test_file_hash_from_hex_checked() {
+        let hex = "ab".repeat(HASH_LEN);
+        let hash = FileHash::from_hex_checked(&hex, HashAlgorithm::Sha256).unwrap();
+        assert_eq!(hash.as_bytes().len(), HASH_LEN);
+
+        // A truncated, 20-byte hash is rejected with a descriptive message
+        let short = "ab".repeat(20);
+        let err = FileHash::from_hex_checked(&short, HashAlgorithm::Sha256).unwrap_err();
+        assert!(matches!(err, crate::MovsError::InvalidHash(_)));
+        assert!(err.to_string().contains("20 bytes"));
+
+        assert!(FileHash::from_hex_checked(&"ab".repeat(33), HashAlgorithm::Sha256).is_err());
+        assert!(FileHash::from_hex_checked("zz", HashAlgorithm::Sha256).is_err());
+
+        // The lenient constructor still accepts any length
+        assert!(FileHash::from_hex(&short).is_ok());
+    }
+
     #[test]
     fn test_snapshot_id_generation() {
         let id1 = SnapshotId::generate();
         let id2 = SnapshotId::generate();
-        
+
         // IDs should be different
         assert_ne!(id1, id2);
-        
+
         // Should start with "snapshot_"
         assert!(id1.as_str().starts_with("snapshot_"));
     }
 
+    #[test]
+    fn test_snapshot_id_timestamp_roundtrip() {
+        let before = Utc::now();
+        let id = SnapshotId::generate();
+        let after = Utc::now();
+
+        let timestamp = id.timestamp().unwrap();
+        assert!(before <= timestamp && timestamp <= after);
+
+        let random = SnapshotId::generate_with(IdScheme::TimestampPlusRandom);
+        assert!(random.timestamp().is_some());
+    }
+
+    #[test]
+    fn test_snapshot_id_timestamp_rejects_other_schemes() {
+        assert!(SnapshotId::generate_with(IdScheme::Uuid)
+            .timestamp()
+            .is_none());
+        assert!(SnapshotId::new("snapshot_2024".to_string())
+            .timestamp()
+            .is_none());
+        assert!(
+            SnapshotId::new("snapshot_20241301_120000_000000".to_string())
+                .timestamp()
+                .is_none()
+        );
+
+        let legacy = SnapshotId::new("snapshot_20240102_030405_123456".to_string());
+        assert_eq!(
+            legacy.timestamp().unwrap().to_rfc3339(),
+            "2024-01-02T03:04:05.123456+00:00"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_id_ordering() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let mut ids: Vec<_> = [3, 1, 2]
+            .into_iter()
+            .map(|s| SnapshotId::generate_at(IdScheme::Timestamp, start + Duration::seconds(s)))
+            .collect();
+        let expected: Vec<_> = [1, 2, 3]
+            .into_iter()
+            .map(|s| SnapshotId::generate_at(IdScheme::Timestamp, start + Duration::seconds(s)))
+            .collect();
+
+        ids.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_snapshot_builder() {
+        let now = Utc::now();
+        let parent = SnapshotId::generate();
+
+        let mut builder = SnapshotBuilder::new()
+            .message("Streamed")
+            .author("Producer")
+            .parent(parent.clone());
+        builder.add_file(FileEntry::new(
+            PathBuf::from("vox.wav"),
+            hash_bytes(b"v1"),
+            2,
+            now,
+        ));
+        builder.add_file(FileEntry::new(
+            PathBuf::from("drums.wav"),
+            hash_bytes(b"d"),
+            1,
+            now,
+        ));
+        builder.add_file(FileEntry::new(
+            PathBuf::from("vox.wav"),
+            hash_bytes(b"v2"),
+            2,
+            now,
+        ));
+        assert_eq!(builder.file_count(), 2);
+
+        let metadata = builder.build();
+        assert_eq!(metadata.message, "Streamed");
+        assert_eq!(metadata.author.as_deref(), Some("Producer"));
+        assert_eq!(metadata.parent, Some(parent));
+        let paths: Vec<_> = metadata.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("drums.wav"), PathBuf::from("vox.wav")]
+        );
+        assert_eq!(metadata.files[1].hash, hash_bytes(b"v2"));
+        metadata.validate().unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_id_schemes_unique() {
+        use std::collections::HashSet;
+
+        for scheme in [
+            IdScheme::Timestamp,
+            IdScheme::Uuid,
+            IdScheme::TimestampPlusRandom,
+        ] {
+            let ids: HashSet<_> = (0..10_000)
+                .map(|_| SnapshotId::generate_with(scheme))
+                .collect();
+            assert_eq!(
+                ids.len(),
+                10_000,
+                "Duplicate IDs generated with {:?}",
+                scheme
+            );
+            assert!(ids.iter().all(|id| id.as_str().starts_with("snapshot_")));
+        }
+    }
+
     #[test]
     fn test_snapshot_metadata_helpers() {
         let files = vec![
@@ -251,7 +1104,57 @@ mod tests {
         assert_eq!(metadata.file_count(), 2);
         assert_eq!(metadata.total_size(), 5100);
         assert!(metadata.find_file(&PathBuf::from("test.txt")).is_some());
-        assert!(metadata.find_file(&PathBuf::from("nonexistent.txt")).is_none());
+        assert!(metadata
+            .find_file(&PathBuf::from("nonexistent.txt"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_file_entry_kind_defaults_to_file() {
+        let json = r#"{
+            "path": "kick.wav",
+            "hash": {"bytes": [1, 2, 3]},
+            "size": 3,
+            "modified": "2024-01-01T00:00:00Z"
+        }"#;
+
+        let entry: FileEntry = serde_json::from_str(json).unwrap();
+        assert_eq!(entry.kind, EntryKind::File);
+        assert!(entry.is_file());
+
+        let link = entry.with_kind(EntryKind::Symlink {
+            target: PathBuf::from("../Library/kick.wav"),
+        });
+        let roundtrip: FileEntry =
+            serde_json::from_str(&serde_json::to_string(&link).unwrap()).unwrap();
+        assert_eq!(roundtrip.kind, link.kind);
+    }
+
+    #[test]
+    fn test_file_entry_same_content_and_path() {
+        let kick = FileEntry::new(
+            PathBuf::from("kick.wav"),
+            FileHash::new(vec![1, 2, 3]),
+            3,
+            Utc::now(),
+        );
+        let copy = FileEntry::new(
+            PathBuf::from("Samples/kick.wav"),
+            kick.hash.clone(),
+            3,
+            Utc::now(),
+        );
+        let edited = FileEntry::new(
+            kick.path.clone(),
+            FileHash::new(vec![4, 5, 6]),
+            3,
+            Utc::now(),
+        );
+
+        assert!(kick.same_content(&copy));
+        assert!(!kick.same_path(&copy));
+        assert!(kick.same_path(&edited));
+        assert!(!kick.same_content(&edited));
     }
 
     #[test]
@@ -266,4 +1169,188 @@ mod tests {
         assert!(diff.has_changes());
         assert_eq!(diff.total_changes(), 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_snapshot_diff_summary() {
+        let mut diff = SnapshotDiff::new();
+        assert_eq!(diff.summary(), "no changes");
+
+        diff.added.push(PathBuf::from("a.wav"));
+        diff.added.push(PathBuf::from("b.wav"));
+        diff.modified.push(PathBuf::from("song.als"));
+
+        assert_eq!(diff.summary(), "2 added, 1 modified, 0 removed");
+    }
+
+    #[test]
+    fn test_snapshot_diff_percentages() {
+        let mut diff = SnapshotDiff::new();
+        assert_eq!(diff.as_percentages(10), DiffPercentages::default());
+
+        diff.modified.push(PathBuf::from("song.als"));
+        diff.removed.push(PathBuf::from("old.wav"));
+
+        let percentages = diff.as_percentages(4);
+        assert_eq!(percentages.modified, 25.0);
+        assert_eq!(percentages.removed, 25.0);
+        assert_eq!(percentages.added, 0.0);
+        assert_eq!(percentages.churn, 50.0);
+
+        // Empty base must not divide by zero
+        assert_eq!(diff.as_percentages(0), DiffPercentages::default());
+    }
+
+    #[test]
+    fn test_snapshot_validate() {
+        let entry = |path: &str| {
+            FileEntry::new(
+                PathBuf::from(path),
+                FileHash::new(vec![7; HASH_LEN]),
+                1,
+                Utc::now(),
+            )
+        };
+        let snapshot = |files| {
+            SnapshotMetadata::new(
+                SnapshotId::new("snapshot_1".to_string()),
+                "Test".to_string(),
+                None,
+                None,
+                files,
+            )
+        };
+
+        assert!(snapshot(vec![entry("a.wav"), entry("Samples/b.wav")])
+            .validate()
+            .is_ok());
+
+        let err = snapshot(vec![entry("a.wav"), entry("a.wav")])
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("duplicate path 'a.wav'"));
+
+        assert!(snapshot(vec![entry("../outside.wav")]).validate().is_err());
+        assert!(snapshot(vec![entry("/abs.wav")]).validate().is_err());
+
+        let mut short_hash = entry("a.wav");
+        short_hash.hash = FileHash::new(vec![1, 2, 3]);
+        assert!(snapshot(vec![short_hash]).validate().is_err());
+
+        let mut own_parent = snapshot(vec![]);
+        own_parent.parent = Some(own_parent.id.clone());
+        assert!(own_parent.validate().is_err());
+
+        let mut empty_id = snapshot(vec![]);
+        empty_id.id = SnapshotId::new(String::new());
+        assert!(empty_id.validate().is_err());
+    }
+
+    #[test]
+    fn test_find_file_normalizes_query() {
+        let metadata = SnapshotMetadata::new(
+            SnapshotId::new("snapshot_1".to_string()),
+            "Test".to_string(),
+            None,
+            None,
+            vec![FileEntry::new(
+                PathBuf::from("Samples/kick.wav"),
+                FileHash::new(vec![1]),
+                1,
+                Utc::now(),
+            )],
+        );
+
+        assert!(metadata
+            .find_file(Path::new("./Samples/kick.wav"))
+            .is_some());
+        #[cfg(windows)]
+        assert!(metadata.find_file(Path::new("Samples\\kick.wav")).is_some());
+        assert!(metadata.find_file(Path::new("../kick.wav")).is_none());
+    }
+
+    #[test]
+    fn test_group_by_extension() {
+        let entry = |path: &str, size| {
+            FileEntry::new(
+                PathBuf::from(path),
+                FileHash::new(vec![1]),
+                size,
+                Utc::now(),
+            )
+        };
+        let metadata = SnapshotMetadata::new(
+            SnapshotId::new("snapshot_1".to_string()),
+            "Test".to_string(),
+            None,
+            None,
+            vec![
+                entry("Samples/kick.wav", 100),
+                entry("Samples/SNARE.WAV", 50),
+                entry("beat.mid", 3),
+                entry("README", 7),
+                entry("Samples", 0).with_kind(EntryKind::Directory),
+            ],
+        );
+
+        let groups = metadata.files_by_extension();
+        assert_eq!(groups["wav"].len(), 2);
+        assert_eq!(groups["mid"].len(), 1);
+        assert_eq!(groups[""][0].path, PathBuf::from("README"));
+        assert_eq!(groups.len(), 3);
+
+        let sizes = metadata.size_by_extension();
+        assert_eq!(sizes["wav"], 150);
+        assert_eq!(sizes[""], 7);
+    }
+
+    #[test]
+    fn test_classify_path() {
+        assert_eq!(classify_path(Path::new("Audio/Kick.WAV")), FileType::Wav);
+        assert_eq!(classify_path(Path::new("pad.aif")), FileType::Aiff);
+        assert_eq!(classify_path(Path::new("chords.mid")), FileType::Midi);
+        assert_eq!(
+            classify_path(Path::new("Bounce.FLAC")),
+            FileType::CompressedAudio
+        );
+        assert!(!FileType::CompressedAudio.is_compressible());
+        assert!(FileType::Wav.is_compressible());
+        assert_eq!(classify_path(Path::new("Song.als")), FileType::ProjectFile);
+        assert_eq!(classify_path(Path::new("notes.txt")), FileType::Other);
+        assert_eq!(classify_path(Path::new("README")), FileType::Other);
+    }
+
+    #[test]
+    fn test_file_type_is_optional_in_json() {
+        let entry = FileEntry::new(
+            PathBuf::from("a.wav"),
+            FileHash::new(vec![1]),
+            1,
+            Utc::now(),
+        );
+        let json = serde_json::to_string(&entry).unwrap();
+        assert!(!json.contains("file_type"));
+
+        let typed = entry.with_file_type(FileType::Wav);
+        let json = serde_json::to_string(&typed).unwrap();
+        let parsed: FileEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.file_type, Some(FileType::Wav));
+    }
+
+    #[test]
+    fn test_snapshot_diff_json_roundtrip() {
+        let diff = SnapshotDiff {
+            added: vec![PathBuf::from("samples").join("kick.wav")],
+            modified: vec![PathBuf::from("song.als")],
+            removed: vec![],
+        };
+
+        let json = serde_json::to_string(&diff).unwrap();
+        assert_eq!(
+            json,
+            r#"{"added":["samples/kick.wav"],"modified":["song.als"],"removed":[]}"#
+        );
+
+        let restored: SnapshotDiff = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, diff);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::diff::diff_entries;
+use crate::error::Result;
+use crate::metadata::persistence::load_snapshot;
+use crate::tree::{ScanOptions, WorkingTree};
+use crate::types::{EntryKind, SnapshotId};
+use std::path::{Path, PathBuf};
+
+/// Result of checking the working tree against a snapshot
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Files whose current content differs from the snapshot
+    pub mismatched: Vec<PathBuf>,
+
+    /// Files recorded in the snapshot but absent from the working tree
+    pub missing: Vec<PathBuf>,
+
+    /// Files in the working tree that the snapshot does not contain
+    pub extra: Vec<PathBuf>,
+}
+
+impl VerifyReport {
+    /// Check if the working tree matches the snapshot exactly
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Check that the working tree matches a snapshot without restoring anything
+///
+/// Every tracked file is re-hashed in parallel and compared against the hash
+/// recorded in the snapshot. This is read-only. Empty directories are
+/// compared only if the snapshot recorded any, i.e. it was taken with
+/// `ScanOptions::track_empty_dirs`.
+///
+/// # Arguments
+///
+/// * `project_root` - Root directory of the project
+/// * `snapshot_id` - ID of the snapshot to verify against
+pub fn verify_snapshot(project_root: &Path, snapshot_id: &SnapshotId) -> Result<VerifyReport> {
+    let metadata = load_snapshot(project_root, snapshot_id)?;
+    let options = ScanOptions {
+        track_empty_dirs: metadata
+            .files
+            .iter()
+            .any(|entry| entry.kind == EntryKind::Directory),
+        ..ScanOptions::default()
+    };
+    let current = WorkingTree::open(project_root)?
+        .with_scan_options(options)
+        .hash_all()?;
+
+    let diff = diff_entries(&metadata.files, &current);
+
+    Ok(VerifyReport {
+        mismatched: diff.modified,
+        missing: diff.removed,
+        extra: diff.added,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::MovsError;
+    use crate::metadata::init_repository;
+    use crate::snapshot::create_snapshot;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify_matching_tree() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox").unwrap();
+
+        let id = create_snapshot(project_root, "First", None, &Default::default()).unwrap();
+
+        let report = verify_snapshot(project_root, &id).unwrap();
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn test_verify_reports_differences() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox").unwrap();
+
+        let id = create_snapshot(project_root, "First", None, &Default::default()).unwrap();
+
+        fs::write(project_root.join("song.als"), b"edited").unwrap();
+        fs::remove_file(project_root.join("vox.wav")).unwrap();
+        fs::write(project_root.join("new.wav"), b"new").unwrap();
+
+        let report = verify_snapshot(project_root, &id).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.mismatched, vec![PathBuf::from("song.als")]);
+        assert_eq!(report.missing, vec![PathBuf::from("vox.wav")]);
+        assert_eq!(report.extra, vec![PathBuf::from("new.wav")]);
+    }
+
+    #[test]
+    fn test_verify_snapshot_with_empty_dirs() {
+        use crate::snapshot::{SnapshotBuilder, SnapshotOptions};
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"project").unwrap();
+        fs::create_dir(project_root.join("Recorded")).unwrap();
+
+        let options = SnapshotOptions {
+            scan: ScanOptions {
+                track_empty_dirs: true,
+                ..ScanOptions::default()
+            },
+            ..SnapshotOptions::default()
+        };
+        let (id, _) = SnapshotBuilder::new(project_root, "First")
+            .with_options(&options)
+            .create()
+            .unwrap();
+
+        assert!(verify_snapshot(project_root, &id).unwrap().is_ok());
+
+        fs::remove_dir(project_root.join("Recorded")).unwrap();
+        let report = verify_snapshot(project_root, &id).unwrap();
+        assert_eq!(report.missing, vec![PathBuf::from("Recorded")]);
+    }
+
+    #[test]
+    fn test_verify_unknown_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+
+        init_repository(project_root).unwrap();
+
+        let result = verify_snapshot(project_root, &SnapshotId::new("nope".to_string()));
+        assert!(matches!(result, Err(MovsError::SnapshotNotFound(_))));
+    }
+}
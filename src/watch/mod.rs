@@ -0,0 +1,312 @@
+use crate::error::{MovsError, Result};
+use crate::metadata::lock::lock_for_write;
+use crate::metadata::persistence::load_snapshot;
+use crate::metadata::refs::read_head;
+use crate::metadata::{get_movs_dir, repository_exists, MOVS_DIR};
+use crate::snapshot::commit_files;
+use crate::tree::{WorkingTree, IGNORE_FILE};
+use crate::types::SnapshotId;
+use notify::event::{Event, EventKind};
+use notify::{RecommendedWatcher, RecursiveMode};
+use std::collections::BTreeSet;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Paths reported by filesystem events since the last reset
+#[derive(Debug, Default)]
+struct DirtyState {
+    paths: BTreeSet<PathBuf>,
+
+    /// Events were lost, so only a full scan can be trusted
+    overflowed: bool,
+}
+
+/// Tracks which paths changed, so commits only hash those
+///
+/// Filesystem events are collected in the background from the moment the
+/// watcher is created. `commit` rehashes just the paths they named and
+/// takes everything else from HEAD, which keeps continuous auto-versioning
+/// cheap. Events only say what changed relative to the watcher's own last
+/// commit, so the first commit, and any commit after HEAD was moved
+/// elsewhere, e.g. by another process or a branch switch, is a full scan.
+/// So is the next commit after the event stream overflows or reports an
+/// error.
+pub struct Watcher {
+    root: PathBuf,
+    state: Arc<Mutex<DirtyState>>,
+
+    /// HEAD as of the watcher's last commit, matching the working tree
+    /// apart from the paths in `state`
+    base: Mutex<Option<SnapshotId>>,
+
+    _watcher: RecommendedWatcher,
+}
+
+impl Watcher {
+    /// Start watching a project for changes
+    pub fn new(project_root: &Path) -> Result<Self> {
+        if !repository_exists(project_root) {
+            return Err(MovsError::RepositoryNotFound(get_movs_dir(project_root)));
+        }
+
+        let root = project_root.to_path_buf();
+        let canonical = project_root.canonicalize()?;
+        let state = Arc::new(Mutex::new(DirtyState::default()));
+
+        let handler_state = Arc::clone(&state);
+        let handler_root = root.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let mut state = lock(&handler_state);
+            match event {
+                Ok(event) if event.need_rescan() => state.overflowed = true,
+                Ok(Event {
+                    kind: EventKind::Access(_),
+                    ..
+                }) => {}
+                Ok(event) => {
+                    for path in event.paths {
+                        let relative = path
+                            .strip_prefix(&handler_root)
+                            .or_else(|_| path.strip_prefix(&canonical));
+                        match relative {
+                            Ok(relative) if relative.starts_with(MOVS_DIR) => {}
+                            Ok(relative) if relative.as_os_str().is_empty() => {
+                                state.overflowed = true
+                            }
+                            Ok(relative) => {
+                                state.paths.insert(relative.to_path_buf());
+                            }
+                            Err(_) => state.overflowed = true,
+                        }
+                    }
+                }
+                Err(_) => state.overflowed = true,
+            }
+        })
+        .map_err(watch_error)?;
+
+        notify::Watcher::watch(&mut watcher, project_root, RecursiveMode::Recursive)
+            .map_err(watch_error)?;
+
+        Ok(Self {
+            root,
+            state,
+            base: Mutex::new(None),
+            _watcher: watcher,
+        })
+    }
+
+    /// Paths changed since the watcher started or was last reset, sorted
+    ///
+    /// Paths are relative to the project root and may name directories,
+    /// or files that have since been deleted. Returns `None` if events were
+    /// lost, meaning any path may have changed.
+    pub fn changed_paths(&self) -> Option<Vec<PathBuf>> {
+        let state = lock(&self.state);
+        (!state.overflowed).then(|| state.paths.iter().cloned().collect())
+    }
+
+    /// Forget all changes seen so far
+    pub fn reset(&self) {
+        *lock(&self.state) = DirtyState::default();
+    }
+
+    /// Create a snapshot if anything changed, hashing only changed paths
+    ///
+    /// Behaves like `snapshot::commit`. Entries for paths no event touched
+    /// are copied from HEAD; a full scan is done instead on the watcher's
+    /// first commit, when HEAD moved since its last one, when events were
+    /// lost, when HEAD is a scoped snapshot, or when an ignore file
+    /// changed. Changes seen before the call are consumed, while those
+    /// arriving during it are kept for the next commit.
+    pub fn commit(&self, message: &str, author: Option<&str>) -> Result<Option<SnapshotId>> {
+        // HEAD must not move between checking it and committing on top of it
+        let _lock = lock_for_write(&self.root)?;
+        let taken = mem::take(&mut *lock(&self.state));
+
+        let result = self.commit_changes(&taken, message, author);
+        if result.is_err() {
+            // Keep the changes so the next attempt still sees them
+            let mut state = lock(&self.state);
+            state.paths.extend(taken.paths);
+            state.overflowed |= taken.overflowed;
+        }
+        result
+    }
+
+    fn commit_changes(
+        &self,
+        changes: &DirtyState,
+        message: &str,
+        author: Option<&str>,
+    ) -> Result<Option<SnapshotId>> {
+        let ignore_changed = changes
+            .paths
+            .iter()
+            .any(|path| path.file_name().is_some_and(|name| name == IGNORE_FILE));
+        let mut base = self.base.lock().unwrap_or_else(|e| e.into_inner());
+        let head = read_head(&self.root)?;
+        let parent = match &head {
+            Some(head) if !changes.overflowed && !ignore_changed && base.as_ref() == Some(head) => {
+                Some(load_snapshot(&self.root, head)?)
+            }
+            _ => None,
+        };
+        let Some(parent) = parent.filter(|parent| parent.scope.is_none()) else {
+            let committed = commit_files(&self.root, message, author, None)?;
+            *base = committed.clone().or(head);
+            return Ok(committed);
+        };
+
+        let dirty: Vec<PathBuf> = changes.paths.iter().cloned().collect();
+        let mut files: Vec<_> = parent
+            .files
+            .into_iter()
+            .filter(|entry| !dirty.iter().any(|path| entry.path.starts_with(path)))
+            .collect();
+        files.extend(WorkingTree::open(&self.root)?.hash_paths(&dirty)?);
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let committed = commit_files(&self.root, message, author, Some(files))?;
+        *base = committed.clone().or(head);
+        Ok(committed)
+    }
+}
+
+impl std::fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watcher")
+            .field("root", &self.root)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Lock the shared state, tolerating a panic in the event handler
+fn lock(state: &Mutex<DirtyState>) -> MutexGuard<'_, DirtyState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn watch_error(error: notify::Error) -> MovsError {
+    match error.kind {
+        notify::ErrorKind::Io(e) => MovsError::Io(e),
+        kind => MovsError::StorageError(format!("Cannot watch project: {:?}", kind)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::init_repository;
+    use std::fs;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    /// Wait until the watcher has seen `path`, since events arrive asynchronously
+    fn wait_for(watcher: &Watcher, path: &str) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if watcher
+                .changed_paths()
+                .is_none_or(|paths| paths.contains(&PathBuf::from(path)))
+            {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        panic!("No event for '{}'", path);
+    }
+
+    #[test]
+    fn test_watcher_commits_changed_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox").unwrap();
+
+        let watcher = Watcher::new(project_root).unwrap();
+        let first = watcher.commit("First", None).unwrap().unwrap();
+        assert_eq!(watcher.commit("Again", None).unwrap(), None);
+
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        fs::remove_file(project_root.join("vox.wav")).unwrap();
+        fs::create_dir(project_root.join("Samples")).unwrap();
+        fs::write(project_root.join("Samples/kick.wav"), b"kick").unwrap();
+        wait_for(&watcher, "song.als");
+        wait_for(&watcher, "vox.wav");
+        wait_for(&watcher, "Samples");
+
+        let second = watcher.commit("Second", None).unwrap().unwrap();
+        assert_ne!(first, second);
+        assert!(watcher.changed_paths().is_some_and(|p| p.is_empty()));
+
+        // Same result as a full scan
+        let metadata = load_snapshot(project_root, &second).unwrap();
+        let scanned = WorkingTree::open(project_root).unwrap().hash_all().unwrap();
+        let key = |e: &crate::types::FileEntry| (e.path.clone(), e.hash.clone());
+        assert_eq!(
+            metadata.files.iter().map(key).collect::<Vec<_>>(),
+            scanned.iter().map(key).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_watcher_rescans_unless_head_is_its_own() {
+        use crate::metadata::refs::write_head;
+        use crate::snapshot::create_snapshot;
+
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+        let first = create_snapshot(project_root, "v1", None, &Default::default()).unwrap();
+
+        // Edited before the watcher started, so no event names it
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        let song_hash = |id: &SnapshotId| {
+            load_snapshot(project_root, id)
+                .unwrap()
+                .find_file(Path::new("song.als"))
+                .unwrap()
+                .hash
+                .clone()
+        };
+
+        let watcher = Watcher::new(project_root).unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox").unwrap();
+        wait_for(&watcher, "vox.wav");
+        let second = watcher.commit("Second", None).unwrap().unwrap();
+        assert_eq!(song_hash(&second), crate::hash::hash_bytes(b"v2"));
+
+        // HEAD moved behind the watcher's back
+        write_head(project_root, &first).unwrap();
+        fs::write(project_root.join("vox.wav"), b"vox v2").unwrap();
+        wait_for(&watcher, "vox.wav");
+        let third = watcher.commit("Third", None).unwrap().unwrap();
+        assert_eq!(song_hash(&third), crate::hash::hash_bytes(b"v2"));
+    }
+
+    #[test]
+    fn test_watcher_overflow_falls_back_to_full_scan() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        init_repository(project_root).unwrap();
+        fs::write(project_root.join("song.als"), b"v1").unwrap();
+
+        let watcher = Watcher::new(project_root).unwrap();
+        watcher.commit("First", None).unwrap().unwrap();
+
+        // Simulate lost events for a change the watcher never saw
+        fs::write(project_root.join("song.als"), b"v2").unwrap();
+        wait_for(&watcher, "song.als");
+        watcher.reset();
+        lock(&watcher.state).overflowed = true;
+        assert_eq!(watcher.changed_paths(), None);
+
+        assert!(watcher.commit("Second", None).unwrap().is_some());
+        assert_eq!(watcher.changed_paths(), Some(Vec::new()));
+    }
+}